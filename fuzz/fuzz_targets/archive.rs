@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yet_another_imgs2pdf::zip;
+
+// an uploaded or local CBZ/ZIP archive, down to the contents of its images
+fuzz_target!(|data: &[u8]| {
+    if let Ok(images) = zip::images(data) {
+        for image in &images {
+            let _ = zip::read(data, image);
+        }
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use yet_another_imgs2pdf::{PageOptions, PdfMergerBuilder};
+
+// an input as uploaded or taken from an archive, all the way to a saved PDF
+fuzz_target!(|data: &[u8]| {
+    let mut merger = PdfMergerBuilder::new().scale_percent(100.0).build().unwrap();
+    if merger
+        .append_image_data(Path::new("input"), data, PageOptions::default())
+        .is_ok()
+    {
+        merger.finish();
+        merger.save(std::io::sink()).unwrap();
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use yet_another_imgs2pdf::manifest;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(toml) = std::str::from_utf8(data) {
+        let _ = manifest::parse(toml, Path::new(""));
+    }
+});
@@ -0,0 +1,58 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use printpdf::image_crate::codecs::{gif::GifDecoder, png::PngDecoder};
+use printpdf::image_crate::{AnimationDecoder, DynamicImage, Frames, ImageResult};
+
+use crate::scratch::ScratchDir;
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase)
+}
+
+/// The frames of the animated GIF or APNG at `path`, or `None` for any other
+/// file
+fn frames(path: &Path) -> Option<ImageResult<Frames<'static>>> {
+    let open = || File::open(path).map(BufReader::new);
+    let frames = match extension(path)?.as_str() {
+        "gif" => GifDecoder::new(open().ok()?).map(|d| d.into_frames()),
+        "png" | "apng" => {
+            let decoder = PngDecoder::new(open().ok()?).ok()?;
+            if !decoder.is_apng() {
+                return None;
+            }
+            Ok(decoder.apng().into_frames())
+        }
+        _ => return None,
+    };
+    Some(frames)
+}
+
+/// Whether `path` is a GIF or APNG of more than one frame
+pub fn is_animated(path: &Path) -> bool {
+    match frames(path) {
+        Some(Ok(frames)) => frames.take(2).count() > 1,
+        _ => false,
+    }
+}
+
+/// Writes the first frame of `animation` and every `step`th after it as PNGs
+/// into a scratch directory, each as it is shown with the frames before it
+/// drawn underneath
+pub fn expand(animation: &Path, step: usize) -> ImageResult<ScratchDir> {
+    let mut expanded = ScratchDir::new("animation", animation)?;
+    let frames = match frames(animation) {
+        Some(frames) => frames?,
+        None => return Ok(expanded),
+    };
+    for (i, frame) in frames.enumerate().step_by(step.max(1)) {
+        let path = expanded.dir().join(format!("frame-{:06}.png", i + 1));
+        DynamicImage::ImageRgba8(frame?.into_buffer()).save(&path)?;
+        expanded.paths.push(path);
+    }
+    Ok(expanded)
+}
@@ -0,0 +1,192 @@
+use printpdf::lopdf::{self, Dictionary, Object, ObjectId};
+
+use crate::layers;
+use crate::pagetree::{inherited, INHERITABLE};
+
+/// Moves the pages of `doc` to the end of `base`, along with everything they
+/// draw on, their outline entries, named destinations and layers
+pub fn append_pages(base: &mut lopdf::Document, mut doc: lopdf::Document) -> lopdf::Result<()> {
+    doc.renumber_objects_with(base.max_id + 1);
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    // what the pages got from the tree they leave, and not from the new one
+    let own = pages
+        .iter()
+        .map(|page| {
+            INHERITABLE
+                .iter()
+                .filter_map(|key| Some((key.to_vec(), inherited(&doc, *page, key)?)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let catalog = doc.catalog()?.clone();
+    base.max_id = doc.max_id;
+    base.objects.append(&mut doc.objects);
+
+    let root = base.catalog()?.get(b"Pages")?.as_reference()?;
+    for (page, attributes) in pages.iter().zip(own) {
+        let dict = base.get_object_mut(*page)?.as_dict_mut()?;
+        for (key, value) in attributes {
+            if !dict.has(&key) {
+                dict.set(key, value);
+            }
+        }
+        if !dict.has(b"Rotate") {
+            dict.set("Rotate", 0);
+        }
+        dict.set("Parent", root);
+    }
+    let tree = base.get_object_mut(root)?.as_dict_mut()?;
+    let count = tree.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    tree.set("Count", count + pages.len() as i64);
+    match tree.get_mut(b"Kids") {
+        Ok(Object::Array(kids)) => kids.extend(pages.iter().map(|page| Object::from(*page))),
+        _ => tree.set(
+            "Kids",
+            pages
+                .iter()
+                .map(|page| Object::from(*page))
+                .collect::<Vec<_>>(),
+        ),
+    }
+
+    if let Ok(outline) = catalog.get(b"Outlines").and_then(Object::as_reference) {
+        append_outline(base, outline)?;
+    }
+    let dests = catalog
+        .get(b"Names")
+        .and_then(|names| base.dereference(names))
+        .and_then(|(_, names)| names.as_dict())
+        .and_then(|names| names.get(b"Dests"))
+        .and_then(|dests| base.dereference(dests))
+        .and_then(|(_, dests)| dests.as_dict())
+        .and_then(|dests| dests.get(b"Names"))
+        .and_then(Object::as_array)
+        .cloned();
+    if let Ok(dests) = dests {
+        append_named_dests(base, dests)?;
+    }
+    let layers = catalog
+        .get(b"OCProperties")
+        .and_then(|properties| base.dereference(properties))
+        .and_then(|(_, properties)| properties.as_dict())
+        .and_then(|properties| properties.get(b"OCGs"))
+        .and_then(Object::as_array)
+        .cloned();
+    for layer in layers.iter().flatten() {
+        layers::register(base, layer.as_reference()?)?;
+    }
+    // the catalog, page tree root and information of `doc` are left over
+    base.prune_objects();
+    Ok(())
+}
+
+/// Puts the top level entries of the outline `outline` after those of the
+/// outline of `doc`, or makes it the outline of `doc` if it has none
+fn append_outline(doc: &mut lopdf::Document, outline: ObjectId) -> lopdf::Result<()> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let existing = doc
+        .get_dictionary(root)?
+        .get(b"Outlines")
+        .and_then(Object::as_reference);
+    let existing = match existing {
+        Ok(existing) => existing,
+        Err(_) => {
+            let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+            catalog.set("Outlines", outline);
+            catalog.set("PageMode", "UseOutlines");
+            return Ok(());
+        }
+    };
+    let added = doc.get_dictionary(outline)?.clone();
+    let (first, last) = match (
+        added.get(b"First").and_then(Object::as_reference),
+        added.get(b"Last").and_then(Object::as_reference),
+    ) {
+        (Ok(first), Ok(last)) => (first, last),
+        _ => return Ok(()),
+    };
+    let mut item = Some(first);
+    while let Some(id) = item {
+        let dict = doc.get_object_mut(id)?.as_dict_mut()?;
+        dict.set("Parent", existing);
+        item = dict.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    let previous = doc
+        .get_dictionary(existing)?
+        .get(b"Last")
+        .and_then(Object::as_reference);
+    match previous {
+        Ok(previous) => {
+            doc.get_object_mut(previous)?
+                .as_dict_mut()?
+                .set("Next", first);
+            doc.get_object_mut(first)?
+                .as_dict_mut()?
+                .set("Prev", previous);
+        }
+        Err(_) => doc
+            .get_object_mut(existing)?
+            .as_dict_mut()?
+            .set("First", first),
+    }
+    let added_count = added.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    let tree = doc.get_object_mut(existing)?.as_dict_mut()?;
+    tree.set("Last", last);
+    let count = tree.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+    tree.set("Count", count.abs() + added_count.abs());
+    Ok(())
+}
+
+/// Adds the name and destination pairs of a flat name tree to the named
+/// destinations of `doc`. Those of a `doc` with a name tree of several
+/// levels are kept as they are.
+fn append_named_dests(doc: &mut lopdf::Document, added: Vec<Object>) -> lopdf::Result<()> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let dests = names_mut(doc, root)?
+        .get(b"Dests")
+        .and_then(Object::as_reference)
+        .ok();
+    let tree = match dests {
+        Some(id) => doc.get_object_mut(id)?.as_dict_mut()?,
+        None => {
+            let names = names_mut(doc, root)?;
+            if !matches!(names.get(b"Dests"), Ok(Object::Dictionary(_))) {
+                names.set("Dests", Dictionary::new());
+            }
+            names.get_mut(b"Dests")?.as_dict_mut()?
+        }
+    };
+    if tree.has(b"Kids") {
+        return Ok(());
+    }
+    let mut names = match tree.get(b"Names") {
+        Ok(Object::Array(names)) => names.clone(),
+        _ => Vec::new(),
+    };
+    names.extend(added);
+    let mut pairs = names
+        .chunks_exact(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect::<Vec<_>>();
+    // name trees have to be sorted by key
+    pairs.sort_by(|a, b| a.0.as_str().ok().cmp(&b.0.as_str().ok()));
+    let names = pairs.into_iter().flat_map(|(name, dest)| [name, dest]);
+    tree.set("Names", names.collect::<Vec<_>>());
+    Ok(())
+}
+
+/// The names dictionary of the catalog `root`, added if it has none
+fn names_mut(doc: &mut lopdf::Document, root: ObjectId) -> lopdf::Result<&mut Dictionary> {
+    let id = match doc.get_dictionary(root)?.get(b"Names") {
+        Ok(Object::Reference(id)) => *id,
+        _ => root,
+    };
+    let dict = doc.get_object_mut(id)?.as_dict_mut()?;
+    if id != root {
+        return Ok(dict);
+    }
+    if !matches!(dict.get(b"Names"), Ok(Object::Dictionary(_))) {
+        dict.set("Names", Dictionary::new());
+    }
+    dict.get_mut(b"Names")?.as_dict_mut()
+}
@@ -0,0 +1,48 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+
+use crate::order;
+use crate::scratch::ScratchDir;
+use yet_another_imgs2pdf::zip;
+
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["cbz", "zip"];
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ARCHIVE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Extracts the images of `archive` into a scratch directory in the order
+/// they are stored, or by name with numbers in order when `by_name`. Entries
+/// that cannot be read are returned with the reason instead.
+pub fn extract(
+    archive: &Path,
+    by_name: bool,
+) -> io::Result<(ScratchDir, Vec<(String, io::Error)>)> {
+    let data = std::fs::read(archive)?;
+    let mut entries = zip::images(&data)?;
+    if by_name {
+        entries.sort_by(|a, b| order::natural_cmp(Path::new(&a.name), Path::new(&b.name)));
+    }
+
+    let mut pages = ScratchDir::new("archive", archive)?;
+    let mut failed = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let file = match zip::read(&data, entry) {
+            Ok(file) => file,
+            Err(e) => {
+                failed.push((entry.name.clone(), e));
+                continue;
+            }
+        };
+        // numbered so that entries of the same name in different folders
+        // stay apart, keeping the extension the format is told by
+        let name = entry.name.rsplit(['/', '\\']).next().unwrap_or_default();
+        let path = pages.dir().join(format!("{i:05}-{name}"));
+        std::fs::write(&path, file)?;
+        pages.paths.push(path);
+    }
+    Ok((pages, failed))
+}
@@ -0,0 +1,96 @@
+use printpdf::image_crate::{imageops::FilterType, DynamicImage, GrayImage};
+
+/// Pages are analysed at this width
+const SAMPLE_WIDTH: u32 = 300;
+/// Share of each edge ignored, where scans pick up shadows and borders
+const EDGE: f64 = 0.05;
+/// Pages with less ink than this count as blank
+const MIN_INK: f64 = 0.0005;
+/// Mostly blank pages have ink on at most this share of their rows
+const SPARSE_ROWS: f64 = 0.25;
+/// A heading line is at least this many times taller than a typical line
+const HEADING_SCALE: f64 = 2.0;
+/// Difference between the left and right margin still counted as centered
+const CENTER_TOLERANCE: f64 = 0.1;
+
+/// Whether a page looks like the opening of a chapter: a centered heading on
+/// an otherwise mostly blank page, or a first line set much larger than the
+/// text below it
+pub fn is_chapter_page(img: &DynamicImage) -> bool {
+    let thumb = img
+        .resize(SAMPLE_WIDTH, u32::MAX, FilterType::Triangle)
+        .to_luma8();
+    let ink = ink_mask(&thumb);
+    let (w, h) = thumb.dimensions();
+    let (x0, x1) = ((w as f64 * EDGE) as u32, (w as f64 * (1.0 - EDGE)) as u32);
+    let (y0, y1) = ((h as f64 * EDGE) as u32, (h as f64 * (1.0 - EDGE)) as u32);
+    if x1 <= x0 || y1 <= y0 {
+        return false;
+    }
+
+    let dark = |x: u32, y: u32| ink[(y * w + x) as usize];
+    let rows = (y0..y1)
+        .map(|y| (x0..x1).any(|x| dark(x, y)))
+        .collect::<Vec<_>>();
+    let total = (y0..y1)
+        .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+        .filter(|(x, y)| dark(*x, *y))
+        .count();
+    if (total as f64) < MIN_INK * ((x1 - x0) * (y1 - y0)) as f64 {
+        return false;
+    }
+
+    // runs of rows with ink are lines of text, as (first row, height)
+    let mut lines = Vec::new();
+    let mut start = None;
+    for (i, has_ink) in rows.iter().chain([&false]).enumerate() {
+        match (start, *has_ink) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                lines.push((s, i - s));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let (first, first_h) = lines[0];
+    let centered = |from: usize, to: usize| {
+        let ys = y0 + from as u32..y0 + to as u32;
+        let cols = (x0..x1)
+            .filter(|x| ys.clone().any(|y| dark(*x, y)))
+            .collect::<Vec<_>>();
+        let (left, right) = (cols[0] - x0, x1 - 1 - cols[cols.len() - 1]);
+        (left as f64 - right as f64).abs() < CENTER_TOLERANCE * (x1 - x0) as f64
+    };
+
+    let ink_rows = lines.iter().map(|(_, h)| h).sum::<usize>();
+    if (ink_rows as f64) < SPARSE_ROWS * rows.len() as f64 && centered(first, first + first_h) {
+        return true;
+    }
+    let mut heights = lines[1..].iter().map(|(_, h)| *h).collect::<Vec<_>>();
+    if heights.is_empty() {
+        return false;
+    }
+    heights.sort_unstable();
+    let typical = heights[heights.len() / 2] as f64;
+    first < rows.len() / 2
+        && first_h as f64 >= HEADING_SCALE * typical
+        && centered(first, first + first_h)
+}
+
+/// Pixels noticeably darker than the paper
+pub fn ink_mask(img: &GrayImage) -> Vec<bool> {
+    let mut histogram = [0usize; 256];
+    for p in img.pixels() {
+        histogram[p.0[0] as usize] += 1;
+    }
+    // the 90th percentile of brightness stands in for the paper
+    let mut seen = 0;
+    let limit = img.pixels().len() * 9 / 10;
+    let paper = (0..256).find(|v| {
+        seen += histogram[*v];
+        seen >= limit
+    });
+    let threshold = (paper.unwrap_or(255) as f64 * 0.6) as u8;
+    img.pixels().map(|p| p.0[0] < threshold).collect()
+}
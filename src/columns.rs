@@ -0,0 +1,95 @@
+use printpdf::image_crate::{imageops::FilterType, DynamicImage};
+
+use crate::chapters::ink_mask;
+
+/// Pages are analysed at this width
+const SAMPLE_WIDTH: u32 = 600;
+/// Share of each edge ignored, where scans pick up shadows and borders
+const EDGE: f64 = 0.05;
+/// Pages with less ink than this hold no columns worth splitting
+const MIN_INK: f64 = 0.002;
+/// A gutter is looked for this far, as a share of a column, around where it
+/// would be if the columns were equally wide
+const SEARCH: f64 = 0.25;
+/// Share of the rows a gutter may cross, for stray marks and rules
+const GUTTER_INK: f64 = 0.01;
+/// Narrowest gutter, as a share of the page width
+const MIN_GUTTER: f64 = 0.008;
+
+/// Splits a scanned page set in `columns` columns of text into one image per
+/// column, left to right, each with where it starts on the page. The page is
+/// kept whole when not every gutter can be found.
+pub fn split(img: DynamicImage, columns: usize) -> Vec<(u32, DynamicImage)> {
+    match gutters(&img, columns) {
+        Some(cuts) => {
+            let ends = cuts.iter().copied().chain([img.width()]);
+            let starts = [0].into_iter().chain(cuts.iter().copied());
+            starts
+                .zip(ends)
+                .map(|(x0, x1)| (x0, img.crop_imm(x0, 0, x1 - x0, img.height())))
+                .collect()
+        }
+        None => vec![(0, img)],
+    }
+}
+
+/// Where the page is cut between columns, in its pixels
+fn gutters(img: &DynamicImage, columns: usize) -> Option<Vec<u32>> {
+    if columns < 2 {
+        return None;
+    }
+    let thumb = img
+        .resize(SAMPLE_WIDTH, u32::MAX, FilterType::Triangle)
+        .to_luma8();
+    let ink = ink_mask(&thumb);
+    let (w, h) = thumb.dimensions();
+    let (y0, y1) = ((h as f64 * EDGE) as u32, (h as f64 * (1.0 - EDGE)) as u32);
+    let (x0, x1) = ((w as f64 * EDGE) as u32, (w as f64 * (1.0 - EDGE)) as u32);
+    if y1 <= y0 || x1 <= x0 {
+        return None;
+    }
+
+    // how many rows have ink in each pixel column
+    let profile = (0..w)
+        .map(|x| (y0..y1).filter(|y| ink[(y * w + x) as usize]).count())
+        .collect::<Vec<_>>();
+    let rows = (y1 - y0) as f64;
+    let total = profile[x0 as usize..x1 as usize].iter().sum::<usize>();
+    if (total as f64) < MIN_INK * rows * (x1 - x0) as f64 {
+        return None;
+    }
+
+    let clear = |x: usize| profile[x] as f64 <= GUTTER_INK * rows;
+    let column_w = (x1 - x0) as f64 / columns as f64;
+    let min_gutter = ((w as f64 * MIN_GUTTER).round() as usize).max(1);
+    let (mut cuts, mut previous) = (Vec::new(), x0 as usize);
+    for k in 1..columns {
+        let expected = x0 as f64 + column_w * k as f64;
+        let from = (expected - SEARCH * column_w).max(x0 as f64) as usize;
+        let to = ((expected + SEARCH * column_w) as usize).min(x1 as usize);
+        // the widest run of clear pixel columns in the window
+        let (mut best, mut start) = (None::<(usize, usize)>, None);
+        for x in from..=to {
+            match (start, x < to && clear(x)) {
+                (None, true) => start = Some(x),
+                (Some(s), false) => {
+                    if best.is_none_or(|(bs, be)| x - s > be - bs) {
+                        best = Some((s, x));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        let (s, e) = best.filter(|(s, e)| e - s >= min_gutter)?;
+        // text on both sides, not a margin or an empty half page
+        let has_ink = |r: std::ops::Range<usize>| profile[r].iter().any(|c| *c > 0);
+        if !has_ink(previous..s) || !has_ink(e..x1 as usize) {
+            return None;
+        }
+        previous = e;
+        let cut = (s + e) / 2;
+        cuts.push((cut as u64 * img.width() as u64 / w as u64) as u32);
+    }
+    Some(cuts)
+}
@@ -0,0 +1,517 @@
+use std::collections::HashSet;
+
+use printpdf::image_crate::{DynamicImage, GenericImageView};
+use printpdf::lopdf::{self, dictionary, Object};
+
+/// Most pixels looked at to tell what an image holds
+const SAMPLES: u32 = 1 << 16;
+/// Most distinct colors an image of flat colors has
+const FLAT_COLORS: usize = 256;
+/// Share of the pixels of a black and white image that may be neither,
+/// such as the smoothed edges of scanned text
+const BILEVEL_MIDTONES: f64 = 0.02;
+
+/// What an image looks like, which decides how it is best compressed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Content {
+    /// Black and white only, like scanned text, for CCITT G4
+    Bilevel,
+    /// A few flat colors, like a diagram or screenshot, kept lossless
+    Flat,
+    /// Anything else, for JPEG
+    Photo,
+}
+
+/// Tells what `img` holds from a grid of samples. `gray` is whether all of
+/// them are without color.
+pub fn classify(img: &DynamicImage) -> (Content, bool) {
+    let (w, h) = img.dimensions();
+    let step = ((w as u64 * h as u64) as f64 / SAMPLES as f64)
+        .sqrt()
+        .max(1.0) as u32;
+    let mut colors = HashSet::new();
+    let (mut gray, mut samples, mut midtones) = (true, 0, 0);
+    for y in (0..h).step_by(step as usize) {
+        for x in (0..w).step_by(step as usize) {
+            let [r, g, b, _] = img.get_pixel(x, y).0;
+            let (low, high) = (r.min(g).min(b), r.max(g).max(b));
+            gray &= high - low <= 4;
+            if (32..224).contains(&high) {
+                midtones += 1;
+            }
+            if colors.len() <= FLAT_COLORS {
+                colors.insert([r, g, b]);
+            }
+            samples += 1;
+        }
+    }
+    let content = if gray && midtones as f64 <= BILEVEL_MIDTONES * samples as f64 {
+        Content::Bilevel
+    } else if colors.len() <= FLAT_COLORS {
+        Content::Flat
+    } else {
+        Content::Photo
+    };
+    (content, gray)
+}
+
+/// Turns the uncompressed 1 bit images of `doc` into CCITT G4 ones
+pub fn encode_bilevel_images(doc: &mut lopdf::Document) -> lopdf::Result<()> {
+    for object in doc.objects.values_mut() {
+        let stream = match object {
+            Object::Stream(stream) => stream,
+            _ => continue,
+        };
+        let dict = &stream.dict;
+        let bilevel = dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Image")
+            && dict.get(b"BitsPerComponent").and_then(Object::as_i64).ok() == Some(1);
+        if !bilevel {
+            continue;
+        }
+        // printpdf deflates images in release builds
+        let packed = match dict.get(b"Filter") {
+            Err(_) => stream.content.clone(),
+            Ok(_) => match stream.decompressed_content() {
+                Ok(content) => content,
+                Err(_) => continue,
+            },
+        };
+        let width = dict.get(b"Width")?.as_i64()? as usize;
+        let height = dict.get(b"Height")?.as_i64()? as usize;
+        if packed.len() < width.div_ceil(8) * height {
+            continue;
+        }
+        stream.dict.set("Filter", "CCITTFaxDecode");
+        stream.dict.set(
+            "DecodeParms",
+            dictionary! {
+                "K" => -1,
+                "Columns" => width as i64,
+                "Rows" => height as i64,
+            },
+        );
+        stream.set_content(g4(&packed, width, height));
+    }
+    Ok(())
+}
+
+/// Bits written first to last, from the highest bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, (code, len): (u16, u8)) {
+        for i in (0..len).rev() {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            if code >> i & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+            }
+            self.used = (self.used + 1) % 8;
+        }
+    }
+
+    /// The codes of a run of `len` pixels of a color
+    fn run(&mut self, mut len: usize, black: bool) {
+        let (terminating, makeup) = match black {
+            false => (&WHITE_TERMINATING, &WHITE_MAKEUP),
+            true => (&BLACK_TERMINATING, &BLACK_MAKEUP),
+        };
+        while len >= 2560 {
+            self.write(EXTENDED_MAKEUP[EXTENDED_MAKEUP.len() - 1]);
+            len -= 2560;
+        }
+        if len >= 64 {
+            let step = len / 64;
+            match step {
+                ..=27 => self.write(makeup[step - 1]),
+                _ => self.write(EXTENDED_MAKEUP[step - 28]),
+            }
+            len %= 64;
+        }
+        self.write(terminating[len]);
+    }
+}
+
+const PASS: (u16, u8) = (0b0001, 4);
+const HORIZONTAL: (u16, u8) = (0b001, 3);
+/// Codes of a change `-3` to `3` pixels from that on the line above
+const VERTICAL: [(u16, u8); 7] = [
+    (0b0000010, 7),
+    (0b000010, 6),
+    (0b010, 3),
+    (0b1, 1),
+    (0b011, 3),
+    (0b000011, 6),
+    (0b0000011, 7),
+];
+const END_OF_LINE: (u16, u8) = (0b0000_0000_0001, 12);
+
+/// `packed` rows of `width` pixels, a bit each and 1 for white, encoded as
+/// CCITT Group 4 (T.6) with the end of block code
+fn g4(packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let row_len = width.div_ceil(8);
+    let mut out = BitWriter::default();
+    // the imaginary line above the first is white
+    let mut above = vec![false; width];
+    let mut line = vec![false; width];
+    for y in 0..height {
+        let row = &packed[y * row_len..(y + 1) * row_len];
+        for (x, black) in line.iter_mut().enumerate() {
+            *black = row[x / 8] & 0x80 >> (x % 8) == 0;
+        }
+        encode_line(&mut out, &line, &above);
+        std::mem::swap(&mut line, &mut above);
+    }
+    out.write(END_OF_LINE);
+    out.write(END_OF_LINE);
+    out.bytes
+}
+
+/// Whether a new color starts at `x` of `line`, which starts after white
+fn changes_at(line: &[bool], x: usize) -> bool {
+    match x {
+        0 => line[0],
+        _ => line[x] != line[x - 1],
+    }
+}
+
+/// The first pixel from `from` on with a color other than `black`, or the
+/// width
+fn next_other(line: &[bool], from: usize, black: bool) -> usize {
+    (from..line.len())
+        .find(|x| line[*x] != black)
+        .unwrap_or(line.len())
+}
+
+fn encode_line(out: &mut BitWriter, line: &[bool], above: &[bool]) {
+    let width = line.len();
+    // a0 starts just before the line, on white
+    let (mut a0, mut black): (Option<usize>, bool) = (None, false);
+    loop {
+        let start = a0.map_or(0, |a0| a0 + 1);
+        let a1 = next_other(line, start, black);
+        let b1 = (start..width)
+            .find(|x| changes_at(above, *x) && above[*x] != black)
+            .unwrap_or(width);
+        let b2 = (b1 + 1..width)
+            .find(|x| changes_at(above, *x))
+            .unwrap_or(width);
+        if b2 < a1 {
+            out.write(PASS);
+            a0 = Some(b2);
+        } else if a1.abs_diff(b1) <= 3 {
+            out.write(VERTICAL[(a1 as isize - b1 as isize + 3) as usize]);
+            a0 = Some(a1);
+            black = !black;
+        } else {
+            let a2 = next_other(line, a1 + 1, !black);
+            out.write(HORIZONTAL);
+            out.run(a1 - a0.unwrap_or(0), black);
+            out.run(a2 - a1, !black);
+            a0 = Some(a2);
+        }
+        if a0.is_some_and(|a0| a0 >= width) {
+            return;
+        }
+    }
+}
+
+/// Codes of white runs of 0 to 63 pixels, as bits and their length
+const WHITE_TERMINATING: [(u16, u8); 64] = [
+    (0b00110101, 8),
+    (0b000111, 6),
+    (0b0111, 4),
+    (0b1000, 4),
+    (0b1011, 4),
+    (0b1100, 4),
+    (0b1110, 4),
+    (0b1111, 4),
+    (0b10011, 5),
+    (0b10100, 5),
+    (0b00111, 5),
+    (0b01000, 5),
+    (0b001000, 6),
+    (0b000011, 6),
+    (0b110100, 6),
+    (0b110101, 6),
+    (0b101010, 6),
+    (0b101011, 6),
+    (0b0100111, 7),
+    (0b0001100, 7),
+    (0b0001000, 7),
+    (0b0010111, 7),
+    (0b0000011, 7),
+    (0b0000100, 7),
+    (0b0101000, 7),
+    (0b0101011, 7),
+    (0b0010011, 7),
+    (0b0100100, 7),
+    (0b0011000, 7),
+    (0b00000010, 8),
+    (0b00000011, 8),
+    (0b00011010, 8),
+    (0b00011011, 8),
+    (0b00010010, 8),
+    (0b00010011, 8),
+    (0b00010100, 8),
+    (0b00010101, 8),
+    (0b00010110, 8),
+    (0b00010111, 8),
+    (0b00101000, 8),
+    (0b00101001, 8),
+    (0b00101010, 8),
+    (0b00101011, 8),
+    (0b00101100, 8),
+    (0b00101101, 8),
+    (0b00000100, 8),
+    (0b00000101, 8),
+    (0b00001010, 8),
+    (0b00001011, 8),
+    (0b01010010, 8),
+    (0b01010011, 8),
+    (0b01010100, 8),
+    (0b01010101, 8),
+    (0b00100100, 8),
+    (0b00100101, 8),
+    (0b01011000, 8),
+    (0b01011001, 8),
+    (0b01011010, 8),
+    (0b01011011, 8),
+    (0b01001010, 8),
+    (0b01001011, 8),
+    (0b00110010, 8),
+    (0b00110011, 8),
+    (0b00110100, 8),
+];
+/// Codes of white runs of 64 to 1728 pixels, in steps of 64
+const WHITE_MAKEUP: [(u16, u8); 27] = [
+    (0b11011, 5),
+    (0b10010, 5),
+    (0b010111, 6),
+    (0b0110111, 7),
+    (0b00110110, 8),
+    (0b00110111, 8),
+    (0b01100100, 8),
+    (0b01100101, 8),
+    (0b01101000, 8),
+    (0b01100111, 8),
+    (0b011001100, 9),
+    (0b011001101, 9),
+    (0b011010010, 9),
+    (0b011010011, 9),
+    (0b011010100, 9),
+    (0b011010101, 9),
+    (0b011010110, 9),
+    (0b011010111, 9),
+    (0b011011000, 9),
+    (0b011011001, 9),
+    (0b011011010, 9),
+    (0b011011011, 9),
+    (0b010011000, 9),
+    (0b010011001, 9),
+    (0b010011010, 9),
+    (0b011000, 6),
+    (0b010011011, 9),
+];
+/// Codes of black runs of 0 to 63 pixels
+const BLACK_TERMINATING: [(u16, u8); 64] = [
+    (0b0000110111, 10),
+    (0b010, 3),
+    (0b11, 2),
+    (0b10, 2),
+    (0b011, 3),
+    (0b0011, 4),
+    (0b0010, 4),
+    (0b00011, 5),
+    (0b000101, 6),
+    (0b000100, 6),
+    (0b0000100, 7),
+    (0b0000101, 7),
+    (0b0000111, 7),
+    (0b00000100, 8),
+    (0b00000111, 8),
+    (0b000011000, 9),
+    (0b0000010111, 10),
+    (0b0000011000, 10),
+    (0b0000001000, 10),
+    (0b00001100111, 11),
+    (0b00001101000, 11),
+    (0b00001101100, 11),
+    (0b00000110111, 11),
+    (0b00000101000, 11),
+    (0b00000010111, 11),
+    (0b00000011000, 11),
+    (0b000011001010, 12),
+    (0b000011001011, 12),
+    (0b000011001100, 12),
+    (0b000011001101, 12),
+    (0b000001101000, 12),
+    (0b000001101001, 12),
+    (0b000001101010, 12),
+    (0b000001101011, 12),
+    (0b000011010010, 12),
+    (0b000011010011, 12),
+    (0b000011010100, 12),
+    (0b000011010101, 12),
+    (0b000011010110, 12),
+    (0b000011010111, 12),
+    (0b000001101100, 12),
+    (0b000001101101, 12),
+    (0b000011011010, 12),
+    (0b000011011011, 12),
+    (0b000001010100, 12),
+    (0b000001010101, 12),
+    (0b000001010110, 12),
+    (0b000001010111, 12),
+    (0b000001100100, 12),
+    (0b000001100101, 12),
+    (0b000001010010, 12),
+    (0b000001010011, 12),
+    (0b000000100100, 12),
+    (0b000000110111, 12),
+    (0b000000111000, 12),
+    (0b000000100111, 12),
+    (0b000000101000, 12),
+    (0b000001011000, 12),
+    (0b000001011001, 12),
+    (0b000000101011, 12),
+    (0b000000101100, 12),
+    (0b000001011010, 12),
+    (0b000001100110, 12),
+    (0b000001100111, 12),
+];
+/// Codes of black runs of 64 to 1728 pixels, in steps of 64
+const BLACK_MAKEUP: [(u16, u8); 27] = [
+    (0b0000001111, 10),
+    (0b000011001000, 12),
+    (0b000011001001, 12),
+    (0b000001011011, 12),
+    (0b000000110011, 12),
+    (0b000000110100, 12),
+    (0b000000110101, 12),
+    (0b0000001101100, 13),
+    (0b0000001101101, 13),
+    (0b0000001001010, 13),
+    (0b0000001001011, 13),
+    (0b0000001001100, 13),
+    (0b0000001001101, 13),
+    (0b0000001110010, 13),
+    (0b0000001110011, 13),
+    (0b0000001110100, 13),
+    (0b0000001110101, 13),
+    (0b0000001110110, 13),
+    (0b0000001110111, 13),
+    (0b0000001010010, 13),
+    (0b0000001010011, 13),
+    (0b0000001010100, 13),
+    (0b0000001010101, 13),
+    (0b0000001011010, 13),
+    (0b0000001011011, 13),
+    (0b0000001100100, 13),
+    (0b0000001100101, 13),
+];
+/// Codes of runs of either color of 1792 to 2560 pixels, in steps of 64
+const EXTENDED_MAKEUP: [(u16, u8); 13] = [
+    (0b00000001000, 11),
+    (0b00000001100, 11),
+    (0b00000001101, 11),
+    (0b000000010010, 12),
+    (0b000000010011, 12),
+    (0b000000010100, 12),
+    (0b000000010101, 12),
+    (0b000000010110, 12),
+    (0b000000010111, 12),
+    (0b000000011100, 12),
+    (0b000000011101, 12),
+    (0b000000011110, 12),
+    (0b000000011111, 12),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of `width` pixels, black where `black` says, packed as the
+    /// images of printpdf are
+    fn packed(width: usize, rows: &[&dyn Fn(usize) -> bool]) -> Vec<u8> {
+        let row_len = width.div_ceil(8);
+        let mut packed = vec![0xff; row_len * rows.len()];
+        for (y, black) in rows.iter().enumerate() {
+            for x in (0..width).filter(|x| black(*x)) {
+                packed[y * row_len + x / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+        packed
+    }
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // the expected codes are those libtiff writes for the same rows
+
+    #[test]
+    fn lines_are_coded_by_mode() {
+        const WIDTH: usize = 3000;
+        let rows: [&dyn Fn(usize) -> bool; 10] = [
+            // horizontal, with a white run over 64 and a black one over 2560
+            &|x| (100..2900).contains(&x),
+            // pass, then vertical at the end of the line
+            &|_| false,
+            // horizontal, with a white run over 2560
+            &|x| x >= 2700,
+            // vertical, 2 left, 3 right, 1 left, 1 right, 2 right, 3 left, 0
+            &|x| x >= 2698,
+            &|x| x >= 2701,
+            &|x| x >= 2700,
+            &|x| x >= 2701,
+            &|x| x >= 2703,
+            &|x| x >= 2700,
+            &|x| x >= 2700,
+        ];
+        assert_eq!(
+            g4(&packed(WIDTH, &rows), WIDTH, rows.len()),
+            unhex("3b1501f0c90648c80fc8805b0540a0eae1c170010010")
+        );
+    }
+
+    #[test]
+    fn every_run_length_has_its_code() {
+        // white and black runs of every length with a terminating code, then
+        // of every length with a makeup code
+        let mut runs = (0..64).flat_map(|n| [n, n + 1]).collect::<Vec<_>>();
+        runs.extend((1..=40).flat_map(|k| [64 * k + k % 64, 64 * k + k * 7 % 64]));
+        let black = runs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, run)| std::iter::repeat(i % 2 == 1).take(*run))
+            .collect::<Vec<_>>();
+        let width = black.len();
+        let expected = concat!(
+            "26a88f978c3366708f0cf8a6621a04270a501c900843073a060ea0b9a8183581",
+            "053867230341101b09706e418282100b950060ab0ca24c32ca40cc2603348103",
+            "41030692340d446c1ac890691130d32281a84543548b06b1170d72500d84a41b",
+            "49506d12b0db2580a84b41548202b1050572140c842c194a90291530532a8048",
+            "5540dc9201c1250272b0050564160ad02c95b02b29405852c168990331330672",
+            "680786e76381e33270c8072bc06486c2dec16c3309b6019869937e0340da2c9e",
+            "06a0ca596606c0282d14036833967381288b31004b042cd200981a0b48302686",
+            "5969e80720d22d4d40e60da5ab501d0190b5ab03a81396ba703b03316c0c03b9",
+            "96c88029072da2e0530ce5b60c0a8194530100aa0d6532a00b40d8534ac0b60a",
+            "e582606403829b4806505a2020c00864060100c0a4068180d0208090d0090182",
+            "026360260d440504805035c80a8980a82b101614016037202e2a02e058407058",
+            "072203a2e03a1080f1400f030407ca407c0b80080080",
+        );
+        assert_eq!(
+            g4(&packed(width, &[&|x| black[x]]), width, 1),
+            unhex(expected)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use printpdf::image_crate;
+
+/// Words in a file name that mark the front cover
+const COVER_WORDS: [&str; 3] = ["cover", "front", "cvr"];
+/// How far the size of the cover has to be from that of the other pages
+const ODD_SIZE: f64 = 0.08;
+
+/// Whether the name of `path` says it is the front cover, as in
+/// `000-cover.jpg` or `Cover01.png`, but not `back cover.jpg`
+fn named_cover(path: &Path) -> bool {
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase();
+    let words = stem
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.trim_matches(|c: char| c.is_ascii_digit()))
+        .collect::<Vec<_>>();
+    words.iter().any(|w| COVER_WORDS.contains(w)) && !words.contains(&"back")
+}
+
+/// Which of `images` is the cover: the first one named like a front cover,
+/// or else the only portrait image sized unlike the rest, as covers are
+/// often scanned or drawn apart from the pages
+pub fn find(images: &[PathBuf]) -> Option<usize> {
+    if let Some(i) = images.iter().position(|p| named_cover(p)) {
+        return Some(i);
+    }
+    let sizes = images
+        .iter()
+        .map(|p| image_crate::image_dimensions(p).ok())
+        .collect::<Vec<_>>();
+    let portrait = |size: &Option<(u32, u32)>| size.filter(|(w, h)| w < h);
+    let (mut widths, mut heights): (Vec<_>, Vec<_>) = sizes.iter().filter_map(portrait).unzip();
+    if widths.len() < 3 {
+        return None;
+    }
+    widths.sort_unstable();
+    heights.sort_unstable();
+    let median = (widths[widths.len() / 2], heights[heights.len() / 2]);
+    let off = |len: u32, median: u32| (len as f64 / median as f64 - 1.0).abs() > ODD_SIZE;
+    let mut odd = sizes.iter().enumerate().filter(|(_, size)| {
+        portrait(size).is_some_and(|(w, h)| off(w, median.0) || off(h, median.1))
+    });
+    match (odd.next(), odd.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// Moves the cover of `images` in front of the rest, returning whether one
+/// was found
+pub fn move_first(images: &mut [PathBuf]) -> bool {
+    match find(images) {
+        Some(i) => {
+            images[..=i].rotate_right(1);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_are_told_by_name() {
+        for name in [
+            "cover.jpg",
+            "000-Cover.png",
+            "Cover01.jpg",
+            "vol 1 (front).png",
+        ] {
+            assert!(named_cover(Path::new(name)), "{name}");
+        }
+        for name in ["001.jpg", "back cover.jpg", "discover.png", "covered.png"] {
+            assert!(!named_cover(Path::new(name)), "{name}");
+        }
+
+        let mut images = ["001.jpg", "002.jpg", "zz-cover.jpg", "003.jpg"].map(PathBuf::from);
+        assert!(move_first(&mut images));
+        assert_eq!(
+            images,
+            ["zz-cover.jpg", "001.jpg", "002.jpg", "003.jpg"].map(PathBuf::from)
+        );
+    }
+}
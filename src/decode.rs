@@ -0,0 +1,479 @@
+use printpdf::image_crate::{self, imageops::FilterType, DynamicImage, ImageResult};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+#[cfg(feature = "heif")]
+use crate::heif;
+#[cfg(feature = "isolate")]
+use crate::isolate;
+#[cfg(feature = "ocr")]
+use crate::ocr;
+use crate::{columns, downscale, enhance, metadata};
+use crate::{CancelToken, Preset, Rotation};
+
+/// Contrast boost for e-ink, which shows mid grays washed out
+const E_INK_CONTRAST: f32 = 20.0;
+
+/// How inputs are decoded, cleaned up and scaled before they are laid out
+#[derive(Clone, Copy)]
+pub struct Decoder {
+    pub scale: (u32, u32),
+    pub scale_percent: Option<f64>,
+    pub rotation: Rotation,
+    pub preset: Option<Preset>,
+    pub dewarp: bool,
+    /// Grays out and raises the contrast for e-ink screens
+    pub e_ink: bool,
+    /// Splits pages set in this many columns into a page per column
+    pub columns: usize,
+    /// Percentages cut off the top, right, bottom and left edge
+    pub trim: [f64; 4],
+    /// Keeps fine print legible when downscaling
+    pub protect_text: bool,
+    /// Turns inputs without an EXIF orientation upright by the direction of
+    /// their text
+    #[cfg(feature = "ocr")]
+    pub rotate_by_text: bool,
+    /// How long decoding a single input may take before it is skipped
+    pub timeout: Option<Duration>,
+    /// Decode in a child process held to these limits
+    #[cfg(feature = "isolate")]
+    pub isolate: Option<isolate::Limits>,
+}
+
+/// A decoded input ready to be laid out
+pub struct Prepared {
+    pub img: DynamicImage,
+    /// Width of the input before it was scaled
+    pub source_w: u32,
+    /// Where the image starts in the input, in its pixels, when it was
+    /// trimmed or split off
+    pub origin: (u32, u32),
+    /// The baseline JPEG the image was decoded from, when nothing changed
+    /// its pixels and the file can be embedded as it is
+    pub jpeg: Option<Jpeg>,
+}
+
+/// A JPEG file kept to be embedded without decoding and encoding it again
+pub struct Jpeg {
+    pub data: Vec<u8>,
+    /// 1 for grayscale, 3 for color
+    pub components: u8,
+}
+
+/// Decodes the file at `path`, handing HEIC, HEIF and AVIF images to
+/// libheif
+pub fn open_file(path: &Path) -> ImageResult<DynamicImage> {
+    #[cfg(feature = "heif")]
+    if heif::is_heif(path) {
+        return heif::decode(path);
+    }
+    image_crate::open(path)
+}
+
+impl Decoder {
+    /// Decodes `image` and turns it upright, giving up after `timeout`. A
+    /// decoder that was given up on keeps running in the background until
+    /// the run ends.
+    pub fn open(&self, image: &Path) -> ImageResult<DynamicImage> {
+        Ok(self.open_upright(image)?.0)
+    }
+
+    /// Same as [`Decoder::open`], also telling whether the direction of the
+    /// text is what turned the image
+    fn open_upright(&self, image: &Path) -> ImageResult<(DynamicImage, bool)> {
+        let img = self.read(image)?;
+        Ok(self.upright(img, || {
+            metadata::exif_orientation(&std::fs::read(image).ok()?)
+        }))
+    }
+
+    /// Decodes `data`, the file of an input that is not on disk, and turns
+    /// it upright. There is no timeout or isolation for these.
+    fn open_data(&self, data: &[u8]) -> ImageResult<(DynamicImage, bool)> {
+        #[cfg(feature = "heif")]
+        if heif::is_heif_data(data) {
+            let img = heif::decode_data(data)?;
+            return Ok(self.upright(img, || None));
+        }
+        let img = image_crate::io::Reader::new(Cursor::new(data))
+            .with_guessed_format()?
+            .decode()?;
+        Ok(self.upright(img, || metadata::exif_orientation(data)))
+    }
+
+    /// `img` turned as set, asking `exif` for the EXIF orientation of the
+    /// input when it is followed. Inputs without one are turned by the
+    /// direction of their text when that is detected, which is told apart.
+    fn upright(
+        &self,
+        img: DynamicImage,
+        exif: impl FnOnce() -> Option<u16>,
+    ) -> (DynamicImage, bool) {
+        let exif = match self.rotation {
+            Rotation::Auto => exif(),
+            _ => None,
+        };
+        let img = oriented(img, self.orientation(exif));
+        #[cfg(feature = "ocr")]
+        if self.rotate_by_text && self.rotation == Rotation::Auto && exif.is_none() {
+            // tesseract says how far to turn clockwise, as EXIF does
+            let orientation = match ocr::text_rotation(&img) {
+                Ok(90) => 6,
+                Ok(180) => 3,
+                Ok(270) => 8,
+                _ => return (img, false),
+            };
+            return (oriented(img, orientation), true);
+        }
+        (img, false)
+    }
+
+    /// The EXIF orientation to turn inputs by, given that of the input when
+    /// it is followed
+    fn orientation(&self, exif: Option<u16>) -> u16 {
+        match self.rotation {
+            Rotation::Auto => exif.unwrap_or(1),
+            Rotation::None => 1,
+            Rotation::Cw90 => 6,
+            Rotation::Cw180 => 3,
+            Rotation::Cw270 => 8,
+        }
+    }
+
+    fn read(&self, image: &Path) -> ImageResult<DynamicImage> {
+        #[cfg(feature = "isolate")]
+        if let Some(limits) = self.isolate {
+            return isolate::decode(image, limits, self.timeout);
+        }
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return open_file(image),
+        };
+        let (tx, rx) = mpsc::channel();
+        let path = image.to_path_buf();
+        std::thread::spawn(move || tx.send(open_file(&path)));
+        match rx.recv_timeout(timeout) {
+            Ok(decoded) => decoded,
+            Err(_) => Err(image_crate::ImageError::IoError(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("decoding took longer than {:?}", timeout),
+            ))),
+        }
+    }
+
+    /// Applies the preset, dewarping, trimming and e-ink tuning to `img`,
+    /// returning where what is left starts in the input
+    fn clean(&self, mut img: DynamicImage) -> (DynamicImage, (u32, u32)) {
+        let mut origin = (0, 0);
+        if self.preset == Some(Preset::Whiteboard) {
+            img = enhance::whiteboard(img);
+        } else if self.dewarp {
+            img = enhance::dewarp(img);
+        }
+        if self.trim != [0.0; 4] {
+            (img, origin) = trimmed(&img, self.trim);
+        }
+        if self.e_ink {
+            img = img.grayscale().adjust_contrast(E_INK_CONTRAST);
+        }
+        (img, origin)
+    }
+
+    /// `img` at `percent` of its size, or else scaled as set for all inputs
+    pub fn scaled(&self, img: &DynamicImage, percent: Option<f64>) -> DynamicImage {
+        let filter = FilterType::Lanczos3;
+        let resized = match percent.or(self.scale_percent) {
+            Some(percent) => {
+                let size = |len: u32| ((len as f64 * percent / 100.0).round() as u32).max(1);
+                let (w, h) = (size(img.width()), size(img.height()));
+                if (w, h) == (img.width(), img.height()) {
+                    return img.clone();
+                }
+                img.resize_exact(w, h, filter)
+            }
+            None => img.resize(self.scale.0, self.scale.1, filter),
+        };
+        if self.protect_text {
+            return downscale::keep_text(img, resized);
+        }
+        resized
+    }
+
+    /// Same as [`Self::scaled`] for a stitched scroll, which is fit to the
+    /// width alone, as fitting its whole length would leave it too narrow to
+    /// read
+    pub fn scaled_to_width(&self, img: &DynamicImage, percent: Option<f64>) -> DynamicImage {
+        if percent.or(self.scale_percent).is_some() {
+            return self.scaled(img, percent);
+        }
+        let width = self.scale.0;
+        let height = (img.height() as f64 * width as f64 / img.width() as f64).round() as u32;
+        let resized = img.resize_exact(width, height.max(1), FilterType::Lanczos3);
+        if self.protect_text {
+            return downscale::keep_text(img, resized);
+        }
+        resized
+    }
+
+    /// Decodes `image` into its pages, one per column when columns are
+    /// split, scaling them to `percent` when `resize` is set
+    pub fn prepare(
+        &self,
+        image: &Path,
+        resize: bool,
+        percent: Option<f64>,
+    ) -> ImageResult<Vec<Prepared>> {
+        let (img, by_text) = self.open_upright(image)?;
+        let mut pages = self.pages(self.clean(img), resize, percent);
+        if !by_text {
+            self.keep_jpeg(&mut pages, resize, || std::fs::read(image).ok());
+        }
+        Ok(pages)
+    }
+
+    /// Same as [`Decoder::prepare`] for `data`, the file of an input that is
+    /// not on disk
+    pub fn prepare_data(
+        &self,
+        data: &[u8],
+        resize: bool,
+        percent: Option<f64>,
+    ) -> ImageResult<Vec<Prepared>> {
+        let (img, by_text) = self.open_data(data)?;
+        let mut pages = self.pages(self.clean(img), resize, percent);
+        if !by_text {
+            self.keep_jpeg(&mut pages, resize, || Some(data.to_vec()));
+        }
+        Ok(pages)
+    }
+
+    /// The pages of a cleaned up input starting at `(x, y)` in it
+    fn pages(
+        &self,
+        (img, (x, y)): (DynamicImage, (u32, u32)),
+        resize: bool,
+        percent: Option<f64>,
+    ) -> Vec<Prepared> {
+        columns::split(img, self.columns)
+            .into_iter()
+            .map(|(column_x, img)| {
+                let source_w = img.width();
+                let img = if resize {
+                    self.scaled(&img, percent)
+                } else {
+                    img
+                };
+                Prepared {
+                    img,
+                    source_w,
+                    origin: (x + column_x, y),
+                    jpeg: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Keeps the file of the input, as read by `file`, with its page when it
+    /// is a baseline JPEG that decoding left as it is
+    fn keep_jpeg(
+        &self,
+        pages: &mut [Prepared],
+        resize: bool,
+        file: impl FnOnce() -> Option<Vec<u8>>,
+    ) {
+        // only a whole input at its own size can be embedded as it is
+        if let [page] = pages {
+            if resize && page.img.width() == page.source_w {
+                page.jpeg = self.passthrough(file);
+            }
+        }
+    }
+
+    fn passthrough(&self, file: impl FnOnce() -> Option<Vec<u8>>) -> Option<Jpeg> {
+        let untouched = self.preset != Some(Preset::Whiteboard)
+            && !self.dewarp
+            && self.trim == [0.0; 4]
+            && !self.e_ink;
+        if !untouched {
+            return None;
+        }
+        let data = file()?;
+        let upright = match self.rotation {
+            Rotation::Auto => metadata::exif_orientation(&data).is_none_or(|o| o == 1),
+            Rotation::None => true,
+            _ => false,
+        };
+        let components = baseline_jpeg_components(&data).filter(|_| upright)?;
+        Some(Jpeg { data, components })
+    }
+}
+
+/// The number of components of a baseline JPEG in grayscale or RGB, the
+/// kinds every PDF reader shows as they are
+fn baseline_jpeg_components(file: &[u8]) -> Option<u8> {
+    if !file.starts_with(b"\xff\xd8") {
+        return None;
+    }
+    let mut i = 2;
+    while let [0xff, marker, hi, lo, ..] = *file.get(i..)? {
+        let len = u16::from_be_bytes([hi, lo]) as usize;
+        match marker {
+            // baseline and extended sequential frames
+            0xc0 | 0xc1 => {
+                let (precision, components) = (*file.get(i + 4)?, *file.get(i + 9)?);
+                let plain = precision == 8 && matches!(components, 1 | 3);
+                return plain.then_some(components);
+            }
+            // progressive, lossless and arithmetic coded frames
+            0xc2..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => return None,
+            0xda => return None,
+            _ => i += 2 + len,
+        }
+    }
+    None
+}
+
+/// `img` turned and mirrored as the EXIF `orientation` says it has to be
+fn oriented(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// `img` without the given percentages of its top, right, bottom and left,
+/// and where what is left starts
+fn trimmed(img: &DynamicImage, [top, right, bottom, left]: [f64; 4]) -> (DynamicImage, (u32, u32)) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let x = (w * left / 100.0).round() as u32;
+    let y = (h * top / 100.0).round() as u32;
+    let cw = ((w * (100.0 - left - right) / 100.0).round() as u32).max(1);
+    let ch = ((h * (100.0 - top - bottom) / 100.0).round() as u32).max(1);
+    (img.crop_imm(x, y, cw, ch), (x, y))
+}
+
+/// Decodes queued inputs on worker threads ahead of the page being laid out,
+/// handing them out in the order they were queued. At most twice as many
+/// inputs as there are workers are held decoded at a time.
+pub struct Pipeline {
+    shared: Arc<Shared>,
+    results: Receiver<(usize, ImageResult<Vec<Prepared>>)>,
+    received: BTreeMap<usize, ImageResult<Vec<Prepared>>>,
+    /// Inputs not yet taken, with their place in the queue
+    waiting: VecDeque<(usize, PathBuf)>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    wake: Condvar,
+}
+
+struct State {
+    todo: VecDeque<(usize, PathBuf, Option<f64>)>,
+    /// Inputs decoded or being decoded but not taken yet
+    ahead: usize,
+    stopped: bool,
+}
+
+impl Pipeline {
+    /// Starts `jobs` workers on `inputs`, each with the percentage it is
+    /// scaled to of its own. With `resize` unset the inputs are only decoded,
+    /// for what has to be scaled later. Workers stop taking inputs once
+    /// `cancel` is cancelled.
+    pub fn start(
+        decoder: Decoder,
+        jobs: usize,
+        resize: bool,
+        inputs: Vec<(PathBuf, Option<f64>)>,
+        cancel: CancelToken,
+    ) -> Pipeline {
+        let waiting = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, (path, _))| (i, path.clone()))
+            .collect();
+        let todo = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (path, percent))| (i, path, percent))
+            .collect();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                todo,
+                ahead: 0,
+                stopped: false,
+            }),
+            wake: Condvar::new(),
+        });
+        let (tx, results) = mpsc::channel();
+        let limit = jobs * 2;
+        for _ in 0..jobs {
+            let (shared, tx, cancel) = (Arc::clone(&shared), tx.clone(), cancel.clone());
+            std::thread::spawn(move || loop {
+                if cancel.is_cancelled() {
+                    return;
+                }
+                let (index, path, percent) = {
+                    let mut state = shared.state.lock().unwrap();
+                    while !state.stopped && !state.todo.is_empty() && state.ahead >= limit {
+                        state = shared.wake.wait(state).unwrap();
+                    }
+                    match state.todo.pop_front().filter(|_| !state.stopped) {
+                        Some(next) => {
+                            state.ahead += 1;
+                            next
+                        }
+                        None => return,
+                    }
+                };
+                if tx
+                    .send((index, decoder.prepare(&path, resize, percent)))
+                    .is_err()
+                {
+                    return;
+                }
+            });
+        }
+        Pipeline {
+            shared,
+            results,
+            received: BTreeMap::new(),
+            waiting,
+        }
+    }
+
+    /// The decoded `image` if it is the next one queued, waiting for it if
+    /// it is not ready yet
+    pub fn take(&mut self, image: &Path) -> Option<ImageResult<Vec<Prepared>>> {
+        let index = match self.waiting.front() {
+            Some((index, path)) if path == image => *index,
+            _ => return None,
+        };
+        self.waiting.pop_front();
+        while !self.received.contains_key(&index) {
+            let (i, prepared) = self.results.recv().ok()?;
+            self.received.insert(i, prepared);
+        }
+        self.shared.state.lock().unwrap().ahead -= 1;
+        self.shared.wake.notify_all();
+        self.received.remove(&index)
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.wake.notify_all();
+    }
+}
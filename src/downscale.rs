@@ -0,0 +1,103 @@
+use printpdf::image_crate::{ColorType, DynamicImage, GenericImageView, GrayImage};
+
+/// Side of the squares of the input checked for text, in its pixels
+const TILE: u32 = 32;
+/// Step in gray between neighbors that counts as the edge of a stroke
+const EDGE: i16 = 64;
+/// Shares of edge pixels in a tile that look like print rather than flat
+/// paper or the busy texture of a photo
+const TEXT_EDGES: (f64, f64) = (0.04, 0.4);
+/// Gray above which a pixel is taken for paper
+const PAPER: u8 = 160;
+/// How far text pixels are pulled from the average toward the darkest pixel
+/// they cover, so that strokes thinner than a pixel stay dark
+const STROKE_WEIGHT: f32 = 0.6;
+/// Downscales to more than this part of the size are left to the filter
+const MAX_SHARE: f64 = 0.75;
+
+/// Whether each [`TILE`] of `gray`, row by row, looks like print: dark
+/// strokes on mostly light paper
+fn text_tiles(gray: &GrayImage) -> (u32, Vec<bool>) {
+    let (w, h) = gray.dimensions();
+    let (columns, rows) = (w.div_ceil(TILE), h.div_ceil(TILE));
+    let mut tiles = Vec::with_capacity((columns * rows) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x0, y0) = (column * TILE, row * TILE);
+            let (x1, y1) = ((x0 + TILE).min(w), (y0 + TILE).min(h));
+            let (mut edges, mut paper) = (0u32, 0u32);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let g = gray.get_pixel(x, y).0[0];
+                    let step = |(nx, ny): (u32, u32)| {
+                        (nx < w && ny < h)
+                            && (gray.get_pixel(nx, ny).0[0] as i16 - g as i16).abs() >= EDGE
+                    };
+                    edges += (step((x + 1, y)) || step((x, y + 1))) as u32;
+                    paper += (g >= PAPER) as u32;
+                }
+            }
+            let area = ((x1 - x0) * (y1 - y0)) as f64;
+            let share = edges as f64 / area;
+            tiles.push((TEXT_EDGES.0..=TEXT_EDGES.1).contains(&share) && paper as f64 > area / 2.0);
+        }
+    }
+    (columns, tiles)
+}
+
+/// `resized`, a downscale of `source`, with the parts of it that look like
+/// print resampled to keep fine strokes legible instead of fading them into
+/// the paper
+pub fn keep_text(source: &DynamicImage, resized: DynamicImage) -> DynamicImage {
+    let ((sw, sh), (w, h)) = (source.dimensions(), resized.dimensions());
+    if w as f64 > sw as f64 * MAX_SHARE || h as f64 > sh as f64 * MAX_SHARE {
+        return resized;
+    }
+    let gray = source.to_luma8();
+    let (columns, tiles) = text_tiles(&gray);
+    if !tiles.contains(&true) {
+        return resized;
+    }
+
+    let src = source.to_rgba8();
+    let mut out = resized.to_rgba8();
+    let (fx, fy) = (sw as f64 / w as f64, sh as f64 / h as f64);
+    for y in 0..h {
+        let y0 = (y as f64 * fy) as u32;
+        let y1 = (((y + 1) as f64 * fy).ceil() as u32).clamp(y0 + 1, sh);
+        for x in 0..w {
+            let x0 = (x as f64 * fx) as u32;
+            let x1 = (((x + 1) as f64 * fx).ceil() as u32).clamp(x0 + 1, sw);
+            let tile = ((y0 + y1) / 2 / TILE * columns + (x0 + x1) / 2 / TILE) as usize;
+            if !tiles[tile] {
+                continue;
+            }
+            let mut sum = [0u32; 4];
+            let mut darkest = (u8::MAX, (x0, y0));
+            for sy in y0..y1 {
+                for sx in x0..x1 {
+                    for (s, c) in sum.iter_mut().zip(src.get_pixel(sx, sy).0) {
+                        *s += c as u32;
+                    }
+                    darkest = darkest.min((gray.get_pixel(sx, sy).0[0], (sx, sy)));
+                }
+            }
+            let n = ((x1 - x0) * (y1 - y0)) as f32;
+            let (_, (ink_x, ink_y)) = darkest;
+            let ink = src.get_pixel(ink_x, ink_y).0;
+            let pixel = out.get_pixel_mut(x, y);
+            for ((c, s), i) in pixel.0.iter_mut().zip(sum).zip(ink) {
+                let mean = s as f32 / n;
+                *c = (mean - STROKE_WEIGHT * (mean - i as f32)).round() as u8;
+            }
+        }
+    }
+
+    let out = DynamicImage::ImageRgba8(out);
+    match resized.color() {
+        ColorType::L8 => DynamicImage::ImageLuma8(out.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(out.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(out.to_rgb8()),
+        _ => out,
+    }
+}
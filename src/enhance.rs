@@ -0,0 +1,239 @@
+use printpdf::image_crate::{
+    imageops::{self, FilterType},
+    DynamicImage, GrayImage, Luma, Rgb, RgbImage,
+};
+
+/// Longest edge of the thumbnail the document outline is searched on
+const DETECT_SIZE: u32 = 512;
+/// Side of the blocks the illumination of the background is sampled in
+const BACKGROUND_BLOCK: u32 = 32;
+
+type Quad = [(f64, f64); 4];
+
+/// Straightens the board, evens out its lighting and boosts the strokes
+pub fn whiteboard(img: DynamicImage) -> DynamicImage {
+    let img = dewarp(img).to_rgb8();
+    DynamicImage::ImageRgb8(whiten_background(&img)).adjust_contrast(30.0)
+}
+
+/// Maps the brightest large quadrilateral in the photo (a sheet of paper or
+/// a board) onto an upright rectangle, leaving the image as-is if none is found
+pub fn dewarp(img: DynamicImage) -> DynamicImage {
+    let thumb = img.thumbnail(DETECT_SIZE, DETECT_SIZE).to_luma8();
+    let quad = match find_document_quad(&thumb) {
+        Some(quad) => quad,
+        None => return img,
+    };
+    let sx = img.width() as f64 / thumb.width() as f64;
+    let sy = img.height() as f64 / thumb.height() as f64;
+    let quad = quad.map(|(x, y)| (x * sx, y * sy));
+
+    let [tl, tr, br, bl] = quad;
+    let out_w = dist(tl, tr).max(dist(bl, br)).round() as u32;
+    let out_h = dist(tl, bl).max(dist(tr, br)).round() as u32;
+    let dst = [
+        (0.0, 0.0),
+        (out_w as f64, 0.0),
+        (out_w as f64, out_h as f64),
+        (0.0, out_h as f64),
+    ];
+    match homography(&dst, &quad) {
+        Some(h) => DynamicImage::ImageRgb8(warp(&img.to_rgb8(), &h, out_w, out_h)),
+        None => img,
+    }
+}
+
+fn find_document_quad(gray: &GrayImage) -> Option<Quad> {
+    let threshold = otsu(gray);
+    let (w, h) = gray.dimensions();
+    let mut label = vec![0u32; (w * h) as usize];
+    let mut best: Option<(usize, Quad)> = None;
+    let mut next = 0;
+    let mut stack = Vec::new();
+
+    for start in 0..(w * h) as usize {
+        if label[start] != 0 || gray.as_raw()[start] <= threshold {
+            continue;
+        }
+        next += 1;
+        label[start] = next;
+        stack.push(start);
+
+        let mut area = 0;
+        // tl, tr, br, bl are the extremes of x + y and x - y
+        let mut quad = [(0.0, 0.0); 4];
+        let mut ext = [f64::MAX, f64::MIN, f64::MIN, f64::MAX];
+        while let Some(i) = stack.pop() {
+            area += 1;
+            let (x, y) = ((i as u32 % w) as f64, (i as u32 / w) as f64);
+            let keys = [x + y, x - y, x + y, x - y];
+            for c in 0..4 {
+                let better = if c == 0 || c == 3 {
+                    keys[c] < ext[c]
+                } else {
+                    keys[c] > ext[c]
+                };
+                if better {
+                    ext[c] = keys[c];
+                    quad[c] = (x, y);
+                }
+            }
+
+            let (x, y) = (i as u32 % w, i as u32 / w);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w as usize),
+                (y + 1 < h).then(|| i + w as usize),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if label[n] == 0 && gray.as_raw()[n] > threshold {
+                    label[n] = next;
+                    stack.push(n);
+                }
+            }
+        }
+        if best.as_ref().is_none_or(|(a, _)| area > *a) {
+            best = Some((area, quad));
+        }
+    }
+
+    let (area, quad) = best?;
+    let covered = area as f64 / (w * h) as f64;
+    (covered > 0.2 && covered < 0.98 && polygon_area(&quad) > 0.15 * (w * h) as f64).then_some(quad)
+}
+
+fn otsu(gray: &GrayImage) -> u8 {
+    let mut hist = [0u64; 256];
+    for Luma([v]) in gray.pixels() {
+        hist[*v as usize] += 1;
+    }
+    let total = gray.as_raw().len() as f64;
+    let sum: f64 = hist
+        .iter()
+        .enumerate()
+        .map(|(i, c)| i as f64 * *c as f64)
+        .sum();
+
+    let (mut best, mut best_var) = (0, 0.0);
+    let (mut w_b, mut sum_b) = (0.0, 0.0);
+    for (t, count) in hist.iter().enumerate() {
+        w_b += *count as f64;
+        if w_b == 0.0 || w_b == total {
+            continue;
+        }
+        sum_b += t as f64 * *count as f64;
+        let m_b = sum_b / w_b;
+        let m_f = (sum - sum_b) / (total - w_b);
+        let var = w_b * (total - w_b) * (m_b - m_f).powi(2);
+        if var > best_var {
+            best_var = var;
+            best = t;
+        }
+    }
+    best as u8
+}
+
+/// Solves the 3x3 projective transform taking `from[i]` to `to[i]`
+fn homography(from: &Quad, to: &Quad) -> Option<[f64; 9]> {
+    let mut m = [[0.0; 9]; 8];
+    for (i, ((x, y), (u, v))) in from.iter().zip(to).enumerate() {
+        m[2 * i] = [*x, *y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, *u];
+        m[2 * i + 1] = [0.0, 0.0, 0.0, *x, *y, 1.0, -v * x, -v * y, *v];
+    }
+
+    // gaussian elimination with partial pivoting
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|a, b| m[*a][col].abs().total_cmp(&m[*b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        m.swap(col, pivot);
+        let pivot_row = m[col];
+        for (r, row) in m.iter_mut().enumerate() {
+            if r != col {
+                let f = row[col] / pivot_row[col];
+                for (x, p) in row.iter_mut().zip(pivot_row).skip(col) {
+                    *x -= f * p;
+                }
+            }
+        }
+    }
+
+    let mut h = [1.0; 9];
+    for (i, row) in m.iter().enumerate() {
+        h[i] = row[8] / row[i];
+    }
+    Some(h)
+}
+
+fn warp(src: &RgbImage, h: &[f64; 9], w: u32, hgt: u32) -> RgbImage {
+    RgbImage::from_fn(w, hgt, |u, v| {
+        let (u, v) = (u as f64 + 0.5, v as f64 + 0.5);
+        let d = h[6] * u + h[7] * v + h[8];
+        let x = (h[0] * u + h[1] * v + h[2]) / d - 0.5;
+        let y = (h[3] * u + h[4] * v + h[5]) / d - 0.5;
+        bilinear(src, x, y)
+    })
+}
+
+fn bilinear(src: &RgbImage, x: f64, y: f64) -> Rgb<u8> {
+    let max_x = (src.width() - 1) as f64;
+    let max_y = (src.height() - 1) as f64;
+    let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (x1, y1) = ((x0 + 1.0).min(max_x), (y0 + 1.0).min(max_y));
+    let (fx, fy) = (x - x0, y - y0);
+
+    let p = |x: f64, y: f64| src.get_pixel(x as u32, y as u32).0;
+    let (a, b, c, d) = (p(x0, y0), p(x1, y0), p(x0, y1), p(x1, y1));
+    Rgb([0, 1, 2].map(|i| {
+        let top = a[i] as f64 * (1.0 - fx) + b[i] as f64 * fx;
+        let bottom = c[i] as f64 * (1.0 - fx) + d[i] as f64 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }))
+}
+
+/// Divides every pixel by a smooth estimate of the local paper color, which
+/// flattens shadows and glare into plain white
+fn whiten_background(img: &RgbImage) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let bw = w.div_ceil(BACKGROUND_BLOCK);
+    let bh = h.div_ceil(BACKGROUND_BLOCK);
+
+    let blocks = RgbImage::from_fn(bw, bh, |bx, by| {
+        let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+        for y in by * BACKGROUND_BLOCK..((by + 1) * BACKGROUND_BLOCK).min(h) {
+            for x in bx * BACKGROUND_BLOCK..((bx + 1) * BACKGROUND_BLOCK).min(w) {
+                for (c, v) in img.get_pixel(x, y).0.iter().enumerate() {
+                    channels[c].push(*v);
+                }
+            }
+        }
+        // the bright end of a block is paper even when it is mostly ink
+        Rgb(channels.map(|mut c| {
+            c.sort_unstable();
+            c[c.len() * 9 / 10]
+        }))
+    });
+    let background = imageops::resize(&blocks, w, h, FilterType::Triangle);
+
+    RgbImage::from_fn(w, h, |x, y| {
+        let p = img.get_pixel(x, y).0;
+        let b = background.get_pixel(x, y).0;
+        Rgb([0, 1, 2].map(|i| (p[i] as u32 * 255 / (b[i] as u32).max(1)).min(255) as u8))
+    })
+}
+
+fn dist(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+fn polygon_area(quad: &Quad) -> f64 {
+    let mut sum = 0.0;
+    for i in 0..4 {
+        let (a, b) = (quad[i], quad[(i + 1) % 4]);
+        sum += a.0 * b.1 - b.0 * a.1;
+    }
+    sum.abs() / 2.0
+}
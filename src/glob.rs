@@ -0,0 +1,76 @@
+/// Matches `text` against a shell pattern: `*` and `?` stay within one path
+/// component, `**` spans any number of them and `[a-z]`/`[!0-9]` are classes
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    matches_from(&pattern, &text)
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // `**/` may also match no directories at all
+            let rest = &pattern[2..];
+            if let Some(after_slash) = rest.strip_prefix(&['/']) {
+                if matches_from(after_slash, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| matches_from(rest, &text[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if matches_from(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && matches_from(&pattern[1..], &text[1..]),
+        Some('[') => match (class(&pattern[1..], text.first()), text.first()) {
+            (Some((matched, len)), Some(_)) => {
+                matched && matches_from(&pattern[1 + len..], &text[1..])
+            }
+            // an unclosed `[` is taken literally
+            (None, Some('[')) => matches_from(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && matches_from(&pattern[2..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && matches_from(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Whether `c` is in the class starting after a `[`, and the length of the
+/// class including its closing `]`
+fn class(pattern: &[char], c: Option<&char>) -> Option<(bool, usize)> {
+    let (negated, start) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    // a `]` right at the start is part of the class
+    let end = start + 1 + pattern.get(start + 1..)?.iter().position(|p| *p == ']')?;
+    let members = &pattern[start..end];
+    let c = match c {
+        Some(c) => *c,
+        None => return Some((false, end + 1)),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < members.len() {
+        if i + 2 < members.len() && members[i + 1] == '-' {
+            found |= (members[i]..=members[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= members[i] == c;
+            i += 1;
+        }
+    }
+    Some((found != negated && c != '/', end + 1))
+}
@@ -0,0 +1,169 @@
+use printpdf::image_crate::{
+    self,
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    DynamicImage, ImageError, ImageResult,
+};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Brands of the HEIF family in the `ftyp` box, HEIC and AVIF among them
+const BRANDS: [&[u8; 4]; 10] = [
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif", b"avis",
+];
+
+/// Whether `data`, the start of a file, is a HEIC, HEIF or AVIF image by the
+/// brands it names
+pub fn is_heif_data(data: &[u8]) -> bool {
+    if data.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let size = data
+        .get(..4)
+        .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let brands = data.get(8..size.min(data.len())).unwrap_or_default();
+    // the major brand, then the minor version, then the compatible brands
+    brands
+        .chunks_exact(4)
+        .enumerate()
+        .any(|(i, brand)| i != 1 && BRANDS.iter().any(|b| &b[..] == brand))
+}
+
+/// Whether the file at `path` is a HEIC, HEIF or AVIF image
+pub fn is_heif(path: &Path) -> bool {
+    let mut start = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|f| f.take(256).read_to_end(&mut start))
+        .is_ok_and(|_| is_heif_data(&start))
+}
+
+/// Width and height of the largest image `data` declares, which is the
+/// whole picture rather than one of its tiles or the thumbnail
+pub fn dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let u32_at = |at: usize| {
+        let b = data.get(at..at + 4)?;
+        Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    data.windows(4)
+        .enumerate()
+        .filter(|(_, w)| w == b"ispe")
+        // the type is followed by the version and flags
+        .filter_map(|(at, _)| Some((u32_at(at + 8)?, u32_at(at + 12)?)))
+        .max_by_key(|&(w, h)| w as u64 * h as u64)
+}
+
+fn other(message: String) -> ImageError {
+    ImageError::IoError(io::Error::other(message))
+}
+
+/// Removes the scratch directory of a conversion on drop
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Decodes the HEIC, HEIF or AVIF image at `path` with `heif-convert` from
+/// libheif, which also turns it as its transformations say. Of a file that
+/// holds several images, such as a burst, the first is taken.
+pub fn decode(path: &Path) -> ImageResult<DynamicImage> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let scratch = Scratch(std::env::temp_dir().join(format!(
+        "imgs2pdf-heif-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )));
+    std::fs::create_dir_all(&scratch.0)?;
+    let out = scratch.0.join("image.png");
+    let output = Command::new("heif-convert")
+        .arg(path)
+        .arg(&out)
+        .stdin(Stdio::null())
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Name("HEIF".to_owned()),
+                    UnsupportedErrorKind::GenericFeature(
+                        "decoding needs heif-convert from libheif on PATH".to_owned(),
+                    ),
+                ),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(other(match err.is_empty() {
+            true => format!("heif-convert exited with {}", output.status),
+            false => err,
+        }));
+    }
+    // several images are written one per file, numbered from 1
+    let first = [out.clone(), scratch.0.join("image-1.png")]
+        .into_iter()
+        .find(|p| p.exists())
+        .ok_or_else(|| other("heif-convert wrote no image".to_owned()))?;
+    image_crate::open(first)
+}
+
+/// Same as [`decode`] for an image that is not on disk
+pub fn decode_data(data: &[u8]) -> ImageResult<DynamicImage> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let input = std::env::temp_dir().join(format!(
+        "imgs2pdf-heif-input-{}-{}.heif",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&input, data)?;
+    let decoded = decode(&input);
+    let _ = std::fs::remove_file(&input);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A box of `kind` holding `body`
+    fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn ispe(w: u32, h: u32) -> Vec<u8> {
+        let mut body = vec![0; 4];
+        body.extend_from_slice(&w.to_be_bytes());
+        body.extend_from_slice(&h.to_be_bytes());
+        boxed(b"ispe", &body)
+    }
+
+    #[test]
+    fn heif_is_told_by_its_brands() {
+        assert!(is_heif_data(&boxed(b"ftyp", b"heic\0\0\0\0mif1heic")));
+        assert!(is_heif_data(&boxed(b"ftyp", b"avif\0\0\0\0avifmif1")));
+        // compatible with HEIF, but meant as something else
+        assert!(is_heif_data(&boxed(b"ftyp", b"xxxx\0\0\0\0mif1")));
+        assert!(!is_heif_data(&boxed(b"ftyp", b"isom\0\0\0\0isomavc1mp41")));
+        // a minor version that happens to read like a brand
+        assert!(!is_heif_data(&boxed(b"ftyp", b"isomheicmp41")));
+        assert!(!is_heif_data(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn the_largest_image_gives_the_size() {
+        let mut data = boxed(b"ftyp", b"heic\0\0\0\0mif1heic");
+        data.extend(ispe(512, 512));
+        data.extend(ispe(4032, 3024));
+        data.extend(ispe(320, 240));
+        assert_eq!(dimensions(&data), Some((4032, 3024)));
+        assert_eq!(dimensions(&data[..20]), None);
+    }
+}
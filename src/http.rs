@@ -0,0 +1,305 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+/// Longest request line and headers accepted, in bytes
+const MAX_HEAD: usize = 64 * 1024;
+
+/// Just enough of an HTTP/1.1 request for the server: the body has to come
+/// with a Content-Length and every connection is closed after one response
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A request that could not be read, answered with `status`
+pub struct Rejected {
+    pub status: u16,
+    pub reason: String,
+}
+
+impl From<io::Error> for Rejected {
+    fn from(e: io::Error) -> Self {
+        Rejected {
+            status: 400,
+            reason: e.to_string(),
+        }
+    }
+}
+
+fn rejected(status: u16, reason: impl Into<String>) -> Rejected {
+    Rejected {
+        status,
+        reason: reason.into(),
+    }
+}
+
+/// Reads a request, refusing bodies longer than `max_body` bytes
+pub fn read_request(stream: impl Read, max_body: usize) -> Result<Request, Rejected> {
+    let mut reader = BufReader::new(stream);
+    let mut head = Vec::new();
+    loop {
+        let before = head.len();
+        (&mut reader)
+            .take((MAX_HEAD + 1 - head.len()) as u64)
+            .read_until(b'\n', &mut head)?;
+        if head.len() > MAX_HEAD {
+            return Err(rejected(431, "the request head is too long"));
+        }
+        if head.len() == before {
+            return Err(rejected(400, "the request ended early"));
+        }
+        if head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n") {
+            break;
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (method, target) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(target)) if !method.is_empty() => (method, target),
+        _ => return Err(rejected(400, "malformed request line")),
+    };
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(n, v)| (n.trim().to_ascii_lowercase(), v.trim().to_owned()))
+        .collect::<Vec<_>>();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_owned(),
+        path: percent_decode(path),
+        query: parse_query(query),
+        headers,
+        body: Vec::new(),
+    };
+
+    if request.header("transfer-encoding").is_some() {
+        return Err(rejected(411, "send the body with a Content-Length"));
+    }
+    let length = match request.header("content-length").map(str::parse::<usize>) {
+        None => 0,
+        Some(Ok(length)) if length <= max_body => length,
+        Some(Ok(_)) => {
+            return Err(rejected(
+                413,
+                format!("bodies are limited to {max_body} bytes"),
+            ))
+        }
+        Some(Err(_)) => return Err(rejected(400, "malformed Content-Length")),
+    };
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+/// Pairs of a query string, decoded
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A file of a `multipart/form-data` body
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Splits a `multipart/form-data` body into its parts, `None` if
+/// `content_type` has no boundary or the body is not framed by it
+pub fn parse_multipart(content_type: &str, body: &[u8]) -> Option<Vec<Part>> {
+    let boundary = content_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("boundary="))
+        .next()?
+        .trim_matches('"');
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut rest = &body[find(body, &delimiter)? + delimiter.len()..];
+    let mut parts = Vec::new();
+    // every part starts on the line after a delimiter and ends before the
+    // CRLF preceding the next one, the last delimiter is followed by `--`
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n")?;
+        let end = find(rest, &delimiter)?;
+        let part = rest[..end].strip_suffix(b"\r\n")?;
+        rest = &rest[end + delimiter.len()..];
+
+        let head_end = find(part, b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&part[..head_end]);
+        let disposition = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(n, _)| n.trim().eq_ignore_ascii_case("content-disposition"))
+            .map(|(_, v)| v.to_owned())
+            .unwrap_or_default();
+        let field = |key: &str| {
+            disposition
+                .split(';')
+                .filter_map(|p| p.trim().strip_prefix(key)?.strip_prefix('='))
+                .map(|v| v.trim_matches('"').to_owned())
+                .next()
+        };
+        parts.push(Part {
+            name: field("name").unwrap_or_default(),
+            filename: field("filename"),
+            data: part[head_end + 4..].to_vec(),
+        });
+    }
+    Some(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, json: String) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: json.into_bytes(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Response::json(
+            status,
+            format!("{{\"error\": {}}}\n", crate::report::quote(message)),
+        )
+    }
+
+    pub fn write(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_request_with_body() {
+        let raw = b"POST /jobs?priority=high&title=My%20scans+2 HTTP/1.1\r\n\
+            Host: localhost\r\nContent-Length: 5\r\n\r\nhello";
+        let request = read_request(&raw[..], 10).ok().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/jobs");
+        let query = [("priority", "high"), ("title", "My scans 2")]
+            .map(|(n, v)| (n.to_owned(), v.to_owned()));
+        assert_eq!(request.query, query);
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.body, b"hello");
+
+        let status = |raw: &[u8]| read_request(raw, 4).err().map(|r| r.status);
+        assert_eq!(status(raw), Some(413));
+        assert_eq!(
+            status(b"GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Some(411)
+        );
+        assert_eq!(status(b"GET / HTTP/1.1\r\n"), Some(400));
+    }
+
+    #[test]
+    fn splits_multipart_body() {
+        let body = b"preamble\r\n--xyz\r\n\
+            Content-Disposition: form-data; name=\"img\"; filename=\"a.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\x89PNG\r\n--x\r\n\
+            --xyz\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhi\r\n\
+            --xyz--\r\n";
+        let parts = parse_multipart("multipart/form-data; boundary=\"xyz\"", body).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "img");
+        assert_eq!(parts[0].filename.as_deref(), Some("a.png"));
+        assert_eq!(parts[0].data, b"\x89PNG\r\n--x");
+        assert_eq!(parts[1].name, "note");
+        assert_eq!(parts[1].filename, None);
+        assert_eq!(parts[1].data, b"hi");
+
+        assert!(parse_multipart("multipart/form-data", body).is_none());
+        assert!(parse_multipart("multipart/form-data; boundary=xyz", b"--xyz\r\nbroken").is_none());
+    }
+}
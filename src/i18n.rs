@@ -0,0 +1,177 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use yet_another_imgs2pdf::template;
+
+/// Languages messages are translated to, in the order of [`MESSAGES`]
+pub const LANGUAGES: [&str; 4] = ["en", "de", "fr", "es"];
+
+/// Every message in each of [`LANGUAGES`], with `{name}` placeholders
+const MESSAGES: &[(&str, [&str; 4])] = &[
+    (
+        "processed",
+        [
+            "Processed {count} input(s) in {seconds}s ({rate}/s)",
+            "{count} Eingabe(n) in {seconds}s verarbeitet ({rate}/s)",
+            "{count} entrée(s) traitée(s) en {seconds}s ({rate}/s)",
+            "{count} entrada(s) procesada(s) en {seconds}s ({rate}/s)",
+        ],
+    ),
+    (
+        "slowest",
+        [
+            ", the slowest `{file}` in {seconds}s",
+            ", am langsamsten `{file}` mit {seconds}s",
+            ", la plus lente `{file}` en {seconds}s",
+            ", la más lenta `{file}` en {seconds}s",
+        ],
+    ),
+    (
+        "skipped-summary",
+        [
+            "Skipped {count} of {total} input(s):",
+            "{count} von {total} Eingabe(n) übersprungen:",
+            "{count} entrée(s) sur {total} ignorée(s) :",
+            "Se omitieron {count} de {total} entrada(s):",
+        ],
+    ),
+    (
+        "skipping",
+        [
+            "Skipping `{file}` because: {reason}",
+            "Überspringe `{file}`, Grund: {reason}",
+            "`{file}` ignoré, raison : {reason}",
+            "Se omite `{file}`, motivo: {reason}",
+        ],
+    ),
+    (
+        "skipping-frame",
+        [
+            "Skipping a frame of `{file}` because: {reason}",
+            "Überspringe ein Einzelbild aus `{file}`, Grund: {reason}",
+            "Une image de `{file}` est ignorée, raison : {reason}",
+            "Se omite un fotograma de `{file}`, motivo: {reason}",
+        ],
+    ),
+    (
+        "skipping-entry",
+        [
+            "Skipping `{entry}` in `{file}` because: {reason}",
+            "Überspringe `{entry}` in `{file}`, Grund: {reason}",
+            "`{entry}` dans `{file}` est ignoré, raison : {reason}",
+            "Se omite `{entry}` en `{file}`, motivo: {reason}",
+        ],
+    ),
+    (
+        "skipping-image-of",
+        [
+            "Skipping an image of `{file}` because: {reason}",
+            "Überspringe ein Bild aus `{file}`, Grund: {reason}",
+            "Une image de `{file}` est ignorée, raison : {reason}",
+            "Se omite una imagen de `{file}`, motivo: {reason}",
+        ],
+    ),
+    (
+        "created",
+        [
+            "Successfully created the PDF `{file}` in {seconds}s",
+            "PDF `{file}` in {seconds} s erstellt",
+            "PDF `{file}` créé en {seconds} s",
+            "PDF `{file}` creado en {seconds} s",
+        ],
+    ),
+    (
+        "unsupported",
+        [
+            "{count} unsupported file(s):",
+            "{count} nicht unterstützte Datei(en):",
+            "{count} fichier(s) non pris en charge :",
+            "{count} archivo(s) no compatible(s):",
+        ],
+    ),
+    (
+        "low-dpi",
+        [
+            "{count} page(s) are below {dpi} dpi and may look blurry:",
+            "{count} Seite(n) unter {dpi} dpi, möglicherweise unscharf:",
+            "{count} page(s) sous {dpi} dpi, risque de flou :",
+            "{count} página(s) por debajo de {dpi} dpi, pueden verse borrosas:",
+        ],
+    ),
+    (
+        "below-min-dpi",
+        [
+            "{count} page(s) are below --min-dpi {dpi}:",
+            "{count} Seite(n) unter --min-dpi {dpi}:",
+            "{count} page(s) sous --min-dpi {dpi} :",
+            "{count} página(s) por debajo de --min-dpi {dpi}:",
+        ],
+    ),
+    (
+        "page-dpi",
+        [
+            "  page {page}: {dpi} dpi",
+            "  Seite {page}: {dpi} dpi",
+            "  page {page} : {dpi} dpi",
+            "  página {page}: {dpi} dpi",
+        ],
+    ),
+    (
+        "could-not-write",
+        [
+            "Could not write `{file}`: {reason}",
+            "`{file}` konnte nicht geschrieben werden: {reason}",
+            "Impossible d'écrire `{file}` : {reason}",
+            "No se pudo escribir `{file}`: {reason}",
+        ],
+    ),
+    (
+        "salvaged",
+        [
+            "Salvaged the PDF to `{file}`",
+            "Das PDF wurde nach `{file}` gerettet",
+            "Le PDF a été sauvegardé dans `{file}`",
+            "El PDF se guardó en `{file}`",
+        ],
+    ),
+];
+
+static LANG: OnceLock<usize> = OnceLock::new();
+
+/// Picks the language of the messages: `requested` if given, otherwise the
+/// locale from the environment, falling back to English
+pub fn init(requested: Option<&str>) {
+    let from_env = || {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|v| !v.is_empty())
+    };
+    let tag = requested.map(str::to_owned).or_else(from_env);
+    let lang = tag.as_deref().and_then(language).unwrap_or(0);
+    let _ = LANG.set(lang);
+}
+
+/// Index in [`LANGUAGES`] of a tag like `de`, `de_AT.UTF-8` or `fr-CA`
+fn language(tag: &str) -> Option<usize> {
+    let code = tag.split(['_', '-', '.', '@']).next()?.to_ascii_lowercase();
+    LANGUAGES.iter().position(|l| *l == code)
+}
+
+/// Message `id` in the chosen language with its placeholders filled
+pub fn t(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let lang = *LANG.get().unwrap_or(&0);
+    let texts = match MESSAGES.iter().find(|(key, _)| *key == id) {
+        Some((_, texts)) => texts,
+        None => return id.to_owned(),
+    };
+    let lookup = |text: &str| {
+        template::render(text, |name| {
+            let (_, value) = args.iter().find(|(n, _)| *n == name)?;
+            Some(value.to_string())
+        })
+    };
+    lookup(texts[lang])
+        .or_else(|_| lookup(texts[0]))
+        .unwrap_or_else(|_| texts[0].to_owned())
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use yet_another_imgs2pdf::glob::glob_match;
+
+/// Read from the input directory when present
+pub const FILE_NAME: &str = ".imgs2pdfignore";
+
+struct Rule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than any file name
+    anchored: bool,
+}
+
+/// Patterns excluding inputs, in the syntax of `.gitignore`
+#[derive(Default)]
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// Reads [`FILE_NAME`] in `dir`, ignoring nothing if there is none
+    pub fn read(dir: &Path) -> io::Result<Ignore> {
+        match fs::read_to_string(dir.join(FILE_NAME)) {
+            Ok(text) => Ok(Ignore::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Ignore::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// One pattern per line; blank lines and lines starting with `#` are
+    /// skipped, `!` re-includes what an earlier pattern excluded and a
+    /// trailing `/` only matches directories
+    pub fn parse(text: &str) -> Ignore {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            rules.push(Rule {
+                pattern: line.strip_prefix('/').unwrap_or(line).to_owned(),
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+        Ignore { rules }
+    }
+
+    /// Whether `path`, relative to the directory of the ignore file, is
+    /// excluded. Everything below an excluded directory is excluded too.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+        let parts = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        if parts.len() == 1 && parts[0] == FILE_NAME {
+            return true;
+        }
+        (1..=parts.len()).any(|n| {
+            let is_dir = is_dir || n < parts.len();
+            self.matches(&parts[..n].join("/"), &parts[n - 1], is_dir)
+        })
+    }
+
+    /// The verdict of the last rule matching `path`
+    fn matches(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let text = if rule.anchored { path } else { name };
+            if glob_match(&rule.pattern, text) {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
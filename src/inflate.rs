@@ -0,0 +1,273 @@
+use std::io;
+
+/// Base lengths of the length symbols 257 to 285, and their extra bits
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances of the distance symbols, and their extra bits
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order the code lengths of the code length alphabet are stored in
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("deflate stream {what}"))
+}
+
+/// Reads a deflate stream least significant bit first
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| invalid("is cut short"))?;
+            self.pos += 1;
+            self.buf |= (byte as u64) << self.count;
+            self.count += 8;
+        }
+        let value = (self.buf & ((1u64 << n) - 1)) as u32;
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops what is left of the current byte, handing back whole bytes
+    /// read ahead
+    fn align(&mut self) {
+        self.pos -= (self.count / 8) as usize;
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// Canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    /// How many codes there are of each length
+    counts: [u16; 16],
+    /// Symbols by code length, then by value
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0usize; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len] as usize;
+        }
+        let mut symbols = vec![0; offsets[15] + counts[15] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize]] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        // codes of each length follow those of the length before, shifted
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("holds an unknown code"))
+    }
+}
+
+/// Decompresses the raw deflate stream `data`, as found in ZIP archives,
+/// failing once it grows past `limit` bytes
+pub fn inflate(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = Vec::with_capacity(limit.min(1 << 26));
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or_else(|| invalid("is cut short"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(invalid("has a damaged stored block"));
+                }
+                let start = bits.pos + 4;
+                let block = data
+                    .get(start..start + len as usize)
+                    .ok_or_else(|| invalid("is cut short"))?;
+                if out.len() + block.len() > limit {
+                    return Err(invalid("is larger than stated"));
+                }
+                out.extend_from_slice(block);
+                bits.pos = start + block.len();
+            }
+            1 => {
+                let mut lengths = [8u8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let (lit, dist) = (Huffman::new(&lengths), Huffman::new(&[5; 30]));
+                codes(&mut bits, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic(&mut bits)?;
+                codes(&mut bits, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err(invalid("has an unknown block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Reads the codes a dynamic block is compressed with
+fn dynamic(bits: &mut Bits) -> io::Result<(Huffman, Huffman)> {
+    let lit_count = bits.bits(5)? as usize + 257;
+    let dist_count = bits.bits(5)? as usize + 1;
+    let clen_count = bits.bits(4)? as usize + 4;
+    let mut clens = [0u8; 19];
+    for &i in &CLEN_ORDER[..clen_count] {
+        clens[i] = bits.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clens);
+
+    let mut lengths = vec![0u8; lit_count + dist_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = clen.decode(bits)?;
+        let (len, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + bits.bits(2)? as usize),
+            17 => (0, 3 + bits.bits(3)? as usize),
+            18 => (0, 11 + bits.bits(7)? as usize),
+            _ => return Err(invalid("has damaged code lengths")),
+        };
+        let run = lengths
+            .get_mut(i..i + repeat)
+            .ok_or_else(|| invalid("has damaged code lengths"))?;
+        run.fill(len);
+        i += repeat;
+    }
+    Ok((
+        Huffman::new(&lengths[..lit_count]),
+        Huffman::new(&lengths[lit_count..]),
+    ))
+}
+
+/// Decodes the literals and back references of a block up to its end
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> io::Result<()> {
+    loop {
+        let symbol = lit.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                let base = *LENGTH_BASE
+                    .get(i)
+                    .ok_or_else(|| invalid("holds a bad length"))?;
+                let len = base as usize + bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = dist.decode(bits)? as usize;
+                let base = *DIST_BASE
+                    .get(d)
+                    .ok_or_else(|| invalid("holds a bad distance"))?;
+                let back = base as usize + bits.bits(DIST_EXTRA[d] as u32)? as usize;
+                if back > out.len() {
+                    return Err(invalid("refers back past its start"));
+                }
+                // copies may overlap what they produce, so go byte by byte
+                let start = out.len() - back;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+        if out.len() > limit {
+            return Err(invalid("is larger than stated"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| format!("page {i} of the archive\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn inflates_every_block_type() {
+        let stored = [1, 6, 0, 249, 255, b's', b't', b'o', b'r', b'e', b'd'];
+        assert_eq!(inflate(&stored, 6).unwrap(), b"stored");
+
+        let fixed = [203, 72, 205, 201, 201, 87, 200, 64, 39, 1];
+        assert_eq!(inflate(&fixed, 100).unwrap(), b"hello hello hello hello");
+
+        let dynamic = [
+            117, 207, 185, 13, 128, 48, 16, 69, 193, 156, 42, 182, 4, 62, 55, 229, 88, 104, 237,
+            37, 194, 66, 136, 250, 17, 49, 143, 116, 178, 169, 169, 184, 181, 118, 100, 187, 194,
+            45, 157, 91, 236, 183, 55, 245, 85, 161, 118, 168, 61, 234, 128, 58, 162, 78, 168, 51,
+            234, 130, 186, 242, 226, 39, 199, 59, 113, 79, 252, 19, 7, 197, 67, 113, 81, 124, 20,
+            39, 245, 89, 62,
+        ];
+        assert_eq!(inflate(&dynamic, 1000).unwrap(), pages(20));
+    }
+
+    #[test]
+    fn damaged_streams_are_errors() {
+        let fixed = [203, 72, 205, 201, 201, 87, 200, 64, 39, 1];
+        // more than the archive said it holds
+        assert!(inflate(&fixed, 10).is_err());
+        for len in 0..fixed.len() {
+            assert!(inflate(&fixed[..len], 100).is_err());
+        }
+        assert!(inflate(&[1, 6, 0, 0, 0, b's'], 100).is_err());
+        assert!(inflate(&[0b111], 100).is_err());
+    }
+}
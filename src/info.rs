@@ -0,0 +1,199 @@
+use printpdf::lopdf::{self, Object, StringFormat};
+use printpdf::OffsetDateTime;
+
+/// Keys the PDF specification defines for the Info dictionary
+const STANDARD_KEYS: [&str; 9] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "Producer",
+    "CreationDate",
+    "ModDate",
+    "Trapped",
+];
+
+/// Parses a `key=value` pair for a custom Info entry. Keys have to be valid
+/// XML names as they are mirrored into the XMP packet.
+pub fn parse_custom_entry(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("`{s}` is not in the form key=value"))?;
+    let key = key.trim();
+    let valid = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(format!(
+            "`{key}` is not a valid key, use letters, digits, `_`, `-` and `.`"
+        ));
+    }
+    if STANDARD_KEYS.contains(&key) {
+        return Err(format!(
+            "`{key}` is a standard key and cannot be set with --meta"
+        ));
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Writes custom entries into the Info dictionary and, under the `pdfx`
+/// namespace Acrobat uses for them, into the XMP metadata
+pub fn add_custom_entries(
+    doc: &mut lopdf::Document,
+    entries: &[(String, String)],
+) -> lopdf::Result<()> {
+    let info = doc.trailer.get(b"Info")?.as_reference()?;
+    let info = doc.get_object_mut(info)?.as_dict_mut()?;
+    for (key, value) in entries {
+        info.set(key.as_str(), text_string(value));
+    }
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let metadata = match doc.get_dictionary(root)?.get(b"Metadata") {
+        Ok(Object::Reference(id)) => *id,
+        _ => return Ok(()),
+    };
+    let stream = doc.get_object_mut(metadata)?.as_stream_mut()?;
+    let content = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    let xmp = String::from_utf8_lossy(&content).into_owned();
+    if let Some(at) = xmp.find("</rdf:Description>") {
+        let mut properties = String::new();
+        for (key, value) in entries {
+            properties.push_str(&format!(
+                "   <pdfx:{key}>{}</pdfx:{key}>\n      ",
+                escape(value)
+            ));
+        }
+        let mut xmp = xmp;
+        xmp.insert_str(at, &properties);
+        stream.set_plain_content(xmp.into_bytes());
+    }
+    Ok(())
+}
+
+/// Reads a date given as `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS`, in UTC
+pub fn parse_date(s: &str) -> Option<OffsetDateTime> {
+    let (date, time) = s.trim().split_once('T').unwrap_or((s.trim(), "00:00:00"));
+    let time = time.strip_suffix('Z').unwrap_or(time);
+    let numbers = |s: &str, sep| {
+        s.split(sep)
+            .map(|n| n.parse::<i64>().ok().filter(|_| !n.starts_with(['+', '-'])))
+            .collect::<Option<Vec<_>>>()
+    };
+    let (year, month, day) = match numbers(date, '-')?[..] {
+        [y, m, d] => (y, m, d),
+        _ => return None,
+    };
+    let (hour, minute, second) = match numbers(time, ':')?[..] {
+        [h, m, s] => (h, m, s),
+        _ => return None,
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_len = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    let valid = (1..=9999).contains(&year)
+        && (1..=12).contains(&month)
+        && (1..=month_len).contains(&day)
+        && hour < 24
+        && minute < 60
+        && second < 60;
+    if !valid {
+        return None;
+    }
+    // days since 1970-01-01 in the proleptic Gregorian calendar
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Some(OffsetDateTime::from_unix_timestamp(
+        days * 86400 + hour * 3600 + minute * 60 + second,
+    ))
+}
+
+/// An ID made from the title of a document, 32 hex digits like the random
+/// ones printpdf picks
+pub fn document_id(title: &str) -> String {
+    let hash = |seed: u64| {
+        title.bytes().fold(seed, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    };
+    format!(
+        "{:016x}{:016x}",
+        hash(0xcbf29ce484222325),
+        hash(0x84222325cbf29ce4)
+    )
+}
+
+/// Puts `id` in place of the random IDs in the trailer and XMP metadata
+pub fn set_ids(doc: &mut lopdf::Document, id: &str) -> lopdf::Result<()> {
+    let ids = vec![Object::string_literal(id), Object::string_literal(id)];
+    doc.trailer.set("ID", Object::Array(ids));
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let metadata = match doc.get_dictionary(root)?.get(b"Metadata") {
+        Ok(Object::Reference(id)) => *id,
+        _ => return Ok(()),
+    };
+    let stream = doc.get_object_mut(metadata)?.as_stream_mut()?;
+    let content = stream
+        .decompressed_content()
+        .unwrap_or_else(|_| stream.content.clone());
+    let mut xmp = String::from_utf8_lossy(&content).into_owned();
+    for tag in ["xmpMM:DocumentID", "xmpMM:InstanceID"] {
+        let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+        if let Some(start) = xmp.find(&open).map(|at| at + open.len()) {
+            if let Some(len) = xmp[start..].find(&close) {
+                xmp.replace_range(start..start + len, &format!("uuid:{id}"));
+            }
+        }
+    }
+    stream.set_plain_content(xmp.into_bytes());
+    Ok(())
+}
+
+/// A PDF text string, UTF-16 with a byte order mark unless plain ASCII is enough
+pub fn text_string(s: &str) -> Object {
+    if s.is_ascii() {
+        return Object::string_literal(s);
+    }
+    let mut bytes = vec![0xfe, 0xff];
+    bytes.extend(s.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// Reads a PDF text string written as UTF-16 with a byte order mark or,
+/// approximately, as PDFDocEncoding
+pub fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,249 @@
+use clap::{Arg, ArgMatches, Command, ValueHint};
+use printpdf::image_crate;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::walk;
+use yet_another_imgs2pdf::metadata;
+use yet_another_imgs2pdf::toml::quote;
+
+pub fn command() -> Command<'static> {
+    Command::new("init-manifest")
+        .about("Write a starter manifest listing the images of a directory, to edit for --manifest")
+        .arg(
+            Arg::new("dir")
+                .allow_invalid_utf8(true)
+                .help("The directory of images to list")
+                .required(true)
+                .value_hint(ValueHint::DirPath),
+        )
+        .arg(
+            Arg::new("recursive")
+                .help("Also list the images of subdirectories, in place of them")
+                .long("recursive")
+                .short('r'),
+        )
+        .arg(
+            Arg::new("out")
+                .allow_invalid_utf8(true)
+                .help("The manifest to write, which must not exist yet [default: print it, to be saved in <dir>]")
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("out")
+                .short('o'),
+        )
+}
+
+/// What was found out about an image from its header
+struct Listed {
+    file: PathBuf,
+    format: String,
+    size: (u32, u32),
+    /// EXIF orientation other than as stored
+    orientation: Option<u16>,
+}
+
+impl Listed {
+    /// Width and height as the image is shown, once its EXIF orientation
+    /// turned it
+    fn shown(&self) -> (u32, u32) {
+        let (w, h) = self.size;
+        match self.orientation {
+            Some(5..=8) => (h, w),
+            _ => (w, h),
+        }
+    }
+}
+
+fn probe(file: &Path) -> Option<Listed> {
+    #[cfg(feature = "heif")]
+    if yet_another_imgs2pdf::heif::is_heif(file) {
+        let size = yet_another_imgs2pdf::heif::dimensions(&std::fs::read(file).ok()?)?;
+        // heif-convert turns the image by its transformations already
+        return Some(Listed {
+            file: file.to_path_buf(),
+            format: "HEIF".to_owned(),
+            size,
+            orientation: None,
+        });
+    }
+    let reader = image_crate::io::Reader::open(file)
+        .and_then(|r| r.with_guessed_format())
+        .ok()?;
+    let format = format!("{:?}", reader.format()?).to_uppercase();
+    let size = reader.into_dimensions().ok()?;
+    let orientation = std::fs::read(file)
+        .ok()
+        .and_then(|data| metadata::exif_orientation(&data))
+        .filter(|&o| o != 1);
+    Some(Listed {
+        file: file.to_path_buf(),
+        format,
+        size,
+        orientation,
+    })
+}
+
+/// How EXIF `orientation` turns an image to show it upright
+fn turned(orientation: u16) -> &'static str {
+    match orientation {
+        2 => "mirrored",
+        3 => "rotated 180 degrees",
+        4 => "flipped upside down",
+        5 => "mirrored and rotated 90 degrees clockwise",
+        6 => "rotated 90 degrees clockwise",
+        7 => "mirrored and rotated 90 degrees counterclockwise",
+        _ => "rotated 90 degrees counterclockwise",
+    }
+}
+
+/// The comment above an image: its format and size, how it is turned by
+/// its EXIF orientation and, when it lies unlike most of the images, that it
+/// may have been scanned sideways
+fn describe(image: &Listed, mostly_portrait: Option<bool>) -> String {
+    let (w, h) = image.size;
+    let mut note = format!("# {w}x{h} {}", image.format);
+    if let Some(orientation) = image.orientation {
+        let (w, h) = image.shown();
+        let _ = write!(note, ", {} to {w}x{h} by EXIF", turned(orientation));
+    }
+    let (w, h) = image.shown();
+    match mostly_portrait {
+        Some(true) if w > h => {
+            note.push_str("\n# landscape among portrait images, maybe turned sideways")
+        }
+        Some(false) if w < h => {
+            note.push_str("\n# portrait among landscape images, maybe turned sideways")
+        }
+        _ => {}
+    }
+    note
+}
+
+/// The manifest listing `images`, with files relative to `base`
+fn manifest(images: &[Listed], left_out: &[PathBuf], base: &Path) -> String {
+    let relative = |file: &Path| {
+        let file = file
+            .strip_prefix(base)
+            .map_or(file.to_path_buf(), Path::to_path_buf);
+        file.to_string_lossy().replace('\\', "/")
+    };
+    let portrait = images.iter().filter(|i| i.shown().0 < i.shown().1).count();
+    let landscape = images.iter().filter(|i| i.shown().0 > i.shown().1).count();
+    // only a clear majority says which way the pages are meant to lie
+    let mostly_portrait = match (portrait, landscape) {
+        (p, l) if p > 2 * l => Some(true),
+        (p, l) if l > 2 * p => Some(false),
+        _ => None,
+    };
+
+    let mut out = String::from(
+        "# Starter manifest: reorder, remove or edit the inputs, and give them\n\
+         # settings such as scale, fold_out or color_mode, then pass it to\n\
+         # --manifest\n",
+    );
+    for file in left_out {
+        let _ = writeln!(out, "# left out, not an image: {}", relative(file));
+    }
+    for image in images {
+        let _ = write!(
+            out,
+            "\n[[input]]\n{}\nfile = {}\n",
+            describe(image, mostly_portrait),
+            quote(&relative(&image.file))
+        );
+    }
+    out
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(matches.value_of_os("dir").unwrap());
+    let out = matches.value_of_os("out").map(Path::new);
+    if let Some(out) = out.filter(|out| out.exists()) {
+        eprintln!(
+            "`{}` exists already, remove it or pick another --out",
+            out.display()
+        );
+        exit(1)
+    }
+    let files = walk::files(
+        dir,
+        matches.is_present("recursive"),
+        &walk::Filter::default(),
+    )?;
+    let (mut images, mut left_out) = (Vec::new(), Vec::new());
+    for file in files {
+        match probe(&file) {
+            Some(image) => images.push(image),
+            None => left_out.push(file),
+        }
+    }
+
+    let base = match out {
+        Some(out) => out
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new(".")),
+        None => dir,
+    };
+    // files outside the directory of the manifest are listed in full
+    let base = std::fs::canonicalize(base).unwrap_or(base.to_path_buf());
+    for image in &mut images {
+        image.file = std::fs::canonicalize(&image.file).unwrap_or(image.file.clone());
+    }
+    for file in &mut left_out {
+        *file = std::fs::canonicalize(&*file).unwrap_or(file.clone());
+    }
+    let text = manifest(&images, &left_out, &base);
+    match out {
+        Some(out) => {
+            std::fs::write(out, text)?;
+            println!("Listed {} image(s) in `{}`", images.len(), out.display());
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(name: &str, size: (u32, u32), orientation: Option<u16>) -> Listed {
+        Listed {
+            file: PathBuf::from("/scans").join(name),
+            format: "JPEG".to_owned(),
+            size,
+            orientation,
+        }
+    }
+
+    #[test]
+    fn images_are_listed_with_their_size_and_turn() {
+        let images = [
+            listed("1.jpg", (100, 150), None),
+            listed("2.jpg", (150, 100), Some(6)),
+            listed("3.jpg", (100, 150), None),
+            listed("4.jpg", (150, 100), None),
+        ];
+        let text = manifest(
+            &images,
+            &[PathBuf::from("/scans/notes.txt")],
+            Path::new("/scans"),
+        );
+        assert!(text.contains("# left out, not an image: notes.txt\n"));
+        assert!(text.contains(
+            "\n[[input]]\n# 150x100 JPEG, rotated 90 degrees clockwise to 100x150 by EXIF\nfile = \"2.jpg\"\n"
+        ));
+        assert!(text.contains(
+            "# 150x100 JPEG\n# landscape among portrait images, maybe turned sideways\nfile = \"4.jpg\""
+        ));
+        assert_eq!(text.matches("sideways").count(), 1);
+
+        let entries = yet_another_imgs2pdf::manifest::parse(&text, Path::new("/scans")).unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[3].file, Path::new("/scans/4.jpg"));
+    }
+}
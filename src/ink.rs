@@ -0,0 +1,23 @@
+use printpdf::image_crate::DynamicImage;
+
+/// Coverage is estimated on a thumbnail no larger than this
+const SAMPLE_SIZE: u32 = 256;
+
+/// Mean cyan, magenta, yellow and black coverage of an image in `0.0..=1.0`,
+/// using a plain RGB to CMYK separation without an ICC profile
+pub fn coverage(img: &DynamicImage) -> [f64; 4] {
+    let thumb = img.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).to_rgb8();
+    let mut sum = [0.0; 4];
+    for px in thumb.pixels() {
+        let [r, g, b] = px.0.map(|v| v as f64 / 255.0);
+        let k = 1.0 - r.max(g).max(b);
+        if k < 1.0 {
+            sum[0] += (1.0 - r - k) / (1.0 - k);
+            sum[1] += (1.0 - g - k) / (1.0 - k);
+            sum[2] += (1.0 - b - k) / (1.0 - k);
+        }
+        sum[3] += k;
+    }
+    let n = (thumb.width() * thumb.height()).max(1) as f64;
+    sum.map(|s| s / n)
+}
@@ -0,0 +1,154 @@
+use printpdf::image_crate::{
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    DynamicImage, GrayAlphaImage, GrayImage, ImageError, ImageResult, RgbImage, RgbaImage,
+};
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{exit, Stdio};
+use std::time::Duration;
+
+/// Exit code of a decoder that does not support the input
+const UNSUPPORTED: i32 = 2;
+
+/// What a decoding child process may use
+#[derive(Clone, Copy)]
+pub struct Limits {
+    /// Address space in bytes
+    pub memory: u64,
+    /// Processor time in seconds
+    pub cpu: u64,
+}
+
+/// Writes the decoded `input` to stdout as its width, height and channel
+/// count followed by the raw 8-bit samples, for [`decode`]
+pub fn run(input: &Path) -> Result<(), Box<dyn Error>> {
+    let img = match crate::decode::open_file(input) {
+        Ok(img) => img,
+        Err(e) => {
+            eprint!("{e}");
+            exit(if matches!(e, ImageError::Unsupported(_)) {
+                UNSUPPORTED
+            } else {
+                1
+            })
+        }
+    };
+    let (width, height) = (img.width(), img.height());
+    let (channels, samples) = match img {
+        DynamicImage::ImageLuma8(img) => (1, img.into_raw()),
+        DynamicImage::ImageLumaA8(img) => (2, img.into_raw()),
+        DynamicImage::ImageRgb8(img) => (3, img.into_raw()),
+        img if img.color().has_alpha() => (4, img.to_rgba8().into_raw()),
+        img => (3, img.to_rgb8().into_raw()),
+    };
+    let mut out = io::stdout().lock();
+    out.write_all(&width.to_le_bytes())?;
+    out.write_all(&height.to_le_bytes())?;
+    out.write_all(&[channels])?;
+    out.write_all(&samples)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Decodes `path` in a child process held to `limits`, killing it once
+/// `timeout` has passed. A decoder that crashes or runs out of memory only
+/// costs the input it was decoding.
+///
+/// The current executable is started again as `<exe> decode <path>`, so it
+/// has to hand such a command line to [`run`].
+pub fn decode(path: &Path, limits: Limits, timeout: Option<Duration>) -> ImageResult<DynamicImage> {
+    let mut cmd = std::process::Command::new(std::env::current_exe()?);
+    cmd.arg("decode")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    restrict(&mut cmd, limits);
+    let mut child = cmd.spawn()?;
+
+    let (mut stdout, mut stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        let mut err = String::new();
+        let read = stdout
+            .read_to_end(&mut out)
+            .and_then(|_| stderr.read_to_string(&mut err));
+        let _ = tx.send(read.map(|_| (out, err)));
+    });
+    let received = match timeout {
+        Some(timeout) => rx.recv_timeout(timeout).ok(),
+        None => rx.recv().ok(),
+    };
+    let (out, err) = match received {
+        Some(read) => read?,
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(other(format!(
+                "decoding took longer than {:?}",
+                timeout.unwrap_or_default()
+            )));
+        }
+    };
+    let status = child.wait()?;
+    match status.code() {
+        Some(0) => from_raw(&out).ok_or_else(|| other("the decoder sent a broken image".into())),
+        Some(UNSUPPORTED) => Err(ImageError::Unsupported(
+            UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Unknown,
+                UnsupportedErrorKind::GenericFeature(err),
+            ),
+        )),
+        Some(_) if !err.is_empty() => Err(other(err)),
+        _ => Err(other(format!("the decoder was stopped ({status})"))),
+    }
+}
+
+/// Reads what [`run`] wrote
+fn from_raw(data: &[u8]) -> Option<DynamicImage> {
+    let word = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+    let (width, height) = (word(0)?, word(4)?);
+    let samples = data.get(9..)?.to_vec();
+    Some(match data.get(8)? {
+        1 => DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, samples)?),
+        2 => DynamicImage::ImageLumaA8(GrayAlphaImage::from_raw(width, height, samples)?),
+        3 => DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, samples)?),
+        4 => DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, samples)?),
+        _ => return None,
+    })
+}
+
+fn other(message: String) -> ImageError {
+    ImageError::IoError(io::Error::other(message))
+}
+
+/// Applies `limits` to the child and, on Linux where unprivileged user
+/// namespaces are allowed, cuts it off from the network
+#[cfg(unix)]
+fn restrict(cmd: &mut std::process::Command, limits: Limits) {
+    use std::os::unix::process::CommandExt;
+
+    let set = |resource, value: u64| {
+        let limit = libc::rlimit {
+            rlim_cur: value as libc::rlim_t,
+            rlim_max: value as libc::rlim_t,
+        };
+        // SAFETY: setrlimit only reads the struct it is given
+        unsafe { libc::setrlimit(resource, &limit) };
+    };
+    // SAFETY: only async-signal-safe calls are made between fork and exec
+    unsafe {
+        cmd.pre_exec(move || {
+            set(libc::RLIMIT_AS, limits.memory);
+            set(libc::RLIMIT_CPU, limits.cpu);
+            #[cfg(target_os = "linux")]
+            libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn restrict(_cmd: &mut std::process::Command, _limits: Limits) {}
@@ -0,0 +1,77 @@
+use printpdf::lopdf::{self, content::Operation, dictionary, Dictionary, Object, ObjectId};
+
+use crate::info::text_string;
+use crate::prepress::add_resource;
+
+/// Adds an optional content group that viewers list as a layer named `name`,
+/// shown by default
+pub fn add_layer(doc: &mut lopdf::Document, name: &str) -> lopdf::Result<ObjectId> {
+    let layer_id = doc.add_object(dictionary! {
+        "Type" => "OCG",
+        "Name" => text_string(name),
+    });
+    register(doc, layer_id)?;
+    Ok(layer_id)
+}
+
+/// Lists the optional content group `layer_id` among the layers of the
+/// document, shown by default
+pub fn register(doc: &mut lopdf::Document, layer_id: ObjectId) -> lopdf::Result<()> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+    if !matches!(catalog.get(b"OCProperties"), Ok(Object::Dictionary(_))) {
+        catalog.set(
+            "OCProperties",
+            dictionary! { "OCGs" => vec![], "D" => dictionary! {} },
+        );
+    }
+    let properties = catalog.get_mut(b"OCProperties")?.as_dict_mut()?;
+    push(properties, b"OCGs", layer_id);
+    if let Ok(Object::Dictionary(config)) = properties.get_mut(b"D") {
+        push(config, b"Order", layer_id);
+        push(config, b"ON", layer_id);
+    }
+    Ok(())
+}
+
+/// Surrounds `ops` with a marked content section that puts them on `layer`,
+/// registering the layer as `name` in the page's resources
+pub fn mark(
+    doc: &mut lopdf::Document,
+    page_id: ObjectId,
+    name: &str,
+    layer: ObjectId,
+    ops: Vec<Operation>,
+) -> lopdf::Result<Vec<Operation>> {
+    add_resource(doc, page_id, "Properties", name, layer)?;
+    let mut marked = vec![Operation::new("BDC", vec!["OC".into(), name.into()])];
+    marked.extend(ops);
+    marked.push(Operation::new("EMC", vec![]));
+    Ok(marked)
+}
+
+/// Layer names are written as raw UTF-8, which viewers misread unless the
+/// name is plain ASCII
+pub fn encode_names(doc: &mut lopdf::Document) {
+    for object in doc.objects.values_mut() {
+        let dict = match object {
+            Object::Dictionary(dict) if dict.type_is(b"OCG") => dict,
+            _ => continue,
+        };
+        let name = match dict.get(b"Name") {
+            Ok(Object::String(bytes, _)) if !bytes.is_ascii() => std::str::from_utf8(bytes),
+            _ => continue,
+        };
+        if let Ok(name) = name {
+            let encoded = text_string(name);
+            dict.set("Name", encoded);
+        }
+    }
+}
+
+fn push(dict: &mut Dictionary, key: &[u8], id: ObjectId) {
+    match dict.get_mut(key) {
+        Ok(Object::Array(ids)) => ids.push(id.into()),
+        _ => dict.set(key.to_vec(), vec![id.into()]),
+    }
+}
@@ -0,0 +1,2774 @@
+mod append;
+mod chapters;
+mod columns;
+mod compress;
+mod decode;
+mod downscale;
+mod enhance;
+pub mod glob;
+#[cfg(feature = "heif")]
+pub mod heif;
+#[cfg(feature = "archive")]
+mod inflate;
+pub mod info;
+pub mod ink;
+#[cfg(feature = "isolate")]
+pub mod isolate;
+mod layers;
+pub mod manifest;
+pub mod metadata;
+#[cfg(feature = "ocr")]
+pub mod ocr;
+pub mod outline;
+pub mod overlay;
+pub mod pagetree;
+pub mod paper;
+pub mod portfolio;
+pub mod prepress;
+mod signature;
+mod stitch;
+pub mod template;
+pub mod toml;
+#[cfg(feature = "archive")]
+pub mod zip;
+
+use printpdf::{
+    image_crate::{self, codecs::jpeg::JpegEncoder, imageops::FilterType},
+    image_crate::{ColorType, DynamicImage, GenericImageView, ImageError, ImageFormat},
+    lopdf, BuiltinFont, Color, Greyscale, Image, Line, Mm, OffsetDateTime, PdfDocument, Point,
+};
+use printpdf::{ColorBits, ColorSpace, ImageFilter, ImageXObject, Px};
+use printpdf::{ImageTransform, PdfDocumentReference, PdfLayerReference};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use decode::{Decoder, Pipeline, Prepared};
+
+const INCH_PER_MM: f64 = 25.4;
+/// Largest page edge most PDF viewers will open (200 inches)
+const MAX_PAGE_EDGE: Mm = Mm(5080.0);
+/// Pages of a grid when no page size is set (A4)
+const NUP_PAGE: (Mm, Mm) = (Mm(210.0), Mm(297.0));
+/// Effective resolution below which pages are reported as blurry
+const LOW_DPI_WARNING: f64 = 72.0;
+/// How far, as a log of the ratio, the shape of split columns may stray from
+/// that of the first input's before the input is reported (about 1.5 times)
+const COLUMN_ASPECT_TOLERANCE: f64 = 0.4;
+/// Variables a bookmark template can refer to
+const BOOKMARK_VARIABLES: [&str; 9] = [
+    "title",
+    "filename",
+    "stem",
+    "dirname",
+    "index",
+    "dir_index",
+    "page",
+    "exif_title",
+    "xmp_title",
+];
+
+/// Variables a layer name template can refer to
+const LAYER_VARIABLES: [&str; 5] = ["filename", "stem", "dirname", "index", "page"];
+
+/// Why a page could not be added or the PDF could not be written
+#[derive(Debug)]
+pub enum Error {
+    /// An input could not be decoded, [`ImageError::Unsupported`] for formats
+    /// that are not supported at all
+    Image(ImageError),
+    Io(io::Error),
+    Pdf(printpdf::Error),
+    /// The PDF could not be post-processed once written
+    Document(lopdf::Error),
+    /// The signature placeholder could not be filled in
+    Signature(&'static str),
+    /// A builder setting that cannot be used, named after its method
+    InvalidOption {
+        option: &'static str,
+        reason: String,
+    },
+    /// The [`CancelToken`] was cancelled before the input was added
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Image(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+            Error::Pdf(e) => e.fmt(f),
+            Error::Document(e) => e.fmt(f),
+            Error::Signature(e) => write!(f, "could not fill in the signature placeholder: {e}"),
+            Error::InvalidOption { option, reason } => write!(f, "{option} {reason}"),
+            Error::Cancelled => write!(f, "the merge was cancelled"),
+        }
+    }
+}
+
+/// How bad a [`Diagnostic`] or a [`Warning`] is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The builder refuses to build, or an input was left out
+    Error,
+    /// The settings work, but not all of them have an effect, or the
+    /// document may not come out as wanted
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A problem with the settings of a [`PdfMergerBuilder`]
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The setting at fault, named after its method
+    pub option: &'static str,
+    /// The setting it clashes with, when it is the combination at fault
+    pub conflicts_with: Option<&'static str>,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    fn error(option: &'static str, reason: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            option,
+            conflicts_with: None,
+            reason: reason.to_owned(),
+        }
+    }
+
+    fn conflict(option: &'static str, with: &'static str, reason: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            option,
+            conflicts_with: Some(with),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.option, self.reason)?;
+        match self.conflicts_with {
+            Some(with) => write!(f, " ({with} is set)"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What a [`Warning`] is about, for tools to tell warnings apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningCode {
+    /// An input could not be read and was left out
+    SkippedInput,
+    /// An input is in a format that cannot be decoded
+    UnsupportedFormat,
+    /// A page has less than the minimum resolution
+    LowResolution,
+    /// A page was kept whole instead of split into columns
+    ColumnsNotSplit,
+    /// The columns of a page are shaped unlike those of the first input
+    ColumnsMismatch,
+    /// A continuous page is longer than some viewers open
+    PageTooLong,
+    /// The text of a page could not be recognized
+    OcrFailed,
+    /// An image could not be written to the preview directory
+    PreviewNotWritten,
+}
+
+impl WarningCode {
+    /// The code in kebab case, as reports write it
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WarningCode::SkippedInput => "skipped-input",
+            WarningCode::UnsupportedFormat => "unsupported-format",
+            WarningCode::LowResolution => "low-resolution",
+            WarningCode::ColumnsNotSplit => "columns-not-split",
+            WarningCode::ColumnsMismatch => "columns-mismatch",
+            WarningCode::PageTooLong => "page-too-long",
+            WarningCode::OcrFailed => "ocr-failed",
+            WarningCode::PreviewNotWritten => "preview-not-written",
+        }
+    }
+}
+
+impl fmt::Display for WarningCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A problem found during a merge that did not stop it
+#[derive(Clone, Debug)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub severity: Severity,
+    /// The input it is about, if it is about one
+    pub source: Option<PathBuf>,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Pdf(e) => Some(e),
+            Error::Document(e) => Some(e),
+            Error::Signature(_) | Error::InvalidOption { .. } | Error::Cancelled => None,
+        }
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<printpdf::Error> for Error {
+    fn from(e: printpdf::Error) -> Self {
+        Error::Pdf(e)
+    }
+}
+
+impl From<lopdf::Error> for Error {
+    fn from(e: lopdf::Error) -> Self {
+        Error::Document(e)
+    }
+}
+
+/// Processing tuned for a kind of source
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Evens out the lighting of photographed whiteboards
+    Whiteboard,
+    /// Puts everything on one continuous page, 80mm wide unless set otherwise
+    Receipt,
+}
+
+/// A reading device the pages are laid out for
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    KindlePaperwhite,
+    Remarkable2,
+    Ipad,
+}
+
+impl Device {
+    /// Screen size in pixels, portrait
+    fn screen(self) -> (u32, u32) {
+        match self {
+            Device::KindlePaperwhite => (1236, 1648),
+            Device::Remarkable2 => (1404, 1872),
+            Device::Ipad => (1640, 2360),
+        }
+    }
+
+    /// Screen pixels per inch
+    fn ppi(self) -> f64 {
+        match self {
+            Device::KindlePaperwhite => 300.0,
+            Device::Remarkable2 => 226.0,
+            Device::Ipad => 264.0,
+        }
+    }
+
+    /// Size of the screen as a page
+    fn page(self) -> (Mm, Mm) {
+        let (w, h) = self.screen();
+        let mm = |px: u32| Mm(px as f64 * INCH_PER_MM / self.ppi());
+        (mm(w), mm(h))
+    }
+
+    /// Blank space kept around the image, clear of the bezel and toolbars
+    pub fn margin(self) -> Mm {
+        match self {
+            Device::KindlePaperwhite => Mm(3.0),
+            Device::Remarkable2 => Mm(6.0),
+            Device::Ipad => Mm(4.0),
+        }
+    }
+
+    fn e_ink(self) -> bool {
+        matches!(self, Device::KindlePaperwhite | Device::Remarkable2)
+    }
+}
+
+/// How images are placed on pages of a set size
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// As large as fits whole, centered
+    #[default]
+    Contain,
+    /// Filling the page, cropped to its proportions
+    Cover,
+    /// Filling the page, distorted to its proportions
+    Stretch,
+    /// At its own size, centered and cut off where it overflows
+    Center,
+}
+
+/// Which way the cells of an N-up page are filled
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellOrder {
+    /// Left to right, then top to bottom
+    #[default]
+    RowMajor,
+    /// Top to bottom, then left to right
+    ColumnMajor,
+}
+
+/// Colors images are embedded in
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// As they are
+    #[default]
+    Keep,
+    /// 8-bit grayscale
+    Gray,
+    /// 1-bit black and white, pixels at least as bright as `threshold`
+    /// (0 to 255) turning white, for small scans of text
+    Bilevel { threshold: u8 },
+}
+
+/// How images are compressed in the PDF
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Every image the same way: as JPEG at the quality set, if any, and
+    /// losslessly otherwise
+    #[default]
+    Fixed,
+    /// Picked per image by what it holds: CCITT G4 for black and white,
+    /// lossless for flat colors and JPEG for photos, at the quality set or
+    /// [`AUTO_QUALITY`]
+    Auto,
+}
+
+/// JPEG quality of photos under [`Compression::Auto`] when none is set
+pub const AUTO_QUALITY: u8 = 85;
+
+/// How inputs are turned before they are laid out
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    /// Upright as their EXIF orientation says, for photos taken with phones
+    #[default]
+    Auto,
+    /// As stored, ignoring the EXIF orientation
+    None,
+    /// A quarter turn clockwise
+    Cw90,
+    Cw180,
+    /// A quarter turn counterclockwise
+    Cw270,
+}
+
+/// Where page bookmarks take their titles from
+#[derive(Clone, Copy)]
+pub enum BookmarkSource {
+    Filename,
+    ExifTitle,
+    XmpTitle,
+    /// The largest line of recognized text, which needs OCR
+    #[cfg(feature = "ocr")]
+    OcrHeading,
+}
+
+/// Stops a merge from another thread, such as when a user of a GUI or a
+/// client of a server gives up on it. Clones share the same state.
+///
+/// Once cancelled, inputs are no longer decoded and adding one fails with
+/// [`Error::Cancelled`]. The pages added before that are kept, so the merger
+/// can still be saved with what it has, or dropped to abort.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Hears how a merge is going as it goes, for embedders to show progress,
+/// warnings and timings their own way. Every method does nothing unless
+/// implemented.
+pub trait ProgressSink {
+    /// `input` is about to be added, the `index`th (1-based) of this merger
+    fn input_started(&mut self, _index: usize, _input: &Path) {}
+
+    /// The `page`th (1-based) page was started
+    fn page_added(&mut self, _page: usize) {}
+
+    /// A problem that did not stop the merge, as also listed by
+    /// [`PDFMerger::warnings`]
+    fn warning(&mut self, _warning: &Warning) {}
+
+    /// `stage` of an input, or of saving, is done and took `elapsed`
+    fn stage_finished(&mut self, _stage: Stage, _elapsed: Duration) {}
+}
+
+/// Steps of a merge timed for a [`ProgressSink`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Decoding an input and preparing it, including waiting for it to be
+    /// decoded ahead
+    Decode,
+    /// Placing an input on its pages, recognizing text included
+    Layout,
+    /// Post-processing and writing the PDF
+    Save,
+}
+
+/// The sink of mergers nobody listens to
+struct Silent;
+
+impl ProgressSink for Silent {}
+
+/// A clickable area of a page
+#[derive(Clone)]
+pub struct Link {
+    pub url: String,
+    /// Left, top, width and height in pixels of the source image
+    pub rect: [u32; 4],
+}
+
+/// Settings that apply to a single input
+#[derive(Default)]
+pub struct PageOptions {
+    pub links: Vec<Link>,
+    /// Scales this input to a percentage of its size instead of the scaling
+    /// set for all of them
+    pub scale: Option<f64>,
+    /// Keeps its own page size instead of being tiled or collected onto a
+    /// continuous page, like a fold-out map
+    pub fold_out: bool,
+    /// Left, top, width and height in pixels of the source image of areas
+    /// such as a signature or photo, embedded losslessly at full resolution
+    /// over the rest when that is compressed or scaled down
+    pub regions: Vec<[u32; 4]>,
+    /// Embeds this input in another color mode than the rest
+    pub color_mode: Option<ColorMode>,
+}
+
+/// Where the first images go as they would be embedded
+struct Preview {
+    dir: PathBuf,
+    left: usize,
+    written: usize,
+}
+
+struct ContinuousPage {
+    width: Option<Mm>,
+    spacing: Mm,
+    images: Vec<DynamicImage>,
+    /// Widths of the images before scaling, to work out their real resolution
+    source_widths: Vec<u32>,
+}
+
+/// Images collected for the next page of a grid
+struct Nup {
+    rows: usize,
+    columns: usize,
+    gutter: Mm,
+    order: CellOrder,
+    images: Vec<DynamicImage>,
+    source_widths: Vec<u32>,
+}
+
+/// Portrait pages waiting for the other half of their spread
+struct Spreads {
+    rtl: bool,
+    /// Whether the next page stands alone, as the cover of a book does
+    cover: bool,
+    /// The first half and its width before scaling
+    pending: Option<(DynamicImage, u32)>,
+}
+
+#[derive(Clone, Copy)]
+struct Tiling {
+    page: (Mm, Mm),
+    overlap: Mm,
+}
+
+/// Settings for a [`PDFMerger`], starting from the defaults of the command
+/// line
+pub struct PdfMergerBuilder {
+    title: String,
+    dpi: f64,
+    scale: (u32, u32),
+    scale_percent: Option<f64>,
+    min_dpi: f64,
+    preset: Option<Preset>,
+    device: Option<Device>,
+    page_size: Option<((Mm, Mm), Fit)>,
+    margins: Option<[Mm; 4]>,
+    columns: usize,
+    trim_margins: [f64; 4],
+    rotation: Rotation,
+    quality: Option<u8>,
+    max_dimension: Option<u32>,
+    color_mode: ColorMode,
+    compression: Compression,
+    /// Directory and number of images to preview
+    preview: Option<(PathBuf, usize)>,
+    dewarp: bool,
+    protect_text: bool,
+    stitch_vertical: bool,
+    /// Width (that of the widest image if unset) and spacing
+    continuous: Option<(Option<Mm>, Mm)>,
+    nup: Option<(usize, usize, Mm, CellOrder)>,
+    /// Right to left, and whether the first page stands alone
+    spreads: Option<(bool, bool)>,
+    tiling: Option<Tiling>,
+    bleed: Option<Mm>,
+    printer_marks: Option<prepress::PrinterMarks>,
+    reserve_signature: bool,
+    append_to: Option<PathBuf>,
+    stamp: Option<overlay::Stamp>,
+    layers: bool,
+    layer_name_template: Option<String>,
+    ink_report: bool,
+    bookmark_source: Option<BookmarkSource>,
+    bookmark_template: Option<String>,
+    bookmark_root: Option<PathBuf>,
+    named_dests: bool,
+    detect_chapters: bool,
+    custom_info: Vec<(String, String)>,
+    author: Option<String>,
+    subject: Option<String>,
+    keywords: Vec<String>,
+    creation_date: Option<OffsetDateTime>,
+    decode_timeout: Option<Duration>,
+    cancel: CancelToken,
+    fixed_date: Option<OffsetDateTime>,
+    progress: Box<dyn ProgressSink>,
+    jobs: usize,
+    #[cfg(feature = "isolate")]
+    isolate: Option<isolate::Limits>,
+    #[cfg(feature = "ocr")]
+    ocr: Option<ocr::Options>,
+    #[cfg(feature = "ocr")]
+    auto_rotate_text: bool,
+}
+
+impl Default for PdfMergerBuilder {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            dpi: 100.0,
+            scale: (1080, 1920),
+            scale_percent: None,
+            min_dpi: LOW_DPI_WARNING,
+            preset: None,
+            device: None,
+            page_size: None,
+            margins: None,
+            columns: 1,
+            trim_margins: [0.0; 4],
+            rotation: Rotation::Auto,
+            quality: None,
+            max_dimension: None,
+            color_mode: ColorMode::Keep,
+            compression: Compression::Fixed,
+            preview: None,
+            dewarp: false,
+            protect_text: false,
+            stitch_vertical: false,
+            continuous: None,
+            nup: None,
+            spreads: None,
+            tiling: None,
+            bleed: None,
+            printer_marks: None,
+            reserve_signature: false,
+            append_to: None,
+            stamp: None,
+            layers: false,
+            layer_name_template: None,
+            ink_report: false,
+            bookmark_source: None,
+            bookmark_template: None,
+            bookmark_root: None,
+            named_dests: false,
+            detect_chapters: false,
+            custom_info: Vec::new(),
+            author: None,
+            subject: None,
+            keywords: Vec::new(),
+            creation_date: None,
+            decode_timeout: None,
+            cancel: CancelToken::new(),
+            fixed_date: None,
+            progress: Box::new(Silent),
+            jobs: 1,
+            #[cfg(feature = "isolate")]
+            isolate: None,
+            #[cfg(feature = "ocr")]
+            ocr: None,
+            #[cfg(feature = "ocr")]
+            auto_rotate_text: false,
+        }
+    }
+}
+
+impl PdfMergerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Resolution pages are laid out at, which sets their size in mm
+    pub fn dpi(mut self, dpi: f64) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    /// Box every image is scaled into, keeping its proportions
+    pub fn scale(mut self, width: u32, height: u32) -> Self {
+        self.scale = (width, height);
+        self.scale_percent = None;
+        self
+    }
+
+    /// Scales every image to a percentage of its own size instead of into a
+    /// box
+    pub fn scale_percent(mut self, percent: f64) -> Self {
+        self.scale_percent = Some(percent);
+        self
+    }
+
+    /// Effective resolution below which pages are reported
+    pub fn min_dpi(mut self, min_dpi: f64) -> Self {
+        self.min_dpi = min_dpi;
+        self
+    }
+
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    /// Lays every page out for the screen of `device`: its size and
+    /// resolution, a margin, and grayscale with more contrast on e-ink. This
+    /// takes the place of the dpi and scale.
+    pub fn device(mut self, device: Device) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Puts every image on a page of `size`, placed as `fit` says, instead
+    /// of making each page as large as its image
+    pub fn page_size(mut self, size: (Mm, Mm), fit: Fit) -> Self {
+        self.page_size = Some((size, fit));
+        self
+    }
+
+    /// Leaves `margins` free on the top, right, bottom and left of every
+    /// page. Pages of a set size keep their size and the image gets smaller,
+    /// other pages grow around their image.
+    pub fn margins(mut self, margins: [Mm; 4]) -> Self {
+        self.margins = Some(margins);
+        self
+    }
+
+    /// Cuts the given percentages off the top, right, bottom and left edge of
+    /// every input, such as the blank margins of book scans
+    pub fn trim_margins(mut self, percentages: [f64; 4]) -> Self {
+        self.trim_margins = percentages;
+        self
+    }
+
+    /// Turns every input as set, instead of upright by its EXIF orientation
+    pub fn rotate(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Encodes every image as a JPEG at `quality`, from 1 to 100, for smaller
+    /// files
+    pub fn quality(mut self, quality: u8) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Turns images to grayscale or black and white just before they go into
+    /// the PDF
+    pub fn color_mode(mut self, mode: ColorMode) -> Self {
+        self.color_mode = mode;
+        self
+    }
+
+    /// Picks how each image is compressed by what it holds instead of
+    /// compressing them all the same way
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Also writes the first `count` images as PNGs to `dir`, as they would
+    /// go into the PDF after every filter, to check the settings on
+    pub fn preview(mut self, dir: impl Into<PathBuf>, count: usize) -> Self {
+        self.preview = Some((dir.into(), count));
+        self
+    }
+
+    /// Scales images down, just before they go into the PDF, until neither
+    /// side is longer than `pixels`. Pages keep their size.
+    pub fn max_dimension(mut self, pixels: u32) -> Self {
+        self.max_dimension = Some(pixels);
+        self
+    }
+
+    /// Splits pages scanned from documents set in `columns` columns into a
+    /// page per column, left to right, to read on small screens. Pages where
+    /// the gutters cannot be found are kept whole.
+    pub fn columns(mut self, columns: usize) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Straightens photographed documents into flat rectangular pages
+    pub fn dewarp(mut self, dewarp: bool) -> Self {
+        self.dewarp = dewarp;
+        self
+    }
+
+    /// Resamples what looks like print on downscaled images so that fine
+    /// strokes stay dark instead of fading into the paper
+    pub fn protect_text(mut self, protect: bool) -> Self {
+        self.protect_text = protect;
+        self
+    }
+
+    /// Merges overlapping scrolling screenshots into continuous pages
+    pub fn stitch_vertical(mut self, stitch: bool) -> Self {
+        self.stitch_vertical = stitch;
+        self
+    }
+
+    /// Puts all images on one page of `width`, or that of the widest image,
+    /// with `spacing` between them
+    pub fn continuous_page(mut self, width: Option<Mm>, spacing: Mm) -> Self {
+        self.continuous = Some((width, spacing));
+        self
+    }
+
+    /// Tiles `rows` by `columns` images onto every page, `gutter` apart, in
+    /// the cells of pages of the set size or else A4, filled as `order` says.
+    /// Images are placed in their cells as they would be on pages of their
+    /// own.
+    pub fn nup(mut self, rows: usize, columns: usize, gutter: Mm, order: CellOrder) -> Self {
+        self.nup = Some((rows, columns, gutter, order));
+        self
+    }
+
+    /// Places every two consecutive portrait images side by side on one
+    /// landscape page, the first on the right when `rtl`, to put scans of a
+    /// book back together into spreads. With `cover_alone` the first image
+    /// keeps a page of its own, as the cover does. Landscape images and a
+    /// page left without its other half stand alone. On pages of the set
+    /// size, turned to lie across, both halves are scaled alike.
+    pub fn spreads(mut self, rtl: bool, cover_alone: bool) -> Self {
+        self.spreads = Some((rtl, cover_alone));
+        self
+    }
+
+    /// Splits images larger than `page` across several pages of that size,
+    /// repeating `overlap` on neighbouring ones
+    pub fn tile_oversized(mut self, page: (Mm, Mm), overlap: Mm) -> Self {
+        self.tiling = Some(Tiling { page, overlap });
+        self
+    }
+
+    pub fn bleed(mut self, bleed: Mm) -> Self {
+        self.bleed = Some(bleed);
+        self
+    }
+
+    pub fn printer_marks(mut self, marks: prepress::PrinterMarks) -> Self {
+        self.printer_marks = Some(marks);
+        self
+    }
+
+    /// Leaves an empty signature field with room for a signature
+    pub fn reserve_signature(mut self, reserve: bool) -> Self {
+        self.reserve_signature = reserve;
+        self
+    }
+
+    /// Adds the pages after those of the PDF at `existing`, which keeps its
+    /// document information, instead of starting a new document
+    pub fn append_to(mut self, existing: impl Into<PathBuf>) -> Self {
+        self.append_to = Some(existing.into());
+        self
+    }
+
+    pub fn stamp(mut self, stamp: overlay::Stamp) -> Self {
+        self.stamp = Some(stamp);
+        self
+    }
+
+    /// Puts stamps and recognized text on layers of their own
+    pub fn layers(mut self, layers: bool) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Names the layer of every input from a template of `{filename}`,
+    /// `{stem}`, `{dirname}`, `{index}` and `{page}`
+    pub fn layer_name_template(mut self, template: impl Into<String>) -> Self {
+        self.layer_name_template = Some(template.into());
+        self
+    }
+
+    /// Estimates the ink coverage of every page
+    pub fn ink_report(mut self, report: bool) -> Self {
+        self.ink_report = report;
+        self
+    }
+
+    pub fn bookmark_source(mut self, source: BookmarkSource) -> Self {
+        self.bookmark_source = Some(source);
+        self
+    }
+
+    /// Titles bookmarks from a template of `{title}`, `{filename}`, `{stem}`,
+    /// `{dirname}`, `{index}`, `{dir_index}`, `{page}`, `{exif_title}` and
+    /// `{xmp_title}`
+    pub fn bookmark_template(mut self, template: impl Into<String>) -> Self {
+        self.bookmark_template = Some(template.into());
+        self
+    }
+
+    /// Nests the bookmarks of inputs in subdirectories of `root` under an
+    /// entry per subdirectory, titled with its path below `root`
+    pub fn group_bookmarks(mut self, root: impl Into<PathBuf>) -> Self {
+        self.bookmark_root = Some(root.into());
+        self
+    }
+
+    /// Names every page after its input so that links can point to it
+    pub fn named_dests(mut self, named: bool) -> Self {
+        self.named_dests = named;
+        self
+    }
+
+    /// Bookmarks pages that look like chapter title pages
+    pub fn detect_chapters(mut self, detect: bool) -> Self {
+        self.detect_chapters = detect;
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    pub fn keywords(mut self, keywords: Vec<String>) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    /// Dates the document to `date` instead of when it is made
+    pub fn creation_date(mut self, date: OffsetDateTime) -> Self {
+        self.creation_date = Some(date);
+        self
+    }
+
+    /// Adds a custom entry to the document information
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.custom_info.push((key.into(), value.into()));
+        self
+    }
+
+    /// Gives up on inputs that take longer than `timeout` to decode
+    pub fn decode_timeout(mut self, timeout: Duration) -> Self {
+        self.decode_timeout = Some(timeout);
+        self
+    }
+
+    /// Makes the same inputs and settings always give the same file, for
+    /// reproducible builds and tests: every date in the PDF is `date` and
+    /// its IDs come from the title instead of chance
+    pub fn deterministic(mut self, date: OffsetDateTime) -> Self {
+        self.fixed_date = Some(date);
+        self
+    }
+
+    /// Stops adding inputs once `token` is cancelled
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Tells `sink` about inputs, pages, warnings and timings as they come
+    pub fn progress(mut self, sink: impl ProgressSink + 'static) -> Self {
+        self.progress = Box::new(sink);
+        self
+    }
+
+    /// Decodes and scales up to `jobs` inputs at once, for those queued with
+    /// [`PDFMerger::queue`]
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Decodes every input in a child process held to `limits`, see
+    /// [`isolate::decode`]
+    #[cfg(feature = "isolate")]
+    pub fn isolate(mut self, limits: isolate::Limits) -> Self {
+        self.isolate = Some(limits);
+        self
+    }
+
+    /// Lays a searchable text layer over the pages
+    #[cfg(feature = "ocr")]
+    pub fn ocr(mut self, options: ocr::Options) -> Self {
+        self.ocr = Some(options);
+        self
+    }
+
+    /// Turns images without an EXIF orientation upright by the direction of
+    /// their text, as detected by tesseract, for book scans fed in upside
+    /// down or sideways
+    #[cfg(feature = "ocr")]
+    pub fn auto_rotate_text(mut self, rotate: bool) -> Self {
+        self.auto_rotate_text = rotate;
+        self
+    }
+
+    /// Every problem with the settings, in the order they are checked.
+    /// [`PdfMergerBuilder::build`] refuses to build on the first error,
+    /// while warnings point out settings that will have no effect.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut found = Vec::new();
+        let mut error = |option, reason: &str| found.push(Diagnostic::error(option, reason));
+        if !(self.dpi.is_finite() && self.dpi > 0.0) {
+            error("dpi", "must be a positive number");
+        }
+        if self.jobs == 0 {
+            error("jobs", "must be a positive int");
+        }
+        if self.columns == 0 {
+            error("columns", "must be a positive int");
+        }
+        if self.quality.is_some_and(|q| !(1..=100).contains(&q)) {
+            error("quality", "must be an int from 1 to 100");
+        }
+        if self.max_dimension == Some(0) {
+            error("max_dimension", "must be a positive int");
+        }
+        if self.preview.as_ref().is_some_and(|(_, count)| *count == 0) {
+            error("preview", "must write at least one image");
+        }
+        let [top, right, bottom, left] = self.trim_margins;
+        if !(self.trim_margins.iter().all(|p| *p >= 0.0)
+            && top + bottom < 100.0
+            && left + right < 100.0)
+        {
+            error(
+                "trim_margins",
+                "must leave some of the image between opposite edges",
+            );
+        }
+        if self
+            .margins
+            .is_some_and(|m| m.iter().any(|m| m.0 < 0.0 || m.0.is_nan()))
+        {
+            error("margin", "must not be negative");
+        }
+        if let Some(((w, h), _)) = self.page_size {
+            if !(w.0 > 0.0 && h.0 > 0.0) {
+                error("page_size", "must be a positive size");
+            }
+            if self.device.is_some() {
+                error("page_size", "cannot be combined with a device");
+            }
+        }
+        let page = match self.device {
+            Some(device) => Some(device.page()),
+            None => self.page_size.map(|(size, _)| size),
+        };
+        if let Some((w, h)) = page {
+            let [top, right, bottom, left] = self.margins.unwrap_or([Mm(0.0); 4]);
+            if left + right >= w || top + bottom >= h {
+                error("margin", "must leave room for the image on the page");
+            }
+        }
+        if let Some((rows, columns, gutter, _)) = self.nup {
+            let (w, h) = page.unwrap_or(NUP_PAGE);
+            let [top, right, bottom, left] = self.margins.unwrap_or([Mm(0.0); 4]);
+            if rows == 0 || columns == 0 {
+                error("nup", "must have at least one row and one column");
+            } else if gutter.0 < 0.0 || gutter.0.is_nan() {
+                error("nup", "must have a gutter that is not negative");
+            } else if w - left - right <= gutter * (columns - 1) as f64
+                || h - top - bottom <= gutter * (rows - 1) as f64
+            {
+                error("nup", "must leave room for the images between the gutters");
+            }
+        }
+        if let Some(percent) = self.scale_percent {
+            if !(percent.is_finite() && percent > 0.0) {
+                error("scale", "must be a positive percentage");
+            }
+        }
+        if let Some(Tiling { page, overlap }) = self.tiling {
+            if overlap < Mm(0.0) || overlap >= page.0 || overlap >= page.1 {
+                error(
+                    "tile_overlap",
+                    "must be a length in mm smaller than the page",
+                );
+            }
+        }
+        let unset = None;
+        let stamp =
+            |text: fn(&overlay::Stamp) -> &Option<String>| self.stamp.as_ref().map_or(&unset, text);
+        let templates = [
+            (
+                "bookmark_template",
+                &self.bookmark_template,
+                &BOOKMARK_VARIABLES[..],
+            ),
+            (
+                "layer_name_template",
+                &self.layer_name_template,
+                &LAYER_VARIABLES[..],
+            ),
+            (
+                "header",
+                stamp(|s| &s.header),
+                &overlay::RUNNING_TEXT_VARIABLES[..],
+            ),
+            (
+                "footer",
+                stamp(|s| &s.footer),
+                &overlay::RUNNING_TEXT_VARIABLES[..],
+            ),
+        ];
+        for (option, template, known) in templates {
+            if let Some(Err(e)) = template
+                .as_ref()
+                .map(|t| template::render(t, |var| known.contains(&var).then(String::new)))
+            {
+                let reason = format!("is invalid, {e}; known variables are {}", known.join(", "));
+                error(option, &reason);
+            }
+        }
+
+        // settings that are left without effect by others
+        let continuous = if self.continuous.is_some() {
+            Some("continuous_page")
+        } else if self.preset == Some(Preset::Receipt) {
+            Some("preset")
+        } else {
+            None
+        };
+        if let Some(continuous) = continuous {
+            let ignored = [
+                ("tile_oversized", self.tiling.is_some()),
+                ("page_size", self.page_size.is_some()),
+                ("device", self.device.is_some()),
+                ("nup", self.nup.is_some()),
+                ("spreads", self.spreads.is_some()),
+                ("margin", self.margins.is_some()),
+            ];
+            for (option, set) in ignored {
+                if set {
+                    found.push(Diagnostic::conflict(
+                        option,
+                        continuous,
+                        "has no effect on a continuous page",
+                    ));
+                }
+            }
+        }
+        if self.tiling.is_some() && self.nup.is_some() {
+            found.push(Diagnostic::conflict(
+                "tile_oversized",
+                "nup",
+                "has no effect on pages of several images",
+            ));
+        }
+        if self.spreads.is_some() && self.nup.is_some() {
+            found.push(Diagnostic::conflict(
+                "spreads",
+                "nup",
+                "has no effect on pages of several images",
+            ));
+        }
+        if self.quality.is_some() && matches!(self.color_mode, ColorMode::Bilevel { .. }) {
+            found.push(Diagnostic::conflict(
+                "quality",
+                "color_mode",
+                "has no effect on black and white images",
+            ));
+        }
+        if self.stitch_vertical && self.columns > 1 {
+            found.push(Diagnostic::conflict(
+                "columns",
+                "stitch_vertical",
+                "has no effect when the columns are stitched back together",
+            ));
+        }
+        #[cfg(feature = "ocr")]
+        if self.auto_rotate_text && self.rotation != Rotation::Auto {
+            found.push(Diagnostic::conflict(
+                "auto_rotate_text",
+                "rotate",
+                "has no effect when every image is turned the same",
+            ));
+        }
+        found
+    }
+
+    pub fn build(self) -> Result<PDFMerger, Error> {
+        let diagnostics = self.validate();
+        if let Some(d) = diagnostics
+            .into_iter()
+            .find(|d| d.severity == Severity::Error)
+        {
+            return Err(Error::InvalidOption {
+                option: d.option,
+                reason: d.reason,
+            });
+        }
+
+        // a device takes over the resolution and size of the pages
+        let (dpi, scale, scale_percent, page_size, margins) = match self.device {
+            Some(device) => {
+                let (w, h) = device.screen();
+                let ppi = device.ppi();
+                let margins = self.margins.unwrap_or([device.margin(); 4]);
+                let [top, right, bottom, left] =
+                    margins.map(|m| (m.0 * ppi / INCH_PER_MM).round() as u32);
+                let content = (
+                    w.saturating_sub(left + right).max(1),
+                    h.saturating_sub(top + bottom).max(1),
+                );
+                (ppi, content, None, Some(device.page()), margins)
+            }
+            None => {
+                let page_size = self.page_size.map(|(size, _)| size);
+                let margins = self.margins.unwrap_or([Mm(0.0); 4]);
+                (self.dpi, self.scale, self.scale_percent, page_size, margins)
+            }
+        };
+        let append_to = match &self.append_to {
+            Some(path) => {
+                let doc = lopdf::Document::load(path)?;
+                if doc.trailer.has(b"Encrypt") {
+                    return Err(Error::InvalidOption {
+                        option: "append_to",
+                        reason: "cannot be an encrypted PDF".to_owned(),
+                    });
+                }
+                Some(doc)
+            }
+            None => None,
+        };
+        let receipt = self.preset == Some(Preset::Receipt);
+        let continuous = match self.continuous {
+            Some((width, spacing)) => Some((width, spacing)),
+            None if receipt => Some((None, Mm(0.0))),
+            None => None,
+        };
+        let mut pdf = PdfDocument::empty(&self.title);
+        if let Some(date) = self.fixed_date {
+            pdf = pdf
+                .with_creation_date(date)
+                .with_mod_date(date)
+                .with_metadata_date(date)
+                .with_document_id(info::document_id(&self.title));
+        }
+        if let Some(date) = self.creation_date {
+            pdf = pdf.with_creation_date(date);
+        }
+        if let Some(author) = self.author {
+            pdf = pdf.with_author(author);
+        }
+        if let Some(subject) = self.subject {
+            pdf = pdf.with_subject(subject);
+        }
+        if !self.keywords.is_empty() {
+            pdf = pdf.with_keywords(self.keywords);
+        }
+        Ok(PDFMerger {
+            pdf,
+            fixed_date: self.fixed_date,
+            title: self.title,
+            dpi,
+            page_size,
+            fit: self.page_size.map_or(Fit::Contain, |(_, fit)| fit),
+            margins,
+            decoder: Decoder {
+                scale,
+                scale_percent,
+                rotation: self.rotation,
+                preset: self.preset,
+                dewarp: self.dewarp,
+                e_ink: self.device.is_some_and(Device::e_ink),
+                columns: self.columns,
+                trim: self.trim_margins,
+                protect_text: self.protect_text,
+                #[cfg(feature = "ocr")]
+                rotate_by_text: self.auto_rotate_text,
+                timeout: self.decode_timeout,
+                #[cfg(feature = "isolate")]
+                isolate: self.isolate,
+            },
+            jobs: self.jobs,
+            pipeline: None,
+            quality: self.quality,
+            max_dimension: self.max_dimension,
+            color_mode: self.color_mode,
+            default_color_mode: self.color_mode,
+            compression: self.compression,
+            preview: self.preview.map(|(dir, left)| Preview {
+                dir,
+                left,
+                written: 0,
+            }),
+            cancel: self.cancel,
+            progress: self.progress,
+            stitcher: self.stitch_vertical.then(stitch::Stitcher::default),
+            continuous: continuous.map(|(width, spacing)| ContinuousPage {
+                width: width.or(receipt.then_some(Mm(80.0))),
+                spacing,
+                images: Vec::new(),
+                source_widths: Vec::new(),
+            }),
+            nup: self.nup.map(|(rows, columns, gutter, order)| Nup {
+                rows,
+                columns,
+                gutter,
+                order,
+                images: Vec::new(),
+                source_widths: Vec::new(),
+            }),
+            spreads: self.spreads.map(|(rtl, cover)| Spreads {
+                rtl,
+                cover,
+                pending: None,
+            }),
+            tiling: self.tiling,
+            bleed: self.bleed,
+            printer_marks: self.printer_marks,
+            reserve_signature: self.reserve_signature,
+            append_to,
+            stamp: self.stamp,
+            layers: self.layers,
+            layer_name_template: self.layer_name_template,
+            layer_name: String::new(),
+            ink_coverage: self.ink_report.then(Vec::new),
+            page_count: 0,
+            min_dpi: self.min_dpi,
+            low_dpi: Vec::new(),
+            bookmark_source: self.bookmark_source,
+            pending_bookmark: None,
+            bookmark_template: self.bookmark_template,
+            bookmark_root: self.bookmark_root,
+            pending_group: None,
+            inputs_seen: 0,
+            dirs_seen: Vec::new(),
+            bookmarks: Vec::new(),
+            named_dests: self.named_dests,
+            pending_dest: None,
+            dests: Vec::new(),
+            page_files: Vec::new(),
+            pending_links: Vec::new(),
+            pending_scale: None,
+            pending_fold_out: false,
+            pending_regions: Vec::new(),
+            processed: Vec::new(),
+            warnings: Vec::new(),
+            current_input: None,
+            column_aspect: None,
+            links: Vec::new(),
+            detect_chapters: self.detect_chapters,
+            chapters_found: 0,
+            custom_info: self.custom_info,
+            portfolio: Vec::new(),
+            #[cfg(feature = "ocr")]
+            ocr: self.ocr,
+            #[cfg(feature = "ocr")]
+            ocr_pages: Vec::new(),
+        })
+    }
+}
+
+/// Lays out images as the pages of one PDF, set up with a
+/// [`PdfMergerBuilder`]
+pub struct PDFMerger {
+    pdf: PdfDocumentReference,
+    title: String,
+    dpi: f64,
+    /// Size every page gets, with the image placed on it as `fit` says
+    page_size: Option<(Mm, Mm)>,
+    fit: Fit,
+    /// Kept free on the top, right, bottom and left of every page
+    margins: [Mm; 4],
+    decoder: Decoder,
+    /// Decodes queued inputs ahead, with more than one job
+    jobs: usize,
+    pipeline: Option<Pipeline>,
+    /// What every date in the PDF is set to, with IDs made from the title, for
+    /// output that only changes with its inputs
+    fixed_date: Option<OffsetDateTime>,
+    /// JPEG quality images are encoded at, if not as they are
+    quality: Option<u8>,
+    /// Longest side images are scaled down to before they go in
+    max_dimension: Option<u32>,
+    /// The color mode of the input being added
+    color_mode: ColorMode,
+    /// The color mode of inputs that do not set one of their own
+    default_color_mode: ColorMode,
+    compression: Compression,
+    preview: Option<Preview>,
+    cancel: CancelToken,
+    progress: Box<dyn ProgressSink>,
+    stitcher: Option<stitch::Stitcher>,
+    continuous: Option<ContinuousPage>,
+    nup: Option<Nup>,
+    spreads: Option<Spreads>,
+    tiling: Option<Tiling>,
+    bleed: Option<Mm>,
+    printer_marks: Option<prepress::PrinterMarks>,
+    reserve_signature: bool,
+    /// The document the pages are added to the end of
+    append_to: Option<lopdf::Document>,
+    stamp: Option<overlay::Stamp>,
+    /// Whether stamps and recognized text go on layers of their own
+    layers: bool,
+    layer_name_template: Option<String>,
+    /// Layer of the input being added
+    layer_name: String,
+    /// Per-page CMYK coverage, collected only when a report is wanted
+    ink_coverage: Option<Vec<[f64; 4]>>,
+    page_count: usize,
+    min_dpi: f64,
+    /// Pages whose source pixels were stretched below `min_dpi`
+    low_dpi: Vec<(usize, f64)>,
+    bookmark_source: Option<BookmarkSource>,
+    /// Title for the next page added, from the input it is made of
+    pending_bookmark: Option<String>,
+    /// Group of the pending bookmark
+    pending_group: Option<String>,
+    bookmark_template: Option<String>,
+    bookmark_root: Option<PathBuf>,
+    /// Inputs and distinct directories seen so far, for template counters
+    inputs_seen: usize,
+    dirs_seen: Vec<PathBuf>,
+    bookmarks: Vec<outline::Bookmark>,
+    named_dests: bool,
+    /// Destination name for the next page added, from the input it is made of
+    pending_dest: Option<String>,
+    dests: Vec<(String, usize)>,
+    /// File name of the input of every page, empty for pages of several,
+    /// for the header and footer
+    page_files: Vec<String>,
+    /// Links of the input being added, in its source pixels
+    pending_links: Vec<Link>,
+    /// Percentage the input being added is scaled to, if it has one of its own
+    pending_scale: Option<f64>,
+    /// Whether the next input keeps its own page size, like a fold-out map
+    pending_fold_out: bool,
+    /// Areas of the input being added kept sharp, in its source pixels
+    pending_regions: Vec<[u32; 4]>,
+    /// Inputs that made it into the PDF
+    processed: Vec<PathBuf>,
+    warnings: Vec<Warning>,
+    /// The input being added, that warnings are about
+    current_input: Option<PathBuf>,
+    /// Width over height of the first column split off, which the columns
+    /// of later inputs are expected to match
+    column_aspect: Option<f64>,
+    /// Link areas by page, in points
+    links: Vec<(usize, [f64; 4], String)>,
+    detect_chapters: bool,
+    chapters_found: usize,
+    custom_info: Vec<(String, String)>,
+    /// Documents embedded when the output is a portfolio
+    portfolio: Vec<portfolio::Member>,
+    #[cfg(feature = "ocr")]
+    ocr: Option<ocr::Options>,
+    /// Recognized text by 0-based page index
+    #[cfg(feature = "ocr")]
+    ocr_pages: Vec<(usize, ocr::PageText)>,
+}
+
+impl PDFMerger {
+    /// Adds the page made of `image`, or collects it when pages are stitched
+    /// or continuous
+    pub fn append_image_page(&mut self, image: &Path) -> Result<(), Error> {
+        self.append_image_page_with(image, PageOptions::default())
+    }
+
+    pub fn append_image_page_with(&mut self, image: &Path, page: PageOptions) -> Result<(), Error> {
+        self.check_cancelled()?;
+        self.start_input(image, page);
+        self.append_decoded(image)?;
+        self.processed.push(image.to_path_buf());
+        Ok(())
+    }
+
+    /// Adds the page made of `data`, the file of an input named `name` that
+    /// is not on disk, such as an upload. It is decoded without the timeout
+    /// or isolation set for files.
+    pub fn append_image_data(
+        &mut self,
+        name: &Path,
+        data: &[u8],
+        page: PageOptions,
+    ) -> Result<(), Error> {
+        self.check_cancelled()?;
+        self.start_input(name, page);
+        let tic = Instant::now();
+        let percent = self.pending_scale.take();
+        let pages = self
+            .decoder
+            .prepare_data(data, self.stitcher.is_none(), percent)?;
+        self.progress.stage_finished(Stage::Decode, tic.elapsed());
+        self.lay_out(name, pages, percent);
+        self.processed.push(name.to_path_buf());
+        Ok(())
+    }
+
+    /// Adds the frames of `input`, such as those extracted from a video, as
+    /// if they were one input, returning why any of them could not be added
+    pub fn append_frames(
+        &mut self,
+        input: &Path,
+        frames: &[PathBuf],
+        page: PageOptions,
+    ) -> Vec<Error> {
+        if self.cancel.is_cancelled() {
+            return vec![Error::Cancelled];
+        }
+        self.start_input(input, page);
+        let mut failed = Vec::new();
+        for frame in frames {
+            if self.cancel.is_cancelled() {
+                failed.push(Error::Cancelled);
+                break;
+            }
+            if let Err(e) = self.append_decoded(frame) {
+                failed.push(Error::Image(e));
+            }
+        }
+        if failed.len() < frames.len() {
+            self.processed.push(input.to_path_buf());
+        }
+        failed
+    }
+
+    /// Starts decoding `images`, each with the percentage it is scaled to of
+    /// its own, on as many threads as set with [`PdfMergerBuilder::jobs`].
+    /// Pages are still added one at a time, and only those added in the
+    /// order queued here are taken from what was decoded ahead.
+    pub fn queue(&mut self, images: Vec<(PathBuf, Option<f64>)>) {
+        if self.jobs > 1 {
+            let resize = self.stitcher.is_none();
+            self.pipeline = Some(Pipeline::start(
+                self.decoder,
+                self.jobs,
+                resize,
+                images,
+                self.cancel.clone(),
+            ));
+        }
+    }
+
+    /// Warns when `image` does not split into columns like the inputs before
+    /// it did, as the pages that follow are then likely out of reading order
+    fn check_columns(&mut self, image: &Path, pages: &[Prepared]) {
+        let columns = self.decoder.columns;
+        if pages.len() != columns {
+            self.warn(
+                WarningCode::ColumnsNotSplit,
+                format!(
+                    "`{}` was kept whole instead of split into {columns} columns, \
+                 check the reading order of the pages around it",
+                    image.display()
+                ),
+            );
+            return;
+        }
+        let aspect = |page: &Prepared| page.img.width() as f64 / page.img.height() as f64;
+        let first = *self.column_aspect.get_or_insert(aspect(&pages[0]));
+        let odd = pages
+            .iter()
+            .any(|page| (aspect(page) / first).ln().abs() > COLUMN_ASPECT_TOLERANCE);
+        if odd {
+            self.warn(
+                WarningCode::ColumnsMismatch,
+                format!(
+                    "The columns of `{}` are shaped unlike those of the first input, \
+                 it may be a single page among spreads or the other way around",
+                    image.display()
+                ),
+            );
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Sets up the bookmark, destination, layer name and links that the next
+    /// page gets from `input`
+    fn start_input(&mut self, input: &Path, page: PageOptions) {
+        self.inputs_seen += 1;
+        self.current_input = Some(input.to_path_buf());
+        self.progress.input_started(self.inputs_seen, input);
+        self.bookmark(input);
+        self.name_dest(input);
+        self.layer_name = self.name_layer(input);
+        self.pending_links = page.links;
+        self.pending_scale = page.scale;
+        self.pending_fold_out = page.fold_out;
+        self.pending_regions = page.regions;
+        // pages of several inputs are embedded as the last of them says
+        self.color_mode = page.color_mode.unwrap_or(self.default_color_mode);
+    }
+
+    fn append_decoded(&mut self, image: &Path) -> image_crate::ImageResult<()> {
+        let tic = Instant::now();
+        let queued = self.pipeline.as_mut().and_then(|p| p.take(image));
+        let percent = self.pending_scale.take();
+        let pages = match queued {
+            Some(pages) => pages?,
+            None => self
+                .decoder
+                .prepare(image, self.stitcher.is_none(), percent)?,
+        };
+        self.progress.stage_finished(Stage::Decode, tic.elapsed());
+        self.lay_out(image, pages, percent);
+        Ok(())
+    }
+
+    /// Adds the pages decoded from `image`, scaled to `percent` when stitched
+    fn lay_out(&mut self, image: &Path, pages: Vec<Prepared>, percent: Option<f64>) {
+        let tic = Instant::now();
+        if self.decoder.columns > 1 {
+            self.check_columns(image, &pages);
+        }
+        for page in pages {
+            let page = match &mut self.stitcher {
+                Some(stitcher) => match stitcher.push(page.img) {
+                    Some(img) => Prepared {
+                        source_w: img.width(),
+                        img: self.decoder.scaled_to_width(&img, percent),
+                        origin: (0, 0),
+                        jpeg: None,
+                    },
+                    None => continue,
+                },
+                None => page,
+            };
+            self.add_image(page);
+        }
+        self.progress.stage_finished(Stage::Layout, tic.elapsed());
+    }
+
+    /// Warns about the input being added
+    fn warn(&mut self, code: WarningCode, message: String) {
+        self.push_warning(Warning {
+            code,
+            severity: Severity::Warning,
+            source: self.current_input.clone(),
+            message,
+        });
+    }
+
+    fn push_warning(&mut self, warning: Warning) {
+        self.progress.warning(&warning);
+        self.warnings.push(warning);
+    }
+
+    /// Records that `input` was left out because of `reason`, for callers
+    /// that carry on past inputs that fail to be added
+    pub fn record_skipped(&mut self, input: &Path, reason: &Error) {
+        let code = match reason {
+            Error::Image(ImageError::Unsupported(_)) => WarningCode::UnsupportedFormat,
+            _ => WarningCode::SkippedInput,
+        };
+        self.push_warning(Warning {
+            code,
+            severity: Severity::Error,
+            source: Some(input.to_path_buf()),
+            message: format!("Skipping `{}`: {reason}", input.display()),
+        });
+    }
+
+    fn add_image(&mut self, prepared: Prepared) {
+        self.write_preview(&prepared.img);
+        let dpi = self.dpi;
+        let Prepared {
+            img,
+            source_w,
+            origin,
+            jpeg,
+        } = prepared;
+        // links only line up with pages made of a single whole image
+        let links = std::mem::take(&mut self.pending_links);
+        let fold_out = std::mem::take(&mut self.pending_fold_out);
+        let regions = std::mem::take(&mut self.pending_regions);
+        if let Some(continuous) = &mut self.continuous {
+            if !fold_out {
+                continuous.images.push(img);
+                continuous.source_widths.push(source_w);
+                return;
+            }
+            // what was collected so far goes before the fold-out
+            if !continuous.images.is_empty() {
+                let collected = ContinuousPage {
+                    images: std::mem::take(&mut continuous.images),
+                    source_widths: std::mem::take(&mut continuous.source_widths),
+                    ..*continuous
+                };
+                self.append_continuous_page(collected);
+            }
+        }
+        if let Some(nup) = &mut self.nup {
+            if !fold_out {
+                nup.images.push(img);
+                nup.source_widths.push(source_w);
+                if nup.images.len() == nup.rows * nup.columns {
+                    self.flush_nup();
+                }
+                return;
+            }
+            // a fold-out gets a page of its own after the cells filled so far
+            self.flush_nup();
+        }
+        if let Some(spreads) = &mut self.spreads {
+            let cover = std::mem::take(&mut spreads.cover);
+            if !fold_out && !cover && img.width() < img.height() {
+                match spreads.pending.take() {
+                    Some(first) => self.append_spread(vec![first, (img, source_w)]),
+                    None => spreads.pending = Some((img, source_w)),
+                }
+                return;
+            }
+            // a page that is not half of a spread ends the one begun before it
+            self.flush_spread();
+        }
+        let (w, h) = img.dimensions();
+        let effective_dpi = dpi * source_w as f64 / w as f64;
+        let page_w = Mm((w as f64 * INCH_PER_MM) / dpi);
+        let page_h = Mm((h as f64 * INCH_PER_MM) / dpi);
+        if let Some(tiling) = self.tiling.filter(|_| !fold_out) {
+            let (tile_w, tile_h) = tiling.page;
+            let fits = |tw: Mm, th: Mm| page_w <= tw && page_h <= th;
+            if !fits(tile_w, tile_h) && !fits(tile_h, tile_w) {
+                self.append_tiles(&img, effective_dpi, tiling);
+                return;
+            }
+        }
+
+        // placed on a page of the set size, or making up a page of its own
+        let (img, [x, y, placed_w, placed_h], sheet) = match self.page_size.filter(|_| !fold_out) {
+            Some(page) => {
+                let (img, rect) = place(img, dpi, page, self.margins, self.fit);
+                (img, rect, page)
+            }
+            None => {
+                let [top, right, bottom, left] = self.margins;
+                let sheet = (page_w + left + right, page_h + top + bottom);
+                (img, [left, bottom, page_w, page_h], sheet)
+            }
+        };
+        // what the image was cropped by, in its pixels before any cropping
+        let crop = ((w - img.width()) / 2, (h - img.height()) / 2);
+        let effective_dpi =
+            img.width() as f64 * source_w as f64 / w as f64 * INCH_PER_MM / placed_w.0;
+        // the regions in pixels of the image as placed, cut down to it
+        let k = source_w as f64 / w as f64;
+        let regions = match self.stitcher {
+            Some(_) => Vec::new(),
+            None => regions
+                .iter()
+                .filter_map(|&[rx, ry, rw, rh]| {
+                    let to_x = |v: u32| {
+                        ((v as f64 - origin.0 as f64) / k - crop.0 as f64)
+                            .clamp(0.0, img.width() as f64)
+                    };
+                    let to_y = |v: u32| {
+                        ((v as f64 - origin.1 as f64) / k - crop.1 as f64)
+                            .clamp(0.0, img.height() as f64)
+                    };
+                    let (x0, y0) = (to_x(rx).floor() as u32, to_y(ry).floor() as u32);
+                    let (x1, y1) = (to_x(rx + rw).ceil() as u32, to_y(ry + rh).ceil() as u32);
+                    (x0 < x1 && y0 < y1).then_some([x0, y0, x1 - x0, y1 - y0])
+                })
+                .collect(),
+        };
+
+        if self.detect_chapters
+            && self.pending_bookmark.is_none()
+            && chapters::is_chapter_page(&img)
+        {
+            self.chapters_found += 1;
+            self.pending_bookmark = Some(format!("Chapter {}", self.chapters_found));
+        }
+        #[cfg(feature = "ocr")]
+        if let Some(opts) = &self.ocr {
+            match ocr::recognize(&img, &opts.lang) {
+                Ok(mut text) => {
+                    if matches!(self.bookmark_source, Some(BookmarkSource::OcrHeading)) {
+                        if let Some(heading) = text.heading() {
+                            self.pending_bookmark = Some(heading);
+                        }
+                    }
+                    text.place([x, y, placed_w, placed_h].map(|v| printpdf::Pt::from(v).0));
+                    self.ocr_pages.push((self.page_count, text));
+                }
+                Err(e) => self.warn(
+                    WarningCode::OcrFailed,
+                    format!(
+                        "Could not recognize the text of page {}: {e}",
+                        self.page_count + 1
+                    ),
+                ),
+            }
+        }
+        if self.stitcher.is_none() {
+            let scale_x = printpdf::Pt::from(placed_w).0 / (img.width() as f64 * k);
+            let scale_y = printpdf::Pt::from(placed_h).0 / (img.height() as f64 * k);
+            let (crop_x, crop_y) = (
+                origin.0 as f64 + crop.0 as f64 * k,
+                origin.1 as f64 + crop.1 as f64 * k,
+            );
+            let left = printpdf::Pt::from(x).0 - crop_x * scale_x;
+            let top = printpdf::Pt::from(y + placed_h).0 + crop_y * scale_y;
+            let area = [x, y, x + placed_w, y + placed_h].map(|v| printpdf::Pt::from(v).0);
+            for link in links {
+                let [lx, ly, lw, lh] = link.rect.map(|v| v as f64);
+                // links are cut down to what is left of the image
+                let rect = [
+                    (left + lx * scale_x).max(area[0]),
+                    (top - (ly + lh) * scale_y).max(area[1]),
+                    (left + (lx + lw) * scale_x).min(area[2]),
+                    (top - ly * scale_y).min(area[3]),
+                ];
+                if rect[0] < rect[2] && rect[1] < rect[3] {
+                    self.links.push((self.page_count, rect, link.url));
+                }
+            }
+        }
+        let layer = self.add_page(sheet.0, sheet.1);
+        self.check_resolution(effective_dpi * self.downsampling(&img));
+        self.record_ink(|| ink::coverage(&img));
+
+        let image_dpi = img.width() as f64 * INCH_PER_MM / placed_w.0;
+        let natural_h = Mm(img.height() as f64 * INCH_PER_MM / image_dpi);
+        let transform = ImageTransform {
+            translate_x: Some(x),
+            translate_y: Some(y),
+            dpi: Some(image_dpi),
+            // only a stretched image is not as tall as its width makes it
+            scale_y: Some(placed_h.0 / natural_h.0).filter(|s| (s - 1.0).abs() > 1e-6),
+            ..Default::default()
+        };
+        // a JPEG that was not cropped goes in without encoding it again
+        let untouched = img.width() == w
+            && img.height() == h
+            && self.quality.is_none()
+            && self.color_mode == ColorMode::Keep
+            && self.downsampling(&img) == 1.0;
+        match jpeg.filter(|_| untouched) {
+            Some(jpeg) => {
+                dct_image(jpeg.data, (w, h), jpeg.components).add_to_layer(layer, transform)
+            }
+            None => {
+                self.embed(&img, layer.clone(), transform);
+                if self.loses_detail(&img) {
+                    for region in regions {
+                        self.embed_region(&img, region, layer.clone(), transform);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether `img` comes out of [`PDFMerger::embed`] compressed lossily or
+    /// with fewer pixels
+    fn loses_detail(&self, img: &DynamicImage) -> bool {
+        self.quality.is_some()
+            || self.compression == Compression::Auto
+            || matches!(self.color_mode, ColorMode::Bilevel { .. })
+            || self.downsampling(img) < 1.0
+    }
+
+    /// Lays the pixels `[x, y, w, h]` of `img` losslessly over where they
+    /// are on the page `img` was embedded on with `transform`
+    fn embed_region(
+        &self,
+        img: &DynamicImage,
+        [x, y, w, h]: [u32; 4],
+        layer: PdfLayerReference,
+        transform: ImageTransform,
+    ) {
+        let region = img.crop_imm(x, y, w, h);
+        // a bilevel page still keeps the shades of its region
+        let region = match self.color_mode {
+            ColorMode::Keep => region,
+            _ => DynamicImage::ImageLuma8(region.to_luma8()),
+        };
+        let mm_per_px = INCH_PER_MM / transform.dpi.unwrap();
+        let below = (img.height() - y - h) as f64 * mm_per_px * transform.scale_y.unwrap_or(1.0);
+        let transform = ImageTransform {
+            translate_x: Some(transform.translate_x.unwrap() + Mm(x as f64 * mm_per_px)),
+            translate_y: Some(transform.translate_y.unwrap() + Mm(below)),
+            ..transform
+        };
+        Image::from_dynamic_image(&region).add_to_layer(layer, transform);
+    }
+
+    /// How much `img` is scaled down to fit within the largest dimension set
+    fn downsampling(&self, img: &DynamicImage) -> f64 {
+        let longest = img.width().max(img.height());
+        match self.max_dimension {
+            Some(max) if longest > max => max as f64 / longest as f64,
+            _ => 1.0,
+        }
+    }
+
+    /// `img` scaled down to the largest dimension and turned gray if asked,
+    /// as it is encoded
+    fn reduced<'a>(&self, img: &'a DynamicImage) -> Cow<'a, DynamicImage> {
+        let resized = match self.max_dimension {
+            Some(max) if self.downsampling(img) < 1.0 => {
+                let resized = img.resize(max, max, FilterType::Lanczos3);
+                Cow::Owned(match self.decoder.protect_text {
+                    true => downscale::keep_text(img, resized),
+                    false => resized,
+                })
+            }
+            _ => Cow::Borrowed(img),
+        };
+        match self.color_mode {
+            ColorMode::Gray => Cow::Owned(DynamicImage::ImageLuma8(resized.to_luma8())),
+            _ => resized,
+        }
+    }
+
+    /// Writes `img` to the preview directory as it would be embedded, while
+    /// there are images left to preview
+    fn write_preview(&mut self, img: &DynamicImage) {
+        let preview = match &mut self.preview {
+            Some(preview) if preview.left > 0 => preview,
+            _ => return,
+        };
+        preview.left -= 1;
+        preview.written += 1;
+        let stem = self
+            .current_input
+            .as_ref()
+            .and_then(|input| input.file_stem())
+            .map_or("image".into(), |stem| stem.to_string_lossy());
+        let path = preview
+            .dir
+            .join(format!("{:03}-{stem}.png", preview.written));
+        let reduced = self.reduced(img);
+        let pixels = match self.color_mode {
+            ColorMode::Bilevel { threshold } => {
+                let mut gray = reduced.to_luma8();
+                for pixel in gray.pixels_mut() {
+                    pixel.0[0] = if pixel.0[0] >= threshold { 255 } else { 0 };
+                }
+                Cow::Owned(DynamicImage::ImageLuma8(gray))
+            }
+            _ => reduced,
+        };
+        let written = std::fs::create_dir_all(path.parent().unwrap())
+            .map_err(ImageError::IoError)
+            .and_then(|()| pixels.save_with_format(&path, ImageFormat::Png));
+        if let Err(e) = written {
+            let message = format!("Could not write the preview `{}`: {e}", path.display());
+            self.warn(WarningCode::PreviewNotWritten, message);
+        }
+    }
+
+    /// Puts `img` on `layer`, scaled down to the largest dimension, turned
+    /// to the color mode and encoded as JPEG at the quality set, if they are
+    fn embed(&self, img: &DynamicImage, layer: PdfLayerReference, transform: ImageTransform) {
+        let resized = self.reduced(img);
+        let transform = ImageTransform {
+            // the same size on the page from fewer pixels
+            dpi: transform
+                .dpi
+                .map(|dpi| dpi * resized.width() as f64 / img.width() as f64),
+            ..transform
+        };
+        let encoded = match (self.color_mode, self.compression) {
+            (ColorMode::Bilevel { threshold }, _) => Some(bilevel_image(&resized, threshold)),
+            (_, Compression::Auto) => match compress::classify(&resized) {
+                (compress::Content::Bilevel, _) => Some(bilevel_image(&resized, 128)),
+                (compress::Content::Flat, true) => Some(Image::from_dynamic_image(
+                    &DynamicImage::ImageLuma8(resized.to_luma8()),
+                )),
+                (compress::Content::Flat, false) => None,
+                (compress::Content::Photo, gray) => {
+                    let quality = self.quality.unwrap_or(AUTO_QUALITY);
+                    match gray {
+                        true => {
+                            jpeg_encoded(&DynamicImage::ImageLuma8(resized.to_luma8()), quality)
+                        }
+                        false => jpeg_encoded(&resized, quality),
+                    }
+                }
+            },
+            (_, Compression::Fixed) => self
+                .quality
+                .and_then(|quality| jpeg_encoded(&resized, quality)),
+        };
+        let image = match encoded {
+            Some(image) => image,
+            None => Image::from_dynamic_image(&resized),
+        };
+        image.add_to_layer(layer, transform);
+    }
+
+    /// Adds a cover page, bypassing stitching, continuous pages and tiling.
+    /// With `size`, or the page size of a device, the image is cropped to the
+    /// proportions of that page and fills it edge to edge, otherwise it is
+    /// laid out like any other input.
+    pub fn append_cover(&mut self, image: &Path, size: Option<(Mm, Mm)>) -> Result<(), Error> {
+        self.check_cancelled()?;
+        self.start_input(image, PageOptions::default());
+        let dpi = self.dpi;
+        let size = size.or(self.page_size);
+        let tic = Instant::now();
+        let img = self.decoder.open(image)?;
+        self.progress.stage_finished(Stage::Decode, tic.elapsed());
+        let tic = Instant::now();
+        let (w, h) = img.dimensions();
+        let (img, page_w, page_h, effective_dpi) = match size {
+            Some((page_w, page_h)) => {
+                let aspect = page_w.0 / page_h.0;
+                let (cw, ch) = if w as f64 / h as f64 > aspect {
+                    (((h as f64 * aspect).round() as u32).clamp(1, w), h)
+                } else {
+                    (w, ((w as f64 / aspect).round() as u32).clamp(1, h))
+                };
+                let img = img.crop_imm((w - cw) / 2, (h - ch) / 2, cw, ch);
+                let effective_dpi = cw as f64 * INCH_PER_MM / page_w.0;
+                (img, page_w, page_h, effective_dpi)
+            }
+            None => {
+                let img = self.decoder.scaled(&img, None);
+                let page_w = Mm((img.width() as f64 * INCH_PER_MM) / dpi);
+                let page_h = Mm((img.height() as f64 * INCH_PER_MM) / dpi);
+                let effective_dpi = dpi * w as f64 / img.width() as f64;
+                (img, page_w, page_h, effective_dpi)
+            }
+        };
+
+        let layer = self.add_page(page_w, page_h);
+        self.check_resolution(effective_dpi * self.downsampling(&img));
+        self.record_ink(|| ink::coverage(&img));
+        self.embed(
+            &img,
+            layer,
+            ImageTransform {
+                dpi: Some(img.width() as f64 * INCH_PER_MM / page_w.0),
+                ..Default::default()
+            },
+        );
+        self.progress.stage_finished(Stage::Layout, tic.elapsed());
+        self.processed.push(image.to_path_buf());
+        Ok(())
+    }
+
+    /// Splits an image that does not fit the tile page across several pages,
+    /// repeating `overlap` of the content on neighbouring tiles and marking
+    /// where the next tile takes over
+    fn append_tiles(&mut self, img: &DynamicImage, effective_dpi: f64, tiling: Tiling) {
+        let dpi = self.dpi;
+        let px_per_mm = dpi / INCH_PER_MM;
+        let (img_w, img_h) = img.dimensions();
+        let tiles = |len_px: u32, page: Mm| {
+            let len = len_px as f64 / px_per_mm;
+            let step = (page - tiling.overlap).0.max(1.0);
+            (
+                ((len - tiling.overlap.0) / step).ceil().max(1.0) as u32,
+                step,
+            )
+        };
+        // use whichever orientation needs fewer sheets
+        let (pw, ph) = tiling.page;
+        let count = |pw, ph| tiles(img_w, pw).0 * tiles(img_h, ph).0;
+        let (pw, ph) = if count(ph, pw) < count(pw, ph) {
+            (ph, pw)
+        } else {
+            (pw, ph)
+        };
+        let (cols, step_x) = tiles(img_w, pw);
+        let (rows, step_y) = tiles(img_h, ph);
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let x0 = ((col as f64 * step_x * px_per_mm).round() as u32).min(img_w - 1);
+                let y0 = ((row as f64 * step_y * px_per_mm).round() as u32).min(img_h - 1);
+                let cw = ((pw.0 * px_per_mm) as u32).min(img_w - x0);
+                let ch = ((ph.0 * px_per_mm) as u32).min(img_h - y0);
+                let tile = img.crop_imm(x0, y0, cw, ch);
+
+                let layer = self.add_page(pw, ph);
+                self.check_resolution(effective_dpi * self.downsampling(&tile));
+                let filled = (cw as f64 / px_per_mm * ch as f64 / px_per_mm) / (pw.0 * ph.0);
+                self.record_ink(|| ink::coverage(&tile).map(|c| c * filled));
+                self.embed(
+                    &tile,
+                    layer.clone(),
+                    ImageTransform {
+                        translate_y: Some(ph - Mm(ch as f64 / px_per_mm)),
+                        dpi: Some(dpi),
+                        ..Default::default()
+                    },
+                );
+
+                layer.set_outline_color(Color::Greyscale(Greyscale::new(0.0, None)));
+                layer.set_outline_thickness(0.5);
+                let tick = Mm(6.0);
+                let mut marks = Vec::new();
+                if col + 1 < cols {
+                    let x = pw - tiling.overlap;
+                    marks.push([(x, Mm(0.0)), (x, tick)]);
+                    marks.push([(x, ph - tick), (x, ph)]);
+                }
+                if row + 1 < rows {
+                    let y = tiling.overlap;
+                    marks.push([(Mm(0.0), y), (tick, y)]);
+                    marks.push([(pw - tick, y), (pw, y)]);
+                }
+                for [a, b] in marks {
+                    layer.add_shape(Line {
+                        points: vec![(Point::new(a.0, a.1), false), (Point::new(b.0, b.1), false)],
+                        has_stroke: true,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds a page saying why `file` is missing, in its place after adding it
+    /// failed
+    pub fn append_unsupported_page(&mut self, file: &Path, reason: &str) -> Result<(), Error> {
+        self.check_cancelled()?;
+        let (dpi, wh) = (self.dpi, self.decoder.scale);
+        let (page_w, page_h) = self.page_size.unwrap_or((
+            Mm((wh.0 as f64 * INCH_PER_MM) / dpi),
+            Mm((wh.1 as f64 * INCH_PER_MM) / dpi),
+        ));
+        let font = self.pdf.add_builtin_font(BuiltinFont::Helvetica)?;
+
+        let layer = self.add_page(page_w, page_h);
+        self.record_ink(|| [0.0; 4]);
+        let name = file
+            .file_name()
+            .unwrap_or(file.as_os_str())
+            .to_string_lossy();
+        layer.use_text("Unsupported file", 18.0, Mm(10.0), page_h - Mm(20.0), &font);
+        layer.use_text(name, 12.0, Mm(10.0), page_h - Mm(30.0), &font);
+        layer.use_text(reason, 10.0, Mm(10.0), page_h - Mm(38.0), &font);
+        Ok(())
+    }
+
+    /// Embeds `member` when the output is to be a portfolio
+    pub fn add_portfolio_member(&mut self, member: portfolio::Member) {
+        self.portfolio.push(member);
+    }
+
+    /// Adds the page shown by viewers that cannot open portfolios, listing
+    /// the members added so far
+    pub fn append_portfolio_cover(&mut self) -> Result<(), Error> {
+        let members = self
+            .portfolio
+            .iter()
+            .map(|m| m.file_name.clone())
+            .collect::<Vec<_>>();
+        let (page_w, page_h) = (Mm(210.0), Mm(297.0));
+        let font = self.pdf.add_builtin_font(BuiltinFont::Helvetica)?;
+
+        self.layer_name.clear();
+        let layer = self.add_page(page_w, page_h);
+        self.record_ink(|| [0.0; 4]);
+        layer.use_text(&self.title, 18.0, Mm(20.0), page_h - Mm(25.0), &font);
+        layer.use_text(
+            format!("This PDF is a portfolio of {} documents.", members.len()),
+            11.0,
+            Mm(20.0),
+            page_h - Mm(35.0),
+            &font,
+        );
+        layer.use_text(
+            "Open it in a viewer that supports PDF portfolios to browse them.",
+            11.0,
+            Mm(20.0),
+            page_h - Mm(41.0),
+            &font,
+        );
+        for (i, name) in members.iter().enumerate() {
+            let y = page_h - Mm(53.0 + 6.0 * i as f64);
+            if y < Mm(20.0) {
+                break;
+            }
+            layer.use_text(name, 10.0, Mm(25.0), y, &font);
+        }
+        Ok(())
+    }
+
+    fn append_continuous_page(&mut self, continuous: ContinuousPage) {
+        // the page is made of many inputs, so warnings are about none of them
+        let input = self.current_input.take();
+        let dpi = self.dpi;
+        let page_w = continuous.width.unwrap_or_else(|| {
+            let widest = continuous.images.iter().map(|img| img.width()).max();
+            Mm((widest.unwrap_or(0) as f64 * INCH_PER_MM) / dpi)
+        });
+        let heights = continuous
+            .images
+            .iter()
+            .map(|img| page_w * (img.height() as f64 / img.width() as f64))
+            .collect::<Vec<Mm>>();
+        let gaps = continuous.images.len().saturating_sub(1) as f64;
+        let page_h = heights
+            .iter()
+            .fold(continuous.spacing * gaps, |acc, h| acc + *h);
+        if page_h > MAX_PAGE_EDGE {
+            self.warn(
+                WarningCode::PageTooLong,
+                format!(
+                    "The continuous page is {:.0}mm long, which some viewers refuse to open",
+                    page_h.0
+                ),
+            );
+        }
+
+        let layer = self.add_page(page_w, page_h);
+        let placed_inches = page_w.0 / INCH_PER_MM;
+        let lowest = continuous
+            .source_widths
+            .iter()
+            .zip(&continuous.images)
+            .map(|(w, img)| *w as f64 * self.downsampling(img) / placed_inches)
+            .fold(f64::INFINITY, f64::min);
+        self.check_resolution(lowest);
+        self.record_ink(|| {
+            let mut total = [0.0; 4];
+            for (img, h) in continuous.images.iter().zip(&heights) {
+                let share = h.0 / page_h.0;
+                for (t, c) in total.iter_mut().zip(ink::coverage(img)) {
+                    *t += c * share;
+                }
+            }
+            total
+        });
+        let mut top = page_h;
+        for (img, h) in continuous.images.iter().zip(heights) {
+            top -= h;
+            self.embed(
+                img,
+                layer.clone(),
+                ImageTransform {
+                    translate_y: Some(top),
+                    dpi: Some(img.width() as f64 * INCH_PER_MM / page_w.0),
+                    ..Default::default()
+                },
+            );
+            top -= continuous.spacing;
+        }
+        self.current_input = input;
+    }
+
+    /// Lays out the images collected for a grid, if there are any
+    fn flush_nup(&mut self) {
+        let nup = match &mut self.nup {
+            Some(nup) if !nup.images.is_empty() => Nup {
+                images: std::mem::take(&mut nup.images),
+                source_widths: std::mem::take(&mut nup.source_widths),
+                ..*nup
+            },
+            _ => return,
+        };
+        // the page is made of many inputs, so it belongs to none of their
+        // layers and warnings are about none of them
+        let input = self.current_input.take();
+        let layer_name = std::mem::take(&mut self.layer_name);
+        let dpi = self.dpi;
+        let (page_w, page_h) = self.page_size.unwrap_or(NUP_PAGE);
+        let [top, right, bottom, left] = self.margins;
+        let (rows, columns) = (nup.rows as f64, nup.columns as f64);
+        let cell = (
+            (page_w - left - right - nup.gutter * (columns - 1.0)) / columns,
+            (page_h - top - bottom - nup.gutter * (rows - 1.0)) / rows,
+        );
+
+        let layer = self.add_page(page_w, page_h);
+        let mut lowest = f64::INFINITY;
+        let mut ink = [0.0; 4];
+        for (i, (img, source_w)) in nup.images.into_iter().zip(nup.source_widths).enumerate() {
+            let (row, column) = match nup.order {
+                CellOrder::RowMajor => (i / nup.columns, i % nup.columns),
+                CellOrder::ColumnMajor => (i % nup.rows, i / nup.rows),
+            };
+            let cell_x = left + (cell.0 + nup.gutter) * column as f64;
+            let cell_y = page_h - top - cell.1 - (cell.1 + nup.gutter) * row as f64;
+            let w = img.width();
+            let (img, [x, y, placed_w, placed_h]) = place(img, dpi, cell, [Mm(0.0); 4], self.fit);
+            let image_dpi = img.width() as f64 * INCH_PER_MM / placed_w.0;
+            lowest = lowest.min(image_dpi * source_w as f64 / w as f64 * self.downsampling(&img));
+            if self.ink_coverage.is_some() {
+                let share = placed_w.0 * placed_h.0 / (page_w.0 * page_h.0);
+                for (t, c) in ink.iter_mut().zip(ink::coverage(&img)) {
+                    *t += c * share;
+                }
+            }
+            let natural_h = Mm(img.height() as f64 * INCH_PER_MM / image_dpi);
+            let transform = ImageTransform {
+                translate_x: Some(cell_x + x),
+                translate_y: Some(cell_y + y),
+                dpi: Some(image_dpi),
+                scale_y: Some(placed_h.0 / natural_h.0).filter(|s| (s - 1.0).abs() > 1e-6),
+                ..Default::default()
+            };
+            self.embed(&img, layer.clone(), transform);
+        }
+        self.check_resolution(lowest);
+        self.record_ink(|| ink);
+        self.layer_name = layer_name;
+        self.current_input = input;
+    }
+
+    /// Lays out the first half of a spread that is still waiting for its
+    /// other one on a page of its own
+    fn flush_spread(&mut self) {
+        if let Some(lone) = self.spreads.as_mut().and_then(|s| s.pending.take()) {
+            self.append_spread(vec![lone]);
+        }
+    }
+
+    /// Adds a page of `halves` side by side, meeting in the middle, at their
+    /// own size or scaled to fit the set page size
+    fn append_spread(&mut self, mut halves: Vec<(DynamicImage, u32)>) {
+        if self.spreads.as_ref().is_some_and(|s| s.rtl) {
+            halves.reverse();
+        }
+        // the page is made of two inputs, so it belongs to none of their
+        // layers and warnings are about none of them
+        let input = self.current_input.take();
+        let layer_name = std::mem::take(&mut self.layer_name);
+        let dpi = self.dpi;
+        let natural = |len: u32| len as f64 * INCH_PER_MM / dpi;
+        let width: f64 = halves.iter().map(|(img, _)| natural(img.width())).sum();
+        let height = halves
+            .iter()
+            .map(|(img, _)| natural(img.height()))
+            .fold(0.0, f64::max);
+        let [top, right, bottom, left] = self.margins.map(|m| m.0);
+        let (page_w, page_h, scale) = match self.page_size {
+            Some((a, b)) => {
+                // a spread lies across the page
+                let (page_w, page_h) = match halves.len() == 2 && a.0 < b.0 {
+                    true => (b.0, a.0),
+                    false => (a.0, b.0),
+                };
+                let (box_w, box_h) = (
+                    (page_w - left - right).max(1.0),
+                    (page_h - top - bottom).max(1.0),
+                );
+                let scale = match self.fit {
+                    Fit::Center => 1.0,
+                    _ => (box_w / width).min(box_h / height),
+                };
+                (page_w, page_h, scale)
+            }
+            None => (width + left + right, height + top + bottom, 1.0),
+        };
+        let box_h = page_h - top - bottom;
+
+        let layer = self.add_page(Mm(page_w), Mm(page_h));
+        let mut x = left + (page_w - left - right - width * scale) / 2.0;
+        let mut lowest = f64::INFINITY;
+        let mut ink = [0.0; 4];
+        for (img, source_w) in halves {
+            let (placed_w, placed_h) =
+                (natural(img.width()) * scale, natural(img.height()) * scale);
+            let image_dpi = img.width() as f64 * INCH_PER_MM / placed_w;
+            let effective_dpi = image_dpi * source_w as f64 / img.width() as f64;
+            lowest = lowest.min(effective_dpi * self.downsampling(&img));
+            if self.ink_coverage.is_some() {
+                let share = placed_w * placed_h / (page_w * page_h);
+                for (t, c) in ink.iter_mut().zip(ink::coverage(&img)) {
+                    *t += c * share;
+                }
+            }
+            let transform = ImageTransform {
+                translate_x: Some(Mm(x)),
+                translate_y: Some(Mm(bottom + (box_h - placed_h) / 2.0)),
+                dpi: Some(image_dpi),
+                ..Default::default()
+            };
+            self.embed(&img, layer.clone(), transform);
+            x += placed_w;
+        }
+        self.check_resolution(lowest);
+        self.record_ink(|| ink);
+        self.layer_name = layer_name;
+        self.current_input = input;
+    }
+
+    fn add_page(&mut self, w: Mm, h: Mm) -> PdfLayerReference {
+        let (page_i, layer_i) = self.pdf.add_page(w, h, &self.layer_name);
+        if let Some(name) = self.pending_dest.take() {
+            self.dests.push((name, self.page_count));
+        }
+        if let Some(title) = self.pending_bookmark.take() {
+            self.bookmarks.push(outline::Bookmark {
+                title,
+                page: self.page_count,
+                group: self.pending_group.take(),
+            });
+        }
+        let file = self.current_input.as_deref().and_then(Path::file_name);
+        self.page_files
+            .push(file.map_or(String::new(), |f| f.to_string_lossy().into_owned()));
+        self.page_count += 1;
+        self.progress.page_added(self.page_count);
+        self.pdf.get_page(page_i).get_layer(layer_i)
+    }
+
+    /// Titles the next page after `input` when bookmarks are wanted. A page
+    /// that is still being collected keeps the title of its first input.
+    fn bookmark(&mut self, input: &Path) {
+        if self.bookmark_source.is_none() && self.bookmark_template.is_none() {
+            return;
+        }
+        let dir = input.parent().unwrap_or(Path::new("")).to_path_buf();
+        let dir_index = match self.dirs_seen.iter().position(|d| *d == dir) {
+            Some(i) => i + 1,
+            None => {
+                self.dirs_seen.push(dir.clone());
+                self.dirs_seen.len()
+            }
+        };
+        let collecting = self.stitcher.is_some()
+            || self.continuous.is_some()
+            || self.nup.is_some()
+            || self.spreads.is_some();
+        if collecting && self.pending_bookmark.is_some() {
+            return;
+        }
+
+        let file = std::cell::OnceCell::new();
+        let read = || file.get_or_init(|| std::fs::read(input).unwrap_or_default());
+        let exif_title = || metadata::exif_title(read());
+        let xmp_title = || metadata::xmp_title(read());
+        let name = |p: Option<&std::ffi::OsStr>| p.map(|s| s.to_string_lossy().into_owned());
+        let stem = name(input.file_stem()).unwrap_or_default();
+        let title = match self.bookmark_source.unwrap_or(BookmarkSource::Filename) {
+            BookmarkSource::Filename => None,
+            BookmarkSource::ExifTitle => exif_title(),
+            BookmarkSource::XmpTitle => xmp_title(),
+            // replaced once the page has been recognized
+            #[cfg(feature = "ocr")]
+            BookmarkSource::OcrHeading => None,
+        }
+        .unwrap_or_else(|| stem.clone());
+
+        let title = match &self.bookmark_template {
+            Some(t) => template::render(t, |var| match var {
+                "title" => Some(title.clone()),
+                "filename" => name(input.file_name()),
+                "stem" => Some(stem.clone()),
+                "dirname" => Some(name(dir.file_name()).unwrap_or_default()),
+                "index" => Some(self.inputs_seen.to_string()),
+                "dir_index" => Some(dir_index.to_string()),
+                "page" => Some((self.page_count + 1).to_string()),
+                "exif_title" => Some(exif_title().unwrap_or_default()),
+                "xmp_title" => Some(xmp_title().unwrap_or_default()),
+                _ => None,
+            })
+            .unwrap_or(title),
+            None => title,
+        };
+        self.pending_bookmark = Some(title);
+        self.pending_group = self
+            .bookmark_root
+            .as_deref()
+            .and_then(|root| dir.strip_prefix(root).ok())
+            .filter(|sub| !sub.as_os_str().is_empty())
+            .map(|sub| sub.display().to_string());
+    }
+
+    /// Name of the layer holding the content of `input`
+    fn name_layer(&self, input: &Path) -> String {
+        let template = match &self.layer_name_template {
+            Some(template) => template,
+            None => return String::new(),
+        };
+        let name = |p: Option<&std::ffi::OsStr>| p.map(|s| s.to_string_lossy().into_owned());
+        template::render(template, |var| match var {
+            "filename" => name(input.file_name()),
+            "stem" => name(input.file_stem()),
+            "dirname" => name(input.parent().and_then(Path::file_name)),
+            "index" => Some(self.inputs_seen.to_string()),
+            "page" => Some((self.page_count + 1).to_string()),
+            _ => None,
+        })
+        .unwrap_or_default()
+    }
+
+    /// Names the next page after the file name of `input` when named
+    /// destinations are wanted, numbering names that are taken already
+    fn name_dest(&mut self, input: &Path) {
+        let collecting = self.stitcher.is_some()
+            || self.continuous.is_some()
+            || self.nup.is_some()
+            || self.spreads.is_some();
+        if !self.named_dests || (collecting && self.pending_dest.is_some()) {
+            return;
+        }
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let base = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let taken = |name: &str| self.dests.iter().any(|(n, _)| n == name);
+        let mut name = base.clone();
+        let mut n = 1;
+        while taken(&name) {
+            n += 1;
+            name = format!("{base}_{n}");
+        }
+        self.pending_dest = Some(name);
+    }
+
+    /// Notes the page just added if its content is printed at fewer than
+    /// `min_dpi` source pixels per inch
+    fn check_resolution(&mut self, effective_dpi: f64) {
+        if effective_dpi < self.min_dpi {
+            self.low_dpi.push((self.page_count, effective_dpi));
+            self.warn(
+                WarningCode::LowResolution,
+                format!(
+                    "Page {} is printed at {effective_dpi:.0} dpi, below {}",
+                    self.page_count, self.min_dpi
+                ),
+            );
+        }
+    }
+
+    fn record_ink(&mut self, coverage: impl FnOnce() -> [f64; 4]) {
+        if let Some(pages) = &mut self.ink_coverage {
+            pages.push(coverage());
+        }
+    }
+
+    /// Lays out pages that are still being collected, so that whatever is
+    /// added next starts a page of its own
+    pub fn finish(&mut self) {
+        // pages made of several inputs belong to none of their layers
+        self.layer_name.clear();
+        self.current_input = None;
+        if let Some(img) = self.stitcher.take().and_then(|mut s| s.finish()) {
+            let source_w = img.width();
+            let img = self.decoder.scaled_to_width(&img, None);
+            self.add_image(Prepared {
+                img,
+                source_w,
+                origin: (0, 0),
+                jpeg: None,
+            });
+        }
+        if let Some(continuous) = self.continuous.take() {
+            if !continuous.images.is_empty() {
+                self.append_continuous_page(continuous);
+            }
+        }
+        self.flush_nup();
+        self.flush_spread();
+    }
+
+    /// Number of pages added so far
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Inputs that made it into the PDF so far
+    pub fn processed(&self) -> &[PathBuf] {
+        &self.processed
+    }
+
+    /// Effective resolution below which pages are reported
+    pub fn min_dpi(&self) -> f64 {
+        self.min_dpi
+    }
+
+    /// Pages (1-based) printed below the minimum resolution, with the
+    /// resolution they ended up at
+    pub fn low_resolution_pages(&self) -> &[(usize, f64)] {
+        &self.low_dpi
+    }
+
+    /// CMYK coverage of every page, when an ink report was asked for
+    pub fn ink_coverage(&self) -> Option<&[[f64; 4]]> {
+        self.ink_coverage.as_deref()
+    }
+
+    /// Problems that did not stop a page from being added
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    #[cfg(feature = "ocr")]
+    pub fn ocr_options(&self) -> Option<&ocr::Options> {
+        self.ocr.as_ref()
+    }
+
+    /// Recognized text by 0-based page index
+    #[cfg(feature = "ocr")]
+    pub fn recognized_text(&self) -> &[(usize, ocr::PageText)] {
+        &self.ocr_pages
+    }
+
+    /// Writes the PDF to `sink` as it is generated, laying out pages still
+    /// being collected first. Only a reserved signature needs the whole file
+    /// in memory, to fill in the byte range it covers.
+    pub fn save(mut self, sink: impl Write) -> Result<(), Error> {
+        self.finish();
+        let tic = Instant::now();
+        let mut sink = BufWriter::new(sink);
+        let post_process = self.bleed.is_some()
+            || self.printer_marks.is_some()
+            || self.reserve_signature
+            || self.stamp.is_some()
+            || !self.bookmarks.is_empty()
+            || !self.dests.is_empty()
+            || !self.links.is_empty()
+            || self.layer_name_template.is_some()
+            || !self.custom_info.is_empty()
+            || !self.portfolio.is_empty()
+            || self.fixed_date.is_some()
+            || self.compression == Compression::Auto
+            || self.append_to.is_some();
+        #[cfg(feature = "ocr")]
+        let post_process = post_process || !self.ocr_pages.is_empty();
+        if post_process {
+            let mut doc = lopdf::Document::load_mem(&self.pdf.save_to_bytes()?)?;
+            if self.compression == Compression::Auto {
+                compress::encode_bilevel_images(&mut doc)?;
+            }
+            outline::add_outline(&mut doc, &self.bookmarks)?;
+            if !self.portfolio.is_empty() {
+                portfolio::add_collection(&mut doc, &self.portfolio)?;
+            }
+            outline::add_named_dests(&mut doc, &self.dests)?;
+            outline::add_links(&mut doc, &self.links)?;
+            layers::encode_names(&mut doc);
+            // the text layer is placed in the coordinates of the unscaled page
+            #[cfg(feature = "ocr")]
+            if !self.ocr_pages.is_empty() {
+                ocr::add_text_layer(&mut doc, &self.ocr_pages, self.layers)?;
+            }
+            if let Some(bleed) = self.bleed {
+                prepress::add_bleed(&mut doc, printpdf::Pt::from(bleed).0)?;
+            }
+            if let Some(marks) = &self.printer_marks {
+                let date = self.fixed_date.unwrap_or_else(OffsetDateTime::now_utc);
+                prepress::add_printer_marks(&mut doc, marks, &self.title, date)?;
+            }
+            if let Some(stamp) = &self.stamp {
+                overlay::apply(&mut doc, stamp, self.layers, &self.page_files)?;
+            }
+            // everything above is about the new pages only
+            if let Some(mut base) = self.append_to.take() {
+                append::append_pages(&mut base, doc)?;
+                doc = base;
+            }
+            if !self.custom_info.is_empty() {
+                info::add_custom_entries(&mut doc, &self.custom_info)?;
+            }
+            if self.reserve_signature {
+                signature::add_signature_field(&mut doc)?;
+            }
+            if self.fixed_date.is_some() {
+                info::set_ids(&mut doc, &info::document_id(&self.title))?;
+            }
+            if self.reserve_signature {
+                let mut bytes = Vec::new();
+                doc.save_to(&mut bytes)?;
+                signature::fill_byte_range(&mut bytes).map_err(Error::Signature)?;
+                sink.write_all(&bytes)?;
+            } else {
+                doc.save_to(&mut sink)?;
+            }
+        } else {
+            self.pdf.save(&mut sink)?;
+        }
+        sink.flush()?;
+        self.progress.stage_finished(Stage::Save, tic.elapsed());
+        Ok(())
+    }
+}
+
+/// An image of JPEG `data`, `size` pixels with 1 or 3 `components`
+fn dct_image(data: Vec<u8>, (w, h): (u32, u32), components: u8) -> Image {
+    Image::from(ImageXObject {
+        width: Px(w as usize),
+        height: Px(h as usize),
+        color_space: match components {
+            1 => ColorSpace::Greyscale,
+            _ => ColorSpace::Rgb,
+        },
+        bits_per_component: ColorBits::Bit8,
+        interpolate: true,
+        image_data: data,
+        image_filter: Some(ImageFilter::DCT),
+        clipping_bbox: None,
+    })
+}
+
+/// `img` in black and white, one bit per pixel, with pixels at least as
+/// bright as `threshold` white
+fn bilevel_image(img: &DynamicImage, threshold: u8) -> Image {
+    let gray = img.to_luma8();
+    let (w, h) = gray.dimensions();
+    let row_len = (w as usize).div_ceil(8);
+    let mut data = vec![0u8; row_len * h as usize];
+    for (x, y, pixel) in gray.enumerate_pixels() {
+        if pixel.0[0] >= threshold {
+            data[y as usize * row_len + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+    Image::from(ImageXObject {
+        width: Px(w as usize),
+        height: Px(h as usize),
+        color_space: ColorSpace::Greyscale,
+        bits_per_component: ColorBits::Bit1,
+        // smoothing would blur the edges back into grays
+        interpolate: false,
+        image_data: data,
+        image_filter: None,
+        clipping_bbox: None,
+    })
+}
+
+/// `img` encoded as a JPEG at `quality`, in grayscale when it has no color
+fn jpeg_encoded(img: &DynamicImage, quality: u8) -> Option<Image> {
+    let gray = matches!(
+        img.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::L16 | ColorType::La16
+    );
+    let (pixels, color, components) = if gray {
+        (img.to_luma8().into_raw(), ColorType::L8, 1)
+    } else {
+        (img.to_rgb8().into_raw(), ColorType::Rgb8, 3)
+    };
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, quality)
+        .encode(&pixels, img.width(), img.height(), color)
+        .ok()?;
+    Some(dct_image(data, img.dimensions(), components))
+}
+
+/// Places `img`, `dpi` pixels per inch at its own size, on a page of `page`
+/// leaving `margins` free on the top, right, bottom and left. Returns the image as placed, cropped
+/// to the proportions of the page for [`Fit::Cover`], with its lower left
+/// corner and size on the page.
+fn place(
+    img: DynamicImage,
+    dpi: f64,
+    page: (Mm, Mm),
+    margins: [Mm; 4],
+    fit: Fit,
+) -> (DynamicImage, [Mm; 4]) {
+    let (w, h) = img.dimensions();
+    let [top, right, bottom, left] = margins.map(|m| m.0);
+    let (box_w, box_h) = (
+        (page.0 .0 - left - right).max(1.0),
+        (page.1 .0 - top - bottom).max(1.0),
+    );
+    let natural = (w as f64 * INCH_PER_MM / dpi, h as f64 * INCH_PER_MM / dpi);
+    let (img, size) = match fit {
+        Fit::Contain => {
+            let s = (box_w / natural.0).min(box_h / natural.1);
+            (img, (natural.0 * s, natural.1 * s))
+        }
+        Fit::Cover => {
+            let aspect = box_w / box_h;
+            let (cw, ch) = if w as f64 / h as f64 > aspect {
+                (((h as f64 * aspect).round() as u32).clamp(1, w), h)
+            } else {
+                (w, ((w as f64 / aspect).round() as u32).clamp(1, h))
+            };
+            (
+                img.crop_imm((w - cw) / 2, (h - ch) / 2, cw, ch),
+                (box_w, box_h),
+            )
+        }
+        Fit::Stretch => (img, (box_w, box_h)),
+        Fit::Center => (img, natural),
+    };
+    let x = left + (box_w - size.0) / 2.0;
+    let y = bottom + (box_h - size.1) / 2.0;
+    (img, [Mm(x), Mm(y), Mm(size.0), Mm(size.1)])
+}
@@ -0,0 +1,44 @@
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+/// Reads the paths listed in `reader`, one per line or, with `nul`, ended by
+/// NUL bytes as `find -print0` writes them. Empty entries are skipped and
+/// the order is kept.
+pub fn read(mut reader: impl Read, nul: bool) -> io::Result<Vec<PathBuf>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let separator = if nul { b'\0' } else { b'\n' };
+    Ok(data
+        .split(|b| *b == separator)
+        .map(|entry| match nul {
+            true => entry,
+            false => entry.strip_suffix(b"\r").unwrap_or(entry),
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(path)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_listed_order() {
+        let lines = read(&b"b.png\r\n\na b.jpg\n01.png"[..], false).unwrap();
+        assert_eq!(lines, ["b.png", "a b.jpg", "01.png"].map(PathBuf::from));
+        let nul = read(&b"line\nbreak.png\0a.png\0\0"[..], true).unwrap();
+        assert_eq!(nul, ["line\nbreak.png", "a.png"].map(PathBuf::from));
+    }
+}
@@ -1,68 +1,792 @@
-use clap::{Arg, ArgGroup, Command, ValueHint};
-use printpdf::{
-    image_crate::{self, GenericImageView},
-    Image, Mm, PdfDocument,
-};
-use printpdf::{ImageTransform, PdfDocumentReference};
+mod animation;
+#[cfg(feature = "archive")]
+mod archive;
+mod cover;
+#[cfg(feature = "server")]
+mod http;
+mod i18n;
+mod ignore;
+mod init;
+mod list;
+#[cfg(feature = "server")]
+mod metrics;
+mod order;
+mod pagerange;
+mod pages;
+mod plan;
+mod preflight;
+mod progress;
+mod prompt;
+#[cfg(feature = "upload")]
+mod remote;
+mod report;
+mod scratch;
+#[cfg(feature = "server")]
+mod serve;
+mod settings;
+mod settle;
+#[cfg(feature = "upload")]
+mod sha256;
+mod similarity;
+mod sink;
+mod sources;
+mod space;
+mod split;
+mod stamp;
+mod style;
+mod tiff;
+#[cfg(feature = "ffmpeg")]
+mod video;
+mod walk;
+mod watch;
+
+use clap::{Arg, ArgGroup, ArgMatches, Command, ValueHint};
+use printpdf::{image_crate::ImageError, lopdf, Mm};
+use sink::Sink;
+use std::collections::HashSet;
 use std::error::Error;
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::{stdout, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::Duration;
+#[cfg(feature = "isolate")]
+use yet_another_imgs2pdf::isolate;
+#[cfg(feature = "ocr")]
+use yet_another_imgs2pdf::ocr;
+use yet_another_imgs2pdf::{
+    info, manifest, paper, portfolio, prepress, BookmarkSource, CellOrder, ColorMode, Compression,
+    Device, Fit, PDFMerger, PageOptions, PdfMergerBuilder, Preset, Rotation, Severity, WarningCode,
+};
 
-const INCH_PER_MM: f64 = 25.4;
+/// Adds a cover page, exiting if its image cannot be read
+fn add_cover(p: &mut PDFMerger, path: &Path, size: Option<(Mm, Mm)>) {
+    if let Err(e) = p.append_cover(path, size) {
+        eprintln!("Could not read the cover `{}`: {e}", path.display());
+        exit(1)
+    }
+}
 
-struct PDFMerger {
-    pdf: PdfDocumentReference,
+/// Digits of the largest page number, to line up lists of pages
+fn page_width(pages: impl Iterator<Item = usize>) -> usize {
+    pages.max().map_or(1, |page| page.to_string().len())
 }
-impl PDFMerger {
-    fn new(title: &str) -> Self {
-        Self {
-            pdf: PdfDocument::empty(title),
-        }
-    }
-
-    fn append_image_page(
-        &self,
-        image: &Path,
-        dpi: f64,
-        layer_name: &str,
-        wh: (u32, u32),
-    ) -> image_crate::ImageResult<()> {
-        let img = image_crate::open(image)?.resize(
-            wh.0,
-            wh.1,
-            image_crate::imageops::FilterType::Lanczos3,
-        );
-        let (w, h) = img.dimensions();
-        let page_w = Mm((w as f64 * INCH_PER_MM) / dpi);
-        let page_h = Mm((h as f64 * INCH_PER_MM) / dpi);
-
-        let (page_i, layer_i) = self.pdf.add_page(page_w, page_h, layer_name);
-        let layer = self.pdf.get_page(page_i).get_layer(layer_i);
-
-        Image::from_dynamic_image(&img).add_to_layer(
-            layer,
-            ImageTransform {
-                dpi: Some(dpi),
-                ..Default::default()
+
+/// Whether two paths name the same file, however they are spelled
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Rejects writing the PDF to stdout where messages cannot be moved off it
+fn check_out(out: &OsStr) -> Result<(), String> {
+    match cfg!(unix) || out != "-" {
+        true => Ok(()),
+        false => Err("`-` (stdout) is only supported on Unix-like systems".to_owned()),
+    }
+}
+
+struct InputOptions<'a> {
+    dpi: f64,
+    wh: (u32, u32),
+    /// Scales inputs to a percentage of their size instead of into `wh`
+    scale: Option<f64>,
+    unsupported_mode: &'a str,
+    manifest: &'a [manifest::Entry],
+    fold_outs: &'a [PathBuf],
+    progress: &'a progress::Bar,
+    /// Takes the images of archives by name instead of as stored
+    #[cfg(feature = "archive")]
+    archive_by_name: bool,
+    /// Moves the cover of every archive in front of its pages
+    #[cfg(feature = "archive")]
+    detect_cover: bool,
+    /// Adds a page for every this many frames of animations, instead of
+    /// one for the first frame
+    frame_step: Option<usize>,
+    #[cfg(feature = "ffmpeg")]
+    every: Option<Duration>,
+}
+
+/// Tells that `input` is skipped and why
+fn skip(p: &mut PDFMerger, input: &Path, e: yet_another_imgs2pdf::Error) {
+    let args: [(&str, &dyn std::fmt::Display); 2] = [("file", &input.display()), ("reason", &e)];
+    style::end_progress();
+    println!("{}", style::warning(&i18n::t("skipping", &args)));
+    p.record_skipped(input, &e);
+}
+
+/// Adds the images `input` was split into, telling which could not be added
+fn add_images_of(p: &mut PDFMerger, input: &Path, images: &[PathBuf], page: PageOptions) {
+    for e in p.append_frames(input, images, page) {
+        let args: [(&str, &dyn std::fmt::Display); 2] =
+            [("file", &input.display()), ("reason", &e)];
+        style::end_progress();
+        println!("{}", style::warning(&i18n::t("skipping-image-of", &args)));
+        p.record_skipped(input, &e);
+    }
+}
+
+/// Adds every input to the merger, returning those in unsupported formats
+fn add_inputs(
+    p: &mut PDFMerger,
+    inputs: &[PathBuf],
+    opts: &InputOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut unsupported = Vec::new();
+    // videos, archives, multi-page TIFFs and animations are turned into
+    // images as they come
+    let multipage = inputs
+        .iter()
+        .filter(|n| tiff::is_multipage(n))
+        .collect::<HashSet<_>>();
+    let animated = inputs
+        .iter()
+        .filter(|n| opts.frame_step.is_some() && animation::is_animated(n))
+        .collect::<HashSet<_>>();
+    let mut queued = Vec::new();
+    for n in inputs {
+        if multipage.contains(n) || animated.contains(n) {
+            continue;
+        }
+        #[cfg(feature = "archive")]
+        if archive::is_archive(n) {
+            continue;
+        }
+        #[cfg(feature = "ffmpeg")]
+        if video::is_video(n) {
+            continue;
+        }
+        let entry = opts.manifest.iter().find(|e| e.file == *n);
+        queued.push((n.clone(), entry.and_then(|e| e.scale)));
+    }
+    p.queue(queued);
+    opts.progress.start(inputs.len());
+    for n in inputs {
+        let entry = opts.manifest.iter().find(|e| e.file == *n);
+        let page = PageOptions {
+            links: entry.map(|e| e.links.clone()).unwrap_or_default(),
+            scale: entry.and_then(|e| e.scale),
+            fold_out: opts.fold_outs.iter().any(|f| same_file(f, n))
+                || entry.is_some_and(|e| e.fold_out),
+            regions: entry.map(|e| e.regions.clone()).unwrap_or_default(),
+            color_mode: entry.and_then(|e| e.color_mode),
+        };
+
+        #[cfg(feature = "ffmpeg")]
+        if video::is_video(n) {
+            match video::frames(n, opts.every) {
+                Ok(frames) => {
+                    for e in p.append_frames(n, &frames.paths, page) {
+                        let args: [(&str, &dyn std::fmt::Display); 2] =
+                            [("file", &n.display()), ("reason", &e)];
+                        style::end_progress();
+                        println!("{}", style::warning(&i18n::t("skipping-frame", &args)));
+                        p.record_skipped(n, &e);
+                    }
+                }
+                Err(e) => {
+                    let args: [(&str, &dyn std::fmt::Display); 2] =
+                        [("file", &n.display()), ("reason", &e)];
+                    style::end_progress();
+                    println!("{}", style::warning(&i18n::t("skipping", &args)));
+                    p.record_skipped(n, &e.into());
+                }
+            }
+            continue;
+        }
+
+        #[cfg(feature = "archive")]
+        if archive::is_archive(n) {
+            match archive::extract(n, opts.archive_by_name) {
+                Ok((mut pages, unreadable)) => {
+                    if opts.detect_cover {
+                        cover::move_first(&mut pages.paths);
+                    }
+                    for (entry, e) in unreadable {
+                        let args: [(&str, &dyn std::fmt::Display); 3] =
+                            [("entry", &entry), ("file", &n.display()), ("reason", &e)];
+                        style::end_progress();
+                        println!("{}", style::warning(&i18n::t("skipping-entry", &args)));
+                        p.record_skipped(n, &e.into());
+                    }
+                    add_images_of(p, n, &pages.paths, page);
+                }
+                Err(e) => skip(p, n, e.into()),
+            }
+            continue;
+        }
+
+        if multipage.contains(n) {
+            match tiff::split(n) {
+                Ok(pages) => add_images_of(p, n, &pages.paths, page),
+                Err(e) => skip(p, n, e.into()),
+            }
+            continue;
+        }
+
+        if animated.contains(n) {
+            match animation::expand(n, opts.frame_step.unwrap_or(1)) {
+                Ok(frames) => add_images_of(p, n, &frames.paths, page),
+                Err(e) => skip(p, n, e.into()),
+            }
+            continue;
+        }
+
+        match p.append_image_page_with(n, page) {
+            Err(yet_another_imgs2pdf::Error::Image(ImageError::Unsupported(e)))
+                if opts.unsupported_mode != "skip" =>
+            {
+                if opts.unsupported_mode == "page" {
+                    p.append_unsupported_page(n, &e.to_string())?;
+                } else {
+                    let e = ImageError::Unsupported(e);
+                    p.record_skipped(n, &yet_another_imgs2pdf::Error::Image(e));
+                }
+                unsupported.push(n.clone());
+            }
+            Err(e) => {
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("file", &n.display()), ("reason", &e)];
+                style::end_progress();
+                println!("{}", style::warning(&i18n::t("skipping", &args)));
+                p.record_skipped(n, &e);
+            }
+            Ok(()) => {}
+        }
+    }
+    opts.progress.finish();
+    Ok(unsupported)
+}
+
+/// Prints the estimated ink coverage of every page and their average
+fn print_ink_report(pages: &[[f64; 4]]) {
+    println!("Estimated ink coverage:");
+    println!(
+        "{:>6} {:>7} {:>7} {:>7} {:>7} {:>8}",
+        "page", "C", "M", "Y", "K", "total"
+    );
+    let mut doc = [0.0; 4];
+    for (i, page) in pages.iter().enumerate() {
+        print_ink_row(&(i + 1).to_string(), page);
+        for c in 0..4 {
+            doc[c] += page[c] / pages.len() as f64;
+        }
+    }
+    print_ink_row("avg", &doc);
+}
+
+fn print_ink_row(label: &str, c: &[f64; 4]) {
+    println!(
+        "{:>6} {:>6.1}% {:>6.1}% {:>6.1}% {:>6.1}% {:>7.1}%",
+        label,
+        c[0] * 100.0,
+        c[1] * 100.0,
+        c[2] * 100.0,
+        c[3] * 100.0,
+        c.iter().sum::<f64>() * 100.0
+    );
+}
+
+/// Splits a directory into one group of images per subdirectory, plus one
+/// named after the directory itself for images directly inside it
+fn portfolio_groups(
+    dir: &Path,
+    sort_by: Option<&str>,
+    filter: &walk::Filter,
+) -> std::io::Result<Vec<(String, Vec<PathBuf>)>> {
+    let ignore = ignore::Ignore::read(dir)?;
+    let root = dir;
+    let list = |dir: &Path| -> std::io::Result<Vec<PathBuf>> {
+        let mut paths = std::fs::read_dir(dir)?
+            .filter_map(|rd| rd.map(|de| de.path()).ok())
+            .filter(|p| {
+                let relative = p.strip_prefix(root).unwrap_or(p);
+                !ignore.is_ignored(relative, p.is_dir()) && filter.allows(relative, p.is_dir())
+            })
+            .collect::<Vec<_>>();
+        if let Some(key) = sort_by.filter(|key| *key != "similarity") {
+            order::sort(&mut paths, key);
+        }
+        Ok(paths)
+    };
+    let name = |p: &Path| {
+        p.file_name()
+            .unwrap_or(p.as_os_str())
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let (mut subdirs, loose): (Vec<_>, Vec<_>) = list(dir)?.into_iter().partition(|p| p.is_dir());
+    subdirs.sort_by(|a, b| order::natural_cmp(a, b));
+    let mut groups = Vec::new();
+    if !loose.is_empty() {
+        groups.push((name(dir), loose));
+    }
+    for sub in subdirs {
+        let files = list(&sub)?
+            .into_iter()
+            .filter(|p| p.is_file())
+            .collect::<Vec<_>>();
+        if !files.is_empty() {
+            groups.push((name(&sub), files));
+        }
+    }
+    Ok(groups)
+}
+
+/// A merger set up with every option given, exiting on invalid values
+fn merger_from_matches(matches: &ArgMatches, title: &str, opts: &InputOptions) -> PDFMerger {
+    let mut b = PdfMergerBuilder::new()
+        .title(title)
+        .progress(opts.progress.clone())
+        .dpi(opts.dpi)
+        .scale(opts.wh.0, opts.wh.1);
+    if let Some(percent) = opts.scale {
+        b = b.scale_percent(percent);
+    }
+    match matches.value_of("min-dpi").map(str::parse::<f64>) {
+        Some(Ok(d)) => b = b.min_dpi(d),
+        Some(Err(_)) => {
+            eprintln!("Value <min-dpi> could not be parsed as a float");
+            exit(1)
+        }
+        None => {}
+    }
+    match matches.value_of("per-image-timeout").map(parse_duration) {
+        Some(Some(d)) if !d.is_zero() => b = b.decode_timeout(d),
+        Some(_) => {
+            eprintln!("Value <per-image-timeout> could not be parsed as a duration");
+            exit(1)
+        }
+        None => {}
+    }
+    let jobs = match matches.value_of("jobs").map(str::parse::<usize>) {
+        Some(Ok(jobs)) => jobs,
+        Some(Err(_)) => {
+            eprintln!("Value <jobs> must be a positive int");
+            exit(1)
+        }
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    b = b.jobs(jobs);
+    #[cfg(feature = "isolate")]
+    if matches.is_present("isolate-decode") {
+        let limit = |id: &str| match matches.value_of(id).unwrap().parse::<u64>() {
+            Ok(limit) if limit > 0 => limit,
+            _ => {
+                eprintln!("Value <{id}> must be a positive int");
+                exit(1)
+            }
+        };
+        b = b.isolate(isolate::Limits {
+            memory: limit("decode-memory-limit") * 1024 * 1024,
+            cpu: limit("decode-cpu-limit"),
+        });
+    }
+    let preset = match matches.value_of("preset") {
+        Some("whiteboard") => Some(Preset::Whiteboard),
+        Some("receipt") => Some(Preset::Receipt),
+        _ => None,
+    };
+    if let Some(preset) = preset {
+        b = b.preset(preset);
+    }
+    let device = match matches.value_of("device") {
+        Some("kindle-paperwhite") => Some(Device::KindlePaperwhite),
+        Some("remarkable2") => Some(Device::Remarkable2),
+        Some("ipad") => Some(Device::Ipad),
+        _ => None,
+    };
+    if let Some(device) = device {
+        b = b.device(device);
+    }
+    if let Some(size) = matches.value_of("page-size") {
+        let page = match paper::parse_paper_size(size) {
+            Some(page) => page,
+            None => {
+                eprintln!("Value <page-size> is not a known paper size or WxHmm");
+                exit(1)
+            }
+        };
+        let fit = match matches.value_of("fit") {
+            Some("cover") => Fit::Cover,
+            Some("stretch") => Fit::Stretch,
+            Some("center") => Fit::Center,
+            _ => Fit::Contain,
+        };
+        b = b.page_size(page, fit);
+    }
+    if let Some(trim) = matches.value_of("trim-margins") {
+        let percentage = |s: &str| match s.trim() {
+            "0" | "0%" => Some(0.0),
+            s => paper::parse_percentage(s),
+        };
+        match paper::parse_sides(trim, percentage) {
+            Some(trim) => b = b.trim_margins(trim),
+            None => {
+                eprintln!(
+                    "Value <trim-margins> must be one, two or four percentages (e.g. 5% or 3%,8%)"
+                );
+                exit(1)
+            }
+        }
+    }
+    let side_ids = ["margin-top", "margin-right", "margin-bottom", "margin-left"];
+    if matches.is_present("margin") || side_ids.iter().any(|id| matches.is_present(id)) {
+        let mut margins = match matches.value_of("margin") {
+            Some(margin) => match paper::parse_sides(margin, paper::parse_length) {
+                Some(margins) => margins,
+                None => {
+                    eprintln!(
+                        "Value <margin> must be one, two or four lengths (e.g. 10mm or 10mm,5mm)"
+                    );
+                    exit(1)
+                }
             },
+            None => [device.map_or(Mm(0.0), Device::margin); 4],
+        };
+        for (margin, id) in margins.iter_mut().zip(side_ids) {
+            if let Some(side) = matches.value_of(id) {
+                *margin = match paper::parse_length(side) {
+                    Some(length) => length,
+                    None => {
+                        eprintln!("Value <{}> could not be parsed as a length", id);
+                        exit(1)
+                    }
+                };
+            }
+        }
+        b = b.margins(margins);
+    }
+    let rotation = match matches.value_of("rotate") {
+        Some("0") => Rotation::None,
+        Some("90") => Rotation::Cw90,
+        Some("180") => Rotation::Cw180,
+        Some("270") => Rotation::Cw270,
+        _ => Rotation::Auto,
+    };
+    b = b.rotate(rotation);
+    if matches.is_present("deterministic") {
+        // the convention of reproducible builds, or else the Unix epoch
+        let epoch = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        b = b.deterministic(printpdf::OffsetDateTime::from_unix_timestamp(epoch));
+    }
+    let color_mode = match matches.value_of("color-mode") {
+        Some("gray") => ColorMode::Gray,
+        Some("bilevel") => match matches.value_of("threshold").unwrap().parse::<u8>() {
+            Ok(threshold) => ColorMode::Bilevel { threshold },
+            Err(_) => {
+                eprintln!("Value <threshold> must be an int from 0 to 255");
+                exit(1)
+            }
+        },
+        _ => ColorMode::Keep,
+    };
+    b = b.color_mode(color_mode);
+    match matches.value_of("quality").map(str::parse::<u8>) {
+        Some(Ok(quality)) => b = b.quality(quality),
+        Some(Err(_)) => {
+            eprintln!("Value <quality> must be an int from 1 to 100");
+            exit(1)
+        }
+        None => {}
+    }
+    if matches.value_of("compress") == Some("auto") {
+        b = b.compression(Compression::Auto);
+    }
+    match matches.value_of("max-dimension").map(str::parse::<u32>) {
+        Some(Ok(pixels)) => b = b.max_dimension(pixels),
+        Some(Err(_)) => {
+            eprintln!("Value <max-dimension> must be a positive int");
+            exit(1)
+        }
+        None => {}
+    }
+    if let Some(dir) = matches.value_of_os("preview-dir") {
+        match matches.value_of("preview-count").unwrap().parse::<usize>() {
+            Ok(count) if count > 0 => b = b.preview(dir, count),
+            _ => {
+                eprintln!("Value <preview-count> must be a positive int");
+                exit(1)
+            }
+        }
+    }
+    match matches.value_of("columns").map(str::parse::<usize>) {
+        Some(Ok(columns)) => b = b.columns(columns),
+        Some(Err(_)) => {
+            eprintln!("Value <columns> must be a positive int");
+            exit(1)
+        }
+        None => {}
+    }
+    if matches.is_present("continuous-page") || preset == Some(Preset::Receipt) {
+        let width = match matches.value_of("continuous-width").map(str::parse::<f64>) {
+            Some(Ok(w)) => Some(Mm(w)),
+            Some(Err(_)) => {
+                eprintln!("Value <continuous-width> could not be parsed as a float");
+                exit(1)
+            }
+            None => None,
+        };
+        let spacing = match matches
+            .value_of("continuous-spacing")
+            .unwrap()
+            .parse::<f64>()
+        {
+            Ok(s) => Mm(s),
+            Err(_) => {
+                eprintln!("Value <continuous-spacing> could not be parsed as a float");
+                exit(1)
+            }
+        };
+        b = b.continuous_page(width, spacing);
+    }
+    if let Some(grid) = matches.value_of("nup") {
+        let (rows, columns) = match grid
+            .to_ascii_lowercase()
+            .split_once('x')
+            .map(|(r, c)| (r.trim().parse::<usize>(), c.trim().parse::<usize>()))
+        {
+            Some((Ok(rows), Ok(columns))) if rows > 0 && columns > 0 => (rows, columns),
+            _ => {
+                eprintln!("Value <nup> must be rows and columns such as 2x2 or 1x2");
+                exit(1)
+            }
+        };
+        let gutter = match paper::parse_length(matches.value_of("nup-gutter").unwrap()) {
+            Some(gutter) => gutter,
+            None => {
+                eprintln!("Value <nup-gutter> could not be parsed as a length");
+                exit(1)
+            }
+        };
+        let order = match matches.value_of("nup-order") {
+            Some("column") => CellOrder::ColumnMajor,
+            _ => CellOrder::RowMajor,
+        };
+        b = b.nup(rows, columns, gutter, order);
+    }
+    if matches.is_present("spreads") {
+        // a cover found among the inputs is put first to stand alone
+        b = b.spreads(
+            matches.is_present("rtl"),
+            matches.is_present("detect-cover"),
         );
-        Ok(())
     }
-
-    fn save(self, sink: impl Write) -> Result<(), printpdf::Error> {
-        self.pdf.save(&mut BufWriter::new(sink))
+    b = b
+        .dewarp(matches.is_present("dewarp"))
+        .protect_text(matches.is_present("protect-text"))
+        .stitch_vertical(matches.is_present("stitch-vertical"));
+    if let Some(size) = matches.value_of("tile-oversized") {
+        let page = match paper::parse_paper_size(size) {
+            Some(page) => page,
+            None => {
+                eprintln!("Value <tile-oversized> is not a known paper size or WxHmm");
+                exit(1)
+            }
+        };
+        let overlap = match matches.value_of("tile-overlap").unwrap().parse::<f64>() {
+            Ok(o) => Mm(o),
+            Err(_) => {
+                eprintln!("Value <tile-overlap> must be a length in mm smaller than the page");
+                exit(1)
+            }
+        };
+        b = b.tile_oversized(page, overlap);
+    }
+    if let Some(bleed) = matches.value_of("bleed") {
+        match paper::parse_length(bleed) {
+            Some(bleed) => b = b.bleed(bleed),
+            None => {
+                eprintln!("Value <bleed> could not be parsed as a length");
+                exit(1)
+            }
+        }
+    }
+    if matches.is_present("printer-marks") {
+        b = b.printer_marks(prepress::PrinterMarks {
+            slug_info: matches.is_present("slug-info"),
+        });
+    }
+    if let Some(stamp) = stamp::from_matches(matches) {
+        b = b.stamp(stamp);
+    }
+    b = b
+        .reserve_signature(matches.is_present("reserve-signature"))
+        .layers(matches.is_present("layers"));
+    if let Some(existing) = matches.value_of_os("append") {
+        b = b.append_to(existing);
+    }
+    if let Some(author) = matches.value_of("author") {
+        b = b.author(author);
+    }
+    if let Some(subject) = matches.value_of("subject") {
+        b = b.subject(subject);
+    }
+    if let Some(keywords) = matches.value_of("keywords") {
+        let keywords = keywords
+            .split(',')
+            .map(|k| k.trim().to_owned())
+            .filter(|k| !k.is_empty())
+            .collect();
+        b = b.keywords(keywords);
+    }
+    if let Some(date) = matches.value_of("creation-date") {
+        match info::parse_date(date) {
+            Some(date) => b = b.creation_date(date),
+            None => {
+                eprintln!("Value <creation-date> must be a date such as 2024-05-31 or 2024-05-31T14:30:00");
+                exit(1)
+            }
+        }
+    }
+    for entry in matches.values_of("meta").into_iter().flatten() {
+        match info::parse_custom_entry(entry) {
+            Ok((key, value)) => b = b.meta(key, value),
+            Err(e) => {
+                eprintln!("Value <meta> {e}");
+                exit(1)
+            }
+        }
+    }
+    let bookmark_source = match matches.value_of("bookmark-source") {
+        Some("filename") => Some(BookmarkSource::Filename),
+        Some("exif-title") => Some(BookmarkSource::ExifTitle),
+        Some("xmp-title") => Some(BookmarkSource::XmpTitle),
+        #[cfg(feature = "ocr")]
+        Some("ocr-heading") => Some(BookmarkSource::OcrHeading),
+        _ => None,
+    };
+    if let Some(source) = bookmark_source {
+        b = b.bookmark_source(source);
+    } else if matches.is_present("bookmarks") && !matches.is_present("bookmark-template") {
+        b = b.bookmark_source(BookmarkSource::Filename);
+    }
+    if matches.is_present("bookmarks") && matches.is_present("recursive") {
+        b = b.group_bookmarks(matches.value_of_os("dir").unwrap());
+    }
+    if let Some(t) = matches.value_of("bookmark-template") {
+        b = b.bookmark_template(t);
+    }
+    if let Some(t) = matches.value_of("layer-name-template") {
+        b = b.layer_name_template(t);
+    }
+    b = b
+        .named_dests(matches.is_present("named-dests"))
+        .detect_chapters(matches.is_present("detect-chapters"))
+        .ink_report(matches.is_present("ink-report"));
+    #[cfg(feature = "ocr")]
+    if matches.value_of("bookmark-source") == Some("ocr-heading") {
+        if !matches.is_present("ocr") {
+            eprintln!("Value <bookmark-source> ocr-heading needs --ocr");
+            exit(1)
+        }
+        if matches.is_present("bookmark-template") {
+            eprintln!(
+                "Value <bookmark-template> cannot be combined with --bookmark-source ocr-heading"
+            );
+            exit(1)
+        }
+    }
+    #[cfg(feature = "ocr")]
+    if matches.is_present("auto-rotate-text") {
+        if !ocr::tesseract_available() {
+            eprintln!("--auto-rotate-text needs `tesseract` on PATH");
+            exit(1)
+        }
+        b = b.auto_rotate_text(true);
+    }
+    #[cfg(feature = "ocr")]
+    if matches.is_present("ocr") {
+        let min_confidence = match matches.value_of("ocr-min-confidence").unwrap().parse() {
+            Ok(c) if (0.0..=100.0).contains(&c) => c,
+            _ => {
+                eprintln!("Value <ocr-min-confidence> must be a percentage between 0 and 100");
+                exit(1)
+            }
+        };
+        if !ocr::tesseract_available() {
+            eprintln!("--ocr needs `tesseract` on PATH");
+            exit(1)
+        }
+        b = b.ocr(ocr::Options {
+            lang: matches.value_of("ocr-lang").unwrap().to_owned(),
+            min_confidence,
+        });
+    }
+    // the same settings make every member of a portfolio, so warn once
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        for d in b.validate() {
+            if d.severity == Severity::Warning {
+                let arg = |option: &str| format!("<{}>", option.replace('_', "-"));
+                let mut message = format!("Value {} {}", arg(d.option), d.reason);
+                if let Some(with) = d.conflicts_with {
+                    message += &format!(" with {} set", arg(with));
+                }
+                println!("{}", style::warning(&message));
+            }
+        }
+    });
+    match b.build() {
+        Ok(p) => p,
+        Err(yet_another_imgs2pdf::Error::InvalidOption { option, reason }) => {
+            eprintln!("Value <{}> {reason}", option.replace('_', "-"));
+            exit(1)
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            exit(1)
+        }
     }
 }
 
+fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value.trim().parse::<f64>().ok()?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return None,
+    };
+    (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+/// Bytes in a size like `20MB`, `512KiB` or `1000000`
+fn parse_size(s: &str) -> Option<u64> {
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value = value.trim().parse::<f64>().ok()?;
+    let bytes = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => value,
+        "k" | "kb" => value * 1e3,
+        "m" | "mb" => value * 1e6,
+        "g" | "gb" => value * 1e9,
+        "kib" => value * 1024.0,
+        "mib" => value * 1024.0 * 1024.0,
+        "gib" => value * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    (bytes.is_finite() && bytes >= 0.0).then_some(bytes as u64)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let matches = Command::new(env!("CARGO_PKG_NAME"))
+    let cmd = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .about("Merge multiple images into a single pdf")
         .author("scrubjay55")
         .arg(
             Arg::new("dir")
+                .allow_invalid_utf8(true)
                 .help("Directory to folder of images")
                 .multiple_occurrences(false)
                 .multiple_values(false)
@@ -70,22 +794,249 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .long("dir")
                 .short('d'),
         )
+        .arg(
+            Arg::new("recursive")
+                .help("Also take the images in every subdirectory of <dir>, depth first")
+                .takes_value(false)
+                .requires("dir")
+                .conflicts_with("portfolio")
+                .long("recursive")
+                .short('r'),
+        )
+        .arg(
+            Arg::new("include")
+                .help("Only take files of <dir> matching these comma-separated globs (e.g. *.jpg,*.png)")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .requires("dir")
+                .long("include"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .help("Leave out files and directories of <dir> matching these comma-separated globs")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .requires("dir")
+                .long("exclude"),
+        )
         .arg(
             Arg::new("imgs")
+                .allow_invalid_utf8(true)
                 .help("Paths to multiple images seperated with a whitespace")
                 .multiple_values(true)
                 .value_hint(ValueHint::FilePath)
                 .long("imgs")
                 .short('i'),
         )
+        .arg(
+            Arg::new("from-file")
+                .allow_invalid_utf8(true)
+                .help("A file listing the images in order, one per line; - among --imgs reads such a list from stdin")
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("from-file"),
+        )
+        .arg(
+            Arg::new("null")
+                .help("The list of --from-file or stdin is separated by NUL bytes, as find -print0 writes it")
+                .takes_value(false)
+                .long("null")
+                .short('0'),
+        )
+        .arg(
+            Arg::new("manifest")
+                .allow_invalid_utf8(true)
+                .help("A TOML file listing the inputs in order, with per-input settings such as links")
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("manifest"),
+        )
+        .arg(
+            Arg::new("manifest-var")
+                .help("Set a variable of --manifest as name=value, over the one in its [vars], repeatable")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .requires("manifest")
+                .long("manifest-var"),
+        )
         .arg(
             Arg::new("out")
+                .allow_invalid_utf8(true)
+                .help("The PDF to write, - to write it to stdout and every message to stderr (Unix-like systems only), or a URL to upload it to")
+                .validator_os(check_out)
                 .value_hint(ValueHint::FilePath)
                 .required(true)
                 .long("out")
                 .short('o'),
         )
+        .arg(
+            Arg::new("split-pages")
+                .help("Cut the PDF into parts of at most this many pages, written as name-001.pdf, name-002.pdf and so on for <out>")
+                .takes_value(true)
+                .conflicts_with_all(&["portfolio", "delete-sources"])
+                .long("split-pages"),
+        )
+        .arg(
+            Arg::new("split-size")
+                .help("Cut the PDF into parts no larger than this, like 20MB for mail attachments, written as name-001.pdf, name-002.pdf and so on for <out>")
+                .takes_value(true)
+                .conflicts_with_all(&["portfolio", "delete-sources"])
+                .long("split-size"),
+        )
+        .arg(
+            Arg::new("append")
+                .help("Add the new pages to the end of this existing PDF, keeping its bookmarks, and write the result to <out>, which may be the same file")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .value_hint(ValueHint::FilePath)
+                .conflicts_with("portfolio")
+                .long("append"),
+        )
+        .arg(
+            Arg::new("force")
+                .help("Overwrite <out> if it already exists, which is refused otherwise")
+                .takes_value(false)
+                .long("force")
+                .short('f'),
+        )
+        .arg(
+            Arg::new("out-auto")
+                .help("Write to <out> numbered as name-1.pdf, name-2.pdf and so on if it already exists")
+                .takes_value(false)
+                .conflicts_with("force")
+                .long("out-auto"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .help("Give up on the whole run after this long (e.g. 30m)")
+                .takes_value(true)
+                .long("timeout"),
+        )
+        .arg(
+            Arg::new("per-image-timeout")
+                .help("Skip inputs that take longer than this to decode (e.g. 30s)")
+                .takes_value(true)
+                .long("per-image-timeout"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .help("Decode and scale up to this many images at once [default: number of CPUs]")
+                .takes_value(true)
+                .short('j')
+                .long("jobs"),
+        )
+        .arg(
+            Arg::new("color")
+                .help("Color warnings and errors and show progress; auto leaves logs and pipes plain and honors NO_COLOR")
+                .possible_values(style::CHOICES)
+                .default_value("auto")
+                .long("color"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .help("Print the order the inputs would be placed in, with the format and size of each, and exit without writing anything")
+                .long("dry-run"),
+        )
+        .arg(
+            Arg::new("sample")
+                .help("Make a quick draft from only this many of the inputs, every so many of them spread over the whole run, to check the settings on a large job")
+                .takes_value(true)
+                .conflicts_with_all(&["delete-sources", "move-processed", "copy-processed"])
+                .long("sample"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .help("Show neither the progress bar nor the summary of the inputs processed")
+                .long("quiet")
+                .short('q'),
+        )
+        .arg(
+            Arg::new("lang")
+                .help("Language of the messages [default: from LC_ALL, LC_MESSAGES or LANG]")
+                .possible_values(i18n::LANGUAGES)
+                .long("lang"),
+        )
+        .arg(
+            Arg::new("settle")
+                .help("Wait until <dir> has not changed for this long (e.g. 10s) before starting")
+                .takes_value(true)
+                .requires("dir")
+                .long("settle"),
+        )
+        .arg(
+            Arg::new("watch")
+                .help("After what is in <dir>, keep adding each new image as it arrives, finishing the PDF on Ctrl-C or after --idle-timeout")
+                .takes_value(false)
+                .requires("dir")
+                .conflicts_with_all(&["portfolio", "settle", "sample", "dry-run"])
+                .long("watch"),
+        )
+        .arg(
+            Arg::new("idle-timeout")
+                .help("Finish watching once no new image has arrived for this long, e.g. 5m")
+                .takes_value(true)
+                .requires("watch")
+                .long("idle-timeout"),
+        )
+        .arg(
+            Arg::new("move-processed")
+                .help("Move inputs that made it into the PDF to this directory once it is written")
+                .allow_invalid_utf8(true)
+                .value_hint(ValueHint::DirPath)
+                .takes_value(true)
+                .long("move-processed"),
+        )
+        .arg(
+            Arg::new("copy-processed")
+                .help("Copy inputs that made it into the PDF to this directory once it is written")
+                .allow_invalid_utf8(true)
+                .value_hint(ValueHint::DirPath)
+                .takes_value(true)
+                .conflicts_with("move-processed")
+                .long("copy-processed"),
+        )
+        .arg(
+            Arg::new("delete-sources")
+                .help("Delete inputs inside <dir> that made it into the PDF once it is written, synced and verified")
+                .takes_value(false)
+                .requires_all(&["dir", "yes-really"])
+                .conflicts_with_all(&["move-processed", "copy-processed"])
+                .long("delete-sources"),
+        )
+        .arg(
+            Arg::new("use-trash")
+                .help("Send inputs to the trash instead of deleting them with --delete-sources")
+                .takes_value(false)
+                .requires("delete-sources")
+                .long("use-trash"),
+        )
+        .arg(
+            Arg::new("yes-really")
+                .help("Confirm --delete-sources")
+                .takes_value(false)
+                .long("yes-really"),
+        )
+        .arg(
+            Arg::new("durable")
+                .help("Sync the PDF and its directory to the device before exiting, for archival copies")
+                .takes_value(false)
+                .long("durable"),
+        )
+        .arg(
+            Arg::new("fallback-out")
+                .allow_invalid_utf8(true)
+                .help("Where to salvage the PDF if <out> cannot be written [default: the temp directory]")
+                .value_hint(ValueHint::FilePath)
+                .takes_value(true)
+                .long("fallback-out"),
+        )
         .arg(Arg::new("dpi").default_value("100.0").long("dpi"))
+        .arg(
+            Arg::new("min-dpi")
+                .help("Fail instead of warning when a page ends up below this effective resolution")
+                .takes_value(true)
+                .long("min-dpi"),
+        )
         .arg(
             Arg::new("scale-width")
                 .default_value("")
@@ -100,6 +1051,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short('h')
                 .default_value("1920"),
         )
+        .arg(
+            Arg::new("scale")
+                .help("Scale every image to a percentage of its size (e.g. 50%) instead of into --scale-width and --scale-height; at 100% JPEGs go in as they are, without encoding them again")
+                .takes_value(true)
+                .conflicts_with_all(&["scale-width", "scale-height"])
+                .long("scale"),
+        )
         .arg(
             Arg::new("auto-sort")
                 .takes_value(false)
@@ -107,19 +1065,579 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short('s'),
         )
         .arg(
-            Arg::new("pdf-title")
-                .hide_default_value(true)
-                .default_value("")
+            Arg::new("sort-by")
+                .help("Sort the inputs by file name with numbers in order, plain character order (lex), modification time, EXIF date taken, or chain them by visual similarity and report uncertain steps")
+                .possible_values(["name", "lex", "mtime", "exif-date", "similarity"])
+                .conflicts_with("auto-sort")
+                .long("sort-by"),
+        )
+        .arg(
+            Arg::new("detect-cover")
+                .help("Find the cover among the images of each .cbz/.zip input, and among the inputs, by its name or odd size and put it first")
+                .takes_value(false)
+                .long("detect-cover"),
+        )
+        .arg(
+            Arg::new("expand-frames")
+                .help("Add a page for every frame of animated GIFs and APNGs instead of only the first")
+                .takes_value(false)
+                .long("expand-frames"),
+        )
+        .arg(
+            Arg::new("frame-step")
+                .help("With --expand-frames, only add every this many frames, starting with the first")
+                .takes_value(true)
+                .requires("expand-frames")
+                .long("frame-step"),
+        )
+        .arg(
+            Arg::new("pdf-title")
+                .hide_default_value(true)
+                .default_value("")
                 .long("pdf-title")
                 .short('t'),
         )
+        .arg(
+            Arg::new("preset")
+                .help("Tune processing for a kind of source")
+                .possible_values(["whiteboard", "receipt"])
+                .long("preset"),
+        )
+        .arg(
+            Arg::new("device")
+                .help("Lay pages out for the screen of a reading device, grayscale with more contrast on e-ink")
+                .possible_values(["kindle-paperwhite", "remarkable2", "ipad"])
+                .conflicts_with_all(&["dpi", "scale-width", "scale-height", "scale"])
+                .long("device"),
+        )
+        .arg(
+            Arg::new("page-size")
+                .help("Put every image on a page of this size (A4, Letter, Legal, A5, ... or WxHmm)")
+                .takes_value(true)
+                .conflicts_with("device")
+                .long("page-size"),
+        )
+        .arg(
+            Arg::new("fit")
+                .help("How images are placed on pages of --page-size: whole, filling the page cropped, stretched, or at their own size")
+                .possible_values(["contain", "cover", "stretch", "center"])
+                .default_value("contain")
+                .long("fit"),
+        )
+        .arg(
+            Arg::new("margin")
+                .help("Keep blank space around the images (e.g. 10mm), or top and bottom then left and right (10mm,5mm), or top, right, bottom and left")
+                .takes_value(true)
+                .long("margin"),
+        )
+        .arg(
+            Arg::new("margin-top")
+                .help("Blank space above the images, over --margin")
+                .takes_value(true)
+                .long("margin-top"),
+        )
+        .arg(
+            Arg::new("margin-right")
+                .help("Blank space right of the images, over --margin")
+                .takes_value(true)
+                .long("margin-right"),
+        )
+        .arg(
+            Arg::new("margin-bottom")
+                .help("Blank space below the images, over --margin")
+                .takes_value(true)
+                .long("margin-bottom"),
+        )
+        .arg(
+            Arg::new("margin-left")
+                .help("Blank space left of the images, over --margin")
+                .takes_value(true)
+                .long("margin-left"),
+        )
+        .arg(
+            Arg::new("trim-margins")
+                .help("Cut a percentage off every edge of the images (e.g. 5%), or top and bottom then left and right (3%,8%), or top, right, bottom and left")
+                .takes_value(true)
+                .long("trim-margins"),
+        )
+        .arg(
+            Arg::new("deterministic")
+                .help("Make the same inputs always give the same file, dated SOURCE_DATE_EPOCH or else 1970")
+                .takes_value(false)
+                .long("deterministic"),
+        )
+        .arg(
+            Arg::new("color-mode")
+                .help("Embed the images as they are, in grayscale, or in black and white for small scans of text")
+                .possible_values(["keep", "gray", "bilevel"])
+                .default_value("keep")
+                .long("color-mode"),
+        )
+        .arg(
+            Arg::new("threshold")
+                .help("Brightness from 0 to 255 from which pixels turn white with --color-mode bilevel")
+                .default_value("128")
+                .long("threshold"),
+        )
+        .arg(
+            Arg::new("quality")
+                .help("Encode the images as JPEG at this quality, from 1 to 100, for a smaller PDF")
+                .takes_value(true)
+                .long("quality"),
+        )
+        .arg(
+            Arg::new("compress")
+                .help("How images are compressed: fixed treats them all alike, auto picks CCITT G4 for black and white, lossless for flat colors and JPEG for photos, per image")
+                .possible_values(["fixed", "auto"])
+                .default_value("fixed")
+                .long("compress"),
+        )
+        .arg(
+            Arg::new("max-dimension")
+                .help("Scale images down until neither side is longer than this many pixels, keeping the size of the pages")
+                .takes_value(true)
+                .long("max-dimension"),
+        )
+        .arg(
+            Arg::new("preview-dir")
+                .help("Also write the first images as PNGs to this directory, as they go into the PDF after every filter, to check the settings on")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .long("preview-dir"),
+        )
+        .arg(
+            Arg::new("preview-count")
+                .help("How many images --preview-dir writes")
+                .default_value("5")
+                .requires("preview-dir")
+                .long("preview-count"),
+        )
+        .arg(
+            Arg::new("rotate")
+                .help("Turn every image clockwise by this many degrees; auto turns photos upright by their EXIF orientation and 0 keeps them as stored")
+                .possible_values(["auto", "0", "90", "180", "270"])
+                .default_value("auto")
+                .long("rotate"),
+        )
+        .arg(
+            Arg::new("columns")
+                .help("Split scanned pages set in this many columns of text into a page per column")
+                .takes_value(true)
+                .long("columns"),
+        )
+        .arg(
+            Arg::new("stitch-vertical")
+                .help("Merge overlapping scrolling screenshots into continuous pages")
+                .takes_value(false)
+                .long("stitch-vertical"),
+        )
+        .arg(
+            Arg::new("continuous-page")
+                .help("Stack all images onto one long page")
+                .takes_value(false)
+                .long("continuous-page"),
+        )
+        .arg(
+            Arg::new("continuous-width")
+                .help("Width of the continuous page in mm [default: widest image]")
+                .takes_value(true)
+                .long("continuous-width"),
+        )
+        .arg(
+            Arg::new("continuous-spacing")
+                .help("Gap between images on the continuous page in mm")
+                .default_value("0")
+                .long("continuous-spacing"),
+        )
+        .arg(
+            Arg::new("nup")
+                .help("Tile this many rows and columns of images onto every page, e.g. 2x2 for contact sheets or 1x2 for 2-up printing")
+                .takes_value(true)
+                .conflicts_with("continuous-page")
+                .long("nup"),
+        )
+        .arg(
+            Arg::new("nup-gutter")
+                .help("Space between the images of --nup (e.g. 5mm)")
+                .default_value("5mm")
+                .long("nup-gutter"),
+        )
+        .arg(
+            Arg::new("nup-order")
+                .help("Fill the pages of --nup row by row or column by column")
+                .possible_values(["row", "column"])
+                .default_value("row")
+                .long("nup-order"),
+        )
+        .arg(
+            Arg::new("spreads")
+                .help("Place every two consecutive portrait images side by side on a landscape page, putting book scans back together into spreads")
+                .takes_value(false)
+                .conflicts_with_all(&["continuous-page", "nup"])
+                .long("spreads"),
+        )
+        .arg(
+            Arg::new("rtl")
+                .help("Put the first image of every spread on the right, for books read right to left")
+                .takes_value(false)
+                .requires("spreads")
+                .long("rtl"),
+        )
+        .arg(
+            Arg::new("dewarp")
+                .help("Straighten photographed documents into flat rectangular pages")
+                .takes_value(false)
+                .long("dewarp"),
+        )
+        .arg(
+            Arg::new("protect-text")
+                .help("When scaling images down, resample what looks like print so that fine strokes stay legible")
+                .takes_value(false)
+                .long("protect-text"),
+        )
+        .arg(
+            Arg::new("tile-oversized")
+                .help("Split images larger than this paper size (e.g. A4) across several pages")
+                .takes_value(true)
+                .long("tile-oversized"),
+        )
+        .arg(
+            Arg::new("fold-out")
+                .allow_invalid_utf8(true)
+                .help("Keep the page size of this input, e.g. a fold-out map, when tiling")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .long("fold-out"),
+        )
+        .arg(
+            Arg::new("tile-overlap")
+                .help("Content repeated on neighbouring tiles in mm")
+                .default_value("10")
+                .long("tile-overlap"),
+        )
+        .arg(
+            Arg::new("bleed")
+                .help("Extend every page by a bleed (e.g. 3mm) that the image is scaled to cover")
+                .takes_value(true)
+                .long("bleed"),
+        )
+        .arg(
+            Arg::new("printer-marks")
+                .help("Add a slug with crop and registration marks around every page")
+                .takes_value(false)
+                .long("printer-marks"),
+        )
+        .arg(
+            Arg::new("slug-info")
+                .help("Print the title, page number and date in the printer marks slug")
+                .takes_value(false)
+                .requires("printer-marks")
+                .long("slug-info"),
+        )
+        .arg(
+            Arg::new("author")
+                .help("Author for the document info")
+                .takes_value(true)
+                .long("author"),
+        )
+        .arg(
+            Arg::new("subject")
+                .help("Subject for the document info")
+                .takes_value(true)
+                .long("subject"),
+        )
+        .arg(
+            Arg::new("keywords")
+                .help("Comma separated keywords for the document info")
+                .takes_value(true)
+                .long("keywords"),
+        )
+        .arg(
+            Arg::new("creation-date")
+                .help("Date the document was created, as 2024-05-31 or 2024-05-31T14:30:00 in UTC, instead of now")
+                .takes_value(true)
+                .long("creation-date"),
+        )
+        .arg(
+            Arg::new("meta")
+                .help("Custom key=value metadata for the document info and XMP, repeatable")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .long("meta"),
+        )
+        .arg(
+            Arg::new("bookmarks")
+                .help("Add a bookmark for every input titled with its file name, grouped by subdirectory with --recursive")
+                .takes_value(false)
+                .long("bookmarks"),
+        )
+        .arg(
+            Arg::new("bookmark-source")
+                .help("Add a bookmark for every input, titled from its file name or embedded title")
+                .possible_values(["filename", "exif-title", "xmp-title"])
+                .long("bookmark-source"),
+        )
+        .arg(
+            Arg::new("bookmark-template")
+                .help("Title bookmarks with a template like \"Chapter {dir_index}: {dirname}\"")
+                .takes_value(true)
+                .long("bookmark-template"),
+        )
+        .arg(
+            Arg::new("layer-name-template")
+                .help("Name the layer of every page with a template like \"{dirname}/{filename}\"")
+                .takes_value(true)
+                .long("layer-name-template"),
+        )
+        .arg(
+            Arg::new("named-dests")
+                .help("Name every page after its input file, for links like doc.pdf#nameddest=scan_042")
+                .takes_value(false)
+                .long("named-dests"),
+        )
+        .arg(
+            Arg::new("detect-chapters")
+                .help("Bookmark pages that look like chapter openings, unless they already get one")
+                .takes_value(false)
+                .long("detect-chapters"),
+        )
+        .arg(
+            Arg::new("cover")
+                .allow_invalid_utf8(true)
+                .help("Put this image on the first page, whatever the sorting")
+                .takes_value(true)
+                .conflicts_with("portfolio")
+                .long("cover"),
+        )
+        .arg(
+            Arg::new("back-cover")
+                .allow_invalid_utf8(true)
+                .help("Put this image on the last page, whatever the sorting")
+                .takes_value(true)
+                .conflicts_with("portfolio")
+                .long("back-cover"),
+        )
+        .arg(
+            Arg::new("cover-size")
+                .help("Fill a page of this paper size or WxHmm with each cover, cropping to fit")
+                .takes_value(true)
+                .long("cover-size"),
+        )
+        .arg(
+            Arg::new("portfolio")
+                .help("Make a PDF portfolio with one embedded document per subdirectory of <dir>")
+                .takes_value(false)
+                .requires("dir")
+                .conflicts_with_all(&["ink-report", "preflight-print", "min-dpi"])
+                .long("portfolio"),
+        )
+        .arg(
+            Arg::new("ink-report")
+                .help("Print the estimated CMYK ink coverage of every page")
+                .takes_value(false)
+                .long("ink-report"),
+        )
+        .arg(
+            Arg::new("preflight-print")
+                .help("Check the finished PDF against basic print rules and list violations")
+                .takes_value(false)
+                .long("preflight-print"),
+        )
+        .arg(
+            Arg::new("reserve-signature")
+                .help("Reserve an empty signature field for an external signer")
+                .takes_value(false)
+                .long("reserve-signature"),
+        )
+        .args(stamp::args())
+        .arg(
+            Arg::new("unsupported")
+                .help("What to do with files in formats that cannot be decoded")
+                .possible_values(["list", "page", "skip"])
+                .default_value("skip")
+                .long("unsupported"),
+        )
         .group(
             ArgGroup::new("input")
-                .args(&["imgs", "dir"])
+                .args(&["imgs", "dir", "from-file", "manifest"])
                 .multiple(false)
                 .required(true),
+        );
+    #[cfg(feature = "ffmpeg")]
+    let cmd = cmd.arg(
+        Arg::new("every")
+            .help("Extract a video frame at this interval (e.g. 5s) instead of every keyframe")
+            .takes_value(true)
+            .long("every"),
+    );
+    #[cfg(feature = "archive")]
+    let cmd = cmd.arg(
+        Arg::new("archive-order")
+            .help("Take the images of .cbz/.zip inputs in the order they are stored, or by name with numbers in order")
+            .possible_values(["archive", "name"])
+            .default_value("archive")
+            .long("archive-order"),
+    );
+    #[cfg(feature = "isolate")]
+    let cmd = cmd
+        .arg(
+            Arg::new("isolate-decode")
+                .help("Decode every input in a child process with limited memory, processor time and no network")
+                .takes_value(false)
+                .long("isolate-decode"),
+        )
+        .arg(
+            Arg::new("decode-memory-limit")
+                .help("Memory in MB an isolated decoder may use")
+                .default_value("2048")
+                .long("decode-memory-limit"),
+        )
+        .arg(
+            Arg::new("decode-cpu-limit")
+                .help("Processor seconds an isolated decoder may use")
+                .default_value("60")
+                .long("decode-cpu-limit"),
+        )
+        .subcommand(
+            // decodes one image for `--isolate-decode`, not meant to be run by hand
+            Command::new("decode")
+                .hide(true)
+                .arg(Arg::new("input").required(true).allow_invalid_utf8(true)),
+        );
+    #[cfg(feature = "ocr")]
+    let cmd = cmd
+        .mut_arg("bookmark-source", |a| a.possible_value("ocr-heading"))
+        .arg(
+            Arg::new("ocr")
+                .help("Recognize the text of every page with tesseract and make it searchable")
+                .takes_value(false)
+                .long("ocr"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("auto-rotate-text")
+                .help("Turn images without an EXIF orientation upright by the direction of their text, for scans fed in upside down or sideways")
+                .takes_value(false)
+                .long("auto-rotate-text"),
+        )
+        .arg(
+            Arg::new("ocr-lang")
+                .help("Tesseract languages to recognize, joined with +, e.g. eng+deu")
+                .default_value("eng")
+                .long("ocr-lang"),
+        )
+        .arg(
+            Arg::new("ocr-min-confidence")
+                .help("Flag pages recognized with a lower mean word confidence, in percent")
+                .default_value("60")
+                .long("ocr-min-confidence"),
+        )
+        .arg(
+            Arg::new("export-text")
+                .allow_invalid_utf8(true)
+                .help("Also write the recognized text to a .txt file, or one file per page into a directory")
+                .takes_value(true)
+                .requires("ocr")
+                .conflicts_with("portfolio")
+                .value_hint(ValueHint::AnyPath)
+                .long("export-text"),
+        )
+        .arg(
+            Arg::new("export-hocr")
+                .allow_invalid_utf8(true)
+                .help("Also write the recognized words and their positions to an hOCR file")
+                .takes_value(true)
+                .requires("ocr")
+                .conflicts_with("portfolio")
+                .value_hint(ValueHint::FilePath)
+                .long("export-hocr"),
+        );
+    let cmd = cmd
+        .arg(
+            Arg::new("report")
+                .allow_invalid_utf8(true)
+                .help("Write every warning of the run to a JSON file, with codes for tools to match on")
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("report"),
+        )
+        .arg(
+            Arg::new("save-settings")
+                .allow_invalid_utf8(true)
+                .help("Record every option and input of this run in a TOML file")
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("save-settings"),
+        )
+        .arg(
+            Arg::new("from-settings")
+                .allow_invalid_utf8(true)
+                .help(
+                    "Replay a file written by --save-settings, options given here take precedence",
+                )
+                .takes_value(true)
+                .value_hint(ValueHint::FilePath)
+                .long("from-settings"),
+        )
+        .subcommand(split::command())
+        .subcommand(pages::command())
+        .subcommand(stamp::command())
+        .subcommand(init::command());
+    #[cfg(feature = "server")]
+    let cmd = cmd.subcommand(serve::command());
+    let cmd = cmd
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true);
+
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    let interactive = prompt::wanted(&args);
+    if interactive {
+        match prompt::ask_for_inputs() {
+            Some(answers) => args.extend(answers),
+            None => exit(1),
+        }
+    }
+    let given = cmd.clone().ignore_errors(true).try_get_matches_from(&args);
+    if let Some(path) = given
+        .as_ref()
+        .ok()
+        .and_then(|m| m.value_of_os("from-settings"))
+        .map(Path::new)
+    {
+        let recorded = match settings::read(path) {
+            Ok(recorded) => recorded,
+            Err(e) => {
+                eprintln!("Could not read <from-settings> `{}`: {e}", path.display());
+                exit(1)
+            }
+        };
+        let replayed = settings::to_args(&cmd, &recorded, given.as_ref().unwrap());
+        args.splice(1..1, replayed.into_iter().map(OsString::from));
+    }
+    let matches = cmd.clone().get_matches_from(args);
+    // with the PDF on stdout, everything else goes to stderr
+    let stdout = match matches.value_of_os("out") {
+        Some(out) if out == "-" && matches.subcommand().is_none() => match style::take_stdout() {
+            Ok(stdout) => Some(stdout),
+            Err(e) => {
+                eprintln!("Value <out> `-` cannot be used: {e}");
+                exit(1)
+            }
+        },
+        _ => None,
+    };
+    i18n::init(matches.value_of("lang"));
+    style::init(matches.value_of("color").unwrap());
+    match matches.subcommand() {
+        Some(("split", m)) => return split::run(m),
+        Some(("pages", m)) => return pages::run(m),
+        Some(("stamp", m)) => return stamp::run(m),
+        Some(("init-manifest", m)) => return init::run(m),
+        #[cfg(feature = "server")]
+        Some(("serve", m)) => return serve::run(m),
+        #[cfg(feature = "isolate")]
+        Some(("decode", m)) => return isolate::run(Path::new(m.value_of_os("input").unwrap())),
+        _ => {}
+    }
 
     let dpi = match matches.value_of("dpi").unwrap().parse::<f64>() {
         Ok(dpi) => dpi,
@@ -142,48 +1660,667 @@ fn main() -> Result<(), Box<dyn Error>> {
             exit(1)
         }
     };
+    let scale = matches
+        .value_of("scale")
+        .map(|s| match paper::parse_percentage(s) {
+            Some(percent) => percent,
+            None => {
+                eprintln!("Value <scale> must be a positive percentage");
+                exit(1)
+            }
+        });
 
-    let mut out_path = PathBuf::from(matches.value_of("out").unwrap());
-    if out_path.extension().is_none() {
-        out_path.set_extension("pdf");
+    let durable = matches.is_present("durable") || matches.is_present("delete-sources");
+    let out = matches.value_of_os("out").unwrap();
+    // a dry run writes nothing to clobber, and appending to <out> itself
+    // updates it in place
+    let in_place = matches.value_of_os("append").is_some_and(|existing| {
+        let mut path = PathBuf::from(out);
+        if path.extension().is_none() {
+            path.set_extension("pdf");
+        }
+        same_file(Path::new(existing), &path)
+    });
+    let overwrite = if matches.is_present("force") || matches.is_present("dry-run") || in_place {
+        sink::Overwrite::Force
+    } else if matches.is_present("out-auto") {
+        sink::Overwrite::Number
+    } else {
+        sink::Overwrite::Refuse
+    };
+    let split_pages = match matches.value_of("split-pages").map(str::parse::<usize>) {
+        Some(Ok(pages)) if pages > 0 => Some(pages),
+        Some(_) => {
+            eprintln!("Value <split-pages> must be a positive int");
+            exit(1)
+        }
+        None => None,
+    };
+    let split_size = match matches.value_of("split-size").map(parse_size) {
+        Some(Some(bytes)) if bytes > 0 => Some(bytes),
+        Some(_) => {
+            eprintln!("Value <split-size> could not be parsed as a size");
+            exit(1)
+        }
+        None => None,
+    };
+    let limits = (split_pages.is_some() || split_size.is_some()).then_some(split::Limits {
+        pages: split_pages,
+        bytes: split_size,
+    });
+    // parts are checked for themselves, the whole is never written
+    let whole_overwrite = match limits {
+        Some(_) => sink::Overwrite::Force,
+        None => overwrite,
+    };
+    let mut sink = match sink::open(out, stdout, durable, whole_overwrite) {
+        Ok(sink) => sink,
+        Err(e) => {
+            eprintln!("Value <out> cannot be used: {e}");
+            exit(1)
+        }
+    };
+    if let Some(limits) = limits {
+        let path = match sink.local_path() {
+            Some(path) => path.to_path_buf(),
+            None => {
+                eprintln!("Value <out> must be a file to be split into parts");
+                exit(1)
+            }
+        };
+        sink = match sink::Parts::new(path, durable, limits, overwrite) {
+            Ok(parts) => Box::new(parts),
+            Err(e) => {
+                eprintln!("Value <out> cannot be used: {e}");
+                exit(1)
+            }
+        };
     }
 
-    let p = PDFMerger::new(matches.value_of("pdf-title").unwrap());
-    let mut imgs_iter = if let Some(imgs) = matches.values_of("imgs") {
-        imgs.map(PathBuf::from).collect::<Vec<PathBuf>>()
-    } else if let Some(f) = matches.value_of("dir") {
-        match std::fs::read_dir(f) {
-            Ok(rds) => rds
-                .filter_map(|rd| rd.map(|de| de.path()).ok())
-                .collect::<Vec<PathBuf>>(),
+    #[cfg(feature = "ffmpeg")]
+    let every = match matches.value_of("every").map(parse_duration) {
+        Some(Some(d)) if !d.is_zero() => Some(d),
+        Some(_) => {
+            eprintln!("Value <every> could not be parsed as a duration");
+            exit(1)
+        }
+        None => None,
+    };
+
+    match matches.value_of("timeout").map(parse_duration) {
+        Some(Some(timeout)) if !timeout.is_zero() => {
+            std::thread::spawn(move || {
+                std::thread::sleep(timeout);
+                eprintln!("\nGave up after the --timeout of {timeout:?}");
+                exit(1)
+            });
+        }
+        Some(_) => {
+            eprintln!("Value <timeout> could not be parsed as a duration");
+            exit(1)
+        }
+        None => {}
+    }
+    let mut manifest_vars = Vec::new();
+    for var in matches.values_of("manifest-var").into_iter().flatten() {
+        match var.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                manifest_vars.push((name.trim().to_owned(), value.to_owned()))
+            }
+            _ => {
+                eprintln!("Value <manifest-var> must look like name=value");
+                exit(1)
+            }
+        }
+    }
+    let manifest = match matches.value_of_os("manifest").map(Path::new) {
+        Some(path) => match manifest::read(path, &manifest_vars) {
+            Ok(entries) => entries,
             Err(e) => {
-                eprintln!("Could not read <dir> `{f}`: {e}");
+                eprintln!("Could not read <manifest> `{}`: {e}", path.display());
                 exit(1)
             }
+        },
+        None => Vec::new(),
+    };
+    let settled = match matches.value_of("settle").map(parse_duration) {
+        Some(Some(quiet)) => {
+            let dir = Path::new(matches.value_of_os("dir").unwrap());
+            println!(
+                "Waiting for `{}` to stay unchanged for {quiet:?}",
+                dir.display()
+            );
+            match settle::wait(dir, quiet) {
+                Ok(snapshot) => Some((dir, snapshot)),
+                Err(e) => {
+                    eprintln!("Could not read <dir> `{}`: {e}", dir.display());
+                    exit(1)
+                }
+            }
         }
+        Some(None) => {
+            eprintln!("Value <settle> could not be parsed as a duration");
+            exit(1)
+        }
+        None => None,
+    };
+    let idle_timeout = match matches.value_of("idle-timeout").map(parse_duration) {
+        Some(Some(idle)) if !idle.is_zero() => Some(idle),
+        Some(_) => {
+            eprintln!("Value <idle-timeout> could not be parsed as a duration");
+            exit(1)
+        }
+        None => None,
+    };
+    for id in ["recursive", "include", "exclude"] {
+        if matches.is_present(id) && !matches.is_present("dir") {
+            eprintln!("--{id} needs --dir");
+            exit(1)
+        }
+    }
+    let filter = walk::Filter::new(
+        matches.values_of("include").into_iter().flatten(),
+        matches.values_of("exclude").into_iter().flatten(),
+    );
+    let nul = matches.is_present("null");
+    let mut imgs_iter = if let Some(imgs) = matches.values_of_os("imgs") {
+        let mut listed = Vec::new();
+        for img in imgs {
+            if img != "-" {
+                listed.push(PathBuf::from(img));
+                continue;
+            }
+            match list::read(std::io::stdin().lock(), nul) {
+                Ok(paths) => listed.extend(paths),
+                Err(e) => {
+                    eprintln!("Could not read the list of <imgs> from stdin: {e}");
+                    exit(1)
+                }
+            }
+        }
+        listed
+    } else if let Some(path) = matches.value_of_os("from-file").map(Path::new) {
+        match File::open(path).and_then(|file| list::read(file, nul)) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("Could not read <from-file> `{}`: {e}", path.display());
+                exit(1)
+            }
+        }
+    } else if let Some(f) = matches.value_of_os("dir").map(Path::new) {
+        match walk::files(f, matches.is_present("recursive"), &filter) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Could not read <dir> `{}`: {e}", f.display());
+                exit(1)
+            }
+        }
+    } else if matches.is_present("manifest") {
+        manifest.iter().map(|e| e.file.clone()).collect()
     } else {
         unreachable!();
     };
-    if matches.is_present("auto-sort") {
-        imgs_iter.sort();
+    let cover_size = matches.value_of("cover-size").map(|size| {
+        if !matches.is_present("cover") && !matches.is_present("back-cover") {
+            eprintln!("Value <cover-size> needs --cover or --back-cover");
+            exit(1)
+        }
+        match paper::parse_paper_size(size) {
+            Some(page) => page,
+            None => {
+                eprintln!("Value <cover-size> is not a known paper size or WxHmm");
+                exit(1)
+            }
+        }
+    });
+    let covers = ["cover", "back-cover"].map(|id| {
+        matches.value_of_os(id).map(|path| {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                eprintln!("Value <{id}> `{}` is not a file", path.display());
+                exit(1)
+            }
+            path
+        })
+    });
+    // covers found among the inputs are only placed once
+    imgs_iter.retain(|input| !covers.iter().flatten().any(|cover| same_file(input, cover)));
+    let fold_outs = matches
+        .values_of_os("fold-out")
+        .map(|f| f.map(PathBuf::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let sort_by = match matches.value_of("sort-by") {
+        Some(sort_by) => Some(sort_by),
+        None => matches.is_present("auto-sort").then_some("name"),
+    };
+    let mut ambiguities = Vec::new();
+    if sort_by == Some("similarity") {
+        let (order, found) = similarity::sort(imgs_iter);
+        imgs_iter = order;
+        ambiguities = found;
+    } else if let Some(key) = sort_by {
+        order::sort(&mut imgs_iter, key);
+    }
+    // a cover given on its own leaves the inputs as they are
+    if matches.is_present("detect-cover") && covers[0].is_none() {
+        cover::move_first(&mut imgs_iter);
+    }
+    let frame_step = match matches.value_of("frame-step").map(str::parse::<usize>) {
+        Some(Ok(step)) if step > 0 => step,
+        Some(_) => {
+            eprintln!("Value <frame-step> must be a positive int");
+            exit(1)
+        }
+        None => 1,
+    };
+    let sample = match matches.value_of("sample").map(str::parse::<usize>) {
+        Some(Ok(count)) if count > 0 => Some(count),
+        Some(_) => {
+            eprintln!("Value <sample> must be a positive int");
+            exit(1)
+        }
+        None => None,
+    };
+    if let Some(count) = sample {
+        let total = imgs_iter.len();
+        imgs_iter = order::sample(imgs_iter, count);
+        println!("Drafting from {} of {total} input(s)", imgs_iter.len());
+    }
+    let unsupported_mode = matches.value_of("unsupported").unwrap();
+    if matches.is_present("dry-run") {
+        let portfolio = matches
+            .value_of_os("dir")
+            .map(Path::new)
+            .filter(|_| matches.is_present("portfolio"));
+        match portfolio {
+            Some(dir) => {
+                let groups = match portfolio_groups(dir, sort_by, &filter) {
+                    Ok(groups) => groups,
+                    Err(e) => {
+                        eprintln!("Could not read <dir> `{}`: {e}", dir.display());
+                        exit(1)
+                    }
+                };
+                for (name, mut inputs) in groups {
+                    if sort_by == Some("similarity") {
+                        inputs = similarity::sort(inputs).0;
+                    }
+                    if let Some(count) = sample {
+                        inputs = order::sample(inputs, count);
+                    }
+                    println!("Document `{name}.pdf`:");
+                    plan::print(&[None, None], &inputs, &fold_outs, unsupported_mode);
+                }
+            }
+            None => plan::print(&covers, &imgs_iter, &fold_outs, unsupported_mode),
+        }
+        return Ok(());
     }
 
-    let tic = std::time::Instant::now();
-    let imgs_len = imgs_iter.len();
-    for (i, n) in imgs_iter.iter().enumerate() {
-        if let Err(e) = p.append_image_page(n, dpi, "", (width, height)) {
-            println!("Skipping `{}` because: {}", n.display(), e);
+    let progress = progress::Bar::new(matches.is_present("quiet"));
+    let opts = InputOptions {
+        dpi,
+        wh: (width, height),
+        scale,
+        unsupported_mode,
+        manifest: &manifest,
+        fold_outs: &fold_outs,
+        progress: &progress,
+        #[cfg(feature = "archive")]
+        archive_by_name: matches.value_of("archive-order") == Some("name"),
+        #[cfg(feature = "archive")]
+        detect_cover: matches.is_present("detect-cover"),
+        frame_step: matches.is_present("expand-frames").then_some(frame_step),
+        #[cfg(feature = "ffmpeg")]
+        every,
+    };
+    let projected = space::projected_size(&imgs_iter, (width, height), scale);
+    if let Some(out_path) = sink.local_path() {
+        if let Err(e) = space::check(out_path, projected) {
+            eprintln!("Not enough space for `{}`: {e}", out_path.display());
+            exit(1)
         }
-        print!("Processing image {}/{}\r", i + 1, imgs_len);
-        stdout().flush().unwrap();
     }
+    let title = matches.value_of("pdf-title").unwrap();
+    let mut p = merger_from_matches(&matches, title, &opts);
+    let tic = std::time::Instant::now();
+    let (mut processed, mut warnings) = (Vec::new(), Vec::new());
+    let unsupported = if let Some(dir) = matches
+        .value_of_os("dir")
+        .map(Path::new)
+        .filter(|_| matches.is_present("portfolio"))
+    {
+        let groups = match portfolio_groups(dir, sort_by, &filter) {
+            Ok(groups) => groups,
+            Err(e) => {
+                eprintln!("Could not read <dir> `{}`: {e}", dir.display());
+                exit(1)
+            }
+        };
+        imgs_iter.clear();
+        let mut unsupported = Vec::new();
+        for (name, mut inputs) in groups {
+            if sort_by == Some("similarity") {
+                let (order, found) = similarity::sort(inputs);
+                inputs = order;
+                ambiguities.extend(found);
+            }
+            if let Some(count) = sample {
+                inputs = order::sample(inputs, count);
+            }
+            let mut member = merger_from_matches(&matches, &name, &opts);
+            unsupported.extend(add_inputs(&mut member, &inputs, &opts)?);
+            member.finish();
+            let pages = member.page_count();
+            processed.extend_from_slice(member.processed());
+            warnings.extend_from_slice(member.warnings());
+            let mut pdf = Vec::new();
+            member.save(&mut pdf)?;
+            p.add_portfolio_member(portfolio::Member {
+                file_name: format!("{name}.pdf"),
+                description: format!("{pages} page(s) from {} input(s)", inputs.len()),
+                pdf,
+            });
+            imgs_iter.extend(inputs);
+        }
+        p.append_portfolio_cover()?;
+        unsupported
+    } else {
+        let [cover, back_cover] = &covers;
+        if let Some(cover) = cover {
+            add_cover(&mut p, cover, cover_size);
+        }
+        let mut unsupported = add_inputs(&mut p, &imgs_iter, &opts)?;
+        if matches.is_present("watch") {
+            let dir = Path::new(matches.value_of_os("dir").unwrap());
+            let recursive = matches.is_present("recursive");
+            let mut watcher = watch::Watcher::new(dir, recursive, &filter, &imgs_iter);
+            match idle_timeout {
+                Some(idle) => println!(
+                    "Watching `{}` for new images until none arrive for {idle:?}, or Ctrl-C",
+                    dir.display()
+                ),
+                None => println!(
+                    "Watching `{}` for new images, press Ctrl-C to finish",
+                    dir.display()
+                ),
+            }
+            watch::run(&mut watcher, idle_timeout, |input| {
+                let pages = p.page_count();
+                unsupported.extend(add_inputs(&mut p, std::slice::from_ref(&input), &opts)?);
+                if p.page_count() > pages {
+                    println!("Added `{}` as page {}", input.display(), p.page_count());
+                }
+                imgs_iter.push(input);
+                Ok(())
+            })?;
+        }
+        if let Some(back_cover) = back_cover {
+            // pages still being collected go before the back cover
+            p.finish();
+            add_cover(&mut p, back_cover, cover_size);
+        }
+        unsupported
+    };
 
-    p.save(&mut File::create(&out_path)?)?;
+    p.finish();
+    let (low_dpi, min_dpi) = (p.low_resolution_pages().to_vec(), p.min_dpi());
+    if matches.is_present("min-dpi") && !low_dpi.is_empty() {
+        let args: [(&str, &dyn std::fmt::Display); 2] =
+            [("count", &low_dpi.len()), ("dpi", &min_dpi)];
+        eprintln!("{}", style::error(&i18n::t("below-min-dpi", &args)));
+        let width = page_width(low_dpi.iter().map(|(page, _)| *page));
+        for (page, dpi) in low_dpi {
+            let (page, dpi) = (format!("{page:>width$}"), format!("{dpi:.0}"));
+            eprintln!("{}", i18n::t("page-dpi", &[("page", &page), ("dpi", &dpi)]));
+        }
+        exit(1)
+    }
+    let ink_coverage = p.ink_coverage().map(<[_]>::to_vec);
+    #[cfg(feature = "ocr")]
+    let uncertain_ocr = match p.ocr_options() {
+        Some(opts) => p
+            .recognized_text()
+            .iter()
+            .filter_map(|(page, text)| {
+                let confidence = text.confidence()?;
+                (confidence < opts.min_confidence).then(|| {
+                    let mut notes = text.script.iter().cloned().collect::<Vec<_>>();
+                    if text.rotate != 0 {
+                        notes.push(format!("turned {}°", text.rotate));
+                    }
+                    (page + 1, confidence, notes)
+                })
+            })
+            .collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+    #[cfg(feature = "ocr")]
+    if let Some(path) = matches.value_of_os("export-text") {
+        ocr::export_text(Path::new(path), p.page_count(), p.recognized_text())?;
+    }
+    #[cfg(feature = "ocr")]
+    if let Some(path) = matches.value_of_os("export-hocr") {
+        ocr::export_hocr(Path::new(path), p.recognized_text())?;
+    }
+    if let Some((dir, snapshot)) = &settled {
+        if settle::Snapshot::take(dir).ok().as_ref() != Some(snapshot) {
+            let message = format!(
+                "`{}` changed during the conversion, run again once it has settled",
+                dir.display()
+            );
+            eprintln!("{}", style::error(&message));
+            exit(1)
+        }
+    }
+    processed.extend_from_slice(p.processed());
+    warnings.extend_from_slice(p.warnings());
+    // remote destinations get the PDF as it is written, unless it is needed
+    // again afterwards
+    let mut pdf = Vec::new();
+    let streamed = {
+        let name = sink.name();
+        let stream = match matches.is_present("preflight-print") {
+            true => None,
+            false => sink.stream().and_then(Result::ok),
+        };
+        match stream {
+            Some(mut stream) => {
+                let streamed = p
+                    .save(&mut stream)
+                    .map_err(|e| e.to_string())
+                    .and_then(|()| stream.finish().map_err(|e| e.to_string()));
+                if let Err(e) = streamed {
+                    let args: [(&str, &dyn std::fmt::Display); 2] =
+                        [("file", &name), ("reason", &e)];
+                    eprintln!("{}", style::error(&i18n::t("could-not-write", &args)));
+                    eprintln!("The PDF went out as it was written, so there is nothing to salvage");
+                    exit(1)
+                }
+                true
+            }
+            None => {
+                p.save(&mut pdf)?;
+                false
+            }
+        }
+    };
+    let written = match streamed {
+        true => Ok(()),
+        false => sink.store(&pdf),
+    };
+    let salvaged = match written {
+        Ok(()) => false,
+        Err(e) => {
+            let args: [(&str, &dyn std::fmt::Display); 2] =
+                [("file", &sink.name()), ("reason", &e)];
+            eprintln!("{}", style::error(&i18n::t("could-not-write", &args)));
+            let fallback = match matches.value_of_os("fallback-out") {
+                Some(path) => PathBuf::from(path),
+                None => std::env::temp_dir().join(
+                    sink.local_path()
+                        .and_then(Path::file_name)
+                        .unwrap_or(OsStr::new("output.pdf")),
+                ),
+            };
+            let mut salvage = sink::LocalFile::new(fallback.clone(), durable);
+            if let Err(e) = salvage.store(&pdf) {
+                let message = format!(
+                    "Could not salvage the PDF to `{}` either: {e}",
+                    fallback.display()
+                );
+                eprintln!("{}", style::error(&message));
+                exit(1)
+            }
+            eprintln!("{}", i18n::t("salvaged", &[("file", &fallback.display())]));
+            sink = Box::new(salvage);
+            true
+        }
+    };
 
-    println!(
-        "Successfully created the PDF `{}` in {:.2}s",
-        out_path.display(),
-        tic.elapsed().as_secs_f32()
-    );
+    let seconds = format!("{:.2}", tic.elapsed().as_secs_f32());
+    let args: [(&str, &dyn std::fmt::Display); 2] = [("file", &sink.name()), ("seconds", &seconds)];
+    println!("{}", style::success(&i18n::t("created", &args)));
+    if let Some(path) = matches.value_of_os("save-settings").map(Path::new) {
+        let recorded = settings::from_matches(&cmd, &matches, &imgs_iter);
+        if let Err(e) = settings::write(path, &recorded) {
+            let message = format!("Could not save settings to `{}`: {e}", path.display());
+            println!("{}", style::warning(&message));
+        }
+    }
+    if let Some(path) = matches.value_of_os("report").map(Path::new) {
+        if let Err(e) = report::write(path, &warnings) {
+            let message = format!("Could not write the report to `{}`: {e}", path.display());
+            println!("{}", style::warning(&message));
+        }
+    }
+    // skipped inputs were told about as they came, the rest are listed below
+    let listed = [
+        WarningCode::SkippedInput,
+        WarningCode::UnsupportedFormat,
+        WarningCode::LowResolution,
+    ];
+    for warning in warnings.iter().filter(|w| !listed.contains(&w.code)) {
+        println!("{}", style::warning(&warning.message));
+    }
+    if !unsupported.is_empty() {
+        let message = i18n::t("unsupported", &[("count", &unsupported.len())]);
+        println!("{}", style::warning(&message));
+        for n in unsupported {
+            println!("  {}", n.display());
+        }
+    }
+    if !low_dpi.is_empty() {
+        let args: [(&str, &dyn std::fmt::Display); 2] =
+            [("count", &low_dpi.len()), ("dpi", &min_dpi)];
+        println!("{}", style::warning(&i18n::t("low-dpi", &args)));
+        let width = page_width(low_dpi.iter().map(|(page, _)| *page));
+        for (page, dpi) in low_dpi {
+            let (page, dpi) = (format!("{page:>width$}"), format!("{dpi:.0}"));
+            println!("{}", i18n::t("page-dpi", &[("page", &page), ("dpi", &dpi)]));
+        }
+    }
+    if !ambiguities.is_empty() {
+        let message = format!(
+            "{} uncertain step(s) in the similarity order, worth a manual look:",
+            ambiguities.len()
+        );
+        println!("{}", style::warning(&message));
+        for a in ambiguities {
+            println!(
+                "  after `{}`: `{}` or `{}`",
+                a.after.display(),
+                a.chosen.display(),
+                a.alternative.display()
+            );
+        }
+    }
+    if let Some(pages) = ink_coverage {
+        print_ink_report(&pages);
+    }
+    #[cfg(feature = "ocr")]
+    if !uncertain_ocr.is_empty() {
+        let message = format!(
+            "{} page(s) were recognized with low confidence, worth a manual look:",
+            uncertain_ocr.len()
+        );
+        println!("{}", style::warning(&message));
+        let width = page_width(uncertain_ocr.iter().map(|(page, _, _)| *page));
+        for (page, confidence, notes) in uncertain_ocr {
+            let page = format!("{page:>width$}");
+            if notes.is_empty() {
+                println!("  page {page}: {confidence:.0}%");
+            } else {
+                println!("  page {page}: {confidence:.0}% ({})", notes.join(", "));
+            }
+        }
+    }
+    if matches.is_present("preflight-print") {
+        let written = match sink.local_path() {
+            Some(out_path) => lopdf::Document::load(out_path)?,
+            None => lopdf::Document::load_mem(&pdf)?,
+        };
+        let violations = preflight::preflight(&written)?;
+        if violations.is_empty() {
+            println!("{}", style::success("Preflight passed"));
+        } else {
+            let message = format!("Preflight found {} problem(s):", violations.len());
+            println!("{}", style::warning(&message));
+            let width = page_width(violations.iter().map(|v| v.page as usize));
+            for v in violations {
+                println!("  page {:>width$}: {}", v.page, v.message);
+            }
+        }
+    }
+    let cleanup = match (
+        matches.value_of_os("move-processed"),
+        matches.value_of_os("copy-processed"),
+    ) {
+        (Some(dir), _) => Some(sources::Cleanup::Move(dir.into())),
+        (_, Some(dir)) => Some(sources::Cleanup::Copy(dir.into())),
+        _ if matches.is_present("delete-sources") => {
+            let dir = matches.value_of_os("dir").unwrap().into();
+            if matches.is_present("use-trash") {
+                Some(sources::Cleanup::Trash(dir))
+            } else {
+                Some(sources::Cleanup::Delete(dir))
+            }
+        }
+        _ => None,
+    };
+    // nothing is deleted unless the PDF on disk is exactly what was written
+    let verified = !matches.is_present("delete-sources")
+        || sink
+            .local_path()
+            .is_some_and(|out_path| std::fs::read(out_path).is_ok_and(|written| written == pdf));
+    if !verified {
+        let message = "The written PDF could not be verified, so no inputs were deleted";
+        println!("{}", style::warning(message));
+    }
+    // inputs stay where they are when the PDF did not end up at <out>
+    if let Some(cleanup) = cleanup.filter(|_| !salvaged && verified) {
+        let failed = sources::clean_up(&processed, &cleanup);
+        let (verb, done, to, dir) = match &cleanup {
+            sources::Cleanup::Move(dir) => ("move", "Moved", "to", dir),
+            sources::Cleanup::Copy(dir) => ("copy", "Copied", "to", dir),
+            sources::Cleanup::Delete(dir) => ("delete", "Deleted", "from", dir),
+            sources::Cleanup::Trash(dir) => ("trash", "Trashed", "from", dir),
+        };
+        println!(
+            "{done} {} processed input(s) {to} `{}`",
+            processed.len() - failed.len(),
+            dir.display()
+        );
+        for (input, e) in failed {
+            let message = format!("Could not {verb} `{}`: {e}", input.display());
+            println!("{}", style::warning(&message));
+        }
+    }
+    if interactive {
+        prompt::wait_before_closing();
+    }
+    if salvaged {
+        exit(1)
+    }
     Ok(())
 }
@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::glob::glob_match;
+use crate::toml::{parse_value, Value};
+use crate::{paper, ColorMode, Link};
+
+const BAD_SCALE: &str = "`scale` must be a positive percentage such as \"50%\"";
+const BAD_COLOR_MODE: &str =
+    "`color_mode` must be \"keep\", \"gray\" or \"bilevel\", the last optionally followed by a threshold from 0 to 255";
+const BAD_IF: &str = "`if` must be true, false or a condition such as \"${mode} == print\"";
+/// Threshold of a bilevel color mode that does not give one, as on the
+/// command line
+const BILEVEL_THRESHOLD: u8 = 128;
+
+/// One input of a manifest and what applies to it only
+#[derive(Default)]
+pub struct Entry {
+    pub file: PathBuf,
+    pub links: Vec<Link>,
+    /// Percentage of its size this input is scaled to
+    pub scale: Option<f64>,
+    /// Keeps its own page size when the other pages get a fixed one
+    pub fold_out: bool,
+    /// Left, top, width and height in pixels of areas kept sharp
+    pub regions: Vec<[u32; 4]>,
+    /// Embedded in this color mode rather than that of the other inputs
+    pub color_mode: Option<ColorMode>,
+}
+
+/// Settings a `[[when]]` table gives the inputs it matches
+struct Rule {
+    /// Pattern the inputs are matched against, lowercased
+    files: Option<String>,
+    wanted: bool,
+    settings: Entry,
+}
+
+/// The table being read, with the line it starts on
+enum Table {
+    Vars,
+    Input {
+        start: usize,
+        file: Option<PathBuf>,
+        entry: Entry,
+        wanted: bool,
+    },
+    Include {
+        start: usize,
+        file: Option<PathBuf>,
+        wanted: bool,
+    },
+    When(Rule),
+}
+
+/// Where a manifest is read: its directory, the variables set from outside
+/// it and the manifests being read that include it
+struct Context<'a> {
+    base: &'a Path,
+    given: &'a BTreeMap<String, String>,
+    reading: Vec<PathBuf>,
+}
+
+/// Reads a manifest listing the inputs in order as `[[input]]` tables, each
+/// with a `file` relative to the manifest and optional `links` such as
+/// `"40,120,300,60 https://example.com"`, `scale = "50%"` to scale it
+/// differently from the other inputs, `fold_out = true` to keep the page
+/// size of an oversized input, `regions` such as `"600,900,400,120"` for a
+/// signature or photo that is not to be compressed with the rest and
+/// `color_mode` such as `"bilevel 150"` to embed it unlike the rest.
+///
+/// A `[vars]` table sets variables that the values after it use as
+/// `${name}`, unless `vars` sets them already. `[[include]]` tables put the
+/// inputs of the manifest in their `file` in their place. `[[when]]` tables
+/// give the inputs whose name, or path when it holds a `/`, matches their
+/// `files` pattern their `scale` and `color_mode` where the inputs set none,
+/// such as `color_mode = "bilevel"` for `files = "*.tif"`, and add their
+/// `links`, `regions` and `fold_out` to those of the inputs. Inputs,
+/// includes and rules with an `if` such as `"${edition} == print"` or
+/// `"${draft} != true"` are left out when it does not hold.
+///
+/// The same TOML subset as the settings files is understood.
+pub fn read(path: &Path, vars: &[(String, String)]) -> Result<Vec<Entry>, String> {
+    let toml = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let given = vars.iter().cloned().collect::<BTreeMap<_, _>>();
+    let context = Context {
+        base: path.parent().unwrap_or(Path::new("")),
+        given: &given,
+        reading: vec![fs::canonicalize(path).unwrap_or(path.to_path_buf())],
+    };
+    parse_in(&toml, &context, given.clone())
+}
+
+/// Parses the text of a manifest, with files relative to `base`
+pub fn parse(toml: &str, base: &Path) -> Result<Vec<Entry>, String> {
+    let context = Context {
+        base,
+        given: &BTreeMap::new(),
+        reading: Vec::new(),
+    };
+    parse_in(toml, &context, BTreeMap::new())
+}
+
+fn parse_in(
+    toml: &str,
+    context: &Context,
+    mut vars: BTreeMap<String, String>,
+) -> Result<Vec<Entry>, String> {
+    let base = context.base;
+    let mut entries = Vec::new();
+    let mut rules = Vec::new();
+    let mut table = None;
+
+    for (n, line) in toml.lines().enumerate() {
+        let (n, line) = (n + 1, line.trim());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let next = match line {
+            "[vars]" => Some(Table::Vars),
+            "[[input]]" => Some(Table::Input {
+                start: n,
+                file: None,
+                entry: Entry::default(),
+                wanted: true,
+            }),
+            "[[include]]" => Some(Table::Include {
+                start: n,
+                file: None,
+                wanted: true,
+            }),
+            "[[when]]" => Some(Table::When(Rule {
+                files: None,
+                wanted: true,
+                settings: Entry::default(),
+            })),
+            _ => None,
+        };
+        if let Some(next) = next {
+            close(
+                table.replace(next),
+                context,
+                &vars,
+                &mut entries,
+                &mut rules,
+            )?;
+            continue;
+        }
+        let invalid = || format!("line {n}: cannot read `{line}`");
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let key = key.trim();
+        let value = parse_value(value.trim()).ok_or_else(invalid)?;
+        let value = substitute(value, &vars).map_err(|e| format!("line {n}: {e}"))?;
+        let at = |e: String| format!("line {n}: {e}");
+        match &mut table {
+            None => return Err(format!("line {n}: `{key}` outside of an [[input]]")),
+            Some(Table::Vars) => {
+                let value = match value {
+                    Value::Single(s) => s,
+                    Value::Flag(b) => b.to_string(),
+                    Value::List(_) => return Err(at(format!("variable `{key}` must be a string"))),
+                };
+                if !context.given.contains_key(key) {
+                    vars.insert(key.to_owned(), value);
+                }
+            }
+            Some(Table::Input {
+                file,
+                entry,
+                wanted,
+                ..
+            }) => match (key, value) {
+                ("file", Value::Single(f)) => *file = Some(base.join(f)),
+                ("file", _) => return Err(at("`file` must be a string".to_owned())),
+                ("if", value) => *wanted = condition(value).ok_or_else(|| at(BAD_IF.to_owned()))?,
+                (key, value) => set(entry, key, value).map_err(at)?,
+            },
+            Some(Table::Include { file, wanted, .. }) => match (key, value) {
+                ("file", Value::Single(f)) => *file = Some(base.join(f)),
+                ("file", _) => return Err(at("`file` must be a string".to_owned())),
+                ("if", value) => *wanted = condition(value).ok_or_else(|| at(BAD_IF.to_owned()))?,
+                (key, _) => return Err(at(format!("unknown key `{key}` in an [[include]]"))),
+            },
+            Some(Table::When(rule)) => match (key, value) {
+                ("files", Value::Single(pattern)) => rule.files = Some(pattern.to_lowercase()),
+                ("files", _) => return Err(at("`files` must be a string".to_owned())),
+                ("file", _) => {
+                    return Err(at(
+                        "a [[when]] matches `files` rather than naming a `file`".to_owned()
+                    ))
+                }
+                ("if", value) => {
+                    rule.wanted = condition(value).ok_or_else(|| at(BAD_IF.to_owned()))?
+                }
+                (key, value) => set(&mut rule.settings, key, value).map_err(at)?,
+            },
+        }
+    }
+    close(table, context, &vars, &mut entries, &mut rules)?;
+
+    // rules apply to every input of the manifest, wherever they stand
+    for rule in rules.iter().filter(|r| r.wanted) {
+        for entry in &mut entries {
+            if rule.matches(&entry.file, base) {
+                entry.fill_in(&rule.settings);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Adds what `table` read to the inputs or rules, reading the manifest it
+/// includes
+fn close(
+    table: Option<Table>,
+    context: &Context,
+    vars: &BTreeMap<String, String>,
+    entries: &mut Vec<Entry>,
+    rules: &mut Vec<Rule>,
+) -> Result<(), String> {
+    match table {
+        None | Some(Table::Vars) => {}
+        Some(Table::Input {
+            start, file: None, ..
+        }) => return Err(format!("line {start}: [[input]] without a file")),
+        Some(Table::Input {
+            file: Some(file),
+            entry,
+            wanted,
+            ..
+        }) => {
+            if wanted {
+                entries.push(Entry { file, ..entry });
+            }
+        }
+        Some(Table::Include {
+            start, file: None, ..
+        }) => return Err(format!("line {start}: [[include]] without a file")),
+        Some(Table::Include {
+            start,
+            file: Some(file),
+            wanted,
+        }) => {
+            if !wanted {
+                return Ok(());
+            }
+            let canonical = fs::canonicalize(&file).unwrap_or(file.clone());
+            if context.reading.contains(&canonical) {
+                return Err(format!(
+                    "line {start}: `{}` includes itself",
+                    file.display()
+                ));
+            }
+            let toml = fs::read_to_string(&file)
+                .map_err(|e| format!("line {start}: cannot read `{}`: {e}", file.display()))?;
+            let mut reading = context.reading.clone();
+            reading.push(canonical);
+            let included = Context {
+                base: file.parent().unwrap_or(Path::new("")),
+                given: context.given,
+                reading,
+            };
+            let found = parse_in(&toml, &included, vars.clone())
+                .map_err(|e| format!("{}: {e}", file.display()))?;
+            entries.extend(found);
+        }
+        Some(Table::When(rule)) => rules.push(rule),
+    }
+    Ok(())
+}
+
+/// Sets `key` of an input, or of the inputs a rule matches
+fn set(entry: &mut Entry, key: &str, value: Value) -> Result<(), String> {
+    match (key, value) {
+        ("links", Value::List(specs)) => {
+            for spec in specs {
+                entry.links.push(parse_link(&spec)?);
+            }
+        }
+        ("fold_out", Value::Flag(b)) => entry.fold_out = b,
+        ("regions", Value::List(specs)) => {
+            for spec in specs {
+                let invalid = || format!("region `{spec}` must look like X,Y,WIDTH,HEIGHT");
+                entry.regions.push(parse_rect(&spec).ok_or_else(invalid)?);
+            }
+        }
+        ("scale", Value::Single(s)) => {
+            let percent = paper::parse_percentage(&s);
+            entry.scale = Some(percent.ok_or(BAD_SCALE)?);
+        }
+        ("color_mode", Value::Single(s)) => {
+            entry.color_mode = Some(parse_color_mode(&s).ok_or(BAD_COLOR_MODE)?);
+        }
+        ("links", _) => return Err("`links` must be a list of strings".to_owned()),
+        ("fold_out", _) => return Err("`fold_out` must be true or false".to_owned()),
+        ("regions", _) => return Err("`regions` must be a list of strings".to_owned()),
+        ("scale", _) => return Err(BAD_SCALE.to_owned()),
+        ("color_mode", _) => return Err(BAD_COLOR_MODE.to_owned()),
+        (key, _) => return Err(format!("unknown key `{key}`")),
+    }
+    Ok(())
+}
+
+impl Entry {
+    /// Takes the scale and color mode of `rule` where this input sets none,
+    /// and adds its links, regions and fold out to those of the input, which
+    /// cannot turn them off
+    fn fill_in(&mut self, rule: &Entry) {
+        self.links.extend(rule.links.iter().cloned());
+        self.scale = self.scale.or(rule.scale);
+        self.fold_out |= rule.fold_out;
+        self.regions.extend(&rule.regions);
+        self.color_mode = self.color_mode.or(rule.color_mode);
+    }
+}
+
+impl Rule {
+    /// Whether `file`, of a manifest in `base`, is one the rule applies to.
+    /// Case is ignored, as in `--include`.
+    fn matches(&self, file: &Path, base: &Path) -> bool {
+        let pattern = match &self.files {
+            Some(pattern) => pattern,
+            None => return true,
+        };
+        let path = file
+            .strip_prefix(base)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .replace('\\', "/")
+            .to_lowercase();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        glob_match(
+            pattern,
+            if pattern.contains('/') {
+                path.as_str()
+            } else {
+                name
+            },
+        )
+    }
+}
+
+/// `value` with every `${name}` in it replaced by that variable
+fn substitute(value: Value, vars: &BTreeMap<String, String>) -> Result<Value, String> {
+    let replace = |s: String| {
+        let mut replaced = String::new();
+        let mut rest = s.as_str();
+        while let Some(start) = rest.find("${") {
+            replaced.push_str(&rest[..start]);
+            let len = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("`{s}` has a `${{` that is not closed"))?;
+            let name = rest[start + 2..start + len].trim();
+            let var = vars
+                .get(name)
+                .ok_or_else(|| format!("unknown variable `{name}`"))?;
+            replaced.push_str(var);
+            rest = &rest[start + len + 1..];
+        }
+        replaced.push_str(rest);
+        Ok::<_, String>(replaced)
+    };
+    Ok(match value {
+        Value::Flag(b) => Value::Flag(b),
+        Value::Single(s) => Value::Single(replace(s)?),
+        Value::List(list) => Value::List(list.into_iter().map(replace).collect::<Result<_, _>>()?),
+    })
+}
+
+/// Whether the `if` of a table holds: `A == B`, `A != B`, or a value that is
+/// neither empty, `false` nor `0`
+fn condition(value: Value) -> Option<bool> {
+    let cond = match value {
+        Value::Flag(b) => return Some(b),
+        Value::Single(s) => s,
+        Value::List(_) => return None,
+    };
+    Some(if let Some((a, b)) = cond.split_once("!=") {
+        a.trim() != b.trim()
+    } else if let Some((a, b)) = cond.split_once("==") {
+        a.trim() == b.trim()
+    } else {
+        !matches!(cond.trim(), "" | "false" | "0")
+    })
+}
+
+/// Parses `keep`, `gray` or `bilevel` with an optional threshold
+fn parse_color_mode(spec: &str) -> Option<ColorMode> {
+    let mut words = spec.split_whitespace();
+    let mode = match (words.next()?, words.next()) {
+        ("keep", None) => ColorMode::Keep,
+        ("gray", None) => ColorMode::Gray,
+        ("bilevel", threshold) => ColorMode::Bilevel {
+            threshold: threshold.map_or(Some(BILEVEL_THRESHOLD), |t| t.parse().ok())?,
+        },
+        _ => return None,
+    };
+    words.next().is_none().then_some(mode)
+}
+
+/// Parses `X,Y,W,H URL`
+fn parse_link(spec: &str) -> Result<Link, String> {
+    let invalid = || format!("link `{spec}` must look like X,Y,WIDTH,HEIGHT URL");
+    let (rect, url) = spec
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    Ok(Link {
+        url: url.trim().to_owned(),
+        rect: parse_rect(rect).ok_or_else(invalid)?,
+    })
+}
+
+/// Parses `X,Y,W,H`, which must not be empty
+fn parse_rect(spec: &str) -> Option<[u32; 4]> {
+    let rect = spec
+        .split(',')
+        .map(|v| v.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    match rect[..] {
+        [x, y, w, h] if w > 0 && h > 0 => Some([x, y, w, h]),
+        _ => None,
+    }
+}
@@ -0,0 +1,186 @@
+const IMAGE_DESCRIPTION: u16 = 0x010e;
+const DATE_TIME: u16 = 0x0132;
+/// Windows Explorer's title field, UTF-16LE in a BYTE array
+const XP_TITLE: u16 = 0x9c9b;
+/// Offset of the IFD holding the camera settings
+const EXIF_IFD: u16 = 0x8769;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+const ORIENTATION: u16 = 0x0112;
+
+/// The EXIF ImageDescription, or the Windows title if there is none, of a
+/// JPEG, PNG or TIFF file
+pub fn exif_title(file: &[u8]) -> Option<String> {
+    let tiff = Tiff::new(exif_block(file)?)?;
+    let ifd = tiff.u32_at(4)? as usize;
+    let description = tiff
+        .value(ifd, IMAGE_DESCRIPTION)
+        .map(|value| String::from_utf8_lossy(value).into_owned());
+    let xp_title = tiff.value(ifd, XP_TITLE).map(|value| {
+        let units = value
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    });
+    [description, xp_title]
+        .into_iter()
+        .flatten()
+        .map(|t| {
+            t.trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                .to_owned()
+        })
+        .find(|t| !t.is_empty())
+}
+
+/// When the picture in a JPEG, PNG or TIFF file was taken, or else last
+/// changed, as EXIF writes it (`YYYY:MM:DD HH:MM:SS`), which sorts in time
+/// order
+pub fn exif_date(file: &[u8]) -> Option<String> {
+    let tiff = Tiff::new(exif_block(file)?)?;
+    let ifd = tiff.u32_at(4)? as usize;
+    let original = || {
+        let exif = tiff.value(ifd, EXIF_IFD)?;
+        let exif = tiff.u32_from(exif.get(..4)?)? as usize;
+        tiff.value(exif, DATE_TIME_ORIGINAL)
+    };
+    let date = original().or_else(|| tiff.value(ifd, DATE_TIME))?;
+    let date = String::from_utf8_lossy(date);
+    let date = date.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    // unknown fields are left blank or zeroed
+    (!date.is_empty() && !date.starts_with("0000")).then(|| date.to_owned())
+}
+
+/// How the picture in a JPEG, PNG or TIFF file has to be turned to show
+/// upright, as the EXIF Orientation from 1 (as stored) to 8
+pub fn exif_orientation(file: &[u8]) -> Option<u16> {
+    let tiff = Tiff::new(exif_block(file)?)?;
+    let ifd = tiff.u32_at(4)? as usize;
+    let orientation = tiff.u16_from(tiff.value(ifd, ORIENTATION)?.get(..2)?)?;
+    (1..=8).contains(&orientation).then_some(orientation)
+}
+
+/// EXIF data, laid out as a TIFF file
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..4)? {
+            b"MM\0*" => true,
+            b"II*\0" => false,
+            _ => return None,
+        };
+        Some(Tiff { data, big_endian })
+    }
+
+    fn u16_at(&self, i: usize) -> Option<u16> {
+        self.u16_from(self.data.get(i..i.checked_add(2)?)?)
+    }
+
+    fn u16_from(&self, bytes: &[u8]) -> Option<u16> {
+        let b = bytes.try_into().ok()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32_at(&self, i: usize) -> Option<u32> {
+        self.u32_from(self.data.get(i..i.checked_add(4)?)?)
+    }
+
+    fn u32_from(&self, bytes: &[u8]) -> Option<u32> {
+        let b = bytes.try_into().ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    /// The raw value of `tag` in the IFD at `ifd`, counting its length in
+    /// bytes as for BYTE and ASCII values, and the first four bytes of
+    /// anything else
+    fn value(&self, ifd: usize, tag: u16) -> Option<&'a [u8]> {
+        for n in 0..self.u16_at(ifd)? as usize {
+            let entry = ifd + 2 + n * 12;
+            if self.u16_at(entry)? != tag {
+                continue;
+            }
+            let len = (self.u32_at(entry + 4)? as usize).max(4);
+            let start = if len <= 4 {
+                entry + 8
+            } else {
+                self.u32_at(entry + 8)? as usize
+            };
+            return self.data.get(start..start.checked_add(len)?);
+        }
+        None
+    }
+}
+
+/// The TIFF structure holding the EXIF data of a file
+fn exif_block(file: &[u8]) -> Option<&[u8]> {
+    if file.starts_with(b"II*\0") || file.starts_with(b"MM\0*") {
+        return Some(file);
+    }
+    if file.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut i = 8;
+        while let Some(header) = file.get(i..i + 8) {
+            let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+            let data = file.get(i + 8..(i + 8).checked_add(len)?)?;
+            if &header[4..] == b"eXIf" {
+                return Some(data);
+            }
+            // chunk data is followed by a CRC
+            i += 12 + len;
+        }
+        return None;
+    }
+    if file.starts_with(b"\xff\xd8") {
+        let mut i = 2;
+        while let [0xff, marker, hi, lo, ..] = *file.get(i..)? {
+            // start of scan, no more metadata after this
+            if marker == 0xda {
+                break;
+            }
+            let len = u16::from_be_bytes([hi, lo]) as usize;
+            let data = file.get(i + 4..(i + 2).checked_add(len)?)?;
+            if marker == 0xe1 {
+                if let Some(tiff) = data.strip_prefix(b"Exif\0\0") {
+                    return Some(tiff);
+                }
+            }
+            i += 2 + len;
+        }
+    }
+    None
+}
+
+/// The `dc:title` of the XMP packet embedded in a file. XMP packets are
+/// meant to be found by scanning, so this works for any container format.
+pub fn xmp_title(file: &[u8]) -> Option<String> {
+    let title = find(file, b"<dc:title")?;
+    let rest = &file[title..];
+    let item = find(rest, b"<rdf:li")?;
+    let start = item + find(&rest[item..], b">")? + 1;
+    let end = start + find(&rest[start..], b"</rdf:li>")?;
+    let text = unescape(&String::from_utf8_lossy(&rest[start..end]));
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
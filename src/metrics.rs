@@ -0,0 +1,246 @@
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use yet_another_imgs2pdf::{ProgressSink, Stage};
+
+/// Upper bounds of the buckets durations are counted in, in seconds
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, the last one for those above every bound
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += secs;
+    }
+
+    /// Writes the cumulative buckets, sum and count of `name`, with `labels`
+    /// such as `stage="decode",` put before the bucket bound
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut total = 0;
+        for (le, count) in BUCKETS.iter().zip(self.counts) {
+            total += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{le}\"}} {total}");
+        }
+        total += self.counts[BUCKETS.len()];
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {total}");
+        let labels = labels.trim_end_matches(',');
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {total}");
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    submitted: u64,
+    rejected: u64,
+    done: u64,
+    failed: u64,
+    cancelled: u64,
+    inputs: u64,
+    skipped: u64,
+    pages: u64,
+    output_bytes: u64,
+    wait: Histogram,
+    duration: Histogram,
+    /// Decode, layout and save
+    stages: [Histogram; 3],
+}
+
+/// What the server has done since it started, for Prometheus to scrape
+#[derive(Default)]
+pub struct Metrics(Mutex<Counters>);
+
+impl Metrics {
+    pub fn job_submitted(&self) {
+        self.0.lock().unwrap().submitted += 1;
+    }
+
+    /// A job was turned away because too many were waiting
+    pub fn job_rejected(&self) {
+        self.0.lock().unwrap().rejected += 1;
+    }
+
+    /// A job started after waiting in the queue for `waited`
+    pub fn job_started(&self, waited: Duration) {
+        self.0.lock().unwrap().wait.observe(waited);
+    }
+
+    /// A job ended as `status` (done, failed or cancelled) after `took`
+    pub fn job_finished(&self, status: &str, took: Duration, pages: usize, output_bytes: usize) {
+        let mut counters = self.0.lock().unwrap();
+        match status {
+            "done" => counters.done += 1,
+            "cancelled" => counters.cancelled += 1,
+            _ => counters.failed += 1,
+        }
+        counters.duration.observe(took);
+        counters.pages += pages as u64;
+        counters.output_bytes += output_bytes as u64;
+    }
+
+    /// An uploaded file was added, or skipped because it could not be
+    pub fn input_added(&self, skipped: bool) {
+        let mut counters = self.0.lock().unwrap();
+        counters.inputs += 1;
+        counters.skipped += skipped as u64;
+    }
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        let i = match stage {
+            Stage::Decode => 0,
+            Stage::Layout => 1,
+            Stage::Save => 2,
+        };
+        self.0.lock().unwrap().stages[i].observe(elapsed);
+    }
+
+    /// The metrics in the Prometheus text format, with the number of jobs
+    /// `queued` and `running` right now
+    pub fn render(&self, queued: usize, running: usize) -> String {
+        let c = self.0.lock().unwrap();
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, u64)]| {
+            let _ = writeln!(out, "# HELP imgs2pdf_{name} {help}");
+            let _ = writeln!(out, "# TYPE imgs2pdf_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "imgs2pdf_{name}{labels} {value}");
+            }
+        };
+        metric(
+            "jobs_submitted_total",
+            "counter",
+            "Jobs accepted into the queue",
+            &[("", c.submitted)],
+        );
+        metric(
+            "jobs_rejected_total",
+            "counter",
+            "Jobs turned away because the queue was full",
+            &[("", c.rejected)],
+        );
+        metric(
+            "jobs_finished_total",
+            "counter",
+            "Jobs that ended, by how",
+            &[
+                ("{status=\"done\"}", c.done),
+                ("{status=\"failed\"}", c.failed),
+                ("{status=\"cancelled\"}", c.cancelled),
+            ],
+        );
+        metric(
+            "jobs_queued",
+            "gauge",
+            "Jobs waiting to be run",
+            &[("", queued as u64)],
+        );
+        metric(
+            "jobs_running",
+            "gauge",
+            "Jobs being run",
+            &[("", running as u64)],
+        );
+        metric(
+            "inputs_total",
+            "counter",
+            "Uploaded files worked on",
+            &[("", c.inputs)],
+        );
+        metric(
+            "inputs_skipped_total",
+            "counter",
+            "Uploaded files left out because they could not be converted",
+            &[("", c.skipped)],
+        );
+        metric(
+            "pages_total",
+            "counter",
+            "Pages in the PDFs of finished jobs",
+            &[("", c.pages)],
+        );
+        metric(
+            "output_bytes_total",
+            "counter",
+            "Size of the PDFs of finished jobs",
+            &[("", c.output_bytes)],
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP imgs2pdf_job_wait_seconds Time jobs spent in the queue"
+        );
+        let _ = writeln!(out, "# TYPE imgs2pdf_job_wait_seconds histogram");
+        c.wait.render(&mut out, "imgs2pdf_job_wait_seconds", "");
+        let _ = writeln!(
+            out,
+            "# HELP imgs2pdf_job_duration_seconds Time jobs took to run"
+        );
+        let _ = writeln!(out, "# TYPE imgs2pdf_job_duration_seconds histogram");
+        c.duration
+            .render(&mut out, "imgs2pdf_job_duration_seconds", "");
+        let _ = writeln!(
+            out,
+            "# HELP imgs2pdf_stage_duration_seconds Time spent decoding and laying out each file, and saving each PDF"
+        );
+        let _ = writeln!(out, "# TYPE imgs2pdf_stage_duration_seconds histogram");
+        for (stage, histogram) in ["decode", "layout", "save"].iter().zip(&c.stages) {
+            histogram.render(
+                &mut out,
+                "imgs2pdf_stage_duration_seconds",
+                &format!("stage=\"{stage}\","),
+            );
+        }
+        out
+    }
+}
+
+/// Times the stages of a merge into the metrics
+pub struct StageTimer(pub Arc<Metrics>);
+
+impl ProgressSink for StageTimer {
+    fn stage_finished(&mut self, stage: Stage, elapsed: Duration) {
+        self.0.stage_finished(stage, elapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let mut histogram = Histogram::default();
+        for ms in [3, 7, 7, 400, 1_000_000] {
+            histogram.observe(Duration::from_millis(ms));
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "t", "stage=\"save\",");
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "t_bucket{stage=\"save\",le=\"0.005\"} 1");
+        assert_eq!(lines[1], "t_bucket{stage=\"save\",le=\"0.01\"} 3");
+        assert_eq!(lines[6], "t_bucket{stage=\"save\",le=\"0.5\"} 4");
+        assert_eq!(lines[13], "t_bucket{stage=\"save\",le=\"300\"} 4");
+        assert_eq!(lines[14], "t_bucket{stage=\"save\",le=\"+Inf\"} 5");
+        assert_eq!(lines[15], "t_sum{stage=\"save\"} 1000.417");
+        assert_eq!(lines[16], "t_count{stage=\"save\"} 5");
+    }
+}
@@ -0,0 +1,442 @@
+use printpdf::image_crate::DynamicImage;
+use printpdf::lopdf::{
+    self,
+    content::{Content, Operation},
+    dictionary, Object, StringFormat,
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::layers;
+use crate::overlay::text_width;
+use crate::prepress::{add_resource, page_size, wrap_page_content};
+
+/// Name the text layer font is registered under in the page resources
+const OCR_FONT: &str = "FOcr";
+const OCR_LAYER: &str = "OCText";
+/// A later line only counts as more prominent when it is this much larger
+const HEADING_MARGIN: f64 = 1.15;
+
+pub struct Options {
+    /// Tesseract languages joined with `+`, e.g. `eng+deu`
+    pub lang: String,
+    /// Pages with a lower mean word confidence are flagged, in percent
+    pub min_confidence: f64,
+}
+
+/// A word as recognized, in pixels of the upright page image
+pub struct Word {
+    pub text: String,
+    /// Block, paragraph and line number of the line the word is on
+    pub line: (u32, u32, u32),
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    pub confidence: f64,
+}
+
+pub struct PageText {
+    pub words: Vec<Word>,
+    /// Clockwise rotation that turned the page image upright
+    pub rotate: u32,
+    pub script: Option<String>,
+    /// Size of the page image as placed, before it was turned upright
+    size: (u32, u32),
+    /// Where the image is on its page in points, as x, y, width and height,
+    /// when it does not fill the page
+    area: Option<[f64; 4]>,
+}
+
+impl PageText {
+    /// Records where the image is on its page, in points
+    pub fn place(&mut self, area: [f64; 4]) {
+        self.area = Some(area);
+    }
+
+    /// Mean word confidence in percent, `None` for a page without words
+    pub fn confidence(&self) -> Option<f64> {
+        if self.words.is_empty() {
+            return None;
+        }
+        let sum = self.words.iter().map(|w| w.confidence).sum::<f64>();
+        Some(sum / self.words.len() as f64)
+    }
+
+    /// The words of every line, top to bottom
+    pub fn lines(&self) -> Vec<Vec<&Word>> {
+        let mut lines: Vec<Vec<&Word>> = Vec::new();
+        for word in &self.words {
+            match lines.last_mut() {
+                Some(line) if line[0].line == word.line => line.push(word),
+                _ => lines.push(vec![word]),
+            }
+        }
+        lines
+    }
+
+    /// Plain text with one line per recognized line and an empty line between
+    /// paragraphs
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        let mut paragraph = None;
+        for line in self.lines() {
+            let (block, par, _) = line[0].line;
+            if paragraph.is_some_and(|p| p != (block, par)) {
+                text.push('\n');
+            }
+            paragraph = Some((block, par));
+            let words = line.iter().map(|w| w.text.as_str()).collect::<Vec<_>>();
+            text.push_str(&words.join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The most prominent line of the page, the one set in the largest type,
+    /// taking the topmost of lines that are about equally large
+    pub fn heading(&self) -> Option<String> {
+        let mut best: Option<(f64, String)> = None;
+        for line in self.lines() {
+            let text = line
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            // single characters are page numbers, bullets or noise
+            if text.chars().filter(|c| c.is_alphanumeric()).count() < 2 {
+                continue;
+            }
+            let mut heights = line.iter().map(|w| w.height).collect::<Vec<_>>();
+            heights.sort_unstable();
+            let height = heights[heights.len() / 2] as f64;
+            if best
+                .as_ref()
+                .is_none_or(|(h, _)| height > h * HEADING_MARGIN)
+            {
+                best = Some((height, text));
+            }
+        }
+        best.map(|(_, text)| text)
+    }
+}
+
+/// The scratch image handed to tesseract, removed on drop
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+pub fn tesseract_available() -> bool {
+    Command::new("tesseract")
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Recognizes the text of a page image with `tesseract`, detecting the
+/// orientation of the page first so that sideways scans are read upright
+pub fn recognize(img: &DynamicImage, lang: &str) -> io::Result<PageText> {
+    let scratch =
+        Scratch(std::env::temp_dir().join(format!("imgs2pdf-ocr-{}.pgm", std::process::id())));
+    write_pgm(&scratch.0, img)?;
+    let (rotate, script) = orientation(&scratch.0);
+    if rotate != 0 {
+        let upright = match rotate {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            _ => img.rotate270(),
+        };
+        write_pgm(&scratch.0, &upright)?;
+    }
+    let tsv = tesseract(&scratch.0, &["-l", lang, "tsv"])?;
+    Ok(PageText {
+        words: parse_tsv(&tsv),
+        rotate,
+        script,
+        size: (img.width(), img.height()),
+        area: None,
+    })
+}
+
+/// Clockwise rotation in degrees that turns `img` upright by the direction
+/// of its text, 0 when it is upright or too sparse to tell. Inputs may be
+/// decoded on several threads, so each gets a scratch image of its own.
+pub fn text_rotation(img: &DynamicImage) -> io::Result<u32> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let scratch = Scratch(std::env::temp_dir().join(format!(
+        "imgs2pdf-osd-{}-{}.pgm",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )));
+    write_pgm(&scratch.0, img)?;
+    Ok(orientation(&scratch.0).0)
+}
+
+/// Tesseract reads PNM without any encoder on our side
+fn write_pgm(path: &Path, img: &DynamicImage) -> io::Result<()> {
+    let gray = img.to_luma8();
+    let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+    write!(file, "P5\n{} {}\n255\n", gray.width(), gray.height())?;
+    file.write_all(gray.as_raw())?;
+    file.flush()
+}
+
+fn tesseract(image: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("tesseract")
+        .arg(image)
+        .arg("stdout")
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or_default().trim().to_owned();
+        return Err(io::Error::other(format!(
+            "tesseract exited with {}: {reason}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Clockwise rotation that makes the page upright and the script it is
+/// written in. Pages too sparse to tell are taken to be upright.
+fn orientation(image: &Path) -> (u32, Option<String>) {
+    let osd = match tesseract(image, &["--psm", "0"]) {
+        Ok(osd) => osd,
+        Err(_) => return (0, None),
+    };
+    let field = |name: &str| {
+        osd.lines()
+            .find_map(|l| l.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let rotate = field("Rotate")
+        .and_then(|r| r.parse::<u32>().ok())
+        .filter(|r| [90, 180, 270].contains(r))
+        .unwrap_or(0);
+    (rotate, field("Script").map(str::to_owned))
+}
+
+/// Words from tesseract's TSV output, which has one row per page, block,
+/// paragraph, line and word
+fn parse_tsv(tsv: &str) -> Vec<Word> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|row| {
+            let cols = row.splitn(12, '\t').collect::<Vec<_>>();
+            if cols.len() < 12 || cols[0] != "5" {
+                return None;
+            }
+            let n = |i: usize| cols[i].parse::<u32>().ok();
+            let text = cols[11].trim();
+            let confidence = cols[10].parse::<f64>().ok().filter(|c| *c >= 0.0)?;
+            if text.is_empty() {
+                return None;
+            }
+            Some(Word {
+                text: text.to_owned(),
+                line: (n(2)?, n(3)?, n(4)?),
+                left: n(6)?,
+                top: n(7)?,
+                width: n(8)?,
+                height: n(9)?,
+                confidence,
+            })
+        })
+        .collect()
+}
+
+/// Lays the recognized words over their pages as invisible text, which makes
+/// them searchable and selectable. `pages` holds 0-based page indices. With
+/// `layered` the text goes on a layer of its own.
+pub fn add_text_layer(
+    doc: &mut lopdf::Document,
+    pages: &[(usize, PageText)],
+    layered: bool,
+) -> lopdf::Result<()> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let layer = if layered {
+        Some(layers::add_layer(doc, "Recognized text")?)
+    } else {
+        None
+    };
+    let page_ids = doc.get_pages().into_values().collect::<Vec<_>>();
+    for (index, text) in pages {
+        let page_id = match page_ids.get(*index) {
+            Some(page_id) => *page_id,
+            None => continue,
+        };
+        let (page_w, page_h) = page_size(doc, page_id)?;
+        let (w, h) = (text.size.0 as f64, text.size.1 as f64);
+        let [left, bottom, area_w, area_h] = text.area.unwrap_or([0.0, 0.0, page_w, page_h]);
+        let (scale, scale_y) = (area_w / w, area_h / h);
+        let (sin, cos) = (text.rotate as f64).to_radians().sin_cos();
+        let (sin, cos) = (sin.round(), cos.round());
+
+        let mut ops = vec![
+            Operation::new("BT", vec![]),
+            // neither filled nor stroked
+            Operation::new("Tr", vec![3.into()]),
+        ];
+        for word in &text.words {
+            // start of the baseline, turned back from the upright image
+            let (u, v) = (word.left as f64, (word.top + word.height) as f64);
+            let (x, y) = match text.rotate {
+                90 => (v, h - u),
+                180 => (w - u, h - v),
+                270 => (w - v, u),
+                _ => (u, v),
+            };
+            let size = (word.height as f64 * scale).max(1.0);
+            let stretch = 100.0 * word.width as f64 * scale / text_width(&word.text, size);
+            ops.extend([
+                Operation::new("Tf", vec![OCR_FONT.into(), size.into()]),
+                Operation::new("Tz", vec![stretch.into()]),
+                Operation::new(
+                    "Tm",
+                    vec![
+                        cos.into(),
+                        sin.into(),
+                        (-sin).into(),
+                        cos.into(),
+                        (left + x * scale).into(),
+                        (bottom + area_h - y * scale_y).into(),
+                    ],
+                ),
+                Operation::new(
+                    "Tj",
+                    vec![Object::String(win_ansi(&word.text), StringFormat::Literal)],
+                ),
+            ]);
+        }
+        ops.push(Operation::new("ET", vec![]));
+        if let Some(layer) = layer {
+            ops = layers::mark(doc, page_id, OCR_LAYER, layer, ops)?;
+        }
+        ops.insert(0, Operation::new("Q", vec![]));
+
+        let before = Content {
+            operations: vec![Operation::new("q", vec![])],
+        };
+        let after = Content { operations: ops };
+        wrap_page_content(doc, page_id, before.encode()?, after.encode()?)?;
+        add_resource(doc, page_id, "Font", OCR_FONT, font_id)?;
+    }
+    Ok(())
+}
+
+/// WinAnsiEncoding agrees with Latin-1 on the characters it shares with it
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Writes the text of every page to `path`, either as one file with pages
+/// separated by form feeds when it ends in `.txt`, or as one file per page
+/// inside the directory `path`. Pages without recognized text stay empty.
+pub fn export_text(path: &Path, page_count: usize, pages: &[(usize, PageText)]) -> io::Result<()> {
+    let text = |i: usize| {
+        pages
+            .iter()
+            .find(|(page, _)| *page == i)
+            .map(|(_, text)| text.text())
+            .unwrap_or_default()
+    };
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("txt"))
+    {
+        let all = (0..page_count).map(text).collect::<Vec<_>>();
+        return std::fs::write(path, all.join("\x0c"));
+    }
+    std::fs::create_dir_all(path)?;
+    let width = page_count.to_string().len().max(4);
+    for i in 0..page_count {
+        std::fs::write(path.join(format!("page-{:0width$}.txt", i + 1)), text(i))?;
+    }
+    Ok(())
+}
+
+/// Writes the recognized words with their boxes as hOCR, in pixels of the
+/// upright page images
+pub fn export_hocr(path: &Path, pages: &[(usize, PageText)]) -> io::Result<()> {
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">"#
+    )?;
+    writeln!(out, r#"<html xmlns="http://www.w3.org/1999/xhtml">"#)?;
+    writeln!(out, "<head>")?;
+    writeln!(
+        out,
+        r#"<meta http-equiv="Content-Type" content="text/html;charset=utf-8"/>"#
+    )?;
+    writeln!(out, r#"<meta name="ocr-system" content="tesseract"/>"#)?;
+    writeln!(
+        out,
+        r#"<meta name="ocr-capabilities" content="ocr_page ocr_line ocrx_word"/>"#
+    )?;
+    writeln!(out, "</head>\n<body>")?;
+    for (index, text) in pages {
+        let n = index + 1;
+        let (w, h) = match text.rotate {
+            90 | 270 => (text.size.1, text.size.0),
+            _ => text.size,
+        };
+        writeln!(
+            out,
+            "<div class='ocr_page' id='page_{n}' title='bbox 0 0 {w} {h}; ppageno {index}'>"
+        )?;
+        for (l, line) in text.lines().iter().enumerate() {
+            let x0 = line.iter().map(|w| w.left).min().unwrap_or(0);
+            let y0 = line.iter().map(|w| w.top).min().unwrap_or(0);
+            let x1 = line.iter().map(|w| w.left + w.width).max().unwrap_or(0);
+            let y1 = line.iter().map(|w| w.top + w.height).max().unwrap_or(0);
+            writeln!(
+                out,
+                " <span class='ocr_line' id='line_{n}_{}' title='bbox {x0} {y0} {x1} {y1}'>",
+                l + 1
+            )?;
+            for (i, word) in line.iter().enumerate() {
+                writeln!(
+                    out,
+                    "  <span class='ocrx_word' id='word_{n}_{}_{}' title='bbox {} {} {} {}; x_wconf {:.0}'>{}</span>",
+                    l + 1,
+                    i + 1,
+                    word.left,
+                    word.top,
+                    word.left + word.width,
+                    word.top + word.height,
+                    word.confidence,
+                    escape_xml(&word.text)
+                )?;
+            }
+            writeln!(out, " </span>")?;
+        }
+        writeln!(out, "</div>")?;
+    }
+    writeln!(out, "</body>\n</html>")?;
+    out.flush()
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
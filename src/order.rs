@@ -0,0 +1,183 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+use yet_another_imgs2pdf::metadata;
+
+/// Orders the inputs by `key`, one of `name` (numbers in names by value),
+/// `lex` (plain character order), `mtime` or `exif-date`. Inputs without a
+/// date go last, and ties keep to the name order.
+pub fn sort(inputs: &mut [PathBuf], key: &str) {
+    match key {
+        "lex" => inputs.sort(),
+        "mtime" => sort_dated(inputs, |p| {
+            std::fs::metadata(p).and_then(|m| m.modified()).ok()
+        }),
+        "exif-date" => sort_dated(inputs, |p| {
+            std::fs::read(p)
+                .ok()
+                .and_then(|file| metadata::exif_date(&file))
+        }),
+        _ => inputs.sort_by(|a, b| natural_cmp(a, b)),
+    }
+}
+
+/// Every k-th of `inputs`, from the first, with k chosen so that no more
+/// than `count` are kept and they spread over the whole run
+pub fn sample(inputs: Vec<PathBuf>, count: usize) -> Vec<PathBuf> {
+    let step = inputs.len().div_ceil(count).max(1);
+    inputs.into_iter().step_by(step).collect()
+}
+
+fn sort_dated<T: Ord>(inputs: &mut [PathBuf], date: impl Fn(&Path) -> Option<T>) {
+    let mut dated = inputs
+        .iter()
+        .map(|p| (date(p), p.clone()))
+        .collect::<Vec<_>>();
+    dated.sort_by(|(da, a), (db, b)| match (da, db) {
+        (Some(da), Some(db)) => da.cmp(db).then_with(|| natural_cmp(a, b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => natural_cmp(a, b),
+    });
+    for (input, (_, p)) in inputs.iter_mut().zip(dated) {
+        *input = p;
+    }
+}
+
+/// Compares paths the way people read numbered names, so that `page2.png`
+/// comes before `page10.png`. Runs of digits compare by their value and,
+/// between equal values, the one with fewer leading zeros comes first.
+pub fn natural_cmp(a: &Path, b: &Path) -> Ordering {
+    let (a, b) = (a.to_string_lossy(), b.to_string_lossy());
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (digits(&mut a), digits(&mut b));
+                let (vx, vy) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let order = vx
+                    .len()
+                    .cmp(&vy.len())
+                    .then_with(|| vx.cmp(vy))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn digits(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names made of a few letters and digits, so that runs of digits,
+    /// leading zeros and shared prefixes come up often
+    fn names(count: usize) -> Vec<PathBuf> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let len = next() % 8;
+                (0..len)
+                    .map(|_| b"ab0019-."[(next() % 8) as usize] as char)
+                    .collect::<String>()
+                    .into()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn natural_order_is_total() {
+        let names = names(60);
+        for a in &names {
+            assert_eq!(natural_cmp(a, a), Ordering::Equal);
+            for b in &names {
+                assert_eq!(
+                    natural_cmp(a, b),
+                    natural_cmp(b, a).reverse(),
+                    "{:?} {:?}",
+                    a,
+                    b
+                );
+                assert_eq!(
+                    natural_cmp(a, b) == Ordering::Equal,
+                    a == b,
+                    "{:?} {:?}",
+                    a,
+                    b
+                );
+                for c in &names {
+                    if natural_cmp(a, b).is_le() && natural_cmp(b, c).is_le() {
+                        assert!(natural_cmp(a, c).is_le(), "{:?} {:?} {:?}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_spread_over_the_run() {
+        let pages = (0..25)
+            .map(|i| PathBuf::from(i.to_string()))
+            .collect::<Vec<_>>();
+        let kept = sample(pages.clone(), 10);
+        assert_eq!(kept.len(), 9);
+        assert_eq!(kept[1], Path::new("3"));
+        assert_eq!(kept.last().unwrap(), Path::new("24"));
+        assert_eq!(sample(pages.clone(), 100), pages);
+    }
+
+    #[test]
+    fn numbers_sort_by_value() {
+        let mut pages = (0..120)
+            .rev()
+            .map(|n| PathBuf::from(format!("page{}.png", n)))
+            .collect::<Vec<_>>();
+        sort(&mut pages, "name");
+        for (n, page) in pages.iter().enumerate() {
+            assert_eq!(page, &PathBuf::from(format!("page{}.png", n)));
+        }
+    }
+
+    #[test]
+    fn names_without_digits_sort_as_text() {
+        let words = names(200)
+            .into_iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .filter(|w| !w.contains(|c: char| c.is_ascii_digit()))
+            .collect::<Vec<_>>();
+        for a in &words {
+            for b in &words {
+                let order = natural_cmp(Path::new(a), Path::new(b));
+                assert_eq!(order, a.cmp(b), "{:?} {:?}", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use printpdf::lopdf::{self, dictionary, Dictionary, Object, ObjectId};
+
+use crate::info::{decode_text_string, text_string};
+
+type Entry<'a> = (&'a str, ObjectId);
+
+pub struct Bookmark {
+    pub title: String,
+    /// Zero based index of the page the bookmark jumps to
+    pub page: usize,
+    /// Entry the bookmark is nested under, shared with the bookmarks next
+    /// to it in the same group
+    pub group: Option<String>,
+}
+
+/// Replaces the document outline with one entry per bookmark, in order, and
+/// opens the outline panel when the document is shown. Bookmarks in a group
+/// are nested under an open entry for it that jumps to the first of them.
+pub fn add_outline(doc: &mut lopdf::Document, bookmarks: &[Bookmark]) -> lopdf::Result<()> {
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    let dest = |page: ObjectId| Object::from(vec![page.into(), "Fit".into()]);
+    // groups, or single ungrouped entries, with their titles and pages
+    let mut nodes: Vec<(Option<&str>, Vec<Entry>)> = Vec::new();
+    for b in bookmarks {
+        let page = match pages.get(b.page) {
+            Some(page) => *page,
+            None => continue,
+        };
+        match nodes.last_mut() {
+            Some((Some(group), children)) if Some(*group) == b.group.as_deref() => {
+                children.push((&b.title, page))
+            }
+            _ => nodes.push((b.group.as_deref(), vec![(&b.title, page)])),
+        }
+    }
+    if nodes.is_empty() {
+        return Ok(());
+    }
+
+    let outline_id = doc.new_object_id();
+    let mut count = nodes.len();
+    let mut top = Vec::new();
+    for (group, children) in nodes {
+        let items = children
+            .iter()
+            .map(|(title, page)| {
+                let item = dictionary! {
+                    "Title" => text_string(title),
+                    "Dest" => dest(*page),
+                };
+                (doc.new_object_id(), item)
+            })
+            .collect::<Vec<_>>();
+        let group = match group {
+            Some(group) => group,
+            None => {
+                top.extend(items);
+                continue;
+            }
+        };
+        let group_id = doc.new_object_id();
+        count += items.len();
+        let mut item = dictionary! {
+            "Title" => text_string(group),
+            "Dest" => dest(children[0].1),
+            // open, showing its entries
+            "Count" => items.len() as i64,
+        };
+        let (first, last) = add_siblings(doc, group_id, items);
+        item.set("First", first);
+        item.set("Last", last);
+        top.push((group_id, item));
+    }
+    let (first, last) = add_siblings(doc, outline_id, top);
+    doc.objects.insert(
+        outline_id,
+        dictionary! {
+            "Type" => "Outlines",
+            "First" => first,
+            "Last" => last,
+            "Count" => count as i64,
+        }
+        .into(),
+    );
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+    catalog.set("Outlines", outline_id);
+    catalog.set("PageMode", "UseOutlines");
+    Ok(())
+}
+
+/// Adds `items` as the entries of `parent`, in order, returning the first
+/// and the last of them
+fn add_siblings(
+    doc: &mut lopdf::Document,
+    parent: ObjectId,
+    items: Vec<(ObjectId, Dictionary)>,
+) -> (ObjectId, ObjectId) {
+    let ids = items.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    for (i, (id, mut item)) in items.into_iter().enumerate() {
+        item.set("Parent", parent);
+        if i > 0 {
+            item.set("Prev", ids[i - 1]);
+        }
+        if let Some(next) = ids.get(i + 1) {
+            item.set("Next", *next);
+        }
+        doc.objects.insert(id, item.into());
+    }
+    (ids[0], ids[ids.len() - 1])
+}
+
+/// Registers a named destination per `(name, page)` pair, so the pages can be
+/// linked to as `doc.pdf#nameddest=name`
+pub fn add_named_dests(doc: &mut lopdf::Document, dests: &[(String, usize)]) -> lopdf::Result<()> {
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    let mut dests = dests
+        .iter()
+        .filter_map(|(name, page)| Some((name, *pages.get(*page)?)))
+        .collect::<Vec<_>>();
+    if dests.is_empty() {
+        return Ok(());
+    }
+    // name trees have to be sorted by key
+    dests.sort_by(|a, b| a.0.cmp(b.0));
+    let names = dests
+        .into_iter()
+        .flat_map(|(name, page_id)| {
+            [
+                Object::string_literal(name.as_bytes()),
+                vec![page_id.into(), "Fit".into()].into(),
+            ]
+        })
+        .collect::<Vec<Object>>();
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+    let tree = dictionary! { "Names" => names };
+    match catalog.get_mut(b"Names") {
+        Ok(Object::Dictionary(existing)) => existing.set("Dests", tree),
+        _ => catalog.set("Names", dictionary! { "Dests" => tree }),
+    }
+    Ok(())
+}
+
+/// Adds a link annotation opening `url` for every `(page, rect, url)`, with
+/// `rect` in user space as `[llx, lly, urx, ury]`
+pub fn add_links(
+    doc: &mut lopdf::Document,
+    links: &[(usize, [f64; 4], String)],
+) -> lopdf::Result<()> {
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    for (page, rect, url) in links {
+        let page_id = match pages.get(*page) {
+            Some(page_id) => *page_id,
+            None => continue,
+        };
+        let annot_id = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => rect.iter().map(|v| Object::Real(*v)).collect::<Vec<_>>(),
+            "Border" => vec![0.into(), 0.into(), 0.into()],
+            "A" => dictionary! {
+                "S" => "URI",
+                "URI" => Object::string_literal(url.as_bytes()),
+            },
+        });
+        let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+        match page.get_mut(b"Annots") {
+            Ok(Object::Array(annots)) => annots.push(annot_id.into()),
+            _ => page.set("Annots", vec![annot_id.into()]),
+        }
+    }
+    Ok(())
+}
+
+/// Titles and target pages of the top level outline entries, skipping any
+/// that do not point straight at a page
+pub fn entries(doc: &lopdf::Document) -> Vec<(String, ObjectId)> {
+    let first = doc
+        .catalog()
+        .and_then(|c| c.get(b"Outlines"))
+        .and_then(Object::as_reference)
+        .and_then(|id| doc.get_dictionary(id))
+        .and_then(|outline| outline.get(b"First"))
+        .and_then(Object::as_reference);
+    let mut next = first.ok();
+    let mut entries = Vec::new();
+    // guards against Next chains that loop
+    let mut budget = doc.objects.len();
+    while let (Some(id), true) = (next, budget > 0) {
+        budget -= 1;
+        let item = match doc.get_dictionary(id) {
+            Ok(item) => item,
+            Err(_) => break,
+        };
+        let dest = item.get(b"Dest").or_else(|_| {
+            item.get(b"A")
+                .and_then(|a| doc.dereference(a))
+                .and_then(|(_, a)| a.as_dict())
+                .and_then(|a| a.get(b"D"))
+        });
+        let page = dest
+            .and_then(|d| doc.dereference(d))
+            .and_then(|(_, d)| d.as_array())
+            .ok()
+            .and_then(|d| d.first())
+            .and_then(|p| p.as_reference().ok());
+        let title = item.get(b"Title").and_then(Object::as_str);
+        if let (Some(page), Ok(title)) = (page, title) {
+            entries.push((decode_text_string(title), page));
+        }
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+    entries
+}
+
+/// Drops the document outline and stops viewers from opening the outline panel
+pub fn remove(doc: &mut lopdf::Document) -> lopdf::Result<()> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+    catalog.remove(b"Outlines");
+    catalog.set("PageMode", "UseNone");
+    Ok(())
+}
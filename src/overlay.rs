@@ -0,0 +1,240 @@
+use printpdf::lopdf::{
+    self,
+    content::{Content, Operation},
+    dictionary, Object, ObjectId,
+};
+
+use crate::layers;
+use crate::prepress::{add_resource, ascii, page_box, wrap_page_content};
+use crate::template;
+
+/// Names the overlay registers in the page resources
+const STAMP_FONT: &str = "FStamp";
+const STAMP_STATE: &str = "GSStamp";
+const WATERMARK_LAYER: &str = "OCWatermark";
+const NUMBERS_LAYER: &str = "OCPageNumbers";
+const RUNNING_TEXT_LAYER: &str = "OCHeaderFooter";
+const WATERMARK_OPACITY: f64 = 0.25;
+/// Share of the page diagonal the watermark text spans
+const WATERMARK_SPAN: f64 = 0.7;
+const PAGE_NUMBER_SIZE: f64 = 10.0;
+/// Distance of the page number baseline from the bottom edge (10mm)
+const PAGE_NUMBER_MARGIN: f64 = 28.35;
+/// Variables the header and footer can refer to
+pub const RUNNING_TEXT_VARIABLES: [&str; 4] = ["page", "total", "filename", "stem"];
+
+/// Helvetica advance widths of the printable ASCII characters, in 1/1000 em
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// What gets drawn on top of every page
+pub struct Stamp {
+    pub watermark: Option<String>,
+    pub page_numbers: bool,
+    /// Templates of the lines at the top and bottom center, filled in per
+    /// page from [`RUNNING_TEXT_VARIABLES`]
+    pub header: Option<String>,
+    pub footer: Option<String>,
+}
+
+/// Draws the stamp over the visible area of every page, the TrimBox if there
+/// is one, upright as the page is displayed. With `layered` the watermark,
+/// the page numbers and the header and footer each go on a layer of their
+/// own. `file_names` are those of the inputs of the pages in order, for the
+/// header and footer, and may be empty or cut short.
+pub fn apply(
+    doc: &mut lopdf::Document,
+    stamp: &Stamp,
+    layered: bool,
+    file_names: &[String],
+) -> lopdf::Result<()> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let state_id = doc.add_object(dictionary! {
+        "Type" => "ExtGState",
+        "ca" => WATERMARK_OPACITY,
+    });
+    let mut layer = |wanted: bool, name: &str| {
+        if layered && wanted {
+            layers::add_layer(doc, name).map(Some)
+        } else {
+            Ok(None)
+        }
+    };
+    let watermark_layer = layer(stamp.watermark.is_some(), "Watermark")?;
+    let numbers_layer = layer(stamp.page_numbers, "Page numbers")?;
+    let running = stamp.header.is_some() || stamp.footer.is_some();
+    let running_layer = layer(running, "Header and footer")?;
+
+    let pages = doc.get_pages();
+    let total = pages.len();
+    for (n, page_id) in pages {
+        let (matrix, w, h) = upright(doc, page_id)?;
+        let mut ops = vec![
+            Operation::new("Q", vec![]),
+            Operation::new("q", vec![]),
+            Operation::new("cm", matrix.iter().map(|v| (*v).into()).collect()),
+        ];
+        if let Some(text) = &stamp.watermark {
+            let text = ascii(text);
+            let angle = h.atan2(w);
+            // short texts would otherwise grow past the page edges
+            let size = (WATERMARK_SPAN * w.hypot(h) / text_width(&text, 1.0)).min(w.min(h) / 2.0);
+            let (sin, cos) = angle.sin_cos();
+            let watermark = vec![
+                Operation::new("q", vec![]),
+                Operation::new("gs", vec![STAMP_STATE.into()]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        cos.into(),
+                        sin.into(),
+                        (-sin).into(),
+                        cos.into(),
+                        (w / 2.0).into(),
+                        (h / 2.0).into(),
+                    ],
+                ),
+                Operation::new("g", vec![0.5.into()]),
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec![STAMP_FONT.into(), size.into()]),
+                // cap height of Helvetica is about 0.72 em
+                Operation::new(
+                    "Td",
+                    vec![
+                        (-text_width(&text, size) / 2.0).into(),
+                        (-0.36 * size).into(),
+                    ],
+                ),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+                Operation::new("Q", vec![]),
+            ];
+            ops.extend(match watermark_layer {
+                Some(layer) => layers::mark(doc, page_id, WATERMARK_LAYER, layer, watermark)?,
+                None => watermark,
+            });
+        }
+        if stamp.page_numbers {
+            let text = n.to_string();
+            let numbers = vec![
+                Operation::new("g", vec![0.into()]),
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec![STAMP_FONT.into(), PAGE_NUMBER_SIZE.into()]),
+                Operation::new(
+                    "Td",
+                    vec![
+                        ((w - text_width(&text, PAGE_NUMBER_SIZE)) / 2.0).into(),
+                        PAGE_NUMBER_MARGIN.into(),
+                    ],
+                ),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+            ];
+            ops.extend(match numbers_layer {
+                Some(layer) => layers::mark(doc, page_id, NUMBERS_LAYER, layer, numbers)?,
+                None => numbers,
+            });
+        }
+        if running {
+            let file_name = file_names.get(n as usize - 1).map_or("", String::as_str);
+            let fill = |text: &String| {
+                let text = template::render(text, |var| match var {
+                    "page" => Some(n.to_string()),
+                    "total" => Some(total.to_string()),
+                    "filename" => Some(file_name.to_owned()),
+                    "stem" => Some(
+                        file_name
+                            .rsplit_once('.')
+                            .map_or(file_name, |(s, _)| s)
+                            .to_owned(),
+                    ),
+                    _ => None,
+                });
+                ascii(&text.unwrap_or_default())
+            };
+            // the footer goes above the page number when there is one
+            let footer_at = match stamp.page_numbers {
+                true => PAGE_NUMBER_MARGIN + 1.5 * PAGE_NUMBER_SIZE,
+                false => PAGE_NUMBER_MARGIN,
+            };
+            let lines = [
+                stamp
+                    .header
+                    .as_ref()
+                    .map(|t| (fill(t), h - PAGE_NUMBER_MARGIN - 0.72 * PAGE_NUMBER_SIZE)),
+                stamp.footer.as_ref().map(|t| (fill(t), footer_at)),
+            ];
+            let mut text = vec![Operation::new("g", vec![0.into()])];
+            for (line, y) in lines.into_iter().flatten() {
+                // long lines are set smaller to stay within the margins
+                let room = w - 2.0 * PAGE_NUMBER_MARGIN;
+                let size = PAGE_NUMBER_SIZE.min(room / text_width(&line, 1.0).max(1.0));
+                text.extend([
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec![STAMP_FONT.into(), size.into()]),
+                    Operation::new(
+                        "Td",
+                        vec![((w - text_width(&line, size)) / 2.0).into(), y.into()],
+                    ),
+                    Operation::new("Tj", vec![Object::string_literal(line)]),
+                    Operation::new("ET", vec![]),
+                ]);
+            }
+            ops.extend(match running_layer {
+                Some(layer) => layers::mark(doc, page_id, RUNNING_TEXT_LAYER, layer, text)?,
+                None => text,
+            });
+        }
+        ops.push(Operation::new("Q", vec![]));
+
+        let before = Content {
+            operations: vec![Operation::new("q", vec![])],
+        };
+        let after = Content { operations: ops };
+        wrap_page_content(doc, page_id, before.encode()?, after.encode()?)?;
+        add_resource(doc, page_id, "Font", STAMP_FONT, font_id)?;
+        add_resource(doc, page_id, "ExtGState", STAMP_STATE, state_id)?;
+    }
+    Ok(())
+}
+
+/// A matrix mapping coordinates on the page as displayed, with the origin in
+/// its lower left corner, to user space, along with the displayed size
+fn upright(doc: &lopdf::Document, page_id: ObjectId) -> lopdf::Result<([f64; 6], f64, f64)> {
+    let [x0, y0, x1, y1] =
+        page_box(doc, page_id, b"TrimBox").or_else(|_| page_box(doc, page_id, b"MediaBox"))?;
+    let (w, h) = (x1 - x0, y1 - y0);
+    let rotate = doc
+        .get_dictionary(page_id)?
+        .get(b"Rotate")
+        .and_then(Object::as_i64)
+        .unwrap_or(0);
+    Ok(match rotate.rem_euclid(360) {
+        90 => ([0.0, 1.0, -1.0, 0.0, x1, y0], h, w),
+        180 => ([-1.0, 0.0, 0.0, -1.0, x1, y1], w, h),
+        270 => ([0.0, -1.0, 1.0, 0.0, x0, y1], h, w),
+        _ => ([1.0, 0.0, 0.0, 1.0, x0, y0], w, h),
+    })
+}
+
+/// Width of `text` set in Helvetica at `size`
+pub fn text_width(text: &str, size: f64) -> f64 {
+    let units = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => HELVETICA_WIDTHS[c as usize - 32] as f64,
+            _ => 556.0,
+        })
+        .sum::<f64>();
+    units * size / 1000.0
+}
@@ -0,0 +1,34 @@
+use std::ops::RangeInclusive;
+
+/// Parses a comma separated list of 1-based pages and ranges such as
+/// `1-3,5,8-` against a document of `total` pages. Open ends run to the
+/// first or last page.
+pub fn parse(spec: &str, total: u32) -> Result<Vec<RangeInclusive<u32>>, String> {
+    let page = |s: &str, default: u32| -> Result<u32, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(default);
+        }
+        match s.parse::<u32>() {
+            Ok(n) if (1..=total).contains(&n) => Ok(n),
+            Ok(n) => Err(format!("page {n} is outside 1-{total}")),
+            Err(_) => Err(format!("`{s}` is not a page number")),
+        }
+    };
+    let mut ranges = Vec::new();
+    for part in spec.split(',') {
+        let range = match part.split_once('-') {
+            Some((first, last)) => page(first, 1)?..=page(last, total)?,
+            None if part.trim().is_empty() => return Err("empty page range".to_string()),
+            None => {
+                let n = page(part, 1)?;
+                n..=n
+            }
+        };
+        if range.is_empty() {
+            return Err(format!("`{}` runs backwards", part.trim()));
+        }
+        ranges.push(range);
+    }
+    Ok(ranges)
+}
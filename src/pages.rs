@@ -0,0 +1,103 @@
+use clap::{Arg, ArgMatches, Command, ValueHint};
+use printpdf::lopdf::{self, Object, ObjectId};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::process::exit;
+
+use crate::pagerange;
+use yet_another_imgs2pdf::pagetree;
+
+pub fn command() -> Command<'static> {
+    Command::new("pages")
+        .about("Reorder, drop or rotate the pages of a PDF")
+        .arg(
+            Arg::new("input")
+                .allow_invalid_utf8(true)
+                .help("The PDF to rearrange")
+                .required(true)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("order")
+                .help("Pages to keep in their new order, e.g. 3,1,2,4- [default: all pages]")
+                .takes_value(true)
+                .long("order"),
+        )
+        .arg(
+            Arg::new("rotate")
+                .help("Rotate pages clockwise by a multiple of 90 degrees, e.g. 5:90 or 2-4:180")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .long("rotate"),
+        )
+        .arg(
+            Arg::new("out")
+                .allow_invalid_utf8(true)
+                .required(true)
+                .value_hint(ValueHint::FilePath)
+                .long("out")
+                .short('o'),
+        )
+        .after_help("Page numbers always refer to the pages of <input>.")
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let doc = lopdf::Document::load(matches.value_of_os("input").unwrap())?;
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    let total = pages.len() as u32;
+
+    let order = match matches.value_of("order") {
+        Some(spec) => match pagerange::parse(spec, total) {
+            Ok(ranges) => ranges.into_iter().flatten().collect::<Vec<u32>>(),
+            Err(e) => {
+                eprintln!("Value <order> is invalid, {e}");
+                exit(1)
+            }
+        },
+        None => (1..=total).collect(),
+    };
+
+    let mut rotations = HashMap::new();
+    for spec in matches.values_of("rotate").into_iter().flatten() {
+        let parsed = spec.rsplit_once(':').and_then(|(range, degrees)| {
+            let degrees = degrees.trim().parse::<i64>().ok().filter(|d| d % 90 == 0)?;
+            Some((pagerange::parse(range, total), degrees))
+        });
+        match parsed {
+            Some((Ok(ranges), degrees)) => {
+                for n in ranges.into_iter().flatten() {
+                    *rotations.entry(n).or_insert(0) += degrees;
+                }
+            }
+            Some((Err(e), _)) => {
+                eprintln!("Value <rotate> is invalid, {e}");
+                exit(1)
+            }
+            None => {
+                eprintln!("Value <rotate> must look like PAGES:DEGREES with a multiple of 90");
+                exit(1)
+            }
+        }
+    }
+
+    let ids = order
+        .iter()
+        .map(|n| pages[*n as usize - 1])
+        .collect::<Vec<_>>();
+    let mut out = pagetree::rearranged(&doc, &ids)?;
+    // copies of a page get their own ids, so go by position
+    let new_pages = out.get_pages().into_values().collect::<Vec<_>>();
+    for (n, page_id) in order.iter().zip(new_pages) {
+        if let Some(degrees) = rotations.get(n) {
+            let page = out.get_object_mut(page_id)?.as_dict_mut()?;
+            let current = page.get(b"Rotate").and_then(Object::as_i64).unwrap_or(0);
+            page.set("Rotate", (current + degrees).rem_euclid(360));
+        }
+    }
+
+    let out_path = Path::new(matches.value_of_os("out").unwrap());
+    out.save(out_path)?;
+    println!("Wrote {} page(s) to `{}`", order.len(), out_path.display());
+    Ok(())
+}
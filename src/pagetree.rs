@@ -0,0 +1,126 @@
+use printpdf::lopdf::{self, Dictionary, Object, ObjectId};
+use std::collections::HashSet;
+
+use crate::outline;
+
+/// Attributes a page can inherit from the page tree nodes above it
+pub(crate) const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Replaces the page tree with a flat one holding `pages` in the given order.
+/// A page listed more than once is copied, and pages left out are dropped
+/// along with everything only they referenced.
+pub fn set_pages(doc: &mut lopdf::Document, pages: &[ObjectId]) -> lopdf::Result<()> {
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let tree = doc.get_dictionary(root)?.get(b"Pages")?.as_reference()?;
+
+    let mut kids = Vec::new();
+    let mut seen = HashSet::new();
+    for page_id in pages {
+        let mut page = doc.get_dictionary(*page_id)?.clone();
+        for key in INHERITABLE {
+            if !page.has(key) {
+                if let Some(value) = inherited(doc, *page_id, key) {
+                    page.set(key.to_vec(), value);
+                }
+            }
+        }
+        page.set("Parent", tree);
+        let id = if seen.insert(*page_id) {
+            doc.objects.insert(*page_id, page.into());
+            *page_id
+        } else {
+            doc.add_object(page)
+        };
+        kids.push(id.into());
+    }
+
+    let tree = doc.get_object_mut(tree)?.as_dict_mut()?;
+    tree.set("Count", kids.len() as i64);
+    tree.set("Kids", kids);
+    for key in INHERITABLE {
+        tree.remove(key);
+    }
+    doc.prune_objects();
+    Ok(())
+}
+
+/// A copy of the document made of `pages` in the given order, keeping the
+/// outline entries that point at one of them. Only what these pages and the
+/// catalog use is copied, so a part of a large document costs no more than
+/// its own pages; references to the pages left out, such as links to them,
+/// are left dangling, which readers take as null.
+pub fn rearranged(doc: &lopdf::Document, pages: &[ObjectId]) -> lopdf::Result<lopdf::Document> {
+    let bookmarks = outline::entries(doc)
+        .into_iter()
+        .filter_map(|(title, target)| {
+            let page = pages.iter().position(|p| *p == target)?;
+            Some(outline::Bookmark {
+                title,
+                page,
+                group: None,
+            })
+        })
+        .collect::<Vec<_>>();
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let kept = pages.iter().collect::<HashSet<_>>();
+
+    let mut part = lopdf::Document::with_version(doc.version.clone());
+    part.max_id = doc.max_id;
+    part.trailer = doc.trailer.clone();
+    let mut todo = Vec::new();
+    dict_references(&doc.trailer, &mut todo);
+    while let Some(id) = todo.pop() {
+        if part.objects.contains_key(&id) {
+            continue;
+        }
+        let object = match doc.get_object(id) {
+            Ok(object) => object,
+            Err(_) => continue,
+        };
+        let page = object.as_dict().is_ok_and(|d| d.type_is(b"Page"));
+        if page && !kept.contains(&id) {
+            continue;
+        }
+        let mut object = object.clone();
+        // the outline is made again for the pages kept
+        if id == root {
+            object.as_dict_mut()?.remove(b"Outlines");
+        }
+        references(&object, &mut todo);
+        part.objects.insert(id, object);
+    }
+    outline::remove(&mut part)?;
+    set_pages(&mut part, pages)?;
+    outline::add_outline(&mut part, &bookmarks)?;
+    Ok(part)
+}
+
+/// Adds what `object` refers to, short of parents, to `todo`
+fn references(object: &Object, todo: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => todo.push(*id),
+        Object::Array(items) => items.iter().for_each(|item| references(item, todo)),
+        Object::Dictionary(dict) => dict_references(dict, todo),
+        Object::Stream(stream) => dict_references(&stream.dict, todo),
+        _ => {}
+    }
+}
+
+fn dict_references(dict: &Dictionary, todo: &mut Vec<ObjectId>) {
+    dict.iter()
+        .filter(|(key, _)| key.as_slice() != b"Parent")
+        .for_each(|(_, value)| references(value, todo));
+}
+
+/// The value of `key` on `page`, or else on the closest page tree node above
+/// it that has one
+pub(crate) fn inherited(doc: &lopdf::Document, page: ObjectId, key: &[u8]) -> Option<Object> {
+    let mut node = doc.get_dictionary(page).ok()?;
+    loop {
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+    }
+}
@@ -0,0 +1,75 @@
+use printpdf::Mm;
+
+const NAMED_SIZES: [(&str, f64, f64); 9] = [
+    ("A2", 420.0, 594.0),
+    ("A3", 297.0, 420.0),
+    ("A4", 210.0, 297.0),
+    ("A5", 148.0, 210.0),
+    ("A6", 105.0, 148.0),
+    ("B5", 176.0, 250.0),
+    ("LETTER", 215.9, 279.4),
+    ("LEGAL", 215.9, 355.6),
+    ("TABLOID", 279.4, 431.8),
+];
+
+/// Parses a named paper size (`A4`, `Letter`, ...) or an explicit
+/// `WxHmm`/`WxHin` size into portrait width and height
+pub fn parse_paper_size(s: &str) -> Option<(Mm, Mm)> {
+    let upper = s.trim().to_ascii_uppercase();
+    if let Some((_, w, h)) = NAMED_SIZES.iter().find(|(name, _, _)| *name == upper) {
+        return Some((Mm(*w), Mm(*h)));
+    }
+
+    let (dims, factor) = if let Some(dims) = upper.strip_suffix("MM") {
+        (dims, 1.0)
+    } else if let Some(dims) = upper.strip_suffix("IN") {
+        (dims, 25.4)
+    } else {
+        return None;
+    };
+    let (w, h) = dims.split_once('X')?;
+    let w = w.trim().parse::<f64>().ok()?;
+    let h = h.trim().parse::<f64>().ok()?;
+    (w > 0.0 && h > 0.0).then_some((Mm(w * factor), Mm(h * factor)))
+}
+
+/// Parses a positive percentage such as `50%`; the `%` may be left out
+pub fn parse_percentage(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let value = s
+        .strip_suffix('%')
+        .unwrap_or(s)
+        .trim()
+        .parse::<f64>()
+        .ok()?;
+    (value.is_finite() && value > 0.0).then_some(value)
+}
+
+/// Parses one, two or four comma-separated values as for CSS margins: one
+/// for every side, top and bottom then left and right, or top, right, bottom
+/// and left
+pub fn parse_sides<T: Copy>(s: &str, parse: impl Fn(&str) -> Option<T>) -> Option<[T; 4]> {
+    let values = s.split(',').map(parse).collect::<Option<Vec<_>>>()?;
+    match values[..] {
+        [all] => Some([all; 4]),
+        [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+        [top, right, bottom, left] => Some([top, right, bottom, left]),
+        _ => None,
+    }
+}
+
+/// Parses a length such as `3mm`, `0.125in` or `9pt`; a bare number is in mm
+pub fn parse_length(s: &str) -> Option<Mm> {
+    let s = s.trim().to_ascii_lowercase();
+    let (value, factor) = if let Some(v) = s.strip_suffix("mm") {
+        (v, 1.0)
+    } else if let Some(v) = s.strip_suffix("in") {
+        (v, 25.4)
+    } else if let Some(v) = s.strip_suffix("pt") {
+        (v, 25.4 / 72.0)
+    } else {
+        (s.as_str(), 1.0)
+    };
+    let value = value.trim().parse::<f64>().ok()?;
+    (value.is_finite() && value >= 0.0).then_some(Mm(value * factor))
+}
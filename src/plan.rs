@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use printpdf::image_crate::{self, ImageError};
+
+use crate::{page_width, same_file, style};
+
+/// What reading the header of an input found
+enum Probe {
+    Image {
+        format: String,
+        width: u32,
+        height: u32,
+    },
+    #[cfg(feature = "ffmpeg")]
+    Video,
+    Unsupported(String),
+    Unreadable(String),
+}
+
+fn probe(input: &Path) -> Probe {
+    #[cfg(feature = "ffmpeg")]
+    if crate::video::is_video(input) {
+        return Probe::Video;
+    }
+    #[cfg(feature = "heif")]
+    if yet_another_imgs2pdf::heif::is_heif(input) {
+        return match std::fs::read(input) {
+            Ok(data) => match yet_another_imgs2pdf::heif::dimensions(&data) {
+                Some((width, height)) => Probe::Image {
+                    format: "HEIF".to_owned(),
+                    width,
+                    height,
+                },
+                None => Probe::Unreadable("the image size was not found".to_owned()),
+            },
+            Err(e) => Probe::Unreadable(e.to_string()),
+        };
+    }
+    let reader = match image_crate::io::Reader::open(input).and_then(|r| r.with_guessed_format()) {
+        Ok(reader) => reader,
+        Err(e) => return Probe::Unreadable(e.to_string()),
+    };
+    let format = match reader.format() {
+        Some(format) => format!("{format:?}").to_uppercase(),
+        None => return Probe::Unsupported("the format was not recognized".to_owned()),
+    };
+    match reader.into_dimensions() {
+        Ok((width, height)) => Probe::Image {
+            format,
+            width,
+            height,
+        },
+        Err(ImageError::Unsupported(e)) => Probe::Unsupported(e.to_string()),
+        Err(e) => Probe::Unreadable(e.to_string()),
+    }
+}
+
+/// Prints the order `inputs` would be placed in between the `covers`, with
+/// the format and size of each from its header, and which would be left out
+/// or only get a placeholder page under `unsupported_mode`
+pub fn print(
+    covers: &[Option<PathBuf>; 2],
+    inputs: &[PathBuf],
+    fold_outs: &[PathBuf],
+    unsupported_mode: &str,
+) {
+    let [cover, back_cover] = covers;
+    let planned = cover
+        .iter()
+        .map(|path| (path, Some("cover")))
+        .chain(inputs.iter().map(|path| {
+            let fold_out = fold_outs.iter().any(|f| same_file(f, path));
+            (path, fold_out.then_some("fold-out"))
+        }))
+        .chain(back_cover.iter().map(|path| (path, Some("back cover"))));
+    let mut rows = Vec::new();
+    let (mut placed, mut skipped) = (0, 0);
+    for (path, note) in planned {
+        let (placed_as, description) = match probe(path) {
+            Probe::Image {
+                format,
+                width,
+                height,
+            } => (true, format!("{format} {width}x{height}")),
+            #[cfg(feature = "ffmpeg")]
+            Probe::Video => (true, "video, one page per extracted frame".to_owned()),
+            Probe::Unsupported(reason) if unsupported_mode == "page" => {
+                (true, format!("placeholder page, {reason}"))
+            }
+            Probe::Unsupported(reason) | Probe::Unreadable(reason) => {
+                (false, format!("skipped, {reason}"))
+            }
+        };
+        let description = match note {
+            Some(note) => format!("{description} ({note})"),
+            None => description,
+        };
+        if placed_as {
+            placed += 1;
+        } else {
+            skipped += 1;
+        }
+        rows.push((placed_as.then_some(placed), path, description));
+    }
+
+    println!("Planned order of {} input(s):", rows.len());
+    let width = page_width(rows.iter().filter_map(|(n, _, _)| *n));
+    for (n, path, description) in rows {
+        match n {
+            Some(n) => println!("  {n:>width$}  {}  {description}", path.display()),
+            None => {
+                let line = format!("  {:>width$}  {}  {description}", "-", path.display());
+                println!("{}", style::warning(&line));
+            }
+        }
+    }
+    println!("{placed} input(s) would be placed and {skipped} skipped, nothing was written");
+}
@@ -0,0 +1,89 @@
+use printpdf::lopdf::{self, dictionary, Object, Stream};
+
+use crate::info::text_string;
+
+/// A document embedded into a portfolio
+pub struct Member {
+    pub file_name: String,
+    pub description: String,
+    pub pdf: Vec<u8>,
+}
+
+/// Embeds every member into the document and turns it into a PDF Portfolio,
+/// which viewers that support collections show as a list of the members
+/// instead of the document's own pages
+pub fn add_collection(doc: &mut lopdf::Document, members: &[Member]) -> lopdf::Result<()> {
+    let mut names = Vec::new();
+    for member in members {
+        let mut file = Stream::new(
+            dictionary! {
+                "Type" => "EmbeddedFile",
+                "Subtype" => "application/pdf",
+                "Params" => dictionary! { "Size" => member.pdf.len() as i64 },
+            },
+            member.pdf.clone(),
+        );
+        file.compress()?;
+        let file_id = doc.add_object(file);
+        let spec_id = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(ascii_file_name(&member.file_name)),
+            "UF" => text_string(&member.file_name),
+            "Desc" => text_string(&member.description),
+            "EF" => dictionary! { "F" => file_id },
+        });
+        names.push((member.file_name.clone(), spec_id));
+    }
+    // name trees have to be sorted by key
+    names.sort_by(|a, b| a.0.cmp(&b.0));
+    let names = names
+        .into_iter()
+        .flat_map(|(name, id)| [text_string(&name), id.into()])
+        .collect::<Vec<Object>>();
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(root)?.as_dict_mut()?;
+    catalog.set(
+        "Names",
+        dictionary! { "EmbeddedFiles" => dictionary! { "Names" => names } },
+    );
+    catalog.set(
+        "Collection",
+        dictionary! {
+            "Type" => "Collection",
+            "View" => "D",
+            "Schema" => dictionary! {
+                "Type" => "CollectionSchema",
+                "FileName" => dictionary! {
+                    "Type" => "CollectionField",
+                    "Subtype" => "F",
+                    "N" => Object::string_literal("Name"),
+                    "O" => 0,
+                },
+                "Description" => dictionary! {
+                    "Type" => "CollectionField",
+                    "Subtype" => "Desc",
+                    "N" => Object::string_literal("Description"),
+                    "O" => 1,
+                },
+                "Size" => dictionary! {
+                    "Type" => "CollectionField",
+                    "Subtype" => "Size",
+                    "N" => Object::string_literal("Size"),
+                    "O" => 2,
+                },
+            },
+            "Sort" => dictionary! { "S" => "FileName" },
+        },
+    );
+    // collections were introduced with PDF 1.7
+    doc.version = "1.7".to_owned();
+    Ok(())
+}
+
+/// The `/F` entry predates Unicode file names, `/UF` carries the real one
+fn ascii_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect()
+}
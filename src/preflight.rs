@@ -0,0 +1,173 @@
+use printpdf::lopdf::{self, Dictionary, Object, ObjectId, Stream};
+
+use yet_another_imgs2pdf::prepress::{page_box, page_size};
+
+/// Resolution below which images print visibly soft
+const MIN_PRINT_DPI: f64 = 300.0;
+/// Difference in points up to which two page sizes count as the same
+const SIZE_TOLERANCE: f64 = 0.5;
+
+pub struct Violation {
+    pub page: u32,
+    pub message: String,
+}
+
+/// Checks a finished document against basic rules for commercial printing:
+/// image resolution, CMYK or gray color, one trim size throughout and bleed
+/// around every page
+pub fn preflight(doc: &lopdf::Document) -> lopdf::Result<Vec<Violation>> {
+    let mut violations = Vec::new();
+    let mut first_trim = None;
+
+    for (page, page_id) in doc.get_pages() {
+        let mut violation = |message: String| violations.push(Violation { page, message });
+
+        for img in placed_images(doc, page_id)? {
+            if img.dpi < MIN_PRINT_DPI {
+                violation(format!(
+                    "image /{} is only {:.0} dpi, at least {:.0} dpi is needed for print",
+                    img.name, img.dpi, MIN_PRINT_DPI
+                ));
+            }
+            if img.color_space == "DeviceRGB" || img.color_space == "CalRGB" {
+                violation(format!(
+                    "image /{} uses {}, print expects CMYK or gray",
+                    img.name, img.color_space
+                ));
+            }
+        }
+
+        let (w, h) = page_size(doc, page_id)?;
+        let trim = page_box(doc, page_id, b"TrimBox").unwrap_or([0.0, 0.0, w, h]);
+        let trim_size = (trim[2] - trim[0], trim[3] - trim[1]);
+        match first_trim {
+            None => first_trim = Some(trim_size),
+            Some((fw, fh)) => {
+                if (fw - trim_size.0).abs() > SIZE_TOLERANCE
+                    || (fh - trim_size.1).abs() > SIZE_TOLERANCE
+                {
+                    violation(format!(
+                        "trim size {:.1}x{:.1}mm differs from the first page's {:.1}x{:.1}mm",
+                        to_mm(trim_size.0),
+                        to_mm(trim_size.1),
+                        to_mm(fw),
+                        to_mm(fh)
+                    ));
+                }
+            }
+        }
+
+        match page_box(doc, page_id, b"BleedBox") {
+            Ok(bleed)
+                if bleed
+                    .iter()
+                    .zip(trim)
+                    .any(|(b, t)| (b - t).abs() > SIZE_TOLERANCE) => {}
+            _ => violation("no bleed around the trim box".to_string()),
+        }
+    }
+    Ok(violations)
+}
+
+pub struct PlacedImage {
+    pub name: String,
+    /// The lower of the horizontal and vertical resolution as placed
+    pub dpi: f64,
+    pub color_space: String,
+}
+
+/// Every image drawn on a page with the resolution it ends up at, following
+/// the transformation matrix through the page content
+pub fn placed_images(doc: &lopdf::Document, page_id: ObjectId) -> lopdf::Result<Vec<PlacedImage>> {
+    let content = doc.get_and_decode_page_content(page_id)?;
+    let mut ctm = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    let mut stack = Vec::new();
+    let mut images = Vec::new();
+
+    for op in content.operations {
+        match op.operator.as_str() {
+            "q" => stack.push(ctm),
+            "Q" => ctm = stack.pop().unwrap_or(ctm),
+            "cm" if op.operands.len() == 6 => {
+                let mut m = [0.0; 6];
+                for (v, o) in m.iter_mut().zip(&op.operands) {
+                    *v = o.as_float()?;
+                }
+                ctm = concat(m, ctm);
+            }
+            "Do" => {
+                let name = match op.operands.first().map(Object::as_name) {
+                    Some(Ok(name)) => name,
+                    _ => continue,
+                };
+                let stream = match xobject(doc, page_id, name) {
+                    Some(stream) => stream,
+                    None => continue,
+                };
+                let dict = &stream.dict;
+                if dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Image") {
+                    continue;
+                }
+                let px_w = dict.get(b"Width").and_then(Object::as_float)?;
+                let px_h = dict.get(b"Height").and_then(Object::as_float)?;
+                let placed_w = ctm[0].hypot(ctm[1]) / 72.0;
+                let placed_h = ctm[2].hypot(ctm[3]) / 72.0;
+                images.push(PlacedImage {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    dpi: (px_w / placed_w).min(px_h / placed_h),
+                    color_space: color_space_name(doc, dict),
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(images)
+}
+
+/// `m` applied before `ctm`, both as `[a b c d e f]`
+fn concat(m: [f64; 6], ctm: [f64; 6]) -> [f64; 6] {
+    [
+        m[0] * ctm[0] + m[1] * ctm[2],
+        m[0] * ctm[1] + m[1] * ctm[3],
+        m[2] * ctm[0] + m[3] * ctm[2],
+        m[2] * ctm[1] + m[3] * ctm[3],
+        m[4] * ctm[0] + m[5] * ctm[2] + ctm[4],
+        m[4] * ctm[1] + m[5] * ctm[3] + ctm[5],
+    ]
+}
+
+fn xobject<'a>(doc: &'a lopdf::Document, page_id: ObjectId, name: &[u8]) -> Option<&'a Stream> {
+    let (inline, ids) = doc.get_page_resources(page_id);
+    let resources = inline
+        .into_iter()
+        .chain(ids.into_iter().filter_map(|id| doc.get_dictionary(id).ok()));
+    for res in resources {
+        let xobjects = match res.get(b"XObject").map(|o| doc.dereference(o)) {
+            Ok(Ok((_, Object::Dictionary(d)))) => d,
+            _ => continue,
+        };
+        if let Ok(Ok((_, Object::Stream(stream)))) = xobjects.get(name).map(|o| doc.dereference(o))
+        {
+            return Some(stream);
+        }
+    }
+    None
+}
+
+/// The family of an image's color space, e.g. `DeviceRGB` or `ICCBased`
+fn color_space_name(doc: &lopdf::Document, dict: &Dictionary) -> String {
+    let cs = match dict.get(b"ColorSpace").map(|o| doc.dereference(o)) {
+        Ok(Ok((_, cs))) => cs,
+        _ => return "unknown".to_string(),
+    };
+    let name = match cs {
+        Object::Array(a) => a.first().and_then(|o| o.as_name().ok()),
+        o => o.as_name().ok(),
+    };
+    name.map(|n| String::from_utf8_lossy(n).into_owned())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn to_mm(pt: f64) -> f64 {
+    pt * 25.4 / 72.0
+}
@@ -0,0 +1,276 @@
+use printpdf::lopdf::{
+    self,
+    content::{Content, Operation},
+    dictionary, Object, ObjectId, Stream,
+};
+
+/// Slug area added around the trimmed page for marks, in points (1/2 inch)
+const SLUG: f64 = 36.0;
+/// Gap between the trim edge and the start of a crop mark
+const CROP_OFFSET: f64 = 9.0;
+const CROP_LENGTH: f64 = 18.0;
+const REGISTRATION_RADIUS: f64 = 5.0;
+/// Name the slug font is registered under in the page resources
+const SLUG_FONT: &str = "FSlug";
+
+/// Grows every page by `bleed` points on all sides and scales the content up
+/// uniformly so that it covers the bleed, marking the original page as the
+/// TrimBox and the grown one as the BleedBox
+pub fn add_bleed(doc: &mut lopdf::Document, bleed: f64) -> lopdf::Result<()> {
+    for page_id in doc.get_pages().into_values() {
+        let (w, h) = page_size(doc, page_id)?;
+        let (bw, bh) = (w + 2.0 * bleed, h + 2.0 * bleed);
+        let scale = (bw / w).max(bh / h);
+        let (tx, ty) = ((bw - w * scale) / 2.0, (bh - h * scale) / 2.0);
+
+        let before = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        scale.into(),
+                        0.into(),
+                        0.into(),
+                        scale.into(),
+                        tx.into(),
+                        ty.into(),
+                    ],
+                ),
+            ],
+        };
+        let after = Content {
+            operations: vec![Operation::new("Q", vec![])],
+        };
+        wrap_page_content(doc, page_id, before.encode()?, after.encode()?)?;
+
+        let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+        let media = [0.0, 0.0, bw, bh];
+        page.set("MediaBox", box_object(media));
+        page.set("CropBox", box_object(media));
+        page.set("BleedBox", box_object(media));
+        page.set("TrimBox", box_object([bleed, bleed, bleed + w, bleed + h]));
+    }
+    Ok(())
+}
+
+pub struct PrinterMarks {
+    /// Adds a line with the document title, page number and date to the slug
+    pub slug_info: bool,
+}
+
+/// Grows every page by a slug on all sides and draws crop and registration
+/// marks around its TrimBox, which defaults to the original page
+pub fn add_printer_marks(
+    doc: &mut lopdf::Document,
+    marks: &PrinterMarks,
+    title: &str,
+    date: printpdf::OffsetDateTime,
+) -> lopdf::Result<()> {
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let date = date.format("%Y-%m-%d");
+    let pages = doc.get_pages();
+    let total = pages.len();
+
+    for (n, page_id) in pages {
+        let (w, h) = page_size(doc, page_id)?;
+        let shift = |b: [f64; 4]| b.map(|v| v + SLUG);
+        let [x0, y0, x1, y1] =
+            shift(page_box(doc, page_id, b"TrimBox").unwrap_or([0.0, 0.0, w, h]));
+        let bleed = page_box(doc, page_id, b"BleedBox").ok().map(shift);
+        // marks have to stay clear of any bleed
+        let crop_offset = CROP_OFFSET.max(x0 - SLUG + 3.0);
+        let media = [0.0, 0.0, w + 2.0 * SLUG, h + 2.0 * SLUG];
+
+        let mut ops = vec![
+            Operation::new("Q", vec![]),
+            Operation::new("q", vec![]),
+            Operation::new("w", vec![0.25.into()]),
+            // registration color: all separations at full strength
+            Operation::new("K", vec![1.into(), 1.into(), 1.into(), 1.into()]),
+            Operation::new("k", vec![1.into(), 1.into(), 1.into(), 1.into()]),
+        ];
+        for (x, y, dx, dy) in [
+            (x0, y0, -1.0, -1.0),
+            (x1, y0, 1.0, -1.0),
+            (x0, y1, -1.0, 1.0),
+            (x1, y1, 1.0, 1.0),
+        ] {
+            line(
+                &mut ops,
+                (x + dx * crop_offset, y),
+                (x + dx * (crop_offset + CROP_LENGTH), y),
+            );
+            line(
+                &mut ops,
+                (x, y + dy * crop_offset),
+                (x, y + dy * (crop_offset + CROP_LENGTH)),
+            );
+        }
+        for center in [
+            (SLUG + w / 2.0, SLUG / 2.0),
+            (SLUG + w / 2.0, SLUG + h + SLUG / 2.0),
+            (SLUG / 2.0, SLUG + h / 2.0),
+            (SLUG + w + SLUG / 2.0, SLUG + h / 2.0),
+        ] {
+            registration_mark(&mut ops, center);
+        }
+        if marks.slug_info {
+            let info = format!("{title}  |  page {n} of {total}  |  {date}");
+            ops.extend([
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec![SLUG_FONT.into(), 6.into()]),
+                Operation::new("Td", vec![x0.into(), 6.into()]),
+                Operation::new("Tj", vec![Object::string_literal(ascii(&info))]),
+                Operation::new("ET", vec![]),
+            ]);
+        }
+        ops.push(Operation::new("Q", vec![]));
+
+        let before = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        1.into(),
+                        0.into(),
+                        0.into(),
+                        1.into(),
+                        SLUG.into(),
+                        SLUG.into(),
+                    ],
+                ),
+            ],
+        };
+        let after = Content { operations: ops };
+        wrap_page_content(doc, page_id, before.encode()?, after.encode()?)?;
+        add_resource(doc, page_id, "Font", SLUG_FONT, font_id)?;
+
+        let page = doc.get_object_mut(page_id)?.as_dict_mut()?;
+        page.set("MediaBox", box_object(media));
+        page.set("CropBox", box_object(media));
+        page.set("TrimBox", box_object([x0, y0, x1, y1]));
+        if let Some(bleed) = bleed {
+            page.set("BleedBox", box_object(bleed));
+        }
+    }
+    Ok(())
+}
+
+/// A page boundary box such as `TrimBox` as `[llx, lly, urx, ury]`
+pub fn page_box(doc: &lopdf::Document, page_id: ObjectId, key: &[u8]) -> lopdf::Result<[f64; 4]> {
+    let b = doc.get_dictionary(page_id)?.get(key)?.as_array()?;
+    let v = |i: usize| b.get(i).ok_or(lopdf::Error::Type)?.as_float();
+    Ok([v(0)?, v(1)?, v(2)?, v(3)?])
+}
+
+fn box_object(b: [f64; 4]) -> Object {
+    b.iter()
+        .map(|v| Object::Real(*v))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Width and height of a page's MediaBox in points
+pub fn page_size(doc: &lopdf::Document, page_id: ObjectId) -> lopdf::Result<(f64, f64)> {
+    let [x0, y0, x1, y1] = page_box(doc, page_id, b"MediaBox")?;
+    Ok((x1 - x0, y1 - y0))
+}
+
+/// Surrounds the existing content streams of a page with two new ones,
+/// without decoding the original content
+pub fn wrap_page_content(
+    doc: &mut lopdf::Document,
+    page_id: ObjectId,
+    before: Vec<u8>,
+    after: Vec<u8>,
+) -> lopdf::Result<()> {
+    let existing = doc.get_page_contents(page_id);
+    let before = doc.add_object(Stream::new(dictionary! {}, before));
+    let after = doc.add_object(Stream::new(dictionary! {}, after));
+
+    let mut contents = vec![before.into()];
+    contents.extend(existing.into_iter().map(Object::from));
+    contents.push(after.into());
+    doc.get_object_mut(page_id)?
+        .as_dict_mut()?
+        .set("Contents", contents);
+    Ok(())
+}
+
+/// Registers `id` under `name` in the `kind` category (such as `Font` or
+/// `ExtGState`) of a page's resources, following references to shared
+/// resource and category dictionaries
+pub fn add_resource(
+    doc: &mut lopdf::Document,
+    page_id: ObjectId,
+    kind: &str,
+    name: &str,
+    id: ObjectId,
+) -> lopdf::Result<()> {
+    let resources = doc.get_or_create_resources(page_id)?.as_dict_mut()?;
+    let category = match resources.get(kind.as_bytes()) {
+        Ok(Object::Reference(category)) => *category,
+        Ok(Object::Dictionary(_)) => {
+            if let Ok(Object::Dictionary(category)) = resources.get_mut(kind.as_bytes()) {
+                category.set(name, id);
+            }
+            return Ok(());
+        }
+        _ => {
+            resources.set(kind, dictionary! { name => id });
+            return Ok(());
+        }
+    };
+    doc.get_object_mut(category)?.as_dict_mut()?.set(name, id);
+    Ok(())
+}
+
+fn line(ops: &mut Vec<Operation>, from: (f64, f64), to: (f64, f64)) {
+    ops.extend([
+        Operation::new("m", vec![from.0.into(), from.1.into()]),
+        Operation::new("l", vec![to.0.into(), to.1.into()]),
+        Operation::new("S", vec![]),
+    ]);
+}
+
+fn registration_mark(ops: &mut Vec<Operation>, (x, y): (f64, f64)) {
+    let r = REGISTRATION_RADIUS;
+    // cubic bezier approximation of a circle
+    let k = r * 0.5523;
+    ops.push(Operation::new("m", vec![(x + r).into(), y.into()]));
+    for [c1, c2, end] in [
+        [(x + r, y + k), (x + k, y + r), (x, y + r)],
+        [(x - k, y + r), (x - r, y + k), (x - r, y)],
+        [(x - r, y - k), (x - k, y - r), (x, y - r)],
+        [(x + k, y - r), (x + r, y - k), (x + r, y)],
+    ] {
+        ops.push(Operation::new(
+            "c",
+            vec![
+                c1.0.into(),
+                c1.1.into(),
+                c2.0.into(),
+                c2.1.into(),
+                end.0.into(),
+                end.1.into(),
+            ],
+        ));
+    }
+    ops.push(Operation::new("S", vec![]));
+    line(ops, (x - 1.6 * r, y), (x + 1.6 * r, y));
+    line(ops, (x, y - 1.6 * r), (x, y + 1.6 * r));
+}
+
+/// The standard fonts use WinAnsiEncoding, so anything outside ASCII is replaced
+pub fn ascii(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii() { c } else { '?' })
+        .collect()
+}
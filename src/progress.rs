@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use yet_another_imgs2pdf::{ProgressSink, Warning, WarningCode};
+
+use crate::{i18n, style};
+
+/// Characters the bar itself is wide
+const WIDTH: usize = 24;
+/// Longest file name shown after the bar, in characters
+const NAME_WIDTH: usize = 32;
+
+#[derive(Default)]
+struct State {
+    /// Inputs expected, zero while none are being added
+    total: usize,
+    done: usize,
+    started: Option<Instant>,
+    current: Option<(PathBuf, Instant)>,
+    slowest: Option<(PathBuf, Duration)>,
+    skipped: Vec<PathBuf>,
+}
+
+impl State {
+    fn finish_current(&mut self) {
+        if let Some((input, since)) = self.current.take() {
+            self.done += 1;
+            let took = since.elapsed();
+            if self
+                .slowest
+                .as_ref()
+                .is_none_or(|(_, slowest)| took > *slowest)
+            {
+                self.slowest = Some((input, took));
+            }
+        }
+    }
+}
+
+/// The progress bar of the inputs being added, fed by the merger as it goes
+/// and shared with the loop that adds them
+#[derive(Clone, Default)]
+pub struct Bar {
+    state: Rc<RefCell<State>>,
+    quiet: bool,
+}
+
+impl Bar {
+    /// With `quiet` neither the bar nor the summary is shown
+    pub fn new(quiet: bool) -> Self {
+        Bar {
+            quiet,
+            ..Default::default()
+        }
+    }
+
+    /// Starts over for `total` inputs
+    pub fn start(&self, total: usize) {
+        *self.state.borrow_mut() = State {
+            total,
+            started: Some(Instant::now()),
+            ..Default::default()
+        };
+    }
+
+    /// Clears the bar and prints how the inputs added since [`Bar::start`]
+    /// went: how many, how fast, the slowest and those skipped
+    pub fn finish(&self) {
+        let mut state = self.state.borrow_mut();
+        state.finish_current();
+        style::end_progress();
+        let elapsed = state.started.map_or(Duration::ZERO, |t| t.elapsed());
+        if !self.quiet && state.total > 0 {
+            let seconds = format!("{:.2}", elapsed.as_secs_f64());
+            let rate = format!("{:.1}", rate(state.done, elapsed));
+            let args: [(&str, &dyn std::fmt::Display); 3] = [
+                ("count", &state.done),
+                ("seconds", &seconds),
+                ("rate", &rate),
+            ];
+            let mut summary = i18n::t("processed", &args);
+            if let Some((input, took)) = state.slowest.as_ref().filter(|_| state.done > 1) {
+                let took = format!("{:.2}", took.as_secs_f64());
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("file", &input.display()), ("seconds", &took)];
+                summary.push_str(&i18n::t("slowest", &args));
+            }
+            println!("{summary}");
+            if !state.skipped.is_empty() {
+                let args: [(&str, &dyn std::fmt::Display); 2] =
+                    [("count", &state.skipped.len()), ("total", &state.total)];
+                println!("{}", style::warning(&i18n::t("skipped-summary", &args)));
+                for input in &state.skipped {
+                    println!("  {}", input.display());
+                }
+            }
+        }
+        state.total = 0;
+    }
+
+    fn draw(&self, state: &State) {
+        if self.quiet {
+            return;
+        }
+        let done = state.done.min(state.total);
+        let filled = WIDTH * done / state.total;
+        let mut bar = "=".repeat(filled);
+        if filled < WIDTH {
+            bar.push('>');
+            bar.push_str(&" ".repeat(WIDTH - filled - 1));
+        }
+        let elapsed = state.started.map_or(Duration::ZERO, |t| t.elapsed());
+        let (rate, eta) = match done {
+            0 => ("-".to_owned(), "-".to_owned()),
+            _ => {
+                let left = elapsed.mul_f64((state.total - done) as f64 / done as f64);
+                (format!("{:.1}/s", rate(done, elapsed)), clock(left))
+            }
+        };
+        let name = state
+            .current
+            .as_ref()
+            .and_then(|(input, _)| input.file_name())
+            .map(|name| shorten(&name.to_string_lossy()))
+            .unwrap_or_default();
+        style::progress(&format!(
+            "[{bar}] {done}/{} {:>3}%  {rate}  ETA {eta}  {name}",
+            state.total,
+            100 * done / state.total
+        ));
+    }
+}
+
+impl ProgressSink for Bar {
+    fn input_started(&mut self, _index: usize, input: &Path) {
+        let mut state = self.state.borrow_mut();
+        if state.total == 0 {
+            return;
+        }
+        state.finish_current();
+        state.current = Some((input.to_path_buf(), Instant::now()));
+        self.draw(&state);
+    }
+
+    fn warning(&mut self, warning: &Warning) {
+        let mut state = self.state.borrow_mut();
+        let skipped = [WarningCode::SkippedInput, WarningCode::UnsupportedFormat];
+        if state.total == 0 || !skipped.contains(&warning.code) {
+            return;
+        }
+        if let Some(input) = &warning.source {
+            if !state.skipped.contains(input) {
+                state.skipped.push(input.clone());
+            }
+        }
+    }
+}
+
+/// Inputs per second
+fn rate(done: usize, elapsed: Duration) -> f64 {
+    done as f64 / elapsed.as_secs_f64().max(0.001)
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour on
+fn clock(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// `name` cut down to [`NAME_WIDTH`] characters, keeping its end where the
+/// extension and any numbering are
+fn shorten(name: &str) -> String {
+    let count = name.chars().count();
+    if count <= NAME_WIDTH {
+        return name.to_owned();
+    }
+    let tail = name.chars().skip(count - (NAME_WIDTH - 1));
+    std::iter::once('…').chain(tail).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_eta_and_names() {
+        assert_eq!(clock(Duration::from_secs(75)), "1:15");
+        assert_eq!(clock(Duration::from_secs(3725)), "1:02:05");
+        assert_eq!(shorten("a.png"), "a.png");
+        let long = format!("{}_0042.png", "scan".repeat(10));
+        let short = shorten(&long);
+        assert_eq!(short.chars().count(), NAME_WIDTH);
+        assert!(short.starts_with('…') && short.ends_with("_0042.png"));
+    }
+}
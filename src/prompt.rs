@@ -0,0 +1,60 @@
+use std::ffi::OsString;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// Whether the program was started without arguments from a terminal, as
+/// happens when it is launched from a file manager
+pub fn wanted(args: &[OsString]) -> bool {
+    args.len() == 1 && stdin().is_terminal()
+}
+
+/// Asks for the folder and output path, returning the arguments to run with
+/// or `None` if input ended before all answers were given
+pub fn ask_for_inputs() -> Option<Vec<OsString>> {
+    println!("No inputs were given, so a few questions instead (Ctrl+C to quit).");
+    let dir = loop {
+        let answer = ask("Folder with the images: ")?;
+        let dir = PathBuf::from(&answer);
+        if dir.is_dir() {
+            break dir;
+        }
+        println!("`{answer}` is not a folder");
+    };
+    let mut suggested = dir.clone();
+    suggested.set_extension("pdf");
+    let out = ask(&format!("Output PDF [{}]: ", suggested.display()))?;
+    let out = if out.is_empty() {
+        suggested
+    } else {
+        PathBuf::from(out)
+    };
+    Some(vec![
+        "--dir".into(),
+        dir.into(),
+        "--out".into(),
+        out.into(),
+        "--auto-sort".into(),
+    ])
+}
+
+/// Waits for Enter so that a window opened for the run stays readable
+pub fn wait_before_closing() {
+    let _ = ask("Press Enter to close");
+}
+
+/// Reads one answer, without the quotes file managers add to dropped paths.
+/// Gives `None` once input ends.
+fn ask(question: &str) -> Option<String> {
+    print!("{question}");
+    stdout().flush().ok()?;
+    let mut line = String::new();
+    if stdin().read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let line = line.trim();
+    let line = line
+        .strip_prefix(['"', '\''])
+        .and_then(|l| l.strip_suffix(['"', '\'']))
+        .unwrap_or(line);
+    Some(line.to_owned())
+}
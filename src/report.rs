@@ -0,0 +1,47 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::{fs, io};
+
+use yet_another_imgs2pdf::Warning;
+
+/// Writes `warnings` to `path` as a JSON array of objects with their `code`,
+/// `severity`, `source` file (or null) and `message`
+pub fn write(path: &Path, warnings: &[Warning]) -> io::Result<()> {
+    let mut json = String::from("[");
+    for (i, w) in warnings.iter().enumerate() {
+        let _ = write!(json, "{}\n  {}", if i > 0 { "," } else { "" }, to_json(w));
+    }
+    json.push_str(if warnings.is_empty() { "]\n" } else { "\n]\n" });
+    fs::write(path, json)
+}
+
+/// One warning as a JSON object on a single line
+pub fn to_json(w: &Warning) -> String {
+    let source = match &w.source {
+        Some(source) => quote(&source.to_string_lossy()),
+        None => "null".to_owned(),
+    };
+    format!(
+        "{{\"code\": {}, \"severity\": {}, \"source\": {source}, \"message\": {}}}",
+        quote(w.code.as_str()),
+        quote(w.severity.as_str()),
+        quote(&w.message),
+    )
+}
+
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,37 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Files taken out of an input into a scratch directory of their own, which
+/// is removed with them on drop
+pub struct ScratchDir {
+    dir: PathBuf,
+    pub paths: Vec<PathBuf>,
+}
+
+impl ScratchDir {
+    /// Makes the scratch directory for the `kind` of files taken out of
+    /// `input`, named after this process and the input so that neither
+    /// concurrent runs nor inputs share one
+    pub fn new(kind: &str, input: &Path) -> io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "imgs2pdf-{kind}-{}-{}",
+            std::process::id(),
+            input.file_stem().unwrap_or_default().to_string_lossy()
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            paths: Vec::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,679 @@
+use clap::{Arg, ArgMatches, Command};
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::error::Error;
+use std::hash::BuildHasher;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::http::{self, Request, Response};
+use crate::metrics::{Metrics, StageTimer};
+use crate::remote;
+use crate::report::{quote, to_json};
+use crate::{parse_duration, style};
+use yet_another_imgs2pdf::{
+    CancelToken, Error as MergeError, PageOptions, PdfMergerBuilder, Warning,
+};
+
+pub fn command() -> Command<'static> {
+    Command::new("serve")
+        .about("Convert images uploaded over HTTP, one queued job per upload")
+        .arg(
+            Arg::new("listen")
+                .help("Address and port to listen on")
+                .default_value("127.0.0.1:8080")
+                .long("listen"),
+        )
+        .arg(
+            Arg::new("concurrency")
+                .help("Jobs converted at the same time [default: number of processors]")
+                .takes_value(true)
+                .long("concurrency"),
+        )
+        .arg(
+            Arg::new("max-queued")
+                .help("Jobs waiting at most, further uploads are turned away")
+                .default_value("100")
+                .long("max-queued"),
+        )
+        .arg(
+            Arg::new("retention")
+                .help("How long finished jobs and their PDFs are kept, e.g. 30m or 2h")
+                .default_value("1h")
+                .long("retention"),
+        )
+        .arg(
+            Arg::new("notify-url")
+                .help("POST the status of every finished job as JSON to this http:// URL")
+                .takes_value(true)
+                .long("notify-url"),
+        )
+        .arg(
+            Arg::new("public-url")
+                .help("Address the server is reached at, for the links to PDFs [default: http://<listen>]")
+                .takes_value(true)
+                .long("public-url"),
+        )
+        .arg(
+            Arg::new("max-upload")
+                .help("Largest upload accepted, in MB")
+                .default_value("256")
+                .long("max-upload"),
+        )
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Priority {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+enum Status {
+    Queued,
+    Running,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Done => "done",
+            Status::Failed(_) => "failed",
+            Status::Cancelled => "cancelled",
+        }
+    }
+}
+
+struct Job {
+    id: String,
+    priority: Priority,
+    title: String,
+    /// Uploaded files in order, handed to the worker once it starts
+    inputs: Vec<(PathBuf, Vec<u8>)>,
+    files: usize,
+    status: Status,
+    cancel: CancelToken,
+    pages: usize,
+    warnings: Vec<Warning>,
+    pdf: Option<Vec<u8>>,
+    submitted: Instant,
+    finished: Option<Instant>,
+}
+
+/// Jobs by their order of submission, and the queued ones by priority and
+/// then that order
+#[derive(Default)]
+struct Queue {
+    jobs: BTreeMap<u64, Job>,
+    waiting: BinaryHeap<(Priority, Reverse<u64>)>,
+    next: u64,
+}
+
+impl Queue {
+    fn push(&mut self, job: Job) -> u64 {
+        let seq = self.next;
+        self.next += 1;
+        self.waiting.push((job.priority, Reverse(seq)));
+        self.jobs.insert(seq, job);
+        seq
+    }
+
+    /// The next queued job to run, skipping those cancelled while waiting
+    fn pop(&mut self) -> Option<u64> {
+        while let Some((_, Reverse(seq))) = self.waiting.pop() {
+            if matches!(self.jobs.get(&seq), Some(job) if matches!(job.status, Status::Queued)) {
+                return Some(seq);
+            }
+        }
+        None
+    }
+
+    fn queued(&self) -> usize {
+        self.jobs
+            .values()
+            .filter(|job| matches!(job.status, Status::Queued))
+            .count()
+    }
+
+    fn running(&self) -> usize {
+        self.jobs
+            .values()
+            .filter(|job| matches!(job.status, Status::Running))
+            .count()
+    }
+
+    /// How many queued jobs will start before `seq`, counting from 1
+    fn position(&self, seq: u64) -> Option<usize> {
+        let key = (self.jobs.get(&seq)?.priority, Reverse(seq));
+        let ahead = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.status, Status::Queued))
+            .filter(|(s, job)| (job.priority, Reverse(**s)) > key)
+            .count();
+        Some(ahead + 1)
+    }
+
+    fn find(&self, id: &str) -> Option<u64> {
+        self.jobs
+            .iter()
+            .find(|(_, job)| job.id == id)
+            .map(|(seq, _)| *seq)
+    }
+
+    /// Drops the jobs that finished more than `retention` ago
+    fn clean_up(&mut self, retention: Duration) {
+        self.jobs
+            .retain(|_, job| job.finished.is_none_or(|at| at.elapsed() < retention));
+    }
+}
+
+struct Server {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    max_queued: usize,
+    retention: Duration,
+    max_upload: usize,
+    ids: RandomState,
+    metrics: Arc<Metrics>,
+    notify_url: Option<String>,
+    /// Prefix of the links to PDFs, without a trailing slash
+    public_url: String,
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let concurrency = match matches.value_of("concurrency").map(str::parse::<usize>) {
+        Some(Ok(n)) if n > 0 => n,
+        Some(_) => {
+            eprintln!("Value <concurrency> must be a positive int");
+            exit(1)
+        }
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let max_queued = match matches.value_of("max-queued").unwrap().parse::<usize>() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("Value <max-queued> must be a positive int");
+            exit(1)
+        }
+    };
+    let retention = match parse_duration(matches.value_of("retention").unwrap()) {
+        Some(d) => d,
+        None => {
+            eprintln!("Value <retention> must be a duration such as 30m or 2h");
+            exit(1)
+        }
+    };
+    let max_upload = match matches.value_of("max-upload").unwrap().parse::<usize>() {
+        Ok(mb) if mb > 0 => mb.saturating_mul(1024 * 1024),
+        _ => {
+            eprintln!("Value <max-upload> must be a positive int");
+            exit(1)
+        }
+    };
+    let notify_url = matches.value_of("notify-url").map(|url| {
+        if remote::parse_url(url).is_none() {
+            eprintln!("Value <notify-url> must be a URL starting with http://");
+            exit(1)
+        }
+        url.to_owned()
+    });
+    let listen = matches.value_of("listen").unwrap();
+    let listener = match TcpListener::bind(listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not listen on `{listen}`: {e}");
+            exit(1)
+        }
+    };
+
+    let server = Arc::new(Server {
+        queue: Mutex::new(Queue::default()),
+        ready: Condvar::new(),
+        max_queued,
+        retention,
+        max_upload,
+        ids: RandomState::new(),
+        metrics: Arc::default(),
+        notify_url,
+        public_url: match matches.value_of("public-url") {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None => format!("http://{}", listener.local_addr()?),
+        },
+    });
+    for _ in 0..concurrency {
+        let server = Arc::clone(&server);
+        thread::spawn(move || work(&server));
+    }
+    {
+        let server = Arc::clone(&server);
+        let every = (retention / 4).clamp(Duration::from_secs(1), Duration::from_secs(60));
+        thread::spawn(move || loop {
+            thread::sleep(every);
+            server.queue.lock().unwrap().clean_up(server.retention);
+        });
+    }
+
+    println!(
+        "Listening on http://{}, converting {concurrency} job(s) at a time",
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!(
+                    "{}",
+                    style::warning(&format!("Could not accept a connection: {e}"))
+                );
+                continue;
+            }
+        };
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(30)));
+            let response = match http::read_request(&stream, server.max_upload) {
+                Ok(request) => server.handle(&request),
+                Err(rejected) => Response::error(rejected.status, &rejected.reason),
+            };
+            let _ = response.write(&mut stream);
+        });
+    }
+    Ok(())
+}
+
+/// Runs queued jobs one after the other, highest priority first
+fn work(server: &Server) {
+    loop {
+        let (seq, title, inputs, cancel) = {
+            let mut queue = server.queue.lock().unwrap();
+            let seq = loop {
+                match queue.pop() {
+                    Some(seq) => break seq,
+                    None => queue = server.ready.wait(queue).unwrap(),
+                }
+            };
+            let job = queue.jobs.get_mut(&seq).unwrap();
+            job.status = Status::Running;
+            server.metrics.job_started(job.submitted.elapsed());
+            let inputs = std::mem::take(&mut job.inputs);
+            (seq, job.title.clone(), inputs, job.cancel.clone())
+        };
+
+        let started = Instant::now();
+        let result = convert(&title, &inputs, cancel, &server.metrics);
+        let outcome = match result {
+            Ok((pdf, pages, warnings)) => {
+                server
+                    .metrics
+                    .job_finished("done", started.elapsed(), pages, pdf.len());
+                (Status::Done, Some(pdf), pages, warnings)
+            }
+            Err(e) => {
+                let status = match e {
+                    MergeError::Cancelled => Status::Cancelled,
+                    e => Status::Failed(e.to_string()),
+                };
+                server
+                    .metrics
+                    .job_finished(status.as_str(), started.elapsed(), 0, 0);
+                (status, None, 0, Vec::new())
+            }
+        };
+
+        let mut queue = server.queue.lock().unwrap();
+        if let Some(job) = queue.jobs.get_mut(&seq) {
+            (job.status, job.pdf, job.pages, job.warnings) = outcome;
+            job.finished = Some(Instant::now());
+            if let Some(url) = &server.notify_url {
+                notify(url.clone(), server.status_json(&queue, seq));
+            }
+        }
+        queue.clean_up(server.retention);
+    }
+}
+
+/// Delivers `json` to `url` in the background, trying again a few times
+/// when it cannot be delivered
+fn notify(url: String, json: String) {
+    thread::spawn(move || {
+        let headers = [("Content-Type", "application/json".to_owned())];
+        let mut failure = String::new();
+        for wait in [0, 2, 10, 30] {
+            thread::sleep(Duration::from_secs(wait));
+            match remote::send(
+                "POST",
+                &url,
+                &headers,
+                json.as_bytes(),
+                Duration::from_secs(10),
+            ) {
+                Ok(reply) if (200..300).contains(&reply.status) => return,
+                Ok(reply) => failure = format!("it answered {}", reply.status),
+                Err(e) => failure = e.to_string(),
+            }
+        }
+        println!(
+            "{}",
+            style::warning(&format!(
+                "Could not notify `{url}` of a finished job: {failure}"
+            ))
+        );
+    });
+}
+
+/// Merges the uploaded files with the defaults of the command line, skipping
+/// those that cannot be read as the command line does
+fn convert(
+    title: &str,
+    inputs: &[(PathBuf, Vec<u8>)],
+    cancel: CancelToken,
+    metrics: &Arc<Metrics>,
+) -> Result<(Vec<u8>, usize, Vec<Warning>), MergeError> {
+    let mut merger = PdfMergerBuilder::new()
+        .title(title)
+        .cancel_token(cancel)
+        .progress(StageTimer(Arc::clone(metrics)))
+        .jobs(1)
+        .build()?;
+    for (name, data) in inputs {
+        match merger.append_image_data(name, data, PageOptions::default()) {
+            Ok(()) => metrics.input_added(false),
+            Err(MergeError::Cancelled) => return Err(MergeError::Cancelled),
+            Err(e) => {
+                metrics.input_added(true);
+                merger.record_skipped(name, &e);
+            }
+        }
+    }
+    merger.finish();
+    if merger.page_count() == 0 {
+        return Err(MergeError::InvalidOption {
+            option: "upload",
+            reason: "has no file that could be converted".to_owned(),
+        });
+    }
+    let (pages, warnings) = (merger.page_count(), merger.warnings().to_vec());
+    let mut pdf = Vec::new();
+    merger.save(&mut pdf)?;
+    Ok((pdf, pages, warnings))
+}
+
+impl Server {
+    fn handle(&self, request: &Request) -> Response {
+        let segments = request
+            .path
+            .trim_matches('/')
+            .split('/')
+            .collect::<Vec<_>>();
+        match (request.method.as_str(), &segments[..]) {
+            ("POST", ["jobs"]) => self.submit(request),
+            ("GET", ["metrics"]) => {
+                let (queued, running) = {
+                    let queue = self.queue.lock().unwrap();
+                    (queue.queued(), queue.running())
+                };
+                Response {
+                    status: 200,
+                    content_type: "text/plain; version=0.0.4",
+                    headers: Vec::new(),
+                    body: self.metrics.render(queued, running).into_bytes(),
+                }
+            }
+            ("GET", ["jobs"]) => {
+                let queue = self.queue.lock().unwrap();
+                let jobs = queue
+                    .jobs
+                    .keys()
+                    .map(|seq| self.status_json(&queue, *seq))
+                    .collect::<Vec<_>>();
+                Response::json(200, format!("[{}]\n", jobs.join(",\n ")))
+            }
+            ("GET", ["jobs", id]) => {
+                let queue = self.queue.lock().unwrap();
+                match queue.find(id) {
+                    Some(seq) => Response::json(200, self.status_json(&queue, seq) + "\n"),
+                    None => Response::error(404, "no such job, or it has expired"),
+                }
+            }
+            ("GET", ["jobs", id, "pdf"]) => {
+                let queue = self.queue.lock().unwrap();
+                let job = match queue.find(id).and_then(|seq| queue.jobs.get(&seq)) {
+                    Some(job) => job,
+                    None => return Response::error(404, "no such job, or it has expired"),
+                };
+                match &job.pdf {
+                    Some(pdf) => Response {
+                        status: 200,
+                        content_type: "application/pdf",
+                        headers: vec![(
+                            "Content-Disposition",
+                            format!(
+                                "attachment; filename={}",
+                                quote(&format!("{}.pdf", job.title))
+                            ),
+                        )],
+                        body: pdf.clone(),
+                    },
+                    None => Response::error(409, &format!("the job is {}", job.status.as_str())),
+                }
+            }
+            ("DELETE", ["jobs", id]) => {
+                let mut queue = self.queue.lock().unwrap();
+                let seq = match queue.find(id) {
+                    Some(seq) => seq,
+                    None => return Response::error(404, "no such job, or it has expired"),
+                };
+                let job = queue.jobs.get_mut(&seq).unwrap();
+                match job.status {
+                    // a running job notices before its next file and is then
+                    // kept as cancelled like the queued ones
+                    Status::Queued => {
+                        job.status = Status::Cancelled;
+                        job.inputs.clear();
+                        job.finished = Some(Instant::now());
+                    }
+                    Status::Running => job.cancel.cancel(),
+                    _ => {
+                        queue.jobs.remove(&seq);
+                    }
+                }
+                Response {
+                    status: 204,
+                    content_type: "text/plain",
+                    headers: Vec::new(),
+                    body: Vec::new(),
+                }
+            }
+            (_, ["jobs", ..]) => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    /// Queues the files of a `multipart/form-data` upload. The `title` and
+    /// `priority` are taken from the query or from form fields of that name.
+    fn submit(&self, request: &Request) -> Response {
+        let content_type = request.header("content-type").unwrap_or_default();
+        if !content_type.starts_with("multipart/form-data") {
+            return Response::error(415, "upload the images as multipart/form-data");
+        }
+        let parts = match http::parse_multipart(content_type, &request.body) {
+            Some(parts) => parts,
+            None => return Response::error(400, "malformed multipart body"),
+        };
+        let mut fields = request.query.clone();
+        let mut inputs = Vec::new();
+        for part in parts {
+            match part.filename {
+                // only the name is kept, paths of the uploader mean nothing here
+                Some(filename) => {
+                    if let Some(name) = Path::new(&filename).file_name() {
+                        inputs.push((PathBuf::from(name), part.data));
+                    }
+                }
+                None => fields.push((part.name, String::from_utf8_lossy(&part.data).into_owned())),
+            }
+        }
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.as_str())
+        };
+        let priority = match field("priority").map(Priority::parse) {
+            None => Priority::Normal,
+            Some(Some(priority)) => priority,
+            Some(None) => return Response::error(400, "priority must be low, normal or high"),
+        };
+        if inputs.is_empty() {
+            return Response::error(400, "the upload has no files");
+        }
+        let title = field("title").unwrap_or("output").to_owned();
+
+        let mut queue = self.queue.lock().unwrap();
+        if queue.queued() >= self.max_queued {
+            self.metrics.job_rejected();
+            return Response::error(503, "too many jobs are waiting, try again later");
+        }
+        let id = format!("{:016x}", self.ids.hash_one((queue.next, Instant::now())));
+        let seq = queue.push(Job {
+            id: id.clone(),
+            priority,
+            title,
+            files: inputs.len(),
+            inputs,
+            status: Status::Queued,
+            cancel: CancelToken::new(),
+            pages: 0,
+            warnings: Vec::new(),
+            pdf: None,
+            submitted: Instant::now(),
+            finished: None,
+        });
+        self.metrics.job_submitted();
+        self.ready.notify_one();
+        let mut response = Response::json(202, self.status_json(&queue, seq) + "\n");
+        response.headers.push(("Location", format!("/jobs/{id}")));
+        response
+    }
+
+    /// Where a job is at as JSON, with a link to its PDF once it is done
+    fn status_json(&self, queue: &Queue, seq: u64) -> String {
+        let job = &queue.jobs[&seq];
+        let position = match job.status {
+            Status::Queued => queue
+                .position(seq)
+                .map_or("null".to_owned(), |p| p.to_string()),
+            _ => "null".to_owned(),
+        };
+        let error = match &job.status {
+            Status::Failed(e) => quote(e),
+            _ => "null".to_owned(),
+        };
+        let pdf_url = match job.pdf {
+            Some(_) => quote(&format!("{}/jobs/{}/pdf", self.public_url, job.id)),
+            None => "null".to_owned(),
+        };
+        let warnings = job.warnings.iter().map(to_json).collect::<Vec<_>>();
+        format!(
+        "{{\"id\": {}, \"status\": {}, \"priority\": {}, \"title\": {}, \"position\": {position}, \
+         \"files\": {}, \"pages\": {}, \"pdf_url\": {pdf_url}, \"warnings\": [{}], \"error\": {error}}}",
+        quote(&job.id),
+        quote(job.status.as_str()),
+        quote(job.priority.as_str()),
+        quote(&job.title),
+        job.files,
+        job.pages,
+        warnings.join(", "),
+    )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(priority: Priority) -> Job {
+        Job {
+            id: String::new(),
+            priority,
+            title: String::new(),
+            inputs: Vec::new(),
+            files: 0,
+            status: Status::Queued,
+            cancel: CancelToken::new(),
+            pages: 0,
+            warnings: Vec::new(),
+            pdf: None,
+            submitted: Instant::now(),
+            finished: None,
+        }
+    }
+
+    #[test]
+    fn higher_priorities_run_first_then_in_order() {
+        let mut queue = Queue::default();
+        let low = queue.push(job(Priority::Low));
+        let first = queue.push(job(Priority::Normal));
+        let high = queue.push(job(Priority::High));
+        let second = queue.push(job(Priority::Normal));
+        let cancelled = queue.push(job(Priority::High));
+        queue.jobs.get_mut(&cancelled).unwrap().status = Status::Cancelled;
+
+        assert_eq!(queue.position(high), Some(1));
+        assert_eq!(queue.position(second), Some(3));
+        assert_eq!(queue.position(low), Some(4));
+        let order = std::iter::from_fn(|| {
+            let seq = queue.pop()?;
+            queue.jobs.get_mut(&seq).unwrap().status = Status::Running;
+            Some(seq)
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(order, [high, first, second, low]);
+    }
+
+    #[test]
+    fn finished_jobs_expire() {
+        let mut queue = Queue::default();
+        let waiting = queue.push(job(Priority::Normal));
+        let done = queue.push(job(Priority::Normal));
+        let job = queue.jobs.get_mut(&done).unwrap();
+        job.status = Status::Done;
+        job.finished = Some(Instant::now());
+
+        queue.clean_up(Duration::from_secs(60));
+        assert_eq!(queue.jobs.len(), 2);
+        queue.clean_up(Duration::ZERO);
+        assert!(queue.jobs.contains_key(&waiting));
+        assert!(!queue.jobs.contains_key(&done));
+    }
+}
@@ -0,0 +1,139 @@
+use clap::{Arg, ArgMatches, Command, ValueSource};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use yet_another_imgs2pdf::toml::{parse_value, quote, Value};
+
+/// Options that only make sense for the run they are given on
+const NOT_RECORDED: [&str; 4] = ["help", "version", "save-settings", "from-settings"];
+/// Written for reference, inputs are replayed from the option that named them
+const INPUTS_KEY: &str = "inputs";
+/// Options naming the inputs, of which only one can be given
+const INPUT_KEYS: [&str; 3] = ["imgs", "dir", "manifest"];
+
+/// Every option of a run with defaults filled in, plus the inputs it resolved to
+pub fn from_matches(
+    cmd: &Command,
+    matches: &ArgMatches,
+    inputs: &[PathBuf],
+) -> Vec<(String, Value)> {
+    let mut cmd = cmd.clone();
+    cmd.build();
+    let mut settings = Vec::new();
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id();
+        if NOT_RECORDED.contains(&id) {
+            continue;
+        }
+        // a default would conflict with the option given in its place
+        let conflicts = |a: &Arg, b: &Arg| {
+            cmd.get_arg_conflicts_with(a)
+                .iter()
+                .any(|c| c.get_id() == b.get_id())
+        };
+        if matches.value_source(id) == Some(ValueSource::DefaultValue)
+            && cmd.get_arguments().any(|other| {
+                matches.value_source(other.get_id()) == Some(ValueSource::CommandLine)
+                    && (conflicts(arg, other) || conflicts(other, arg))
+            })
+        {
+            continue;
+        }
+        let value = if !arg.is_takes_value_set() {
+            Value::Flag(matches.is_present(id))
+        } else {
+            // paths may not be UTF-8, which the settings file cannot hold
+            let values = if arg.is_allow_invalid_utf8_set() {
+                matches
+                    .values_of_os(id)
+                    .map(|v| v.map(|v| v.to_string_lossy().into_owned()).collect())
+            } else {
+                matches
+                    .values_of(id)
+                    .map(|v| v.map(str::to_owned).collect())
+            };
+            let mut values: Vec<String> = match values {
+                Some(values) => values,
+                None => continue,
+            };
+            if arg.is_multiple_occurrences_set() || arg.is_multiple_values_set() {
+                Value::List(values)
+            } else {
+                Value::Single(values.remove(0))
+            }
+        };
+        settings.push((id.to_owned(), value));
+    }
+    let inputs = inputs.iter().map(|p| p.display().to_string()).collect();
+    settings.push((INPUTS_KEY.to_owned(), Value::List(inputs)));
+    settings
+}
+
+/// Command line arguments that replay recorded settings, leaving out those
+/// given on the command line so they can be overridden
+pub fn to_args(cmd: &Command, settings: &[(String, Value)], given: &ArgMatches) -> Vec<String> {
+    let on_command_line = |id: &str| given.value_source(id) == Some(ValueSource::CommandLine);
+    let mut args = Vec::new();
+    for (key, value) in settings {
+        let arg = match cmd.get_arguments().find(|a| a.get_id() == key) {
+            Some(arg) if !NOT_RECORDED.contains(&key.as_str()) => arg,
+            _ => continue,
+        };
+        let is_input = INPUT_KEYS.contains(&key.as_str());
+        if on_command_line(key) || is_input && INPUT_KEYS.iter().any(|k| on_command_line(k)) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap_or(key));
+        match value {
+            Value::Flag(true) => args.push(flag),
+            Value::Flag(false) => {}
+            Value::Single(v) => args.push(format!("{flag}={v}")),
+            Value::List(values) if arg.is_multiple_occurrences_set() => {
+                for v in values {
+                    args.push(format!("{flag}={v}"));
+                }
+            }
+            Value::List(values) => {
+                args.push(flag);
+                args.extend(values.iter().cloned());
+            }
+        }
+    }
+    args
+}
+
+/// Writes settings as a flat TOML table
+pub fn write(path: &Path, settings: &[(String, Value)]) -> io::Result<()> {
+    let mut toml = String::from("# Replay with --from-settings\n");
+    for (key, value) in settings {
+        let value = match value {
+            Value::Flag(b) => b.to_string(),
+            Value::Single(s) => quote(s),
+            Value::List(values) => {
+                let quoted = values.iter().map(|v| quote(v)).collect::<Vec<_>>();
+                format!("[{}]", quoted.join(", "))
+            }
+        };
+        let _ = writeln!(toml, "{key} = {value}");
+    }
+    fs::write(path, toml)
+}
+
+/// Reads settings written by [`write`]. Only the subset of TOML needed for
+/// that is understood: booleans, basic strings and one-line string arrays.
+pub fn read(path: &Path) -> Result<Vec<(String, Value)>, String> {
+    let toml = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut settings = Vec::new();
+    for (n, line) in toml.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("line {}: cannot read `{line}`", n + 1);
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let value = parse_value(value.trim()).ok_or_else(invalid)?;
+        settings.push((key.trim().to_owned(), value));
+    }
+    Ok(settings)
+}
@@ -0,0 +1,38 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Name, size and modification time of every entry of a directory
+#[derive(PartialEq)]
+pub struct Snapshot(Vec<(PathBuf, u64, Option<SystemTime>)>);
+
+impl Snapshot {
+    pub fn take(dir: &Path) -> io::Result<Snapshot> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            entries.push((entry.path(), meta.len(), meta.modified().ok()));
+        }
+        entries.sort();
+        Ok(Snapshot(entries))
+    }
+}
+
+/// Waits until nothing in `dir` has changed for `quiet`, so that files still
+/// being copied in are not picked up half written
+pub fn wait(dir: &Path, quiet: Duration) -> io::Result<Snapshot> {
+    let poll = (quiet / 10).clamp(Duration::from_millis(50), Duration::from_secs(1));
+    let mut last = Snapshot::take(dir)?;
+    let mut since = Instant::now();
+    while since.elapsed() < quiet {
+        std::thread::sleep(poll);
+        let now = Snapshot::take(dir)?;
+        if now != last {
+            last = now;
+            since = Instant::now();
+        }
+    }
+    Ok(last)
+}
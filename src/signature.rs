@@ -0,0 +1,78 @@
+use printpdf::lopdf::{self, dictionary, Object, StringFormat};
+
+/// Bytes reserved for the detached PKCS#7 blob, written as twice as many hex digits
+const SIGNATURE_CONTENTS_LEN: usize = 8192;
+/// Wide enough that any real offset fits into the reserved `/ByteRange` text
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+/// Adds an invisible, unsigned signature field whose `/Contents` is reserved
+/// up front so a signer can fill it in place without rewriting the document.
+pub fn add_signature_field(doc: &mut lopdf::Document) -> lopdf::Result<()> {
+    let first_page = *doc
+        .get_pages()
+        .values()
+        .next()
+        .ok_or(lopdf::Error::PageNumberNotFound(1))?;
+
+    let sig_id = doc.add_object(dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => "adbe.pkcs7.detached",
+        "ByteRange" => vec![
+            0.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+        ],
+        "Contents" => Object::String(vec![0; SIGNATURE_CONTENTS_LEN], StringFormat::Hexadecimal),
+    });
+    let field_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Sig",
+        "T" => Object::string_literal("Signature1"),
+        "F" => 132,
+        "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+        "P" => first_page,
+        "V" => sig_id,
+    });
+
+    let page = doc.get_object_mut(first_page)?.as_dict_mut()?;
+    match page.get_mut(b"Annots") {
+        Ok(Object::Array(annots)) => annots.push(field_id.into()),
+        _ => page.set("Annots", vec![field_id.into()]),
+    }
+
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    doc.get_object_mut(root)?.as_dict_mut()?.set(
+        "AcroForm",
+        dictionary! {
+            "Fields" => vec![field_id.into()],
+            "SigFlags" => 3,
+        },
+    );
+    Ok(())
+}
+
+/// Patches the placeholder `/ByteRange` of a serialized document so that it
+/// covers everything but the reserved `/Contents` hex string.
+pub fn fill_byte_range(pdf: &mut [u8]) -> Result<(), &'static str> {
+    let placeholder = format!("[0 {p} {p} {p}]", p = BYTE_RANGE_PLACEHOLDER);
+    let range_at = find(pdf, placeholder.as_bytes()).ok_or("signature byte range not found")?;
+    let contents_at = find(pdf, b"/Contents<").ok_or("signature contents not found")? + 9;
+    let contents_end = contents_at + SIGNATURE_CONTENTS_LEN * 2 + 2;
+
+    let range = format!(
+        "[0 {} {} {}]",
+        contents_at,
+        contents_end,
+        pdf.len() - contents_end
+    );
+    let padded = format!("{:<width$}", range, width = placeholder.len());
+    pdf[range_at..range_at + padded.len()].copy_from_slice(padded.as_bytes());
+    Ok(())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
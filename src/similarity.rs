@@ -0,0 +1,103 @@
+use printpdf::image_crate::{self, imageops::FilterType};
+use std::path::{Path, PathBuf};
+
+use crate::order;
+
+/// Images are compared as grayscale thumbnails of this size
+const SIGNATURE_SIZE: u32 = 32;
+/// A step is ambiguous when the runner-up is at most this much further away
+/// than the image that was picked
+const AMBIGUITY_RATIO: f64 = 1.1;
+
+/// A place in the order where another image was nearly as good a match
+pub struct Ambiguity {
+    pub after: PathBuf,
+    pub chosen: PathBuf,
+    pub alternative: PathBuf,
+}
+
+/// Orders the inputs so that every image is followed by the one most similar
+/// to it, starting from the image that is least like all the others. Inputs
+/// that cannot be decoded keep their name order at the end.
+pub fn sort(mut inputs: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<Ambiguity>) {
+    inputs.sort_by(|a, b| order::natural_cmp(a, b));
+    let (mut images, mut rest) = (Vec::new(), Vec::new());
+    for input in inputs {
+        match signature(&input) {
+            Some(sig) => images.push((input, sig)),
+            None => rest.push(input),
+        }
+    }
+    let n = images.len();
+    if n < 3 {
+        let mut order = images.into_iter().map(|(p, _)| p).collect::<Vec<_>>();
+        order.append(&mut rest);
+        return (order, Vec::new());
+    }
+
+    let mut dist = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let d = distance(&images[i].1, &images[j].1);
+            dist[i][j] = d;
+            dist[j][i] = d;
+        }
+    }
+    let nearest = |i: usize| {
+        (0..n)
+            .filter(|j| *j != i)
+            .map(|j| dist[i][j])
+            .fold(f64::INFINITY, f64::min)
+    };
+    // the ends of a chain are the images whose best match is the worst
+    let mut current = (0..n)
+        .max_by(|a, b| nearest(*a).total_cmp(&nearest(*b)))
+        .unwrap();
+
+    let mut visited = vec![false; n];
+    let mut chain = vec![current];
+    let mut ambiguities = Vec::new();
+    visited[current] = true;
+    while chain.len() < n {
+        let mut candidates = (0..n).filter(|j| !visited[*j]).collect::<Vec<_>>();
+        candidates.sort_by(|a, b| dist[current][*a].total_cmp(&dist[current][*b]));
+        let next = candidates[0];
+        if let Some(second) = candidates.get(1) {
+            if dist[current][*second] <= dist[current][next] * AMBIGUITY_RATIO {
+                ambiguities.push(Ambiguity {
+                    after: images[current].0.clone(),
+                    chosen: images[next].0.clone(),
+                    alternative: images[*second].0.clone(),
+                });
+            }
+        }
+        visited[next] = true;
+        chain.push(next);
+        current = next;
+    }
+
+    let mut order = chain
+        .into_iter()
+        .map(|i| images[i].0.clone())
+        .collect::<Vec<_>>();
+    order.append(&mut rest);
+    (order, ambiguities)
+}
+
+/// A grayscale thumbnail normalized to zero mean and unit variance, so that
+/// exposure differences between scans do not count
+fn signature(path: &Path) -> Option<Vec<f64>> {
+    let img = image_crate::open(path).ok()?;
+    let thumb = img
+        .resize_exact(SIGNATURE_SIZE, SIGNATURE_SIZE, FilterType::Triangle)
+        .to_luma8();
+    let values = thumb.pixels().map(|p| p.0[0] as f64).collect::<Vec<_>>();
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    let sd = var.sqrt().max(1.0);
+    Some(values.into_iter().map(|v| (v - mean) / sd).collect())
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f64>() / a.len() as f64
+}
@@ -0,0 +1,283 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{space, split, style};
+
+/// Where the finished PDF is stored, named by `--out`
+pub trait Sink {
+    /// Stores the whole of `pdf`, leaving nothing half written behind where
+    /// the destination allows it
+    fn store(&mut self, pdf: &[u8]) -> io::Result<()>;
+
+    /// The destination as messages name it
+    fn name(&self) -> String;
+
+    /// The file the PDF ends up in, when it is a local one that can be read
+    /// back
+    fn local_path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Starts storing a PDF written to it piece by piece, for destinations
+    /// that can take it that way instead of holding it whole first
+    fn stream(&mut self) -> Option<io::Result<Box<dyn Stream + '_>>> {
+        None
+    }
+}
+
+/// A PDF on its way to a [`Sink`] as it is written. Dropping it unfinished
+/// gives up on storing it.
+pub trait Stream: Write {
+    /// Completes storing once the whole PDF was written
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// A file on disk, written next to its place and renamed into it
+pub struct LocalFile {
+    path: PathBuf,
+    durable: bool,
+}
+
+impl LocalFile {
+    /// With `durable` the file and its directory are synced to the device
+    /// before storing returns
+    pub fn new(path: PathBuf, durable: bool) -> Self {
+        LocalFile { path, durable }
+    }
+}
+
+impl Sink for LocalFile {
+    fn store(&mut self, pdf: &[u8]) -> io::Result<()> {
+        let path = &self.path;
+        space::check(path, pdf.len() as u64)?;
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        let part = PathBuf::from(part);
+        let written = File::create(&part).and_then(|mut f| {
+            f.write_all(pdf)?;
+            f.flush()?;
+            if self.durable {
+                f.sync_all()?;
+            }
+            std::fs::rename(&part, path)
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_file(&part);
+        }
+        written?;
+        #[cfg(unix)]
+        if self.durable {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn local_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// The real stdout, taken before messages were moved to stderr
+pub struct Stdout(pub File);
+
+impl Sink for Stdout {
+    fn store(&mut self, pdf: &[u8]) -> io::Result<()> {
+        self.0.write_all(pdf)?;
+        self.0.flush()
+    }
+
+    fn name(&self) -> String {
+        "stdout".to_owned()
+    }
+}
+
+/// Local files holding the PDF cut into parts, `name-001.pdf`,
+/// `name-002.pdf` and so on for `name.pdf`
+pub struct Parts {
+    path: PathBuf,
+    durable: bool,
+    limits: split::Limits,
+    written: Vec<PathBuf>,
+}
+
+impl Parts {
+    /// Parts of `path` within `limits`, which are numbered further as
+    /// `name-1-001.pdf` and so on when the first of them exists and
+    /// `overwrite` is [`Overwrite::Number`]
+    pub fn new(
+        mut path: PathBuf,
+        durable: bool,
+        limits: split::Limits,
+        overwrite: Overwrite,
+    ) -> Result<Self, String> {
+        if part(&path, 1).exists() {
+            match overwrite {
+                Overwrite::Refuse => {
+                    return Err(format!(
+                        "`{}` already exists, pass --force to overwrite the parts or --out-auto to number the new ones",
+                        part(&path, 1).display()
+                    ))
+                }
+                Overwrite::Force => {}
+                Overwrite::Number => {
+                    let base = path;
+                    path = (1..)
+                        .map(|n| suffixed(&base, &format!("-{n}")))
+                        .find(|candidate| !part(candidate, 1).exists())
+                        .unwrap();
+                }
+            }
+        }
+        Ok(Parts {
+            path,
+            durable,
+            limits,
+            written: Vec::new(),
+        })
+    }
+}
+
+impl Sink for Parts {
+    fn store(&mut self, pdf: &[u8]) -> io::Result<()> {
+        let parts = split::by_limits(pdf, self.limits).map_err(io::Error::other)?;
+        for (i, (pages, bytes)) in parts.iter().enumerate() {
+            let path = part(&self.path, i + 1);
+            LocalFile::new(path.clone(), self.durable).store(bytes)?;
+            println!("Wrote {pages} page(s) to `{}`", path.display());
+            if self
+                .limits
+                .bytes
+                .is_some_and(|max| bytes.len() as u64 > max)
+            {
+                let message = format!(
+                    "`{}` is larger than <split-size> since its page cannot be split",
+                    path.display()
+                );
+                println!("{}", style::warning(&message));
+            }
+            self.written.push(path);
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        match self.written.as_slice() {
+            [] => part(&self.path, 1).display().to_string(),
+            [only] => only.display().to_string(),
+            [first, .., last] => format!("{} … {}", first.display(), last.display()),
+        }
+    }
+}
+
+/// `name-00n.ext` next to `name.ext`
+fn part(path: &Path, n: usize) -> PathBuf {
+    suffixed(path, &format!("-{n:03}"))
+}
+
+/// `name{suffix}.ext` next to `name.ext`
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(suffix);
+    name.push(".");
+    name.push(path.extension().unwrap_or_default());
+    path.with_file_name(name)
+}
+
+/// What happens when `--out` names a local file that already exists
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overwrite {
+    Refuse,
+    Force,
+    /// Writes to the first of `name-1.pdf`, `name-2.pdf`, … that is free
+    Number,
+}
+
+/// The sink `out` names: `-` for `stdout`, a URL for one of the remote
+/// backends, or else a path, given `.pdf` when it has no extension
+pub fn open(
+    out: &OsStr,
+    stdout: Option<File>,
+    durable: bool,
+    overwrite: Overwrite,
+) -> Result<Box<dyn Sink>, String> {
+    if let Some(stdout) = stdout {
+        return Ok(Box::new(Stdout(stdout)));
+    }
+    let scheme = out
+        .to_str()
+        .and_then(|out| out.split_once("://"))
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| {
+            scheme.len() > 1
+                && scheme
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+        });
+    match scheme {
+        #[cfg(feature = "upload")]
+        Some(_) => crate::remote::open(out.to_str().unwrap()),
+        #[cfg(not(feature = "upload"))]
+        Some(scheme) => Err(format!(
+            "`{scheme}://` destinations need a build with the `upload` feature"
+        )),
+        None => {
+            let mut path = PathBuf::from(out);
+            if path.extension().is_none() {
+                path.set_extension("pdf");
+            }
+            if path.exists() {
+                match overwrite {
+                    Overwrite::Refuse => {
+                        return Err(format!(
+                            "`{}` already exists, pass --force to overwrite it or --out-auto to number the new one",
+                            path.display()
+                        ))
+                    }
+                    Overwrite::Force => {}
+                    Overwrite::Number => path = numbered(&path),
+                }
+            }
+            Ok(Box::new(LocalFile::new(path, durable)))
+        }
+    }
+}
+
+/// The first of `stem-1.ext`, `stem-2.ext`, … next to `path` that does not
+/// exist yet
+fn numbered(path: &Path) -> PathBuf {
+    (1..)
+        .map(|n| suffixed(path, &format!("-{n}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_past_existing_files() {
+        let dir = std::env::temp_dir().join(format!("imgs2pdf-sink-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("scans.pdf");
+        for taken in ["scans.pdf", "scans-1.pdf"] {
+            File::create(dir.join(taken)).unwrap();
+        }
+        assert_eq!(numbered(&out), dir.join("scans-2.pdf"));
+        let refused = open(out.as_os_str(), None, false, Overwrite::Refuse);
+        assert!(refused.is_err_and(|e| e.contains("already exists")));
+        let sink = open(out.as_os_str(), None, false, Overwrite::Number).unwrap();
+        assert_eq!(sink.local_path(), Some(dir.join("scans-2.pdf").as_path()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
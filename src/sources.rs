@@ -0,0 +1,130 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What happens to inputs that made it into the PDF once it is written
+pub enum Cleanup {
+    Move(PathBuf),
+    Copy(PathBuf),
+    /// Deletes inputs inside this directory, keeping any outside of it
+    Delete(PathBuf),
+    /// Like [`Cleanup::Delete`], but into the trash
+    Trash(PathBuf),
+}
+
+/// Applies `cleanup` to every input, returning those it failed for
+pub fn clean_up(inputs: &[PathBuf], cleanup: &Cleanup) -> Vec<(PathBuf, io::Error)> {
+    let mut failed = Vec::new();
+    for input in inputs {
+        let done = match cleanup {
+            Cleanup::Move(dir) => free_name(dir, input).and_then(|to| move_file(input, &to)),
+            Cleanup::Copy(dir) => free_name(dir, input)
+                .and_then(|to| fs::copy(input, to))
+                .map(drop),
+            Cleanup::Delete(root) | Cleanup::Trash(root) => match is_inside(input, root) {
+                Ok(true) if matches!(cleanup, Cleanup::Trash(_)) => trash(input),
+                Ok(true) => fs::remove_file(input),
+                Ok(false) => Err(io::Error::other(format!(
+                    "it is outside of `{}`, so it was kept",
+                    root.display()
+                ))),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = done {
+            failed.push((input.clone(), e));
+        }
+    }
+    failed
+}
+
+/// Whether `path` is in `root` or a directory below it, once links and `..`
+/// are resolved
+fn is_inside(path: &Path, root: &Path) -> io::Result<bool> {
+    Ok(fs::canonicalize(path)?.starts_with(fs::canonicalize(root)?))
+}
+
+/// A path in `dir` named after `input` that is not taken yet, numbering the
+/// name like `scan_2.jpg` if it is
+fn free_name(dir: &Path, input: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = input
+        .file_name()
+        .ok_or_else(|| io::Error::other("not a file"))?;
+    let mut to = dir.join(name);
+    let stem = input.file_stem().unwrap_or(name).to_string_lossy();
+    let mut n = 1;
+    while to.exists() {
+        n += 1;
+        to = dir.join(format!("{stem}_{n}"));
+        if let Some(ext) = input.extension() {
+            to.set_extension(ext);
+        }
+    }
+    Ok(to)
+}
+
+/// Renames `from`, copying it when `to` is on another filesystem
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }
+        renamed => renamed,
+    }
+}
+
+/// Moves `path` into the home trash as the freedesktop.org specification
+/// describes it, so that file managers can put it back
+#[cfg(all(unix, not(target_os = "macos")))]
+fn trash(path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let data_home = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home()?.join(".local/share"),
+    };
+    let trash = data_home.join("Trash");
+    let to = free_name(&trash.join("files"), path)?;
+    fs::create_dir_all(trash.join("info"))?;
+    let mut info = to.file_name().unwrap_or_default().to_os_string();
+    info.push(".trashinfo");
+
+    let mut encoded = String::new();
+    for b in std::path::absolute(path)?.as_os_str().as_bytes() {
+        match b {
+            b'/' | b'-' | b'_' | b'.' | b'~' => encoded.push(*b as char),
+            b if b.is_ascii_alphanumeric() => encoded.push(*b as char),
+            b => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    let now = printpdf::OffsetDateTime::try_now_local()
+        .unwrap_or_else(|_| printpdf::OffsetDateTime::now_utc());
+    let info_text = format!(
+        "[Trash Info]\nPath={encoded}\nDeletionDate={}\n",
+        now.format("%Y-%m-%dT%H:%M:%S")
+    );
+    fs::write(trash.join("info").join(info), info_text)?;
+    move_file(path, &to)
+}
+
+#[cfg(target_os = "macos")]
+fn trash(path: &Path) -> io::Result<()> {
+    let to = free_name(&home()?.join(".Trash"), path)?;
+    move_file(path, &to)
+}
+
+#[cfg(not(unix))]
+fn trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::other(
+        "the trash is not supported on this system",
+    ))
+}
+
+#[cfg(unix)]
+fn home() -> io::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::other("HOME is not set"))
+}
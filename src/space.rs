@@ -0,0 +1,69 @@
+use printpdf::image_crate;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Images are embedded as uncompressed RGB
+const BYTES_PER_PIXEL: u64 = 3;
+
+/// Rough size of the PDF made from `inputs` scaled to `percent` of their size
+/// or else to fit `wh`, from their headers alone. Inputs that are not images
+/// count for nothing.
+pub fn projected_size(inputs: &[PathBuf], wh: (u32, u32), percent: Option<f64>) -> u64 {
+    inputs
+        .iter()
+        .filter_map(|input| image_crate::image_dimensions(input).ok())
+        .map(|(w, h)| {
+            let ratio = match percent {
+                Some(percent) => percent / 100.0,
+                None => (wh.0 as f64 / w as f64).min(wh.1 as f64 / h as f64),
+            };
+            let (w, h) = ((w as f64 * ratio).round(), (h as f64 * ratio).round());
+            w as u64 * h as u64 * BYTES_PER_PIXEL
+        })
+        .sum()
+}
+
+/// Fails with a readable error if the filesystem that `path` is written to
+/// cannot take `needed` more bytes
+pub fn check(path: &Path, needed: u64) -> io::Result<()> {
+    match available(path) {
+        Some(free) if free < needed => Err(io::Error::other(format!(
+            "{} are needed but only {} are free",
+            megabytes(needed),
+            megabytes(free)
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+/// Bytes free for unprivileged users on the filesystem holding `path`, which
+/// need not exist yet
+#[cfg(unix)]
+fn available(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `dir` is a valid C string and `stat` is only read on success
+    if unsafe { libc::statvfs(dir.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    // the field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available(_path: &Path) -> Option<u64> {
+    None
+}
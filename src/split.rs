@@ -0,0 +1,431 @@
+use clap::{Arg, ArgMatches, Command, ValueHint};
+use printpdf::lopdf::{
+    self,
+    content::{Content, Operation},
+    dictionary, Dictionary, Object, ObjectId, Stream,
+};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::pagerange;
+use yet_another_imgs2pdf::info;
+use yet_another_imgs2pdf::outline;
+use yet_another_imgs2pdf::overlay::text_width;
+use yet_another_imgs2pdf::pagetree;
+use yet_another_imgs2pdf::prepress::{ascii, page_size};
+
+/// Size of the volume cover when the first page of the volume has none (A4)
+const DEFAULT_COVER_SIZE: (f64, f64) = (595.28, 841.89);
+
+pub fn command() -> Command<'static> {
+    Command::new("split")
+        .about("Split a PDF into several documents")
+        .arg(
+            Arg::new("input")
+                .allow_invalid_utf8(true)
+                .help("The PDF to split")
+                .required(true)
+                .value_hint(ValueHint::FilePath),
+        )
+        .arg(
+            Arg::new("by")
+                .help("Start a new document at every top level bookmark, or per page range")
+                .possible_values(["bookmarks", "ranges"])
+                .required(true)
+                .long("by"),
+        )
+        .arg(
+            Arg::new("ranges")
+                .help("Page ranges of the documents, e.g. 1-4,5-9,10-")
+                .takes_value(true)
+                .required_if_eq("by", "ranges")
+                .long("ranges"),
+        )
+        .arg(
+            Arg::new("out-dir")
+                .allow_invalid_utf8(true)
+                .help("Directory to write the parts to [default: next to <input>]")
+                .takes_value(true)
+                .value_hint(ValueHint::DirPath)
+                .long("out-dir")
+                .short('o'),
+        )
+        .arg(
+            Arg::new("volume-covers")
+                .help("Start every part with a page saying which volume and pages it holds")
+                .long("volume-covers"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let input = Path::new(matches.value_of_os("input").unwrap());
+    let doc = lopdf::Document::load(input)?;
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    let stem = input
+        .file_stem()
+        .unwrap_or(input.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+
+    let parts = if matches.value_of("by") == Some("bookmarks") {
+        bookmark_parts(&doc, &pages, &stem)
+    } else {
+        match pagerange::parse(matches.value_of("ranges").unwrap(), pages.len() as u32) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|r| {
+                    let name = format!("{stem}-{}-{}", r.start(), r.end());
+                    let ids = r.map(|n| pages[n as usize - 1]).collect();
+                    (name, ids)
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("Value <ranges> is invalid, {e}");
+                exit(1)
+            }
+        }
+    };
+    if parts.is_empty() {
+        eprintln!(
+            "`{}` has no bookmarks pointing at its pages",
+            input.display()
+        );
+        exit(1)
+    }
+
+    let out_dir = match matches.value_of_os("out-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => input.parent().unwrap_or(Path::new("")).to_path_buf(),
+    };
+    std::fs::create_dir_all(&out_dir)?;
+    let title = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|info| doc.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .and_then(|info| info.get(b"Title"))
+        .and_then(Object::as_str)
+        .map(info::decode_text_string)
+        .ok()
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or_else(|| stem.clone());
+    let count = parts.len();
+    for (i, (name, ids)) in parts.into_iter().enumerate() {
+        // 1-based number of the first page in the whole document
+        let first = pages.iter().position(|p| *p == ids[0]).unwrap_or(0) + 1;
+        let volume = Volume {
+            number: i + 1,
+            count,
+            pages: (first, first + ids.len() - 1),
+        };
+        let mut part = pagetree::rearranged(&doc, &ids)?;
+        if matches.is_present("volume-covers") {
+            let cover = add_volume_cover(&mut part, ids[0], &title, &volume)?;
+            let mut ids = ids.clone();
+            ids.insert(0, cover);
+            pagetree::set_pages(&mut part, &ids)?;
+        }
+        number_pages(&mut part, &volume, matches.is_present("volume-covers"))?;
+        let path = out_dir.join(format!("{name}.pdf"));
+        part.save(&path)?;
+        println!("Wrote {} page(s) to `{}`", ids.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Most pages and bytes each part of a PDF cut by [`by_limits`] may have
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub pages: Option<usize>,
+    pub bytes: Option<u64>,
+}
+
+/// `pdf` cut into parts of consecutive pages within `limits`, each saved
+/// along with its number of pages. A page over the size limit on its own
+/// makes a part of its own.
+pub fn by_limits(pdf: &[u8], limits: Limits) -> lopdf::Result<Vec<(usize, Vec<u8>)>> {
+    let doc = lopdf::Document::load_mem(pdf)?;
+    let pages = doc.get_pages().into_values().collect::<Vec<ObjectId>>();
+    let weights = pages
+        .iter()
+        .map(|page| page_weight(&doc, *page))
+        .collect::<Vec<_>>();
+    let max_pages = limits.pages.unwrap_or(usize::MAX);
+    let max_bytes = limits.bytes.unwrap_or(u64::MAX);
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < pages.len() {
+        let (mut end, mut weight) = (start + 1, weights[start]);
+        while end < pages.len()
+            && end - start < max_pages
+            && weight.saturating_add(weights[end]) <= max_bytes
+        {
+            weight += weights[end];
+            end += 1;
+        }
+        // the weights leave out what pages share, so the part is measured
+        // and given fewer pages until it fits
+        loop {
+            let mut bytes = Vec::new();
+            pagetree::rearranged(&doc, &pages[start..end])?.save_to(&mut bytes)?;
+            let size = bytes.len() as u64;
+            if size <= max_bytes || end == start + 1 {
+                parts.push((end - start, bytes));
+                break;
+            }
+            let fitting = (end - start) as u64 * max_bytes / size;
+            end = start + (fitting as usize).clamp(1, end - start - 1);
+        }
+        start = end;
+    }
+    Ok(parts)
+}
+
+/// Bytes of the streams a page draws on, such as its content and images
+fn page_weight(doc: &lopdf::Document, page: ObjectId) -> u64 {
+    let mut seen = BTreeSet::new();
+    let mut todo = vec![page];
+    let mut weight = 0;
+    while let Some(id) = todo.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Ok(object) = doc.get_object(id) {
+            weight += stream_bytes(object, &mut todo);
+        }
+    }
+    weight
+}
+
+/// Bytes of the streams in `object` itself, adding what it refers to, short
+/// of parents, to `todo`
+fn stream_bytes(object: &Object, todo: &mut Vec<ObjectId>) -> u64 {
+    match object {
+        Object::Reference(id) => {
+            todo.push(*id);
+            0
+        }
+        Object::Array(items) => items.iter().map(|item| stream_bytes(item, todo)).sum(),
+        Object::Dictionary(dict) => dict_bytes(dict, todo),
+        Object::Stream(stream) => stream.content.len() as u64 + dict_bytes(&stream.dict, todo),
+        _ => 0,
+    }
+}
+
+fn dict_bytes(dict: &Dictionary, todo: &mut Vec<ObjectId>) -> u64 {
+    dict.iter()
+        .filter(|(key, _)| key.as_slice() != b"Parent")
+        .map(|(_, value)| stream_bytes(value, todo))
+        .sum()
+}
+
+struct Volume {
+    /// 1-based
+    number: usize,
+    count: usize,
+    /// First and last page in the whole document, 1-based
+    pages: (usize, usize),
+}
+
+/// Adds a page the size of `like` naming the volume and its pages, returning
+/// its id. The page is not put in the page tree.
+fn add_volume_cover(
+    doc: &mut lopdf::Document,
+    like: ObjectId,
+    title: &str,
+    volume: &Volume,
+) -> lopdf::Result<ObjectId> {
+    let (w, h) = page_size(doc, like).unwrap_or(DEFAULT_COVER_SIZE);
+    let lines = [
+        (18.0, ascii(title)),
+        (
+            14.0,
+            format!("Volume {} of {}", volume.number, volume.count),
+        ),
+        (11.0, format!("Pages {}-{}", volume.pages.0, volume.pages.1)),
+    ];
+    let mut operations = vec![Operation::new("BT", vec![])];
+    let mut y = h * 0.66;
+    for (size, text) in lines {
+        let x = (w - text_width(&text, size)) / 2.0;
+        operations.extend([
+            Operation::new("Tf", vec!["F1".into(), size.into()]),
+            Operation::new(
+                "Tm",
+                vec![1.into(), 0.into(), 0.into(), 1.into(), x.into(), y.into()],
+            ),
+            Operation::new("Tj", vec![Object::string_literal(text)]),
+        ]);
+        y -= 2.0 * size;
+    }
+    operations.push(Operation::new("ET", vec![]));
+    let content = Content { operations }.encode()?;
+
+    let font = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let contents = doc.add_object(Stream::new(dictionary! {}, content));
+    Ok(doc.add_object(dictionary! {
+        "Type" => "Page",
+        "MediaBox" => vec![0.into(), 0.into(), w.into(), h.into()],
+        "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+        "Contents" => contents,
+    }))
+}
+
+/// Labels the pages of a part with their numbers in the whole document, so
+/// that viewers count on where the previous volume stopped, and records
+/// which volume it is in the document information
+fn number_pages(doc: &mut lopdf::Document, volume: &Volume, cover: bool) -> lopdf::Result<()> {
+    let mut nums = Vec::new();
+    if cover {
+        let label = format!("Volume {}", volume.number);
+        nums.extend([
+            0.into(),
+            dictionary! { "P" => info::text_string(&label) }.into(),
+        ]);
+    }
+    nums.extend([
+        (cover as i64).into(),
+        dictionary! { "S" => "D", "St" => volume.pages.0 as i64 }.into(),
+    ]);
+    let labels = doc.add_object(dictionary! { "Nums" => nums });
+    let root = doc.trailer.get(b"Root")?.as_reference()?;
+    doc.get_object_mut(root)?
+        .as_dict_mut()?
+        .set("PageLabels", labels);
+    if doc.trailer.get(b"Info").is_ok() {
+        let entry = format!("{} of {}", volume.number, volume.count);
+        info::add_custom_entries(doc, &[("Volume".to_owned(), entry)])?;
+    }
+    Ok(())
+}
+
+/// One part per top level bookmark running up to the next one. Pages before
+/// the first bookmark form a part of their own.
+fn bookmark_parts(
+    doc: &lopdf::Document,
+    pages: &[ObjectId],
+    stem: &str,
+) -> Vec<(String, Vec<ObjectId>)> {
+    let mut starts = outline::entries(doc)
+        .into_iter()
+        .filter_map(|(title, target)| Some((pages.iter().position(|p| *p == target)?, title)))
+        .collect::<Vec<_>>();
+    starts.sort_by_key(|(start, _)| *start);
+    // several bookmarks on one page start a single part
+    starts.dedup_by_key(|(start, _)| *start);
+    if starts.is_empty() {
+        return Vec::new();
+    }
+    if starts[0].0 > 0 {
+        starts.insert(0, (0, stem.to_owned()));
+    }
+
+    let width = starts.len().to_string().len();
+    let mut parts = Vec::new();
+    for (i, (start, title)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(pages.len(), |(next, _)| *next);
+        let name = format!("{:0width$}-{}", i + 1, file_name(title));
+        parts.push((name, pages[*start..end].to_vec()));
+    }
+    parts
+}
+
+/// Keeps a bookmark title usable as a file name on every platform
+fn file_name(title: &str) -> String {
+    let name = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || " -_.,()".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() {
+        "part".to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PDF of `count` pages, each drawing on a stream of `bytes` bytes
+    fn pdf(count: usize, bytes: usize) -> Vec<u8> {
+        let mut doc = lopdf::Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids = (0..count)
+            .map(|i| {
+                let content = Stream::new(dictionary! {}, vec![b'%' + i as u8 % 2; bytes]);
+                let contents = doc.add_object(content);
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+                    "Contents" => contents,
+                })
+                .into()
+            })
+            .collect::<Vec<Object>>();
+        doc.objects.insert(
+            pages_id,
+            dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count as i64 }.into(),
+        );
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        let mut saved = Vec::new();
+        doc.save_to(&mut saved).unwrap();
+        saved
+    }
+
+    #[test]
+    fn parts_keep_within_limits() {
+        let pdf = pdf(5, 1000);
+        let by_pages = Limits {
+            pages: Some(2),
+            bytes: None,
+        };
+        let parts = by_limits(&pdf, by_pages).unwrap();
+        assert_eq!(parts.iter().map(|(n, _)| *n).collect::<Vec<_>>(), [2, 2, 1]);
+
+        let by_size = Limits {
+            pages: None,
+            bytes: Some(3000),
+        };
+        let parts = by_limits(&pdf, by_size).unwrap();
+        assert_eq!(parts.iter().map(|(n, _)| n).sum::<usize>(), 5);
+        assert!(parts.len() > 2 && parts.iter().all(|(_, part)| part.len() <= 3000));
+
+        // a page too large on its own still gets a part
+        let tiny = Limits {
+            pages: None,
+            bytes: Some(10),
+        };
+        assert_eq!(by_limits(&pdf, tiny).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn parts_hold_only_their_own_pages() {
+        let pdf = pdf(4, 5000);
+        let single = Limits {
+            pages: Some(1),
+            bytes: None,
+        };
+        for (_, part) in by_limits(&pdf, single).unwrap() {
+            assert!(part.len() < 6000, "{} bytes", part.len());
+            let doc = lopdf::Document::load_mem(&part).unwrap();
+            assert_eq!(doc.get_pages().len(), 1);
+        }
+    }
+}
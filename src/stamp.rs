@@ -0,0 +1,102 @@
+use clap::{Arg, ArgMatches, Command, ValueHint};
+use printpdf::lopdf;
+use std::error::Error;
+use std::path::Path;
+use std::process::exit;
+use yet_another_imgs2pdf::overlay::{self, Stamp, RUNNING_TEXT_VARIABLES};
+use yet_another_imgs2pdf::template;
+
+/// The stamping options, shared by the merge and the `stamp` subcommand
+pub fn args() -> [Arg<'static>; 5] {
+    [
+        Arg::new("watermark-text")
+            .help("Draw a translucent text diagonally across every page")
+            .takes_value(true)
+            .long("watermark-text"),
+        Arg::new("page-numbers")
+            .help("Number the pages at the bottom center")
+            .long("page-numbers"),
+        Arg::new("header")
+            .help("Write a line at the top center of every page, filling in {page}, {total}, {filename} and {stem}")
+            .takes_value(true)
+            .long("header"),
+        Arg::new("footer")
+            .help("Write a line at the bottom center of every page, above the page number, filling in {page}, {total}, {filename} and {stem}")
+            .takes_value(true)
+            .long("footer"),
+        Arg::new("layers")
+            .help("Put watermarks, page numbers and recognized text on layers viewers can hide")
+            .long("layers"),
+    ]
+}
+
+pub fn from_matches(matches: &ArgMatches) -> Option<Stamp> {
+    let stamp = Stamp {
+        watermark: matches.value_of("watermark-text").map(str::to_owned),
+        page_numbers: matches.is_present("page-numbers"),
+        header: matches.value_of("header").map(str::to_owned),
+        footer: matches.value_of("footer").map(str::to_owned),
+    };
+    let wanted = stamp.watermark.is_some()
+        || stamp.page_numbers
+        || stamp.header.is_some()
+        || stamp.footer.is_some();
+    wanted.then_some(stamp)
+}
+
+pub fn command() -> Command<'static> {
+    Command::new("stamp")
+        .about("Stamp a watermark, page numbers or a header and footer onto an existing PDF")
+        .arg(
+            Arg::new("input")
+                .allow_invalid_utf8(true)
+                .help("The PDF to stamp")
+                .required(true)
+                .value_hint(ValueHint::FilePath),
+        )
+        .args(args())
+        .arg(
+            Arg::new("out")
+                .allow_invalid_utf8(true)
+                .required(true)
+                .value_hint(ValueHint::FilePath)
+                .long("out")
+                .short('o'),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let stamp = match from_matches(matches) {
+        Some(stamp) => stamp,
+        None => {
+            eprintln!(
+                "Nothing to stamp, pass --watermark-text, --page-numbers, --header and/or --footer"
+            );
+            exit(1)
+        }
+    };
+    for (name, text) in [("header", &stamp.header), ("footer", &stamp.footer)] {
+        let known = |var: &str| RUNNING_TEXT_VARIABLES.contains(&var).then(String::new);
+        if let Some(Err(e)) = text.as_ref().map(|t| template::render(t, known)) {
+            eprintln!("Value <{name}> is invalid, {e}");
+            exit(1)
+        }
+    }
+    let input = Path::new(matches.value_of_os("input").unwrap());
+    let mut doc = lopdf::Document::load(input)?;
+    // every page comes from the same file
+    let name = input
+        .file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().into_owned());
+    let file_names = vec![name; doc.get_pages().len()];
+    overlay::apply(&mut doc, &stamp, matches.is_present("layers"), &file_names)?;
+
+    let out_path = Path::new(matches.value_of_os("out").unwrap());
+    doc.save(out_path)?;
+    println!(
+        "Stamped {} page(s) into `{}`",
+        doc.get_pages().len(),
+        out_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use printpdf::image_crate::{DynamicImage, RgbaImage};
+
+/// Fewest matching rows accepted as a real scroll overlap
+const MIN_OVERLAP: usize = 16;
+
+/// Joins consecutive scrolling screenshots into tall images, dropping the
+/// strip each capture repeats from the previous one. Status bars and other
+/// rows that stay fixed on every capture are kept once at the top and bottom.
+#[derive(Default)]
+pub struct Stitcher {
+    width: u32,
+    canvas: Vec<u8>,
+    canvas_sigs: Vec<u64>,
+    prev_sigs: Vec<u64>,
+}
+
+impl Stitcher {
+    /// Adds a capture, returning the finished stitched image when it does not
+    /// continue the previous one
+    pub fn push(&mut self, img: DynamicImage) -> Option<DynamicImage> {
+        let img = img.to_rgba8();
+        let sigs = row_signatures(&img);
+
+        if self.canvas_sigs.is_empty() || img.width() != self.width {
+            let finished = self.finish();
+            self.start(img, sigs);
+            return finished;
+        }
+
+        let h = sigs.len();
+        let cap = h / 4;
+        let header = common_len(self.prev_sigs.iter(), sigs.iter()).min(cap);
+        let footer = common_len(self.prev_sigs.iter().rev(), sigs.iter().rev()).min(cap);
+        let content = &sigs[header..h - footer];
+        // the canvas ends with the previous capture, fixed footer included
+        let body = &self.canvas_sigs[..self.canvas_sigs.len() - footer];
+
+        let overlap = (MIN_OVERLAP..=content.len().min(body.len()))
+            .rev()
+            .find(|k| body[body.len() - k..] == content[..*k]);
+        let k = match overlap {
+            Some(k) => k,
+            None => {
+                let finished = self.finish();
+                self.start(img, sigs);
+                return finished;
+            }
+        };
+
+        let row_len = self.width as usize * 4;
+        let body_len = body.len();
+        self.canvas.truncate(body_len * row_len);
+        self.canvas_sigs.truncate(body_len);
+        self.canvas
+            .extend_from_slice(&img.as_raw()[(header + k) * row_len..]);
+        self.canvas_sigs.extend_from_slice(&sigs[header + k..]);
+        self.prev_sigs = sigs;
+        None
+    }
+
+    /// Returns whatever is stitched so far and resets the stitcher
+    pub fn finish(&mut self) -> Option<DynamicImage> {
+        if self.canvas_sigs.is_empty() {
+            return None;
+        }
+        let height = self.canvas_sigs.len() as u32;
+        let canvas = std::mem::take(self);
+        RgbaImage::from_raw(canvas.width, height, canvas.canvas).map(DynamicImage::ImageRgba8)
+    }
+
+    fn start(&mut self, img: RgbaImage, sigs: Vec<u64>) {
+        self.width = img.width();
+        self.canvas = img.into_raw();
+        self.canvas_sigs = sigs.clone();
+        self.prev_sigs = sigs;
+    }
+}
+
+/// FNV-1a over the row with the low bits of every channel dropped, so light
+/// compression noise does not break an otherwise exact match
+fn row_signatures(img: &RgbaImage) -> Vec<u64> {
+    img.as_raw()
+        .chunks(img.width() as usize * 4)
+        .map(|row| {
+            row.iter().fold(0xcbf29ce484222325, |hash, b| {
+                (hash ^ (*b >> 3) as u64).wrapping_mul(0x100000001b3)
+            })
+        })
+        .collect()
+}
+
+fn common_len<'a>(a: impl Iterator<Item = &'a u64>, b: impl Iterator<Item = &'a u64>) -> usize {
+    a.zip(b).take_while(|(a, b)| a == b).count()
+}
@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::{self, stderr, stdout, IsTerminal, Write};
+use std::sync::OnceLock;
+
+pub const CHOICES: [&str; 3] = ["auto", "always", "never"];
+
+/// Whether stdout and stderr get colors
+static STYLED: OnceLock<(bool, bool)> = OnceLock::new();
+/// Whether the progress line is shown
+static PROGRESS: OnceLock<bool> = OnceLock::new();
+
+/// Decides on styling for `choice`, one of [`CHOICES`]. `auto` colors a
+/// stream only when it is a terminal and `NO_COLOR` is not set. The progress
+/// line is left out of logs and pipes, as carriage returns garble them.
+pub fn init(choice: &str) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = std::env::var_os("TERM").is_some_and(|t| t == "dumb");
+    let auto = |terminal: bool| terminal && !no_color && !dumb;
+    let styled = match choice {
+        "always" => (true, true),
+        "never" => (false, false),
+        _ => (auto(stdout().is_terminal()), auto(stderr().is_terminal())),
+    };
+    let _ = STYLED.set(styled);
+    let _ = PROGRESS.set(choice == "always" || stdout().is_terminal() && !dumb);
+}
+
+/// Sends everything printed to stdout, by this process and the programs it
+/// runs, to stderr instead and gives back the original stdout, for output
+/// that has to be the only thing on it. Call before [`init`] so that colors
+/// follow where messages end up.
+#[cfg(unix)]
+pub fn take_stdout() -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    stdout().flush()?;
+    let original = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if original < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        let e = io::Error::last_os_error();
+        unsafe { libc::close(original) };
+        return Err(e);
+    }
+    Ok(unsafe { File::from_raw_fd(original) })
+}
+
+#[cfg(not(unix))]
+pub fn take_stdout() -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "messages can only be moved off stdout on Unix-like systems",
+    ))
+}
+
+fn styled() -> (bool, bool) {
+    *STYLED.get().unwrap_or(&(false, false))
+}
+
+fn paint(on: bool, code: &str, text: &str) -> String {
+    if on {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// For stdout
+pub fn success(text: &str) -> String {
+    paint(styled().0, "32", text)
+}
+
+/// For stdout
+pub fn warning(text: &str) -> String {
+    paint(styled().0, "33", text)
+}
+
+/// For stderr
+pub fn error(text: &str) -> String {
+    paint(styled().1, "1;31", text)
+}
+
+fn progress_shown() -> bool {
+    *PROGRESS.get().unwrap_or(&false)
+}
+
+/// Shows `text` on a line that the next message overwrites
+pub fn progress(text: &str) {
+    if progress_shown() {
+        print!("\r\x1b[K{text}\r");
+        let _ = stdout().flush();
+    }
+}
+
+/// Clears the progress line before other output
+pub fn end_progress() {
+    if progress_shown() {
+        print!("\r\x1b[K");
+    }
+}
@@ -0,0 +1,39 @@
+/// Fills `{name}` placeholders in a template through `lookup`. Numbers can be
+/// zero padded with `{name:03}`, and `{{`/`}}` stand for literal braces.
+/// Fails on unbalanced braces or when `lookup` does not know a name.
+pub fn render(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        out.push_str(&rest[..at]);
+        let tail = &rest[at..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("unmatched `}}` in `{template}`"));
+        }
+        let end = tail
+            .find('}')
+            .ok_or_else(|| format!("unclosed `{{` in `{template}`"))?;
+        let (name, width) = match tail[1..end].split_once(':') {
+            Some((name, width)) => {
+                let width = width
+                    .parse::<usize>()
+                    .map_err(|_| format!("`{width}` is not a width in `{template}`"))?;
+                (name, width)
+            }
+            None => (&tail[1..end], 0),
+        };
+        let value = lookup(name).ok_or_else(|| format!("unknown variable `{{{name}}}`"))?;
+        out.push_str(&format!("{value:0>width$}"));
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+
+use crate::scratch::ScratchDir;
+
+const TIFF_EXTENSIONS: [&str; 2] = ["tif", "tiff"];
+/// Most pages read from one TIFF file, past which the rest are left out
+const MAX_PAGES: usize = 10_000;
+/// Tags pointing at directories of their own, such as the EXIF data, which
+/// are left out of the single-page files
+const SUB_DIRECTORIES: [u16; 4] = [330, 34665, 34853, 40965];
+/// Tags listing where the pieces of the image are, each with the tag listing
+/// how long they are: strips, tiles and old-style JPEG data
+const IMAGE_DATA: [(u16, u16); 3] = [(273, 279), (324, 325), (513, 514)];
+
+pub fn is_tiff(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| TIFF_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Whether `path` is a TIFF file of more than one page
+pub fn is_multipage(path: &Path) -> bool {
+    is_tiff(path) && std::fs::read(path).is_ok_and(|data| page_count(&data) > 1)
+}
+
+/// A TIFF file, read in its byte order
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// `data` if it is a TIFF file. BigTIFF and files that are not TIFF are
+    /// not.
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let big_endian = match data.get(..4) {
+            Some(b"MM\0*") => true,
+            Some(b"II*\0") => false,
+            _ => return None,
+        };
+        Some(Self { data, big_endian })
+    }
+
+    fn u16_at(&self, at: usize) -> Option<u16> {
+        let b = self.data.get(at..at.checked_add(2)?)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        })
+    }
+
+    fn u32_at(&self, at: usize) -> Option<u32> {
+        let b = self.data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        })
+    }
+
+    fn u16_bytes(&self, n: u16) -> [u8; 2] {
+        match self.big_endian {
+            true => n.to_be_bytes(),
+            false => n.to_le_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, n: u32) -> [u8; 4] {
+        match self.big_endian {
+            true => n.to_be_bytes(),
+            false => n.to_le_bytes(),
+        }
+    }
+
+    /// Where each image file directory starts, in the order they are chained
+    fn directories(&self) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut next = self.u32_at(4).unwrap_or(0) as usize;
+        // a damaged chain may loop back on itself
+        while next != 0 && found.len() < MAX_PAGES && seen.insert(next) {
+            let Some(count) = self.u16_at(next) else {
+                break;
+            };
+            found.push(next);
+            next = self.u32_at(next + 2 + count as usize * 12).unwrap_or(0) as usize;
+        }
+        found
+    }
+
+    /// Where the value of the directory entry at `entry` sits and how long it
+    /// is. Values of up to four bytes sit in the entry itself.
+    fn value_at(&self, entry: usize) -> Option<(usize, usize)> {
+        let size = match self.u16_at(entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 | 13 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let len = (self.u32_at(entry + 4)? as usize).checked_mul(size)?;
+        let at = match len {
+            0..=4 => entry + 8,
+            _ => self.u32_at(entry + 8)? as usize,
+        };
+        self.data.get(at..at.checked_add(len)?)?;
+        Some((at, len))
+    }
+
+    /// The numbers the SHORT or LONG directory entry at `entry` holds
+    fn numbers(&self, entry: usize) -> Option<Vec<usize>> {
+        let (at, len) = self.value_at(entry)?;
+        match self.u16_at(entry + 2)? {
+            3 => (0..len / 2)
+                .map(|i| Some(self.u16_at(at + i * 2)? as usize))
+                .collect(),
+            4 => (0..len / 4)
+                .map(|i| Some(self.u32_at(at + i * 4)? as usize))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// The directory at `at` as a TIFF file of its own, holding only that
+    /// directory and the values and pieces of the image it points at, moved
+    /// to where they land in the new file. Entries of unknown types are left
+    /// out, and `None` is returned when the image is not all in the file.
+    fn single_page(&self, at: usize) -> Option<Vec<u8>> {
+        let count = self.u16_at(at)? as usize;
+        let entries = (0..count)
+            .map(|i| at + 2 + i * 12)
+            .filter(|&entry| {
+                self.value_at(entry).is_some()
+                    && self
+                        .u16_at(entry)
+                        .is_some_and(|tag| !SUB_DIRECTORIES.contains(&tag))
+            })
+            .collect::<Vec<_>>();
+        let find = |tag| {
+            entries
+                .iter()
+                .copied()
+                .find(|&entry| self.u16_at(entry) == Some(tag))
+        };
+
+        let mut page = self.data[..4].to_vec();
+        page.extend_from_slice(&self.u32_bytes(8));
+        page.extend_from_slice(&self.u16_bytes(entries.len() as u16));
+        let table = page.len();
+        // the entries, then a link to no next directory
+        page.resize(table + entries.len() * 12 + 4, 0);
+        for (i, &entry) in entries.iter().enumerate() {
+            let field = table + i * 12;
+            page[field..field + 8].copy_from_slice(&self.data[entry..entry + 8]);
+            let tag = self.u16_at(entry)?;
+            let value = match IMAGE_DATA.iter().find(|(offsets, _)| *offsets == tag) {
+                Some(&(_, lengths)) => {
+                    let offsets = self.numbers(entry)?;
+                    let lengths = self.numbers(find(lengths)?)?;
+                    if offsets.len() != lengths.len() {
+                        return None;
+                    }
+                    let mut moved = Vec::with_capacity(offsets.len() * 4);
+                    for (offset, len) in offsets.into_iter().zip(lengths) {
+                        let piece = self.data.get(offset..offset.checked_add(len)?)?;
+                        align(&mut page);
+                        moved.extend_from_slice(&self.u32_bytes(page.len() as u32));
+                        page.extend_from_slice(piece);
+                        // crafted files may point every piece at the same bytes
+                        if page.len() > 2 * self.data.len() {
+                            return None;
+                        }
+                    }
+                    // the new offsets may not fit the SHORTs the old ones did
+                    page[field + 2..field + 4].copy_from_slice(&self.u16_bytes(4));
+                    moved
+                }
+                None => {
+                    let (at, len) = self.value_at(entry)?;
+                    self.data[at..at + len].to_vec()
+                }
+            };
+            if value.len() <= 4 {
+                page[field + 8..field + 8 + value.len()].copy_from_slice(&value);
+            } else {
+                align(&mut page);
+                let offset = self.u32_bytes(page.len() as u32);
+                page[field + 8..field + 12].copy_from_slice(&offset);
+                page.extend_from_slice(&value);
+            }
+        }
+        Some(page)
+    }
+}
+
+/// Pads `data` to the even offset the next value has to start at
+fn align(data: &mut Vec<u8>) {
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+}
+
+/// How many pages the TIFF file `data` holds
+pub fn page_count(data: &[u8]) -> usize {
+    Tiff::new(data).map_or(0, |tiff| tiff.directories().len())
+}
+
+/// Splits a multi-page TIFF into single-page TIFFs in a scratch directory,
+/// written one at a time, each keeping its own size, resolution and
+/// orientation tags
+pub fn split(tiff: &Path) -> io::Result<ScratchDir> {
+    let data = std::fs::read(tiff)?;
+    let file = Tiff::new(&data)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a TIFF file"))?;
+    let mut pages = ScratchDir::new("tiff", tiff)?;
+    for (i, at) in file.directories().into_iter().enumerate() {
+        let page = file.single_page(at).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("page {} is damaged", i + 1),
+            )
+        })?;
+        let path = pages.dir().join(format!("page-{:04}.tif", i + 1));
+        std::fs::write(&path, page)?;
+        pages.paths.push(path);
+    }
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little endian TIFF header followed by `count` empty directories
+    fn chain(count: u32) -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend_from_slice(&8u32.to_le_bytes());
+        for i in 0..count {
+            data.extend_from_slice(&0u16.to_le_bytes());
+            let next = if i + 1 < count { 8 + (i + 1) * 6 } else { 0 };
+            data.extend_from_slice(&next.to_le_bytes());
+        }
+        data
+    }
+
+    /// A little endian TIFF of `count` gray 32x32 pages, each filled with its
+    /// number in two strips, with the strips listed and the resolution
+    /// stored apart from the directory
+    fn gray_pages(count: u8) -> Vec<u8> {
+        let mut data = b"II*\0\0\0\0\0".to_vec();
+        let mut link = 4;
+        for n in 0..count {
+            let strips = data.len() as u32;
+            data.resize(data.len() + 32 * 32, n);
+            let values = data.len() as u32;
+            for value in [300, 1, strips, strips + 512, 512, 512] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            let directory = (data.len() as u32).to_le_bytes();
+            data[link..link + 4].copy_from_slice(&directory);
+            let entries: [(u16, u16, u32, u32); 9] = [
+                (256, 3, 1, 32),
+                (257, 3, 1, 32),
+                (258, 3, 1, 8),
+                (259, 3, 1, 1),
+                (262, 3, 1, 1),
+                (273, 4, 2, values + 8),
+                (278, 3, 1, 16),
+                (279, 4, 2, values + 16),
+                (282, 5, 1, values),
+            ];
+            data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+            for (tag, kind, count, value) in entries {
+                data.extend_from_slice(&tag.to_le_bytes());
+                data.extend_from_slice(&kind.to_le_bytes());
+                data.extend_from_slice(&count.to_le_bytes());
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            link = data.len();
+            data.extend_from_slice(&0u32.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn counts_and_splits_pages() {
+        let data = chain(3);
+        assert_eq!(page_count(&data), 3);
+        let file = Tiff::new(&data).unwrap();
+        for at in file.directories() {
+            let page = file.single_page(at).unwrap();
+            assert_eq!(Tiff::new(&page).unwrap().directories(), [8]);
+        }
+        assert_eq!(page_count(b"\x89PNG\r\n\x1a\n"), 0);
+        assert_eq!(page_count(&chain(MAX_PAGES as u32 + 5)), MAX_PAGES);
+    }
+
+    #[test]
+    fn pages_hold_only_their_own_strips() {
+        let data = gray_pages(20);
+        let file = Tiff::new(&data).unwrap();
+        let directories = file.directories();
+        assert_eq!(directories.len(), 20);
+        for (n, at) in directories.into_iter().enumerate() {
+            let page = file.single_page(at).unwrap();
+            assert!(page.len() * 10 < data.len());
+
+            let page = Tiff::new(&page).unwrap();
+            let directory = page.directories();
+            assert_eq!(directory.len(), 1);
+            let entry = |i: usize| directory[0] + 2 + i * 12;
+            let strips = page.numbers(entry(5)).unwrap();
+            assert_eq!(strips.len(), 2);
+            for strip in strips {
+                assert!(page.data[strip..strip + 512].iter().all(|&b| b == n as u8));
+            }
+            let (at, len) = page.value_at(entry(8)).unwrap();
+            assert_eq!(page.data[at..at + len], [44, 1, 0, 0, 1, 0, 0, 0]);
+        }
+    }
+
+    #[test]
+    fn pieces_outside_the_file_are_damage() {
+        let mut data = gray_pages(2);
+        // the second strip of the first page starts past the end
+        let at = 8 + 32 * 32 + 12;
+        data[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let file = Tiff::new(&data).unwrap();
+        let directories = file.directories();
+        assert!(file.single_page(directories[0]).is_none());
+        assert!(file.single_page(directories[1]).is_some());
+    }
+
+    #[test]
+    fn looping_chains_end() {
+        let mut data = chain(2);
+        // the second directory links back to the first
+        data[16..20].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(page_count(&data), 2);
+    }
+}
@@ -0,0 +1,74 @@
+use std::fmt::Write as _;
+
+/// A value of the subset of TOML read from settings and manifest files:
+/// booleans, basic strings and one-line string arrays
+pub enum Value {
+    Flag(bool),
+    Single(String),
+    List(Vec<String>),
+}
+
+/// Parses the value of a `key = value` line
+pub fn parse_value(value: &str) -> Option<Value> {
+    Some(match value {
+        "true" => Value::Flag(true),
+        "false" => Value::Flag(false),
+        _ if value.starts_with('[') => {
+            let mut rest = value[1..].trim_start();
+            let mut values = Vec::new();
+            while !rest.starts_with(']') {
+                let (v, after) = unquote(rest)?;
+                values.push(v);
+                rest = after.trim_start();
+                rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+                if rest.is_empty() {
+                    return None;
+                }
+            }
+            Value::List(values)
+        }
+        _ => Value::Single(unquote(value)?.0),
+    })
+}
+
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04X}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parses a basic string at the start of `s`, returning it and the rest
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'u' => {
+                    let hex = (0..4)
+                        .map(|_| chars.next().map(|(_, c)| c))
+                        .collect::<Option<String>>()?;
+                    value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
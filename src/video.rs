@@ -0,0 +1,44 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::scratch::ScratchDir;
+
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mov", "mkv", "avi", "webm", "mts"];
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Extracts one frame of `video` per `every` interval, or every keyframe
+/// when `None`, with `ffmpeg` into a scratch directory
+pub fn frames(video: &Path, every: Option<Duration>) -> io::Result<ScratchDir> {
+    let mut frames = ScratchDir::new("frames", video)?;
+
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-v", "error", "-nostdin"]);
+    match every {
+        Some(every) => {
+            cmd.arg("-i").arg(video);
+            cmd.arg("-vf").arg(format!("fps=1/{}", every.as_secs_f64()));
+        }
+        None => {
+            cmd.args(["-skip_frame", "nokey", "-i"]).arg(video);
+            cmd.args(["-vsync", "vfr"]);
+        }
+    }
+    let status = cmd.arg(frames.dir().join("frame-%06d.png")).status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("ffmpeg exited with {status}")));
+    }
+
+    frames.paths = std::fs::read_dir(frames.dir())?
+        .filter_map(|rd| rd.map(|de| de.path()).ok())
+        .collect();
+    frames.paths.sort();
+    Ok(frames)
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use yet_another_imgs2pdf::glob::glob_match;
+
+use crate::ignore::Ignore;
+use crate::order;
+
+/// Which entries of the input directory are taken
+#[derive(Default)]
+pub struct Filter {
+    /// Takes only files matching one of these, when there are any
+    pub include: Vec<String>,
+    /// Leaves out files and whole directories matching one of these
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    /// Patterns are split at commas and, like in the ignore file, matched
+    /// against the file name unless they hold a `/`, in which case they are
+    /// matched against the path relative to the input directory. Case is
+    /// ignored, so `*.jpg` also takes `IMG_0001.JPG`.
+    pub fn new<'a>(
+        include: impl Iterator<Item = &'a str>,
+        exclude: impl Iterator<Item = &'a str>,
+    ) -> Filter {
+        Filter {
+            include: patterns(include),
+            exclude: patterns(exclude),
+        }
+    }
+
+    /// Whether `path`, relative to the input directory, is taken
+    pub fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let matches = |pattern: &String| {
+            let path = path.to_string_lossy().replace('\\', "/").to_lowercase();
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+            let text = if pattern.contains('/') { &path } else { name };
+            glob_match(pattern, text)
+        };
+        if self.exclude.iter().any(matches) {
+            return false;
+        }
+        is_dir || self.include.is_empty() || self.include.iter().any(matches)
+    }
+}
+
+fn patterns<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    values
+        .flat_map(|p| p.split(','))
+        .map(|p| p.trim().to_lowercase())
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// The files in `dir` that the ignore file and `filter` let through, in name
+/// order with numbers in order. With `recursive` the files of every
+/// subdirectory follow in place of it, depth first.
+pub fn files(dir: &Path, recursive: bool, filter: &Filter) -> io::Result<Vec<PathBuf>> {
+    let ignore = Ignore::read(dir)?;
+    let mut files = Vec::new();
+    visit(dir, dir, recursive, filter, &ignore, &mut files)?;
+    Ok(files)
+}
+
+fn visit(
+    root: &Path,
+    dir: &Path,
+    recursive: bool,
+    filter: &Filter,
+    ignore: &Ignore,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .filter_map(|entry| entry.map(|e| e.path()).ok())
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| order::natural_cmp(a, b));
+    for path in entries {
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let is_dir = path.is_dir();
+        if ignore.is_ignored(relative, is_dir) || !filter.allows(relative, is_dir) {
+            continue;
+        }
+        if !is_dir {
+            files.push(path);
+        } else if recursive {
+            visit(root, &path, recursive, filter, ignore, files)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::walk;
+
+/// How often the directory is listed again
+const POLL: Duration = Duration::from_millis(500);
+
+/// Set by Ctrl-C while watching
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Lists a directory over and over for files that were not in it before
+pub struct Watcher<'a> {
+    dir: PathBuf,
+    recursive: bool,
+    filter: &'a walk::Filter,
+    seen: HashSet<PathBuf>,
+    /// New files not handed out yet, with their size and modification time
+    /// when last listed
+    arriving: HashMap<PathBuf, (u64, Option<SystemTime>)>,
+}
+
+impl<'a> Watcher<'a> {
+    /// Watches `dir` the way it was read for the inputs, for files other
+    /// than those in `existing`
+    pub fn new(
+        dir: &Path,
+        recursive: bool,
+        filter: &'a walk::Filter,
+        existing: &[PathBuf],
+    ) -> Self {
+        Watcher {
+            dir: dir.to_path_buf(),
+            recursive,
+            filter,
+            seen: existing.iter().cloned().collect(),
+            arriving: HashMap::new(),
+        }
+    }
+
+    /// The new files that have not changed since they were last listed, so
+    /// that a scan still being written is not picked up half done
+    pub fn poll(&mut self) -> std::io::Result<Vec<PathBuf>> {
+        let mut ready = Vec::new();
+        for file in walk::files(&self.dir, self.recursive, self.filter)? {
+            if self.seen.contains(&file) {
+                continue;
+            }
+            let meta = match std::fs::metadata(&file) {
+                Ok(meta) => meta,
+                // removed again before it could be read
+                Err(_) => continue,
+            };
+            let now = (meta.len(), meta.modified().ok());
+            if self.arriving.insert(file.clone(), now) == Some(now) {
+                self.arriving.remove(&file);
+                self.seen.insert(file.clone());
+                ready.push(file);
+            }
+        }
+        Ok(ready)
+    }
+}
+
+/// Hands every new file of `watcher` to `add` as it arrives, until Ctrl-C or
+/// until none has arrived for `idle`
+pub fn run(
+    watcher: &mut Watcher,
+    idle: Option<Duration>,
+    mut add: impl FnMut(PathBuf) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    catch_interrupt(true);
+    let mut last = Instant::now();
+    let result = 'watch: loop {
+        if INTERRUPTED.load(Ordering::Relaxed) || idle.is_some_and(|idle| last.elapsed() >= idle) {
+            break Ok(());
+        }
+        std::thread::sleep(POLL);
+        let ready = match watcher.poll() {
+            Ok(ready) => ready,
+            Err(e) => break Err(e.into()),
+        };
+        for file in ready {
+            last = Instant::now();
+            if let Err(e) = add(file) {
+                break 'watch Err(e);
+            }
+        }
+    };
+    // a second Ctrl-C while the PDF is written stops for good
+    catch_interrupt(false);
+    result
+}
+
+#[cfg(unix)]
+fn catch_interrupt(catch: bool) {
+    extern "C" fn interrupted(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    let handler = match catch {
+        true => interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        false => libc::SIG_DFL,
+    };
+    // SAFETY: the handler only stores to an atomic
+    unsafe { libc::signal(libc::SIGINT, handler) };
+}
+
+/// Ctrl-C ends the process as usual, only the idle timeout finishes the PDF
+#[cfg(not(unix))]
+fn catch_interrupt(_catch: bool) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_files_once_settled() {
+        let dir = std::env::temp_dir().join(format!("imgs2pdf-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("001.png");
+        std::fs::write(&old, b"old").unwrap();
+        let filter = walk::Filter::new(std::iter::empty(), std::iter::empty());
+        let mut watcher = Watcher::new(&dir, false, &filter, &[old]);
+        assert!(watcher.poll().unwrap().is_empty());
+
+        let new = dir.join("002.png");
+        std::fs::write(&new, b"half").unwrap();
+        assert!(watcher.poll().unwrap().is_empty(), "just arrived");
+        std::fs::write(&new, b"half written").unwrap();
+        assert!(watcher.poll().unwrap().is_empty(), "still growing");
+        assert_eq!(watcher.poll().unwrap(), [new]);
+        assert!(watcher.poll().unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use std::io;
+
+use printpdf::image_crate::ImageFormat;
+
+use crate::inflate;
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    match data.get(at..at + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(invalid("archive is cut short")),
+    }
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    match data.get(at..at + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(invalid("archive is cut short")),
+    }
+}
+
+/// A file stored in a ZIP archive, as listed in its central directory
+pub struct Entry {
+    /// Its path inside the archive
+    pub name: String,
+    method: u16,
+    encrypted: bool,
+    compressed: usize,
+    size: usize,
+    /// Where its local header starts
+    offset: usize,
+}
+
+/// The entries of the ZIP archive `data` in the order they are stored
+fn entries(data: &[u8]) -> io::Result<Vec<Entry>> {
+    // the end record sits last, followed only by a comment of up to 64 KiB
+    let floor = data.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (floor..data.len().saturating_sub(21))
+        .rev()
+        .find(|&at| data[at..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| invalid("not a ZIP archive"))?;
+    let count = u16_at(data, end + 10)?;
+    let mut at = u32_at(data, end + 16)? as usize;
+    if count == u16::MAX || at == u32::MAX as usize {
+        return Err(invalid("ZIP64 archives are not supported"));
+    }
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if u32_at(data, at)? != 0x0201_4b50 {
+            return Err(invalid("archive has a damaged central directory"));
+        }
+        let name_len = u16_at(data, at + 28)? as usize;
+        let name = data
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| invalid("archive is cut short"))?;
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(data, at + 10)?,
+            encrypted: u16_at(data, at + 8)? & 1 != 0,
+            compressed: u32_at(data, at + 20)? as usize,
+            size: u32_at(data, at + 24)? as usize,
+            offset: u32_at(data, at + 42)? as usize,
+        });
+        let skip = name_len + u16_at(data, at + 30)? as usize + u16_at(data, at + 32)? as usize;
+        at += 46 + skip;
+    }
+    Ok(entries)
+}
+
+/// The contents of `entry`, an entry of the archive `data`, decompressed
+pub fn read(data: &[u8], entry: &Entry) -> io::Result<Vec<u8>> {
+    if entry.encrypted {
+        return Err(invalid("entry is encrypted"));
+    }
+    if u32_at(data, entry.offset)? != 0x0403_4b50 {
+        return Err(invalid("entry has a damaged header"));
+    }
+    let start = entry.offset
+        + 30
+        + u16_at(data, entry.offset + 26)? as usize
+        + u16_at(data, entry.offset + 28)? as usize;
+    let stored = data
+        .get(start..start + entry.compressed)
+        .ok_or_else(|| invalid("archive is cut short"))?;
+    match entry.method {
+        0 => Ok(stored.to_vec()),
+        8 => inflate::inflate(stored, entry.size),
+        method => Err(invalid(&format!(
+            "entry is compressed with unsupported method {method}"
+        ))),
+    }
+}
+
+/// Whether `name` is an image, and not a folder, hidden file or the resource
+/// forks macOS adds to archives it makes
+fn is_page(name: &str) -> bool {
+    !name.ends_with('/')
+        && !name
+            .split('/')
+            .any(|part| part.starts_with('.') || part == "__MACOSX")
+        && ImageFormat::from_path(name).is_ok()
+}
+
+/// The images stored in the CBZ/ZIP archive `data`, in the order they are
+/// stored, each to be read with [`read`]
+pub fn images(data: &[u8]) -> io::Result<Vec<Entry>> {
+    let mut entries = entries(data)?;
+    entries.retain(|e| is_page(&e.name));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A ZIP archive of `files`, each stored or deflated as given
+    fn zip(files: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+        let (mut data, mut directory) = (Vec::new(), Vec::new());
+        for (name, method, contents, size) in files {
+            let offset = data.len() as u32;
+            let header = |sig: u32, central: bool| {
+                let mut h = sig.to_le_bytes().to_vec();
+                if central {
+                    h.extend_from_slice(&20u16.to_le_bytes());
+                }
+                h.extend_from_slice(&20u16.to_le_bytes());
+                h.extend_from_slice(&0u16.to_le_bytes());
+                h.extend_from_slice(&method.to_le_bytes());
+                h.extend_from_slice(&[0; 8]);
+                h.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+                h.extend_from_slice(&(*size as u32).to_le_bytes());
+                h.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                h.extend_from_slice(&0u16.to_le_bytes());
+                if central {
+                    h.extend_from_slice(&[0; 10]);
+                    h.extend_from_slice(&offset.to_le_bytes());
+                }
+                h.extend_from_slice(name.as_bytes());
+                h
+            };
+            data.extend(header(0x0403_4b50, false));
+            data.extend_from_slice(contents);
+            directory.extend(header(0x0201_4b50, true));
+        }
+        let at = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(b"PK\x05\x06\0\0\0\0");
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&at.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn reads_stored_and_deflated_entries() {
+        let hello = [203, 72, 205, 201, 201, 87, 200, 64, 39, 1];
+        let data = zip(&[
+            ("b/10.png", 0, b"ten", 3),
+            ("a/2.png", 8, &hello, 23),
+            ("ComicInfo.xml", 0, b"<x/>", 4),
+        ]);
+        let listed = entries(&data).unwrap();
+        let names = listed.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["b/10.png", "a/2.png", "ComicInfo.xml"]);
+        assert_eq!(read(&data, &listed[0]).unwrap(), b"ten");
+        assert_eq!(read(&data, &listed[1]).unwrap(), b"hello hello hello hello");
+        let images = images(&data).unwrap();
+        let names = images.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["b/10.png", "a/2.png"]);
+
+        assert!(entries(&data[..data.len() - 1]).is_err());
+        assert!(entries(b"not an archive at all").is_err());
+    }
+
+    #[test]
+    fn only_images_are_pages() {
+        assert!(is_page("001.jpg"));
+        assert!(is_page("chapter 1/002.PNG"));
+        assert!(!is_page("chapter 1/"));
+        assert!(!is_page("ComicInfo.xml"));
+        assert!(!is_page("__MACOSX/._001.jpg"));
+        assert!(!is_page(".thumb.jpg"));
+    }
+}
@@ -0,0 +1,388 @@
+// each test crate uses only some of these
+#![allow(dead_code)]
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use printpdf::image_crate::{DynamicImage, ImageBuffer, Luma, Rgb};
+use printpdf::lopdf::{self, content::Content, Document, Object};
+use printpdf::OffsetDateTime;
+use yet_another_imgs2pdf::{PDFMerger, PdfMergerBuilder};
+
+/// Small xorshift generator, so property tests repeat from a seed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number from `low` up to but not including `high`
+    pub fn below(&mut self, low: u64, high: u64) -> u64 {
+        low + self.next_u64() % (high - low)
+    }
+
+    pub fn float(&mut self, low: f64, high: f64) -> f64 {
+        low + (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * (high - low)
+    }
+}
+
+/// A directory of generated inputs, removed when dropped
+pub struct Fixtures {
+    pub dir: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("imgs2pdf-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Fixtures { dir }
+    }
+
+    /// Writes a `w` by `h` image in the format its extension names, with a
+    /// pattern that only depends on `seed`
+    pub fn image(&self, name: &str, (w, h): (u32, u32), seed: u8) -> PathBuf {
+        let path = self.dir.join(name);
+        pattern((w, h), seed).save(&path).unwrap();
+        path
+    }
+
+    /// Writes `img` in the format the extension of `name` names
+    pub fn save(&self, name: &str, img: &DynamicImage) -> PathBuf {
+        let path = self.dir.join(name);
+        img.save(&path).unwrap();
+        path
+    }
+
+    /// Same as [`Fixtures::image`] in grayscale
+    pub fn gray_image(&self, name: &str, (w, h): (u32, u32), seed: u8) -> PathBuf {
+        let path = self.dir.join(name);
+        DynamicImage::ImageLuma8(pattern((w, h), seed).to_luma8())
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    /// Writes a PNG stored sideways, with an EXIF orientation saying how to
+    /// turn it upright
+    pub fn oriented_png(&self, name: &str, (w, h): (u32, u32), orientation: u16) -> PathBuf {
+        let path = self.image(name, (w, h), 7);
+        let png = std::fs::read(&path).unwrap();
+        std::fs::write(&path, with_exif_orientation(&png, orientation)).unwrap();
+        path
+    }
+}
+
+impl Drop for Fixtures {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Bands and a gradient, different enough between seeds and sizes for a
+/// mixed up page to show
+fn pattern((w, h): (u32, u32), seed: u8) -> DynamicImage {
+    DynamicImage::ImageRgb8(ImageBuffer::from_fn(w, h, |x, y| {
+        let band = ((x * 4 / w.max(1)) as u8).wrapping_mul(60);
+        let ramp = (y * 255 / h.max(1)) as u8;
+        Rgb([band, ramp, seed.wrapping_mul(37)])
+    }))
+}
+
+/// A white image with black text-like strokes, for black and white output
+pub fn strokes((w, h): (u32, u32)) -> DynamicImage {
+    DynamicImage::ImageLuma8(ImageBuffer::from_fn(w, h, |x, y| {
+        Luma([if y % 8 < 2 && x % 12 < 9 { 0 } else { 255 }])
+    }))
+}
+
+/// `png` with an eXIf chunk holding only `orientation`, right after IHDR
+fn with_exif_orientation(png: &[u8], orientation: u16) -> Vec<u8> {
+    let mut exif = b"MM\0*\0\0\0\x08\0\x01".to_vec();
+    exif.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+    exif.extend(orientation.to_be_bytes());
+    exif.extend([0, 0, 0, 0, 0, 0]);
+    let mut chunk = (exif.len() as u32).to_be_bytes().to_vec();
+    let body = [b"eXIf".as_slice(), &exif].concat();
+    chunk.extend(&body);
+    chunk.extend(crc32(&body).to_be_bytes());
+    // signature, then IHDR with its length, type and CRC
+    let ihdr_end = 8 + 8 + 13 + 4;
+    [&png[..ihdr_end], &chunk, &png[ihdr_end..]].concat()
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// A builder that gives the same file for the same inputs, at the inputs'
+/// own size so tiny fixtures stay quick
+pub fn builder() -> PdfMergerBuilder {
+    PdfMergerBuilder::new()
+        .title("fixture")
+        .scale_percent(100.0)
+        .deterministic(OffsetDateTime::unix_epoch())
+}
+
+/// The saved PDF of `merger` after adding `inputs`
+pub fn merge(mut merger: PDFMerger, inputs: &[PathBuf]) -> Vec<u8> {
+    for input in inputs {
+        merger.append_image_page(input).unwrap();
+    }
+    merger.finish();
+    let mut pdf = Vec::new();
+    merger.save(&mut pdf).unwrap();
+    pdf
+}
+
+/// A placed image: its size in pixels and where it sits on the page, in pt
+pub struct Placed {
+    pub name: String,
+    pub pixels: (i64, i64),
+    /// Left, bottom, width and height
+    pub rect: [f64; 4],
+}
+
+/// The media box and every image drawn on each page, in page order
+pub fn pages(pdf: &[u8]) -> Vec<([f64; 4], Vec<Placed>)> {
+    let doc = Document::load_mem(pdf).unwrap();
+    doc.get_pages()
+        .into_values()
+        .map(|id| {
+            let page = doc.get_dictionary(id).unwrap();
+            let media_box = numbers(&doc, page.get(b"MediaBox").unwrap());
+            let images = placed(&doc, id);
+            (
+                [media_box[0], media_box[1], media_box[2], media_box[3]],
+                images,
+            )
+        })
+        .collect()
+}
+
+fn placed(doc: &Document, page: lopdf::ObjectId) -> Vec<Placed> {
+    let content = Content::decode(&doc.get_page_content(page).unwrap()).unwrap();
+    let xobjects = page_xobjects(doc, page);
+    let mut matrix = None;
+    let mut images = Vec::new();
+    for op in &content.operations {
+        match op.operator.as_str() {
+            "cm" => {
+                let m = op
+                    .operands
+                    .iter()
+                    .map(Object::as_float)
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                matrix = Some([m[0], m[3], m[4], m[5]]);
+            }
+            "Do" => {
+                let name = op.operands[0].as_name().unwrap();
+                let image = match xobjects.iter().find(|(n, _)| n == name) {
+                    Some((_, image)) => image,
+                    None => continue,
+                };
+                let dict = &image.as_stream().unwrap().dict;
+                let size = |key: &[u8]| dict.get(key).unwrap().as_i64().unwrap();
+                let [w, h, x, y] = matrix.take().expect("an image drawn without a cm");
+                images.push(Placed {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    pixels: (size(b"Width"), size(b"Height")),
+                    rect: [x, y, w, h],
+                });
+            }
+            _ => {}
+        }
+    }
+    images
+}
+
+fn page_xobjects(doc: &Document, page: lopdf::ObjectId) -> Vec<(Vec<u8>, Object)> {
+    let (resources, ids) = doc.get_page_resources(page);
+    let dicts = resources
+        .into_iter()
+        .chain(ids.iter().filter_map(|id| doc.get_dictionary(*id).ok()));
+    let mut found = Vec::new();
+    for dict in dicts {
+        let xobjects = match dict.get(b"XObject").map(|o| doc.dereference(o)) {
+            Ok(Ok((_, Object::Dictionary(xobjects)))) => xobjects,
+            _ => continue,
+        };
+        for (name, object) in xobjects.iter() {
+            if let Ok((_, object)) = doc.dereference(object) {
+                found.push((name.clone(), object.clone()));
+            }
+        }
+    }
+    found
+}
+
+fn numbers(doc: &Document, object: &Object) -> Vec<f64> {
+    let (_, object) = doc.dereference(object).unwrap();
+    object
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|o| o.as_float().unwrap())
+        .collect()
+}
+
+/// A readable outline of a PDF for golden files: its info, IDs and, for
+/// each page, the media box and each image with how it is stored and
+/// placed. Lengths are rounded to a hundredth of a point.
+pub fn summary(pdf: &[u8]) -> String {
+    let doc = Document::load_mem(pdf).unwrap();
+    let mut out = String::new();
+    if let Ok(Ok((_, Object::Dictionary(info)))) =
+        doc.trailer.get(b"Info").map(|o| doc.dereference(o))
+    {
+        let keys = [
+            &b"Title"[..],
+            b"Author",
+            b"Subject",
+            b"Keywords",
+            b"CreationDate",
+            b"ModDate",
+        ];
+        // printpdf writes the fields that are not set as empty strings
+        for key in keys {
+            if let Ok(Object::String(value, _)) = info.get(key) {
+                if value.is_empty() {
+                    continue;
+                }
+                let key = String::from_utf8_lossy(key);
+                writeln!(out, "{}: {}", key, String::from_utf8_lossy(value)).unwrap();
+            }
+        }
+    }
+    if let Ok(Object::Array(ids)) = doc.trailer.get(b"ID") {
+        let ids = ids
+            .iter()
+            .map(|id| match id {
+                Object::String(id, _) => id.iter().map(|b| format!("{:02x}", b)).collect(),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+        writeln!(out, "ID: {}", ids.join(" ")).unwrap();
+    }
+    for (n, id) in doc.get_pages() {
+        let page = doc.get_dictionary(id).unwrap();
+        let media_box = numbers(&doc, page.get(b"MediaBox").unwrap());
+        writeln!(out, "page {}: {}", n, rounded(&media_box)).unwrap();
+        let xobjects = page_xobjects(&doc, id);
+        for image in placed(&doc, id) {
+            let (_, object) = xobjects
+                .iter()
+                .find(|(name, _)| *name == image.name.as_bytes())
+                .unwrap();
+            let dict = &object.as_stream().unwrap().dict;
+            // names, or arrays of them as filters may be given
+            let name_of = |key: &[u8]| match dict.get(key) {
+                Ok(Object::Name(name)) => String::from_utf8_lossy(name).into_owned(),
+                Ok(Object::Array(names)) => names
+                    .iter()
+                    .filter_map(|o| o.as_name_str().ok())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                _ => "-".to_owned(),
+            };
+            let bits = dict
+                .get(b"BitsPerComponent")
+                .and_then(Object::as_i64)
+                .unwrap_or(0);
+            writeln!(
+                out,
+                "  image {}: {}x{} {} {} bit {} at {}",
+                image.name,
+                image.pixels.0,
+                image.pixels.1,
+                name_of(b"ColorSpace"),
+                bits,
+                name_of(b"Filter"),
+                rounded(&image.rect),
+            )
+            .unwrap();
+        }
+    }
+    let outline = doc
+        .catalog()
+        .and_then(|c| c.get(b"Outlines"))
+        .and_then(Object::as_reference)
+        .and_then(|id| doc.get_dictionary(id));
+    if let Ok(outline) = outline {
+        outline_entries(&doc, outline, 0, &mut out);
+    }
+    out
+}
+
+/// Lines for the entries under `parent` of the outline and what they hold,
+/// with the page each jumps to
+fn outline_entries(doc: &Document, parent: &lopdf::Dictionary, depth: usize, out: &mut String) {
+    let pages = doc.get_pages();
+    let mut next = parent.get(b"First").and_then(Object::as_reference).ok();
+    while let Some(id) = next {
+        let item = doc.get_dictionary(id).unwrap();
+        let title = item.get(b"Title").and_then(Object::as_str).unwrap();
+        let target = item.get(b"Dest").unwrap().as_array().unwrap()[0]
+            .as_reference()
+            .unwrap();
+        let page = pages.iter().find(|(_, p)| **p == target).unwrap().0;
+        let indent = "  ".repeat(depth);
+        let title = String::from_utf8_lossy(title);
+        writeln!(out, "{}bookmark {}: page {}", indent, title, page).unwrap();
+        outline_entries(doc, item, depth + 1, out);
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+    }
+}
+
+fn rounded(numbers: &[f64]) -> String {
+    numbers
+        .iter()
+        .map(|n| format!("{:.2}", n))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compares `actual` with the golden file of `case`, writing it instead
+/// when `UPDATE_GOLDEN` is set
+pub fn assert_golden(case: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", case));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no golden file at {}, run with UPDATE_GOLDEN=1 to write it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        expected, actual,
+        "{} differs from its golden file, run with UPDATE_GOLDEN=1 if the change is meant",
+        case
+    );
+}
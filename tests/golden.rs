@@ -0,0 +1,163 @@
+mod common;
+
+use common::{assert_golden, builder, merge, strokes, summary, Fixtures};
+use printpdf::Mm;
+use yet_another_imgs2pdf::{info, BookmarkSource, ColorMode, Compression, Fit, Rotation};
+
+#[test]
+fn mixed_formats() {
+    let fixtures = Fixtures::new("mixed");
+    let inputs = [
+        fixtures.image("a.png", (40, 30), 1),
+        fixtures.image("b.bmp", (30, 40), 2),
+        fixtures.image("c.gif", (24, 24), 3),
+        fixtures.image("d.tiff", (50, 20), 4),
+        fixtures.gray_image("e.png", (20, 50), 5),
+    ];
+    let pdf = merge(builder().build().unwrap(), &inputs);
+    assert_golden("mixed_formats", &summary(&pdf));
+}
+
+#[test]
+fn a4_with_margins() {
+    let fixtures = Fixtures::new("a4");
+    let inputs = [
+        fixtures.image("wide.png", (60, 20), 1),
+        fixtures.image("tall.png", (20, 60), 2),
+    ];
+    let pdfs = [Fit::Contain, Fit::Cover, Fit::Stretch].map(|fit| {
+        let merger = builder()
+            .page_size((Mm(210.0), Mm(297.0)), fit)
+            .margins([Mm(10.0), Mm(15.0), Mm(20.0), Mm(25.0)])
+            .build()
+            .unwrap();
+        summary(&merge(merger, &inputs))
+    });
+    assert_golden("a4_with_margins", &pdfs.join("\n"));
+}
+
+#[test]
+fn bilevel() {
+    let fixtures = Fixtures::new("bilevel");
+    let inputs = [fixtures.save("text.png", &strokes((36, 24)))];
+    let merger = builder()
+        .color_mode(ColorMode::Bilevel { threshold: 128 })
+        .build()
+        .unwrap();
+    assert_golden("bilevel", &summary(&merge(merger, &inputs)));
+}
+
+/// Black and white text goes in as CCITT G4, a few flat colors losslessly
+/// and a gradient of many colors as JPEG
+#[test]
+fn auto_compression() {
+    let fixtures = Fixtures::new("auto-compression");
+    let inputs = [
+        fixtures.save("text.png", &strokes((36, 24))),
+        fixtures.image("flat.png", (40, 30), 1),
+        fixtures.image("photo.png", (40, 100), 2),
+    ];
+    let merger = builder().compression(Compression::Auto).build().unwrap();
+    assert_golden("auto_compression", &summary(&merge(merger, &inputs)));
+}
+
+#[test]
+fn jpeg_passthrough() {
+    let fixtures = Fixtures::new("jpeg");
+    let inputs = [
+        fixtures.image("color.jpg", (32, 16), 1),
+        fixtures.gray_image("gray.jpg", (16, 32), 2),
+    ];
+    let pdf = merge(builder().build().unwrap(), &inputs);
+    assert_golden("jpeg_passthrough", &summary(&pdf));
+    // the files themselves are embedded
+    let embedded = |path| {
+        let file = std::fs::read(path).unwrap();
+        pdf.windows(file.len()).any(|w| w == file)
+    };
+    assert!(inputs.iter().all(embedded));
+}
+
+#[test]
+fn exif_orientation() {
+    let fixtures = Fixtures::new("exif");
+    let inputs = [fixtures.oriented_png("sideways.png", (40, 20), 6)];
+    let turned = merge(builder().build().unwrap(), &inputs);
+    let kept = merge(builder().rotate(Rotation::None).build().unwrap(), &inputs);
+    assert_golden(
+        "exif_orientation",
+        &[summary(&turned), summary(&kept)].join("\n"),
+    );
+}
+
+#[test]
+fn same_inputs_same_file() {
+    let fixtures = Fixtures::new("repeat");
+    let inputs = [
+        fixtures.image("a.png", (40, 30), 1),
+        fixtures.image("b.jpg", (30, 40), 2),
+    ];
+    let first = merge(builder().build().unwrap(), &inputs);
+    let second = merge(builder().build().unwrap(), &inputs);
+    assert!(first == second, "two runs gave different files");
+}
+
+#[test]
+fn document_info() {
+    let fixtures = Fixtures::new("info");
+    let inputs = [fixtures.image("a.png", (20, 20), 1)];
+    let merger = builder()
+        .author("A. Author")
+        .subject("Fixtures")
+        .keywords(vec!["one".to_owned(), "two".to_owned()])
+        .creation_date(info::parse_date("2024-02-29T13:05:09").unwrap())
+        .build()
+        .unwrap();
+    assert_golden("document_info", &summary(&merge(merger, &inputs)));
+}
+
+#[test]
+fn bookmarks_grouped_by_directory() {
+    let fixtures = Fixtures::new("bookmarks");
+    std::fs::create_dir_all(fixtures.dir.join("part1/extra")).unwrap();
+    let inputs = [
+        fixtures.image("cover.png", (20, 20), 1),
+        fixtures.image("part1/a.png", (20, 20), 2),
+        fixtures.image("part1/b.png", (20, 20), 3),
+        fixtures.image("part1/extra/c.png", (20, 20), 4),
+        fixtures.image("end.png", (20, 20), 5),
+    ];
+    let merger = builder()
+        .bookmark_source(BookmarkSource::Filename)
+        .group_bookmarks(&fixtures.dir)
+        .build()
+        .unwrap();
+    assert_golden(
+        "bookmarks_grouped_by_directory",
+        &summary(&merge(merger, &inputs)),
+    );
+}
+
+#[test]
+fn appended_to_existing() {
+    let fixtures = Fixtures::new("append");
+    let first = [
+        fixtures.image("a.png", (40, 30), 1),
+        fixtures.image("b.png", (30, 40), 2),
+    ];
+    let merger = builder()
+        .author("A. Author")
+        .bookmark_source(BookmarkSource::Filename)
+        .build()
+        .unwrap();
+    let existing = fixtures.dir.join("existing.pdf");
+    std::fs::write(&existing, merge(merger, &first)).unwrap();
+
+    let more = [fixtures.image("c.png", (24, 24), 3)];
+    let merger = builder()
+        .bookmark_source(BookmarkSource::Filename)
+        .append_to(&existing)
+        .build()
+        .unwrap();
+    assert_golden("appended_to_existing", &summary(&merge(merger, &more)));
+}
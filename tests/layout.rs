@@ -0,0 +1,339 @@
+mod common;
+
+use common::{builder, merge, pages, Fixtures, Rng};
+use printpdf::lopdf::{content::Content, Document, Object};
+use printpdf::{image_crate, Mm};
+use yet_another_imgs2pdf::overlay::Stamp;
+use yet_another_imgs2pdf::{CellOrder, ColorMode, Fit, PageOptions};
+
+const PT_PER_MM: f64 = 72.0 / 25.4;
+/// Rounding in the content stream and of cropped pixels
+const TOLERANCE: f64 = 0.05;
+
+/// Images of any shape on pages of any size stay inside the margins, and
+/// keep their proportions unless stretched
+#[test]
+fn images_fit_their_page() {
+    let fixtures = Fixtures::new("layout");
+    let mut rng = Rng::new(0x5eed);
+    for case in 0..40 {
+        let size = (rng.below(1, 120) as u32, rng.below(1, 120) as u32);
+        let input = fixtures.image(&format!("{}.png", case), size, case as u8);
+        let page = (rng.float(80.0, 400.0), rng.float(80.0, 400.0));
+        let margins = [(); 4].map(|_| rng.float(0.0, 30.0));
+        let (fit, fit_name) = [
+            (Fit::Contain, "contain"),
+            (Fit::Cover, "cover"),
+            (Fit::Stretch, "stretch"),
+        ][rng.below(0, 3) as usize];
+        let merger = builder()
+            .page_size((Mm(page.0), Mm(page.1)), fit)
+            .margins(margins.map(Mm))
+            .build()
+            .unwrap();
+        let pdf = merge(merger, &[input]);
+
+        let context = format!("{:?} on {:?} with {:?}, {}", size, page, margins, fit_name);
+        let pages = pages(&pdf);
+        assert_eq!(pages.len(), 1, "{}", context);
+        let (media_box, images) = &pages[0];
+        assert!(
+            (media_box[2] - page.0 * PT_PER_MM).abs() < TOLERANCE
+                && (media_box[3] - page.1 * PT_PER_MM).abs() < TOLERANCE,
+            "{}: media box {:?}",
+            context,
+            media_box
+        );
+        let [top, right, bottom, left] = margins.map(|m| m * PT_PER_MM);
+        let (box_w, box_h) = (media_box[2] - left - right, media_box[3] - top - bottom);
+        let [x, y, w, h] = images[0].rect;
+        assert!(
+            x >= left - TOLERANCE
+                && y >= bottom - TOLERANCE
+                && x + w <= left + box_w + TOLERANCE
+                && y + h <= bottom + box_h + TOLERANCE,
+            "{}: image at {:?}",
+            context,
+            images[0].rect
+        );
+        let (pw, ph) = (images[0].pixels.0 as f64, images[0].pixels.1 as f64);
+        match fit {
+            Fit::Contain => {
+                // touches the box on at least one axis, at its own proportions
+                let touches = (w - box_w).abs() < TOLERANCE || (h - box_h).abs() < TOLERANCE;
+                assert!(touches, "{}: {} by {} pt", context, w, h);
+                assert!((w / h - pw / ph).abs() < 0.01 * pw / ph, "{}", context);
+            }
+            Fit::Cover | Fit::Stretch => {
+                let fills = (w - box_w).abs() < TOLERANCE && (h - box_h).abs() < TOLERANCE;
+                assert!(fills, "{}: {} by {} pt", context, w, h);
+                if fit == Fit::Cover {
+                    // cropped to the box, give or take a pixel
+                    let crop_error = (pw / ph - w / h).abs() * ph.min(pw);
+                    assert!(crop_error <= (w / h).max(h / w) + 1.0, "{}", context);
+                }
+            }
+            Fit::Center => unreachable!(),
+        }
+    }
+}
+
+/// Images of an N-up page each sit in their own cell, filled row by row or
+/// column by column
+#[test]
+fn nup_fills_cells_in_order() {
+    let fixtures = Fixtures::new("nup");
+    // told apart by their widths
+    let inputs = (0..5)
+        .map(|i| fixtures.image(&format!("{}.png", i), (20 + i, 10 + 3 * i), i as u8))
+        .collect::<Vec<_>>();
+    let (page, margin, gutter) = ((300.0, 200.0), 10.0, 6.0);
+    for (order, name) in [
+        (CellOrder::RowMajor, "row major"),
+        (CellOrder::ColumnMajor, "column major"),
+    ] {
+        let merger = builder()
+            .page_size((Mm(page.0), Mm(page.1)), Fit::Contain)
+            .margins([Mm(margin); 4])
+            .nup(2, 2, Mm(gutter), order)
+            .build()
+            .unwrap();
+        let pages = pages(&merge(merger, &inputs));
+        assert_eq!(pages.len(), 2, "{}", name);
+        assert_eq!(pages[1].1.len(), 1, "{}", name);
+
+        let cell = (
+            (page.0 - 2.0 * margin - gutter) / 2.0 * PT_PER_MM,
+            (page.1 - 2.0 * margin - gutter) / 2.0 * PT_PER_MM,
+        );
+        for (p, (_, images)) in pages.iter().enumerate() {
+            for image in images {
+                let i = image.pixels.0 as usize - 20;
+                let n = i - 4 * p;
+                let (row, column) = match order {
+                    CellOrder::RowMajor => (n / 2, n % 2),
+                    CellOrder::ColumnMajor => (n % 2, n / 2),
+                };
+                let left = margin * PT_PER_MM + column as f64 * (cell.0 + gutter * PT_PER_MM);
+                let top =
+                    (page.1 - margin) * PT_PER_MM - row as f64 * (cell.1 + gutter * PT_PER_MM);
+                let [x, y, w, h] = image.rect;
+                assert!(
+                    x >= left - TOLERANCE
+                        && x + w <= left + cell.0 + TOLERANCE
+                        && y + h <= top + TOLERANCE
+                        && y >= top - cell.1 - TOLERANCE,
+                    "{}: image {} at {:?}",
+                    name,
+                    i,
+                    image.rect
+                );
+                let touches = (w - cell.0).abs() < TOLERANCE || (h - cell.1).abs() < TOLERANCE;
+                assert!(touches, "{}: image {} is {} by {} pt", name, i, w, h);
+            }
+        }
+    }
+}
+
+/// Two portrait images make up a spread, meeting in the middle of their
+/// page, while landscape images and pages left over stand alone
+#[test]
+fn spreads_pair_portrait_pages() {
+    let fixtures = Fixtures::new("spreads");
+    // told apart by their widths
+    let inputs = [(20, 40), (21, 40), (60, 30), (22, 40)]
+        .iter()
+        .enumerate()
+        .map(|(i, &size)| fixtures.image(&format!("{}.png", i), size, i as u8))
+        .collect::<Vec<_>>();
+    for rtl in [false, true] {
+        let merger = builder().spreads(rtl, false).build().unwrap();
+        let pages = pages(&merge(merger, &inputs));
+        let counts = pages
+            .iter()
+            .map(|(_, images)| images.len())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [2, 1, 1], "rtl {}", rtl);
+
+        let (media_box, images) = &pages[0];
+        let mut halves = images.iter().collect::<Vec<_>>();
+        halves.sort_by(|a, b| a.rect[0].total_cmp(&b.rect[0]));
+        let widths = halves.iter().map(|i| i.pixels.0).collect::<Vec<_>>();
+        assert_eq!(widths, if rtl { [21, 20] } else { [20, 21] });
+        let [left, right] = [&halves[0].rect, &halves[1].rect];
+        assert!(
+            (left[0] + left[2] - right[0]).abs() < TOLERANCE,
+            "rtl {}",
+            rtl
+        );
+        assert!(
+            (right[0] + right[2] - media_box[2]).abs() < TOLERANCE,
+            "rtl {}",
+            rtl
+        );
+        assert!(media_box[2] > media_box[3], "rtl {}", rtl);
+    }
+
+    // the cover keeps a page of its own, moving the pairs along by one
+    let merger = builder().spreads(false, true).build().unwrap();
+    let pages = pages(&merge(merger, &inputs[..2]));
+    let counts = pages
+        .iter()
+        .map(|(_, images)| images.len())
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [1, 1]);
+}
+
+/// A stitched scroll is scaled to the width of the scale box alone, however
+/// long it gets
+#[test]
+fn stitched_scrolls_keep_their_width() {
+    let fixtures = Fixtures::new("stitch");
+    let input = fixtures.image("scroll.png", (20, 400), 1);
+    let merger = builder()
+        .scale(40, 60)
+        .stitch_vertical(true)
+        .build()
+        .unwrap();
+    let pages = pages(&merge(merger, &[input]));
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].1[0].pixels, (40, 800));
+}
+
+/// The header and footer are filled in for every page with its number, the
+/// page count and the name of its input
+#[test]
+fn header_and_footer_are_filled_in() {
+    let fixtures = Fixtures::new("running-text");
+    let names = ["front.png", "back.png"];
+    let inputs = names.map(|name| fixtures.image(name, (30, 40), 1));
+    let stamp = Stamp {
+        watermark: None,
+        page_numbers: true,
+        header: Some("{filename}".to_owned()),
+        footer: Some("Page {page} of {total}".to_owned()),
+    };
+    let merger = builder().stamp(stamp).build().unwrap();
+    let doc = Document::load_mem(&merge(merger, &inputs)).unwrap();
+    for (i, (_, page)) in doc.get_pages().into_iter().enumerate() {
+        let content = Content::decode(&doc.get_page_content(page).unwrap()).unwrap();
+        let lines = content
+            .operations
+            .iter()
+            .filter(|op| op.operator == "Tj")
+            .filter_map(|op| match &op.operands[0] {
+                Object::String(text, _) => Some(String::from_utf8_lossy(text).into_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let footer = format!("Page {} of 2", i + 1);
+        let expected = [(i + 1).to_string(), names[i].to_owned(), footer];
+        assert_eq!(lines, expected, "page {}", i + 1);
+    }
+
+    let stamp = Stamp {
+        watermark: None,
+        page_numbers: false,
+        header: Some("{chapter}".to_owned()),
+        footer: None,
+    };
+    assert!(builder().stamp(stamp).build().is_err());
+}
+
+/// The preview holds the first images as they are embedded, scaled down and
+/// thresholded like them
+#[test]
+fn preview_shows_images_as_embedded() {
+    let fixtures = Fixtures::new("preview");
+    let inputs = (0..3)
+        .map(|i| fixtures.image(&format!("{}.png", i), (40, 30), i as u8))
+        .collect::<Vec<_>>();
+    let dir = fixtures.dir.join("preview");
+    let merger = builder()
+        .max_dimension(20)
+        .color_mode(ColorMode::Bilevel { threshold: 128 })
+        .preview(&dir, 2)
+        .build()
+        .unwrap();
+    merge(merger, &inputs);
+
+    let mut written = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    written.sort();
+    assert_eq!(written, ["001-0.png", "002-1.png"]);
+    let preview = image_crate::open(dir.join("001-0.png")).unwrap().to_luma8();
+    assert_eq!(preview.dimensions(), (20, 15));
+    assert!(preview.pixels().all(|p| p.0[0] == 0 || p.0[0] == 255));
+}
+
+/// A region is laid over the compressed image exactly where it was, at the
+/// resolution of the source, and only when the image is compressed
+#[test]
+fn regions_sit_over_their_pixels() {
+    let fixtures = Fixtures::new("regions");
+    let input = fixtures.image("form.png", (100, 80), 3);
+    for quality in [None, Some(30)] {
+        let mut b = builder()
+            .page_size((Mm(120.0), Mm(120.0)), Fit::Contain)
+            .margins([Mm(10.0); 4]);
+        if let Some(quality) = quality {
+            b = b.quality(quality);
+        }
+        let mut merger = b.build().unwrap();
+        let page = PageOptions {
+            regions: vec![[20, 10, 30, 20]],
+            ..Default::default()
+        };
+        merger.append_image_page_with(&input, page).unwrap();
+        merger.finish();
+        let mut pdf = Vec::new();
+        merger.save(&mut pdf).unwrap();
+
+        let pages = pages(&pdf);
+        let images = &pages[0].1;
+        if quality.is_none() {
+            assert_eq!(images.len(), 1, "nothing to keep sharp");
+            continue;
+        }
+        assert_eq!(images.len(), 2);
+        let [x, y, w, h] = images[0].rect;
+        let region = &images[1];
+        assert_eq!(region.pixels, (30, 20));
+        let expected = [
+            x + w * 0.2,
+            y + h * (80.0 - 10.0 - 20.0) / 80.0,
+            w * 0.3,
+            h * 0.25,
+        ];
+        for (actual, expected) in region.rect.iter().zip(expected) {
+            assert!((actual - expected).abs() < TOLERANCE, "{:?}", region.rect);
+        }
+    }
+}
+
+/// Fine print scaled far down keeps dark strokes with text protection, where
+/// plain resampling fades it into the paper
+#[test]
+fn protected_text_stays_dark() {
+    let fixtures = Fixtures::new("protect-text");
+    let input = fixtures.save("print.png", &common::strokes((256, 256)));
+    let darkest = |protect: bool| {
+        let dir = fixtures.dir.join(format!("preview-{protect}"));
+        let merger = builder()
+            .scale_percent(12.5)
+            .protect_text(protect)
+            .preview(&dir, 1)
+            .build()
+            .unwrap();
+        merge(merger, std::slice::from_ref(&input));
+        let preview = image_crate::open(dir.join("001-print.png"))
+            .unwrap()
+            .to_luma8();
+        assert_eq!(preview.dimensions(), (32, 32));
+        preview.pixels().map(|p| p.0[0]).min().unwrap()
+    };
+    let (plain, protected) = (darkest(false), darkest(true));
+    assert!(protected + 60 < plain, "{protected} vs {plain}");
+}
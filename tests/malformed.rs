@@ -0,0 +1,90 @@
+mod common;
+
+use std::path::Path;
+
+use common::{builder, Fixtures, Rng};
+use yet_another_imgs2pdf::{manifest, PageOptions, Severity, WarningCode};
+
+/// Cut short or scribbled over inputs fail to decode instead of panicking,
+/// as the fuzz targets check at length
+#[test]
+fn damaged_inputs_are_errors() {
+    let fixtures = Fixtures::new("malformed");
+    let mut rng = Rng::new(261);
+    for name in ["a.png", "b.jpg", "c.bmp", "d.gif", "e.tiff"] {
+        let file = std::fs::read(fixtures.image(name, (23, 17), 1)).unwrap();
+        for _ in 0..10 {
+            let mut damaged = file[..rng.below(0, file.len() as u64) as usize].to_vec();
+            if !damaged.is_empty() && rng.below(0, 2) == 0 {
+                let i = rng.below(0, damaged.len() as u64) as usize;
+                damaged[i] = rng.next_u64() as u8;
+            }
+            let mut merger = builder().build().unwrap();
+            let added = merger.append_image_data(Path::new(name), &damaged, PageOptions::default());
+            if added.is_ok() {
+                merger.finish();
+                merger.save(std::io::sink()).unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn damaged_manifests_are_errors() {
+    let manifest = "[[input]]\nfile = \"a.png\"\nscale = \"50%\"\nlinks = [\"1,2,3,4 https://example.com\"]\nregions = [\"5,6,7,8\"]\n";
+    assert_eq!(manifest::parse(manifest, Path::new("")).unwrap().len(), 1);
+    for end in 0..manifest.len() {
+        let _ = manifest::parse(&manifest[..end], Path::new(""));
+    }
+    for bad in [
+        "file = \"a\"",
+        "[[input]]\nfile = \"a",
+        "[[input]]\nlinks = [\"",
+        "[[input]]\nregions = [\"1,2,0,4\"]",
+        "[[input]]\nfile = \"\\u12\"",
+        "[[input]]\nfile = \"${unset}.png\"",
+        "[[input]]\nfile = \"${open.png\"",
+        "[[input]]\nfile = \"a\"\ncolor_mode = \"bilevel 300\"",
+        "[vars]\nnames = [\"a\"]",
+        "[[include]]\nif = true",
+        "[[when]]\nfile = \"a\"",
+    ] {
+        assert!(manifest::parse(bad, Path::new("")).is_err(), "{:?}", bad);
+    }
+}
+
+/// A manifest that includes itself, however indirectly, is an error rather
+/// than read forever
+#[test]
+fn manifest_include_loops_are_errors() {
+    let fixtures = Fixtures::new("manifest-loop");
+    let dir = &fixtures.dir;
+    std::fs::write(dir.join("a.toml"), "[[include]]\nfile = \"b.toml\"\n").unwrap();
+    std::fs::write(dir.join("b.toml"), "[[include]]\nfile = \"a.toml\"\n").unwrap();
+    assert!(manifest::read(&dir.join("a.toml"), &[]).is_err());
+}
+
+#[test]
+fn warnings_carry_codes_and_sources() {
+    let fixtures = Fixtures::new("warnings");
+    let good = fixtures.image("good.png", (20, 20), 1);
+    let bad = fixtures.dir.join("bad.png");
+    std::fs::write(&bad, b"not a png").unwrap();
+    let mut merger = builder().dpi(50.0).build().unwrap();
+    merger.append_image_page(&good).unwrap();
+    let e = merger.append_image_page(&bad).unwrap_err();
+    merger.record_skipped(&bad, &e);
+
+    let found = merger
+        .warnings()
+        .iter()
+        .map(|w| (w.code, w.severity, w.source.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        [
+            (WarningCode::LowResolution, Severity::Warning, Some(good)),
+            (WarningCode::SkippedInput, Severity::Error, Some(bad)),
+        ]
+    );
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::path::Path;
+
+use common::Fixtures;
+use yet_another_imgs2pdf::{manifest, ColorMode};
+
+/// Variables, includes, conditions and rules are resolved as a manifest is
+/// read, with variables given from outside it taking the place of its own
+#[test]
+fn manifests_resolve_variables_includes_and_rules() {
+    let fixtures = Fixtures::new("manifest");
+    let dir = &fixtures.dir;
+    std::fs::create_dir_all(dir.join("scans")).unwrap();
+    let part = "[[input]]\nfile = \"${prefix}-2.TIF\"\n";
+    std::fs::write(dir.join("scans/part.toml"), part).unwrap();
+    let book = "[vars]\nprefix = \"page\"\nedition = \"web\"\n\n\
+        [[input]]\nfile = \"${prefix}-1.png\"\n\n\
+        [[include]]\nfile = \"scans/part.toml\"\n\n\
+        [[input]]\nfile = \"crop-marks.png\"\nif = \"${edition} == print\"\n\n\
+        [[when]]\nfiles = \"*.tif\"\ncolor_mode = \"bilevel 150\"\n\n\
+        [[when]]\nfiles = \"*.png\"\nif = \"${edition} != web\"\ncolor_mode = \"gray\"\n";
+    std::fs::write(dir.join("book.toml"), book).unwrap();
+
+    let web = manifest::read(&dir.join("book.toml"), &[]).unwrap();
+    let files = web.iter().map(|e| e.file.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        files,
+        [dir.join("page-1.png"), dir.join("scans/page-2.TIF")]
+    );
+    assert!(web[0].color_mode.is_none());
+    assert!(web[1].color_mode == Some(ColorMode::Bilevel { threshold: 150 }));
+
+    let vars = [("edition".to_owned(), "print".to_owned())];
+    let print = manifest::read(&dir.join("book.toml"), &vars).unwrap();
+    assert_eq!(print.len(), 3);
+    assert!(print[2].color_mode == Some(ColorMode::Gray));
+}
+
+/// Rules give an input the scale and color mode it does not set, and add
+/// their links, regions and fold out to its own
+#[test]
+fn rules_fill_in_and_add_settings() {
+    let toml = "[[input]]\nfile = \"a.png\"\nscale = \"80%\"\nlinks = [\"1,2,3,4 https://example.com/a\"]\n\n\
+        [[input]]\nfile = \"b.png\"\n\n\
+        [[when]]\nfiles = \"*.png\"\nscale = \"50%\"\nfold_out = true\n\
+        links = [\"5,6,7,8 https://example.com/all\"]\nregions = [\"1,1,2,2\"]\n";
+    let entries = manifest::parse(toml, Path::new("")).unwrap();
+    let scales = entries.iter().map(|e| e.scale).collect::<Vec<_>>();
+    assert_eq!(scales, [Some(80.0), Some(50.0)]);
+    let links = entries.iter().map(|e| e.links.len()).collect::<Vec<_>>();
+    assert_eq!(links, [2, 1]);
+    assert!(entries
+        .iter()
+        .all(|e| e.fold_out && e.regions == [[1, 1, 2, 2]]));
+}